@@ -1,10 +1,11 @@
-use ktio::seq::{SeqFormat, Sequence, Sequences};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::{
-    collections::HashMap,
-    fs::File,
-    io::{BufRead, BufWriter, Write},
+use crate::render::{gray, png_dir, png_name, point_pixels, write_png};
+use ktio::{
+    filter::RecordFilter,
+    format::OutputFormat,
+    seq::{SeqInput, Sequence},
 };
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::{collections::HashMap, io::Write};
 
 const GB_4: usize = 4 * (1 << 30);
 type Point = (f64, f64);
@@ -45,6 +46,10 @@ pub struct CgrComputer {
     memory: usize,
     cgr_center: Point,
     cgr_map: HashMap<u8, Point>,
+    filter: Option<RecordFilter>,
+    format: OutputFormat,
+    png: Option<(String, usize)>,
+    skip_masked: bool,
 }
 
 impl CgrComputer {
@@ -53,10 +58,14 @@ impl CgrComputer {
         Self {
             in_path,
             out_path,
-            threads: rayon::current_num_threads(),
+            threads: ktio::threads::default_threads(),
             memory: GB_4,
             cgr_center,
             cgr_map,
+            filter: None,
+            format: OutputFormat::default(),
+            png: None,
+            skip_masked: false,
         }
     }
 
@@ -64,20 +73,74 @@ impl CgrComputer {
         self.threads = threads;
     }
 
+    pub fn set_filter(&mut self, filter: Option<RecordFilter>) {
+        self.filter = filter;
+    }
+
+    // points are written as (x,y) with spaces, as columns with other delimiters
+    pub fn set_format(&mut self, format: OutputFormat) {
+        self.format = format;
+    }
+
+    // p<n> for the point of the n-th base, or p<n>_x and p<n>_y columns
+    fn point_names(&self, points: usize) -> Vec<String> {
+        (1..=points)
+            .flat_map(|n| {
+                if self.format.delim == " " {
+                    vec![format!("p{}", n)]
+                } else {
+                    vec![format!("p{}_x", n), format!("p{}_y", n)]
+                }
+            })
+            .collect()
+    }
+
+    fn header(&self, input: &SeqInput) -> Result<Option<String>, String> {
+        if !self.format.header {
+            return Ok(None);
+        }
+        let mut records = input.records()?;
+        records.set_filter(self.filter.clone());
+        let longest = records.map(|record| record.seq.len()).max().unwrap_or(0);
+        Ok(self.format.header_row(&self.point_names(longest)))
+    }
+
+    // also write a (dir, size) size x size grayscale PNG of the points of each record,
+    // named after its ID
+    pub fn set_png(&mut self, png: Option<(String, usize)>) -> Result<(), String> {
+        if let Some((dir, _)) = &png {
+            png_dir(dir)?;
+        }
+        self.png = png;
+        Ok(())
+    }
+
+    // leave out soft-masked (lowercase) bases, the walk restarts from the centre after them
+    pub fn set_skip_masked(&mut self, skip_masked: bool) {
+        self.skip_masked = skip_masked;
+    }
+
+    fn write_image(&self, id: &str, points: &[Point]) -> Result<(), String> {
+        if let Some((dir, size)) = &self.png {
+            let points: Vec<(Point, f64)> = points.iter().map(|&point| (point, 1_f64)).collect();
+            let pixels = point_pixels(&points, self.cgr_center.0 * 2.0, *size);
+            write_png(&format!("{}/{}", dir, png_name(id)), *size, &gray(&pixels))?;
+        }
+        Ok(())
+    }
+
     pub fn vectorise(&self) -> Result<(), String> {
-        let mut reader = ktio::seq::get_reader(&self.in_path).unwrap();
-        let buffer = reader
-            .fill_buf()
-            .map_err(|_| String::from("Invalid stream"))?;
-        let format = if buffer[0] == b'>' {
-            SeqFormat::Fasta
-        } else {
-            SeqFormat::Fastq
-        };
-        let records = Sequences::new(format, reader).unwrap();
-        let file = File::create(&self.out_path)
-            .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
-        let mut out_buffer = BufWriter::new(file);
+        // stdin is spooled when the header needs a first pass for the longest record
+        let input = SeqInput::open(&self.in_path, 1 + self.format.header as usize)?;
+        let header = self.header(&input)?;
+        let mut records = input.records()?;
+        records.set_filter(self.filter.clone());
+        let mut out_buffer = self.format.writer(&self.out_path)?;
+        if let Some(header) = header {
+            out_buffer
+                .write_all(header.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+        }
         let pool = rayon::ThreadPoolBuilder::new()
             .num_threads(self.threads)
             .build()
@@ -94,15 +157,22 @@ impl CgrComputer {
                         .par_iter()
                         .map(|seq| {
                             let kvec = self.vectorise_one(&seq.seq).unwrap();
+                            self.write_image(&seq.id, &kvec)?;
                             let kvec_str: Vec<String> = kvec
                                 .iter()
-                                .map(|val| format!("({},{})", val.0, val.1))
+                                .map(|val| {
+                                    self.format.point(&[
+                                        self.format.number(val.0, None),
+                                        self.format.number(val.1, None),
+                                    ])
+                                })
                                 .collect();
-                            format!("{}\n", kvec_str.join(" "))
+                            Ok(self.format.row(&seq.id, &kvec_str))
                         })
-                        .collect::<Vec<String>>()
+                        .collect::<Result<Vec<String>, String>>()?
                         .join("");
                     out_buffer.write_all(result.as_bytes()).unwrap();
+                    Ok::<(), String>(())
                 };
 
                 for record in records {
@@ -110,18 +180,18 @@ impl CgrComputer {
                     buffer.push(record);
 
                     if total >= self.memory {
-                        process_buffer(&buffer);
+                        process_buffer(&buffer)?;
                         buffer.clear();
                         total = 0;
                     }
                 }
 
                 if !buffer.is_empty() {
-                    process_buffer(&buffer);
+                    process_buffer(&buffer)?;
                 }
-            });
-        });
-        Ok(())
+                Ok(())
+            })
+        })
     }
 
     fn vectorise_one(&self, seq: &[u8]) -> Result<Vec<Point>, String> {
@@ -129,6 +199,10 @@ impl CgrComputer {
         let mut cgr_marker = self.cgr_center;
 
         for s in seq.iter() {
+            if self.skip_masked && s.is_ascii_lowercase() {
+                cgr_marker = self.cgr_center;
+                continue;
+            }
             if let Some(&cgr_corner) = self.cgr_map.get(s) {
                 cgr_marker = (
                     (cgr_corner.0 + cgr_marker.0) / 2.0,
@@ -147,6 +221,7 @@ impl CgrComputer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ktio::format::IdPolicy;
     use std::fs;
 
     const PATH_FQ: &str = "../test_data/reads.fq";
@@ -186,6 +261,33 @@ mod tests {
         assert_eq!(vec, res);
     }
 
+    #[test]
+    fn cgr_skip_masked_test() {
+        let mut cgr = CgrComputer::new(PATH_FQ.to_owned(), "".to_owned(), 1);
+        cgr.set_skip_masked(true);
+        let vec = cgr.vectorise_one(b"ATgaT").unwrap();
+        assert_eq!(vec, vec![(0.25, 0.25), (0.625, 0.125), (0.75, 0.25)]);
+    }
+
+    #[test]
+    fn cgr_header_test() {
+        let out_path = "../test_data/computed_reads.header.cgr";
+        let mut cgr = CgrComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 1);
+        let mut format = OutputFormat::new("\t");
+        format.header = true;
+        format.ids = IdPolicy::First;
+        cgr.set_format(format);
+        cgr.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        let mut lines = vectors.lines();
+        let header = lines.next().unwrap();
+        assert!(header.starts_with("id\tp1_x\tp1_y\tp2_x\t"));
+        assert!(header.ends_with("\tp72_y"));
+        let row = lines.next().unwrap();
+        assert!(row.starts_with("Read_1\t"));
+        assert_eq!(row.split('\t').count(), header.split('\t').count());
+    }
+
     #[test]
     fn cgr_complete_unnorm_test() {
         let mut cgr = CgrComputer::new(PATH_FQ.to_owned(), "../test_data/reads.cgr".to_owned(), 1);
@@ -197,4 +299,24 @@ mod tests {
             fs::read("../test_data/reads.cgr").unwrap()
         )
     }
+
+    #[test]
+    fn cgr_csv_test() {
+        let out_path = "../test_data/computed_reads.cgr.csv";
+        let mut cgr = CgrComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 1);
+        cgr.set_format(OutputFormat::new(","));
+        cgr.vectorise().unwrap();
+        // same coordinates, one column per coordinate
+        let exp: Vec<String> = fs::read_to_string("../test_data/expected_reads.cgr")
+            .unwrap()
+            .lines()
+            .map(|line| line.replace(") (", ",").replace(['(', ')'], ""))
+            .collect();
+        let res: Vec<String> = fs::read_to_string(out_path)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(exp, res);
+    }
 }
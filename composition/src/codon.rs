@@ -0,0 +1,296 @@
+use kmer::{
+    encoder::{Dna, Encoder},
+    numeric_to_kmer_in, Molecule,
+};
+use ktio::{
+    filter::RecordFilter,
+    format::{IdPolicy, OutputFormat},
+    matrix::MatrixWriter,
+    seq::{SeqInput, Sequence},
+};
+use rayon::prelude::*;
+use std::io::Write;
+
+const NUMBER_SIZE: usize = 8;
+const CODONS: usize = 64;
+// TAA, TAG and TGA of the standard genetic code, A=0 C=1 G=2 T=3
+const STOP_CODONS: [usize; 3] = [48, 50, 56];
+
+// reading frame whose codons are counted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frame {
+    // codons from this offset (0, 1 or 2) of the forward strand
+    Offset(usize),
+    // of the six frames of both strands, the one with the longest run of codons
+    // without stops, counted over the whole sequence
+    LongestOrf,
+}
+
+// codons of a frame of 2-bit bases, None for codons with other symbols
+fn codons(codes: &[Option<u64>], offset: usize) -> impl Iterator<Item = Option<usize>> + '_ {
+    codes
+        .get(offset..)
+        .unwrap_or(&[])
+        .chunks_exact(3)
+        .map(|codon| {
+            codon.iter().try_fold(0_usize, |acc, code| {
+                code.map(|code| (acc << 2) | code as usize)
+            })
+        })
+}
+
+// longest run of codons without stops or other symbols
+fn longest_orf(codes: &[Option<u64>], offset: usize) -> usize {
+    let (mut longest, mut run) = (0, 0);
+    for codon in codons(codes, offset) {
+        match codon {
+            Some(codon) if !STOP_CODONS.contains(&codon) => {
+                run += 1;
+                longest = usize::max(longest, run);
+            }
+            _ => run = 0,
+        }
+    }
+    longest
+}
+
+// 64 dimensional codon usage of each record, codons are named in ACGT
+pub struct CodonComputer {
+    in_path: String,
+    out_path: String,
+    threads: usize,
+    norm: bool,
+    frame: Frame,
+    format: OutputFormat,
+    filter: Option<RecordFilter>,
+    molecule: Molecule,
+}
+
+impl CodonComputer {
+    pub fn new(in_path: String, out_path: String) -> Self {
+        Self {
+            in_path,
+            out_path,
+            threads: ktio::threads::default_threads(),
+            norm: true,
+            frame: Frame::Offset(0),
+            format: OutputFormat::default(),
+            filter: None,
+            molecule: Molecule::Dna,
+        }
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads;
+    }
+
+    pub fn set_norm(&mut self, norm: bool) {
+        self.norm = norm;
+    }
+
+    pub fn set_frame(&mut self, frame: Frame) -> Result<(), String> {
+        if let Frame::Offset(offset) = frame {
+            if offset > 2 {
+                return Err(format!("Frame offset must be 0, 1 or 2, got {}", offset));
+            }
+        }
+        self.frame = frame;
+        Ok(())
+    }
+
+    // delimiter, precision of frequencies, header and ID columns of the output
+    pub fn set_format(&mut self, format: OutputFormat) {
+        self.format = format;
+    }
+
+    pub fn set_filter(&mut self, filter: Option<RecordFilter>) {
+        self.filter = filter;
+    }
+
+    // letters of the codons of the header, U in place of T for RNA
+    pub fn set_molecule(&mut self, molecule: Molecule) {
+        self.molecule = molecule;
+    }
+
+    pub fn feature_names(&self) -> Vec<String> {
+        (0..CODONS as u64)
+            .map(|codon| numeric_to_kmer_in(codon, 3, self.molecule))
+            .collect()
+    }
+
+    fn vectorise_one(&self, seq: &[u8]) -> Vec<f64> {
+        let forward: Vec<Option<u64>> = seq.iter().map(|&base| Dna.encode(base)).collect();
+        let (codes, offset) = match self.frame {
+            Frame::Offset(offset) => (forward, offset),
+            Frame::LongestOrf => {
+                let reverse: Vec<Option<u64>> = forward
+                    .iter()
+                    .rev()
+                    .map(|code| code.map(|code| Dna.complement(code)))
+                    .collect();
+                // forward frames first, so ties keep the forward strand
+                let (strand, offset) = [(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2)]
+                    .into_iter()
+                    .rev()
+                    .max_by_key(|&(strand, offset)| {
+                        longest_orf(if strand == 0 { &forward } else { &reverse }, offset)
+                    })
+                    .unwrap();
+                (if strand == 0 { forward } else { reverse }, offset)
+            }
+        };
+        let mut vec = vec![0_f64; CODONS];
+        let mut total = 0_f64;
+        for codon in codons(&codes, offset).flatten() {
+            vec[codon] += 1_f64;
+            total += 1_f64;
+        }
+        if self.norm {
+            vec.iter_mut().for_each(|el| *el /= f64::max(1_f64, total));
+        }
+        vec
+    }
+
+    pub fn vectorise(&self) -> Result<(), String> {
+        let mut records = SeqInput::open(&self.in_path, 1)?.records()?;
+        records.set_filter(self.filter.clone());
+        let mut matrix = match self.format.matrix {
+            Some(matrix) => Some(MatrixWriter::new(
+                &self.out_path,
+                matrix,
+                &self.feature_names(),
+                self.format.ids == IdPolicy::First,
+            )?),
+            None => None,
+        };
+        let mut out_buffer = match matrix {
+            Some(_) => None,
+            None => Some(self.format.writer(&self.out_path)?),
+        };
+        if let (Some(out_buffer), Some(header)) = (
+            out_buffer.as_mut(),
+            self.format.header_row(&self.feature_names()),
+        ) {
+            out_buffer
+                .write_all(header.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+        }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let vecs: Vec<Vec<f64>> = pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|seq| self.vectorise_one(&seq.seq))
+                    .collect()
+            });
+            if let Some(matrix) = matrix.as_mut() {
+                for (seq, vec) in batch.iter().zip(vecs.iter()) {
+                    matrix.write_row(&seq.id, vec)?;
+                }
+            } else if let Some(out_buffer) = out_buffer.as_mut() {
+                let result: String = batch
+                    .iter()
+                    .zip(vecs.iter())
+                    .map(|(seq, vec)| {
+                        let fields: Vec<String> = vec
+                            .iter()
+                            .map(|val| {
+                                if self.norm {
+                                    self.format.number(*val, Some(NUMBER_SIZE - 2))
+                                } else {
+                                    format!("{}", val)
+                                }
+                            })
+                            .collect();
+                        self.format.row(&seq.id, &fields)
+                    })
+                    .collect();
+                out_buffer
+                    .write_all(result.as_bytes())
+                    .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+            }
+        }
+        if let Some(matrix) = matrix {
+            matrix.finish()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const PATH_FA: &str = "../test_data/reads.fa";
+
+    #[test]
+    fn codon_vec_test() {
+        let mut com = CodonComputer::new(PATH_FA.to_owned(), "".to_owned());
+        com.set_norm(false);
+        let names = com.feature_names();
+        let count = |vec: &[f64], codon: &str| vec[names.iter().position(|n| n == codon).unwrap()];
+        // ATG GCC TAA, then a partial codon
+        let vec = com.vectorise_one(b"ATGGCCTAAG");
+        assert_eq!(count(&vec, "ATG"), 1.0);
+        assert_eq!(count(&vec, "TAA"), 1.0);
+        assert_eq!(vec.iter().sum::<f64>(), 3.0);
+        // codons with N are skipped
+        com.set_frame(Frame::Offset(1)).unwrap();
+        let vec = com.vectorise_one(b"AATGNCCTAA");
+        assert_eq!(count(&vec, "ATG"), 1.0);
+        assert_eq!(count(&vec, "TAA"), 1.0);
+        assert_eq!(vec.iter().sum::<f64>(), 2.0);
+        assert!(com.set_frame(Frame::Offset(3)).is_err());
+    }
+
+    #[test]
+    fn codon_orf_test() {
+        let mut com = CodonComputer::new(PATH_FA.to_owned(), "".to_owned());
+        com.set_norm(false);
+        com.set_frame(Frame::LongestOrf).unwrap();
+        let names = com.feature_names();
+        let count = |vec: &[f64], codon: &str| vec[names.iter().position(|n| n == codon).unwrap()];
+        // frame 1 is open, frame 0 hits a stop and frame 2 is shorter
+        let vec = com.vectorise_one(b"GAATAAGCCCGGG");
+        assert_eq!(vec.iter().sum::<f64>(), 4.0);
+        assert_eq!(count(&vec, "AAT"), 1.0);
+        assert_eq!(count(&vec, "GGG"), 1.0);
+        // stops in every forward frame, TTA TTA TTA of the reverse strand is open
+        let vec = com.vectorise_one(b"TAATAATAA");
+        assert_eq!(count(&vec, "TTA"), 3.0);
+        assert_eq!(vec.iter().sum::<f64>(), 3.0);
+    }
+
+    #[test]
+    fn codon_file_test() {
+        let out_path = "../test_data/computed_reads.codons";
+        let mut com = CodonComputer::new(PATH_FA.to_owned(), out_path.to_owned());
+        com.set_format(OutputFormat {
+            header: true,
+            ids: IdPolicy::First,
+            ..OutputFormat::new("\t")
+        });
+        com.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        let mut lines = vectors.lines();
+        assert!(lines.next().unwrap().starts_with("id\tAAA\tAAC\t"));
+        for line in lines {
+            let total: f64 = line
+                .split('\t')
+                .skip(1)
+                .map(|val| val.parse::<f64>().unwrap())
+                .sum();
+            assert!((total - 1.0).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use kmer::encoder::{Dna, Encoder};
+
+// names of the columns of base_features
+pub const BASE_FEATURES: [&str; 5] = ["gc", "gc_skew", "at_skew", "length", "entropy"];
+
+// GC fraction, GC skew (G - C) / (G + C), AT skew (A - T) / (A + T), length in bases and
+// Shannon entropy of the base frequencies in bits, bases other than ACGT(U) only count
+// towards the length
+pub fn base_features(seq: &[u8]) -> [f64; 5] {
+    let mut counts = [0_f64; 4];
+    for code in seq.iter().filter_map(|&base| Dna.encode(base)) {
+        counts[code as usize] += 1_f64;
+    }
+    let [a, c, g, t] = counts;
+    let total = a + c + g + t;
+    let ratio = |num: f64, den: f64| if den > 0_f64 { num / den } else { 0_f64 };
+    let entropy = counts
+        .iter()
+        .filter(|&&count| count > 0_f64)
+        .map(|&count| {
+            let p = count / total;
+            -p * p.log2()
+        })
+        .sum::<f64>();
+    [
+        ratio(g + c, total),
+        ratio(g - c, g + c),
+        ratio(a - t, a + t),
+        seq.len() as f64,
+        entropy,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_features_test() {
+        assert_eq!(base_features(b"ACGT"), [0.5, 0.0, 0.0, 4.0, 2.0]);
+        // G 3/5, C 1/5 and A 1/5 of the ACGT bases
+        let features = base_features(b"GGGCNA");
+        assert_eq!(features[..4], [0.8, 0.5, 1.0, 6.0]);
+        let entropy = -(0.6 * 0.6_f64.log2()) - 2.0 * 0.2 * 0.2_f64.log2();
+        assert!((features[4] - entropy).abs() < 1e-12);
+        assert_eq!(base_features(b"NN"), [0.0, 0.0, 0.0, 2.0, 0.0]);
+    }
+}
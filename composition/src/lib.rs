@@ -1,3 +1,8 @@
 pub mod cgr;
+pub mod codon;
+pub mod features;
+pub mod markov;
 pub mod oligo;
 pub mod oligocgr;
+pub mod render;
+pub mod stats;
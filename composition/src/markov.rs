@@ -0,0 +1,110 @@
+use kmer::{kmer::KmerGenerator, Kmer};
+
+// how observed counts are compared against the expected counts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Enrichment {
+    // observed / expected
+    Ratio,
+    // ln((observed + 1) / (expected + 1))
+    LogOdds,
+}
+
+// fixed order Markov model of a single sequence
+pub struct MarkovModel {
+    order: usize,
+    // (order + 1)-mer frequencies
+    words: Vec<f64>,
+    // order-mer frequencies, the conditioning contexts
+    contexts: Vec<f64>,
+}
+
+fn frequencies(seq: &[u8], size: usize) -> Vec<f64> {
+    let mut freqs = vec![0_f64; 1 << (2 * size)];
+    let mut total = 0_f64;
+    for (fmer, _) in KmerGenerator::new(seq, size) {
+        freqs[fmer as usize] += 1_f64;
+        total += 1_f64;
+    }
+    freqs.iter_mut().for_each(|f| *f /= f64::max(1_f64, total));
+    freqs
+}
+
+impl MarkovModel {
+    pub fn fit(seq: &[u8], order: usize) -> Self {
+        Self {
+            order,
+            words: frequencies(seq, order + 1),
+            contexts: if order == 0 {
+                vec![1_f64]
+            } else {
+                frequencies(seq, order)
+            },
+        }
+    }
+
+    // probability of the forward k-mer under the model
+    pub fn probability(&self, kmer: Kmer, ksize: usize) -> f64 {
+        let word = |start: usize, size: usize| -> usize {
+            let shift = 2 * (ksize - start - size);
+            ((kmer >> shift) & ((1 << (2 * size)) - 1)) as usize
+        };
+        let mut prob = self.words[word(0, self.order + 1)];
+        for start in 1..=ksize - self.order - 1 {
+            let context = self.contexts[word(start, self.order)];
+            if context == 0_f64 {
+                return 0_f64;
+            }
+            prob *= self.words[word(start, self.order + 1)] / context;
+        }
+        prob
+    }
+
+    // expected count of a canonical k-mer among total k-mers
+    pub fn expected(&self, kmer: Kmer, ksize: usize, total: f64) -> f64 {
+        let rmer = KmerGenerator::rev_comp(kmer, ksize);
+        if rmer == kmer {
+            return total * self.probability(kmer, ksize);
+        }
+        total * (self.probability(kmer, ksize) + self.probability(rmer, ksize))
+    }
+}
+
+impl Enrichment {
+    pub fn score(self, observed: f64, expected: f64) -> f64 {
+        match self {
+            Enrichment::Ratio if expected == 0_f64 => 0_f64,
+            Enrichment::Ratio => observed / expected,
+            Enrichment::LogOdds => ((observed + 1_f64) / (expected + 1_f64)).ln(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markov_probability_test() {
+        // order 0, uniform bases
+        let model = MarkovModel::fit(b"ACGTACGT", 0);
+        assert_eq!(model.probability(0, 2), 1.0 / 16.0);
+        // order 1, A is always followed by C
+        let model = MarkovModel::fit(b"ACGTACGT", 1);
+        // AC = 0001
+        assert!((model.probability(0b0001, 2) - 2.0 / 7.0).abs() < 1e-9);
+        // ACG = 000110, f(AC) * f(CG) / f(C)
+        assert!((model.probability(0b000110, 3) - 16.0 / 49.0).abs() < 1e-9);
+        // AA never occurs
+        assert_eq!(model.probability(0, 3), 0.0);
+        // ACG and CGT are reverse complements
+        let expected = model.expected(0b000110, 3, 6.0);
+        assert!((expected - 6.0 * 32.0 / 49.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn enrichment_score_test() {
+        assert_eq!(Enrichment::Ratio.score(4.0, 2.0), 2.0);
+        assert_eq!(Enrichment::Ratio.score(4.0, 0.0), 0.0);
+        assert_eq!(Enrichment::LogOdds.score(1.0, 1.0), 0.0);
+    }
+}
@@ -1,17 +1,179 @@
-use kmer::kmer::KmerGenerator;
-use kmer::numeric_to_kmer;
+use crate::features::{base_features, BASE_FEATURES};
+use crate::markov::{Enrichment, MarkovModel};
+use crate::stats::{median, ColumnMoments, ColumnScaler, RobustModel, Scaling};
+use kmer::kmer::{
+    compress_homopolymers, hard_mask, reverse_complement, KmerGenerator, MAX_DENSE_KSIZE,
+};
+use kmer::{
+    numeric_to_kmer_in,
+    segments::{segments, windows, SEGMENT_SIZE},
+    sketch::strand_neutral_hash,
+    spaced::{SpacedKmerGenerator, SpacedSeed},
+    stats::KmerStats,
+    strand::Strand,
+    Molecule,
+};
+use ktio::filter::RecordFilter;
 use ktio::mmap::MMWriter;
-use ktio::seq::{SeqFormat, Sequence, Sequences};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::collections::HashMap;
+use ktio::seq::{SeqFormat, SeqInput, Sequence, Sequences};
+use ktio::{
+    format::{IdPolicy, OutputCompression, OutputFormat},
+    matrix::MatrixWriter,
+};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::{BufRead, BufWriter, Write};
+use std::io::{BufWriter, Write};
 use std::sync::Arc;
 use std::sync::Mutex;
 
 const NUMBER_SIZE: usize = 8;
 const GB_4: usize = 4 * (1 << 30);
 
+// how the strands of a k-mer make up its column
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Orientation {
+    // a k-mer and its reverse complement share the column of the lexicographically
+    // smaller one
+    #[default]
+    Min,
+    // a k-mer and its reverse complement share a bucket of a strand-neutral hash, same
+    // number of columns as Min
+    Hash,
+    // every 4^k k-mer has a column, for stranded libraries, k-mers as observed
+    Forward,
+    // reverse complements of the observed k-mers
+    Reverse,
+}
+
+impl Orientation {
+    fn strand(self) -> Strand {
+        match self {
+            Orientation::Forward => Strand::Forward,
+            Orientation::Reverse => Strand::Reverse,
+            _ => Strand::Canonical,
+        }
+    }
+}
+
+// how k-mer counts of a record become its features
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Normalisation {
+    // raw k-mer counts
+    Counts,
+    // counts over the total k-mers of the record
+    #[default]
+    Freq,
+    // frequencies standardised per k-mer across all records of the input, in two passes
+    ZScore,
+    // relative abundance, counts over those expected from the base composition of the
+    // record (the odds ratio f(xy) / f(x)f(y) for dinucleotides)
+    OddsRatio,
+    // 1 for k-mers seen in the record and 0 otherwise, for Jaccard-style comparisons,
+    // packed vectors are written as one column of bits, see packed_bits
+    Presence {
+        packed: bool,
+    },
+    // observed counts scored against a Markov model of this order of each record
+    Markov {
+        order: usize,
+        enrichment: Enrichment,
+    },
+}
+
+// rescaling of the columns across all records
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rescale {
+    // parameters fitted to the input in a first pass, saved to <output>.scaling.json
+    Fit(Scaling),
+    // parameters saved for another dataset
+    Saved(ColumnScaler),
+}
+
+// what each row of the output is a vector of
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Rows {
+    #[default]
+    Records,
+    // each record followed by its reverse complement, with _rc after its ID, such as to
+    // train models on both strands
+    AugmentRc,
+    // windows of size bases every step bases, with the ID and coordinates of the window
+    Windows {
+        size: usize,
+        step: usize,
+    },
+}
+
+// which k-mers of a record are counted, the default is every canonical k-mer
+#[derive(Debug, Clone)]
+pub struct OligoKmers {
+    pub orientation: Orientation,
+    // k-mers of the care positions of a spaced seed, whose weight is the k-mer size
+    pub seed: Option<SpacedSeed>,
+    // use every stride-th k-mer position only, for approximate profiles
+    pub stride: usize,
+    // k-mers of homopolymer compressed sequences, for long reads
+    pub hpc: bool,
+    // leave out k-mers overlapping soft-masked (lowercase) bases, such as repeats
+    pub skip_masked: bool,
+    // letters of the k-mers of the header, U in place of T for RNA
+    pub molecule: Molecule,
+}
+
+impl Default for OligoKmers {
+    fn default() -> Self {
+        Self {
+            orientation: Orientation::default(),
+            seed: None,
+            stride: 1,
+            hpc: false,
+            skip_masked: false,
+            molecule: Molecule::default(),
+        }
+    }
+}
+
+// what is written, the default is a row of k-mer columns per record
+#[derive(Debug, Clone, Default)]
+pub struct OligoOutput {
+    // delimiter, precision of frequencies, header and ID columns of the output
+    pub format: OutputFormat,
+    pub rows: Rows,
+    // the length of each record after its ID
+    pub with_lengths: bool,
+    // GC content, GC and AT skews, length and base entropy of each record after its k-mers
+    pub extra_features: bool,
+    // k-mers and skipped k-mers of each record in <out_path>.stats
+    pub record_stats: bool,
+}
+
+// what the vectors are made of, the default is frequencies of canonical k-mers
+#[derive(Debug, Clone, Default)]
+pub struct OligoOptions {
+    pub kmers: OligoKmers,
+    pub normalisation: Normalisation,
+    pub scale: Option<Rescale>,
+    pub output: OligoOutput,
+    pub filter: Option<RecordFilter>,
+}
+
+// hex digits of a presence vector packed 8 columns to a byte, column i is bit i % 8 of
+// byte i / 8 (as numpy.unpackbits with bitorder="little")
+pub fn packed_bits(vec: &[f64]) -> String {
+    vec.chunks(8)
+        .map(|cols| {
+            let byte = cols
+                .iter()
+                .enumerate()
+                .filter(|(_, val)| **val > 0_f64)
+                .fold(0_u8, |byte, (bit, _)| byte | (1 << bit));
+            format!("{:02x}", byte)
+        })
+        .collect()
+}
+
 pub struct OligoComputer {
     in_path: String,
     out_path: String,
@@ -20,15 +182,29 @@ pub struct OligoComputer {
     threads: usize,
     pos_map: Vec<usize>,
     pos_kmer: HashMap<usize, u64>,
-    norm: bool,
-    delim: String,
     memory: usize,
-    header: bool,
+    stats: Mutex<KmerStats>,
+    segment_size: usize,
+    options: OligoOptions,
 }
 
 impl OligoComputer {
     pub fn new(in_path: String, out_path: String, ksize: usize) -> Self {
-        let (min_mer_pos_map, pos_min_mer_map, kcount) = KmerGenerator::kmer_pos_maps(ksize);
+        // large k uses the closed-form rank instead of a 4^k lookup table
+        let (min_mer_pos_map, pos_min_mer_map, kcount) = if ksize <= MAX_DENSE_KSIZE {
+            let (_, pos_min_mer_map, kcount) = KmerGenerator::kmer_pos_maps(ksize);
+            (
+                KmerGenerator::canonical_columns(ksize),
+                pos_min_mer_map,
+                kcount,
+            )
+        } else {
+            (
+                Vec::new(),
+                HashMap::new(),
+                KmerGenerator::canonical_count(ksize),
+            )
+        };
         Self {
             in_path,
             out_path,
@@ -36,11 +212,11 @@ impl OligoComputer {
             kcount,
             pos_map: min_mer_pos_map,
             pos_kmer: pos_min_mer_map,
-            threads: rayon::current_num_threads(),
-            norm: true,
-            delim: " ".to_owned(),
+            threads: ktio::threads::default_threads(),
             memory: GB_4,
-            header: false,
+            stats: Mutex::new(KmerStats::default()),
+            segment_size: SEGMENT_SIZE,
+            options: OligoOptions::default(),
         }
     }
 
@@ -48,26 +224,300 @@ impl OligoComputer {
         self.threads = threads;
     }
 
-    pub fn set_norm(&mut self, norm: bool) {
-        self.norm = norm;
+    pub fn set_max_memory(&mut self, memory: usize) {
+        self.memory = memory;
     }
 
-    pub fn set_delim(&mut self, delim: String) {
-        self.delim = delim;
+    // records longer than this are counted in parallel segments and summed
+    pub fn set_segment_size(&mut self, size: usize) {
+        self.segment_size = usize::max(1, size);
     }
 
-    pub fn set_max_memory(&mut self, memory: usize) {
-        self.memory = memory;
+    // combinations of k-mers, normalisation and output that cannot be computed are
+    // rejected here, before any input is read
+    pub fn set_options(&mut self, mut options: OligoOptions) -> Result<(), String> {
+        let dense = !self.pos_map.is_empty();
+        let kmers = &options.kmers;
+        let output = &options.output;
+        let min = kmers.orientation == Orientation::Min;
+        let stranded = !kmers.orientation.strand().is_canonical();
+        let windows = matches!(output.rows, Rows::Windows { .. });
+        let sparse = self.ksize > MAX_DENSE_KSIZE || output.format.sparse.is_some();
+        let packed = options.normalisation == Normalisation::Presence { packed: true };
+
+        if let Some(seed) = kmers.seed.as_ref() {
+            if seed.weight() != self.ksize {
+                return Err(format!(
+                    "Seed {} has weight {}, not the k-mer size {}",
+                    seed.pattern(),
+                    seed.weight(),
+                    self.ksize
+                ));
+            }
+        }
+        if stranded && !dense {
+            return Err(format!(
+                "Stranded k-mers support k-mer sizes up to {}",
+                MAX_DENSE_KSIZE
+            ));
+        }
+        match options.normalisation {
+            Normalisation::Markov { order, .. } => {
+                if order >= self.ksize {
+                    return Err(format!(
+                        "Markov order must be smaller than k-mer size: {}",
+                        self.ksize
+                    ));
+                }
+                if !dense || !min {
+                    return Err(
+                        "Markov model requires small k and min canonical k-mers".to_string()
+                    );
+                }
+                if kmers.seed.is_some() {
+                    return Err("Markov scores are not computed for spaced k-mers".to_string());
+                }
+            }
+            Normalisation::OddsRatio => {
+                if kmers.seed.is_some() {
+                    return Err("Odds ratios are not computed for spaced k-mers".to_string());
+                }
+                if !dense || !min {
+                    return Err("Odds ratios require small k and min canonical k-mers".to_string());
+                }
+            }
+            Normalisation::ZScore if !dense => {
+                return Err(format!(
+                    "Z-scores support k-mer sizes up to {}",
+                    MAX_DENSE_KSIZE
+                ))
+            }
+            _ => {}
+        }
+        if packed && (output.extra_features || windows || sparse || output.format.matrix.is_some())
+        {
+            return Err(
+                "Packed bits are written for dense presence vectors as text rows".to_string(),
+            );
+        }
+        if options.scale.is_some()
+            && (options.normalisation == Normalisation::ZScore || packed || windows || sparse)
+        {
+            return Err(
+                "Scaling applies to dense vectors of records other than z-scores".to_string(),
+            );
+        }
+        if windows {
+            if sparse || output.format.matrix.is_some() {
+                return Err("Windows are written as dense text rows".to_string());
+            }
+            if options.normalisation == Normalisation::ZScore {
+                return Err("Z-scores are not computed for windows".to_string());
+            }
+        }
+        if sparse {
+            if output.format.header || output.format.matrix.is_some() {
+                return Err(format!(
+                    "Sparse vectors, as of k > {}, are written as text rows without a header",
+                    MAX_DENSE_KSIZE
+                ));
+            }
+            if !matches!(
+                options.normalisation,
+                Normalisation::Counts | Normalisation::Freq
+            ) {
+                return Err("Only frequencies and counts are written as sparse vectors".to_string());
+            }
+        }
+
+        options.kmers.stride = usize::max(1, options.kmers.stride);
+        if let Rows::Windows { size, step } = options.output.rows {
+            options.output.rows = Rows::Windows {
+                size: usize::max(1, size),
+                step: usize::max(1, step),
+            };
+        }
+        // stranded k-mers are counted over all 4^k k-mers instead of canonical ones
+        self.kcount = if stranded {
+            1 << (2 * self.ksize)
+        } else {
+            KmerGenerator::canonical_count(self.ksize)
+        };
+        self.options = options;
+        Ok(())
+    }
+
+    // odds ratios are ratios of counts over an order 0 Markov model of each record
+    fn scores(&self) -> Option<(usize, Enrichment)> {
+        match self.options.normalisation {
+            Normalisation::OddsRatio => Some((0, Enrichment::Ratio)),
+            Normalisation::Markov { order, enrichment } => Some((order, enrichment)),
+            _ => None,
+        }
+    }
+
+    fn strand(&self) -> Strand {
+        self.options.kmers.orientation.strand()
+    }
+
+    fn packed(&self) -> bool {
+        self.options.normalisation == Normalisation::Presence { packed: true }
+    }
+
+    // bases covered by a k-mer
+    fn span(&self) -> usize {
+        self.options
+            .kmers
+            .seed
+            .as_ref()
+            .map_or(self.ksize, SpacedSeed::span)
+    }
+
+    // records, each followed by its reverse complement when augmenting
+    fn augmented(&self, records: impl Iterator<Item = Sequence>) -> impl Iterator<Item = Sequence> {
+        let augment_rc = self.options.output.rows == Rows::AugmentRc;
+        records.flat_map(move |record| {
+            let rc = augment_rc.then(|| Sequence {
+                n: record.n,
+                id: format!("{}_rc", record.id),
+                desc: record.desc.clone(),
+                seq: reverse_complement(&record.seq),
+                qual: record
+                    .qual
+                    .as_ref()
+                    .map(|qual| qual.iter().rev().cloned().collect()),
+            });
+            std::iter::once(record).chain(rc)
+        })
+    }
+
+    fn scaled(&self) -> bool {
+        self.options.scale.is_some()
+    }
+
+    // scaling fitted to the moments of the input and saved next to the output, or the
+    // given one when it has the same features
+    fn scaler(&self, moments: Option<&ColumnMoments>) -> Result<Option<ColumnScaler>, String> {
+        match (&self.options.scale, moments) {
+            (Some(Rescale::Fit(scaling)), Some(moments)) => {
+                let scaler = ColumnScaler::fit(*scaling, self.feature_names(), moments);
+                scaler.save(&format!("{}.scaling.json", self.out_path))?;
+                Ok(Some(scaler))
+            }
+            (Some(Rescale::Saved(scaler)), _) => {
+                if scaler.features() != self.feature_names() {
+                    return Err("Scaling parameters are of other features".to_string());
+                }
+                Ok(Some(scaler.clone()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // whether values are frequencies written to the precision of the output, counts and
+    // presence are written as they are unless scaled
+    fn fractional(&self) -> bool {
+        !matches!(
+            self.options.normalisation,
+            Normalisation::Counts | Normalisation::Presence { .. }
+        ) || self.scaled()
+    }
+
+    // columns of the vectors, k-mers then extra features
+    fn dims(&self) -> usize {
+        if self.options.output.extra_features {
+            self.kcount + BASE_FEATURES.len()
+        } else {
+            self.kcount
+        }
+    }
+
+    fn sequence<'a>(&self, seq: &'a [u8]) -> Cow<'a, [u8]> {
+        // masked bases become Ns before homopolymers are compressed
+        let seq = if self.options.kmers.skip_masked {
+            Cow::Owned(hard_mask(seq))
+        } else {
+            Cow::Borrowed(seq)
+        };
+        if self.options.kmers.hpc {
+            Cow::Owned(compress_homopolymers(&seq))
+        } else {
+            seq
+        }
+    }
+
+    pub fn kmer_stats(&self) -> KmerStats {
+        *self.stats.lock().unwrap()
+    }
+
+    // sampling factor is recorded so that raw counts can be rescaled
+    fn write_meta(&self) -> Result<(), String> {
+        if self.options.kmers.stride == 1 {
+            return Ok(());
+        }
+        let path = format!("{}.meta", self.out_path);
+        std::fs::write(&path, format!("stride\t{}\n", self.options.kmers.stride))
+            .map_err(|_| format!("Unable to write to file: {}", path))
+    }
+
+    fn stats_writer(&self) -> Result<Option<BufWriter<File>>, String> {
+        if !self.options.output.record_stats {
+            return Ok(None);
+        }
+        let path = format!("{}.stats", self.out_path);
+        let file = File::create(&path).map_err(|_| format!("Unable to write to file: {}", path))?;
+        let mut buff = BufWriter::new(file);
+        buff.write_all(b"id\tkmers\tskipped_kmers\n").unwrap();
+        Ok(Some(buff))
     }
 
-    pub fn set_header(&mut self, header: bool) {
-        self.header = header;
+    // column names of the output vectors
+    pub fn feature_names(&self) -> Vec<String> {
+        self.get_header()
+    }
+
+    // columns of the written rows, the length of records first when requested
+    fn column_names(&self) -> Vec<String> {
+        let mut names = if self.packed() {
+            vec!["bits".to_string()]
+        } else {
+            self.get_header()
+        };
+        if self.options.output.with_lengths {
+            names.insert(0, "length".to_string());
+        }
+        names
     }
 
     fn get_header(&self) -> Vec<String> {
+        let mut names = self.kmer_names();
+        if self.options.output.extra_features {
+            names.extend(BASE_FEATURES.iter().map(|name| name.to_string()));
+        }
+        names
+    }
+
+    fn kmer_names(&self) -> Vec<String> {
+        let molecule = self.options.kmers.molecule;
+        if !self.strand().is_canonical() {
+            return (0..self.kcount as u64)
+                .map(|kmer| numeric_to_kmer_in(kmer, self.ksize, molecule))
+                .collect();
+        }
+        if self.options.kmers.orientation == Orientation::Hash {
+            return (0..self.kcount)
+                .map(|bucket| format!("h{}", bucket))
+                .collect();
+        }
+        if self.pos_map.is_empty() {
+            return (0..4_u64.pow(self.ksize as u32))
+                .filter(|&kmer| kmer <= KmerGenerator::rev_comp(kmer, self.ksize))
+                .map(|kmer| numeric_to_kmer_in(kmer, self.ksize, molecule))
+                .collect();
+        }
         let mut kmers = vec![String::new(); self.kcount];
         for (&pos, &kmer) in self.pos_kmer.iter() {
-            kmers[pos] = numeric_to_kmer(kmer, self.ksize);
+            kmers[pos] = numeric_to_kmer_in(kmer, self.ksize, molecule);
         }
         kmers
     }
@@ -76,33 +526,69 @@ impl OligoComputer {
     // TODO remove stdin if needed
     #[cfg(not(tarpaulin_include))]
     pub fn vectorise(&self) -> Result<(), String> {
-        if self.in_path == "-" || !self.norm {
+        let output = &self.options.output;
+        if let Rows::Windows { size, step } = output.rows {
+            return self.vectorise_windows(size, step);
+        }
+        if self.sparse() {
+            return self.vectorise_sparse();
+        }
+        // scores are not fixed width, only frequencies can be memory mapped
+        if self.in_path == "-"
+            || self.options.normalisation != Normalisation::Freq
+            || output.format.ids == IdPolicy::First
+            || output.rows == Rows::AugmentRc
+            || self.scaled()
+            || output.with_lengths
+            || output.extra_features
+            || output.format.matrix.is_some()
+            || output.format.compression != OutputCompression::None
+        {
             return self.vectorise_batch();
         }
         self.vectorise_mmap()
     }
 
     fn vectorise_batch(&self) -> Result<(), String> {
-        let mut reader = ktio::seq::get_reader(&self.in_path).unwrap();
-        let buffer = reader
-            .fill_buf()
-            .map_err(|_| String::from("Invalid stream"))?;
-        let format = if buffer[0] == b'>' {
-            SeqFormat::Fasta
+        *self.stats.lock().unwrap() = KmerStats::default();
+        self.write_meta()?;
+        let format = &self.options.output.format;
+        let with_lengths = self.options.output.with_lengths;
+        let zscores = self.options.normalisation == Normalisation::ZScore;
+        // stdin is spooled when z-scores or scaling need a first pass
+        let first_pass = zscores || matches!(self.options.scale, Some(Rescale::Fit(_)));
+        let input = SeqInput::open(&self.in_path, 1 + first_pass as usize)?;
+        let moments = if first_pass {
+            Some(self.column_moments(&input)?)
         } else {
-            SeqFormat::Fastq
+            None
         };
-        let records = Sequences::new(format, reader).unwrap();
-        let file = File::create(&self.out_path)
-            .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
-        let mut out_buffer = BufWriter::new(file);
+        let scaler = self.scaler(moments.as_ref())?;
+        let moments = moments.filter(|_| zscores);
+        let mut records = input.records()?;
+        records.set_filter(self.options.filter.clone());
+        let mut matrix = match format.matrix {
+            Some(matrix) => Some(MatrixWriter::new(
+                &self.out_path,
+                matrix,
+                &self.column_names(),
+                format.ids == IdPolicy::First,
+            )?),
+            None => None,
+        };
+        let mut out_buffer = match matrix {
+            Some(_) => None,
+            None => Some(format.writer(&self.out_path)?),
+        };
+        let mut stats_buffer = self.stats_writer()?;
         let pool = rayon::ThreadPoolBuilder::new()
             .num_threads(self.threads)
             .build()
             .unwrap();
 
-        if self.header {
-            let header = self.get_header().join(&self.delim) + "\n";
+        if let (Some(out_buffer), Some(header)) =
+            (out_buffer.as_mut(), format.header_row(&self.column_names()))
+        {
             out_buffer.write_all(header.as_bytes()).unwrap();
         }
 
@@ -113,28 +599,69 @@ impl OligoComputer {
 
                 // Define a closure to handle buffer processing
                 let mut process_buffer = |buffer: &Vec<Sequence>| {
-                    let result = buffer
+                    let (kvecs, stats): (Vec<Vec<f64>>, Vec<KmerStats>) = buffer
                         .par_iter()
                         .map(|seq| {
-                            let kvec = self.vectorise_one(&seq.seq);
-                            let kvec_str: Vec<String> = kvec
-                                .iter()
-                                .map(|val| {
-                                    if self.norm {
-                                        format!("{:.*}", NUMBER_SIZE - 2, val)
+                            let stats = KmerStats::from_seq(&self.sequence(&seq.seq), self.ksize);
+                            let mut kvec = self.vectorise_one(&seq.seq);
+                            if let Some(moments) = moments.as_ref() {
+                                moments.standardise(&mut kvec);
+                            }
+                            if let Some(scaler) = scaler.as_ref() {
+                                scaler.apply(&mut kvec);
+                            }
+                            (kvec, stats)
+                        })
+                        .unzip();
+                    if let Some(matrix) = matrix.as_mut() {
+                        for (seq, kvec) in buffer.iter().zip(kvecs.iter()) {
+                            if with_lengths {
+                                let mut row = vec![seq.seq.len() as f64];
+                                row.extend_from_slice(kvec);
+                                matrix.write_row(&seq.id, &row).unwrap();
+                            } else {
+                                matrix.write_row(&seq.id, kvec).unwrap();
+                            }
+                        }
+                    } else if let Some(out_buffer) = out_buffer.as_mut() {
+                        let result: Vec<String> = buffer
+                            .par_iter()
+                            .zip(kvecs.par_iter())
+                            .map(|(seq, kvec)| {
+                                let mut kvec_str: Vec<String> = Vec::with_capacity(kvec.len() + 1);
+                                if with_lengths {
+                                    kvec_str.push(seq.seq.len().to_string());
+                                }
+                                if self.packed() {
+                                    kvec_str.push(packed_bits(kvec));
+                                    return format.row(&seq.id, &kvec_str);
+                                }
+                                kvec_str.extend(kvec.iter().map(|val| {
+                                    if self.fractional() {
+                                        format.number(*val, Some(NUMBER_SIZE - 2))
                                     } else {
                                         format!("{}", val)
                                     }
-                                })
-                                .collect();
-                            format!("{}\n", kvec_str.join(&self.delim))
-                        })
-                        .collect::<Vec<String>>()
-                        .join("");
-                    out_buffer.write_all(result.as_bytes()).unwrap();
+                                }));
+                                format.row(&seq.id, &kvec_str)
+                            })
+                            .collect();
+                        out_buffer.write_all(result.join("").as_bytes()).unwrap();
+                    }
+                    for (seq, &stats) in buffer.iter().zip(stats.iter()) {
+                        *self.stats.lock().unwrap() += stats;
+                        if let Some(stats_buffer) = stats_buffer.as_mut() {
+                            writeln!(
+                                stats_buffer,
+                                "{}\t{}\t{}",
+                                seq.id, stats.kmers, stats.skipped
+                            )
+                            .unwrap();
+                        }
+                    }
                 };
 
-                for record in records {
+                for record in self.augmented(records) {
                     total += record.seq.len();
                     buffer.push(record);
 
@@ -150,40 +677,196 @@ impl OligoComputer {
                 }
             });
         });
+        if let Some(matrix) = matrix {
+            matrix.finish()?;
+        }
+
+        Ok(())
+    }
+
+    // vectors of k > MAX_DENSE_KSIZE have too many columns to be held or written densely
+    fn sparse(&self) -> bool {
+        self.ksize > MAX_DENSE_KSIZE || self.options.output.format.sparse.is_some()
+    }
+
+    // rows of the non-zero columns, in column order
+    fn vectorise_sparse(&self) -> Result<(), String> {
+        let format = &self.options.output.format;
+        let with_lengths = self.options.output.with_lengths;
+        *self.stats.lock().unwrap() = KmerStats::default();
+        self.write_meta()?;
+        let mut records = SeqInput::open(&self.in_path, 1)?.records()?;
+        records.set_filter(self.options.filter.clone());
+        let mut records = self.augmented(records);
+        let mut out_buffer = format.writer(&self.out_path)?;
+        let mut stats_buffer = self.stats_writer()?;
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let (rows, stats): (Vec<String>, Vec<KmerStats>) = pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|seq| {
+                        // the length column, when written, shifts k-mers by one
+                        let offset = with_lengths as usize;
+                        let mut pairs: Vec<(usize, String)> = Vec::new();
+                        if with_lengths {
+                            pairs.push((0, seq.seq.len().to_string()));
+                        }
+                        pairs.extend(self.sparse_vector(&seq.seq).into_iter().map(|(col, val)| {
+                            if self.fractional() {
+                                (col + offset, format.number(val, Some(NUMBER_SIZE - 2)))
+                            } else {
+                                (col + offset, format!("{}", val))
+                            }
+                        }));
+                        (
+                            format.sparse_row(&seq.id, &pairs),
+                            KmerStats::from_seq(&self.sequence(&seq.seq), self.ksize),
+                        )
+                    })
+                    .unzip()
+            });
+            out_buffer
+                .write_all(rows.concat().as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+            for (seq, &stats) in batch.iter().zip(stats.iter()) {
+                *self.stats.lock().unwrap() += stats;
+                if let Some(stats_buffer) = stats_buffer.as_mut() {
+                    writeln!(
+                        stats_buffer,
+                        "{}\t{}\t{}",
+                        seq.id, stats.kmers, stats.skipped
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // rows of <id> <start> <end> and the vector of each window, coordinates are 0-based
+    // and end exclusive
+    fn vectorise_windows(&self, size: usize, step: usize) -> Result<(), String> {
+        *self.stats.lock().unwrap() = KmerStats::default();
+        self.write_meta()?;
+        let mut records = SeqInput::open(&self.in_path, 1)?.records()?;
+        records.set_filter(self.options.filter.clone());
+        let mut out_buffer = self.options.output.format.writer(&self.out_path)?;
+        let mut stats_buffer = self.stats_writer()?;
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+        // windows are always written with their IDs
+        let format = OutputFormat {
+            ids: IdPolicy::First,
+            ..self.options.output.format.clone()
+        };
+        let mut names = vec!["start".to_string(), "end".to_string()];
+        names.extend(self.get_header());
+        if let Some(header) = format.header_row(&names) {
+            out_buffer
+                .write_all(header.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+        }
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let (rows, stats): (Vec<String>, Vec<KmerStats>) = pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|seq| {
+                        let rows: Vec<String> = windows(seq.seq.len(), size, step)
+                            .par_iter()
+                            .map(|&(start, end)| {
+                                let mut fields = vec![start.to_string(), end.to_string()];
+                                fields.extend(self.vectorise_one(&seq.seq[start..end]).iter().map(
+                                    |val| {
+                                        if self.fractional() {
+                                            format.number(*val, Some(NUMBER_SIZE - 2))
+                                        } else {
+                                            format!("{}", val)
+                                        }
+                                    },
+                                ));
+                                format.row(&seq.id, &fields)
+                            })
+                            .collect();
+                        (
+                            rows.concat(),
+                            KmerStats::from_seq(&self.sequence(&seq.seq), self.ksize),
+                        )
+                    })
+                    .unzip()
+            });
+            out_buffer
+                .write_all(rows.concat().as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+            for (seq, &stats) in batch.iter().zip(stats.iter()) {
+                *self.stats.lock().unwrap() += stats;
+                if let Some(stats_buffer) = stats_buffer.as_mut() {
+                    writeln!(
+                        stats_buffer,
+                        "{}\t{}\t{}",
+                        seq.id, stats.kmers, stats.skipped
+                    )
+                    .unwrap();
+                }
+            }
+        }
 
         Ok(())
     }
 
     fn vectorise_mmap(&self) -> Result<(), String> {
         // only works for normalised (we need fixed length outputs)
-        assert!(self.norm);
-        let per_line_size = self.kcount * (NUMBER_SIZE + 1);
+        assert_eq!(self.options.normalisation, Normalisation::Freq);
+        let format = &self.options.output.format;
+        let filter = self.options.filter.as_ref();
+        *self.stats.lock().unwrap() = KmerStats::default();
+        self.write_meta()?;
+        // frequencies are at most 1, so all have the same width
+        let precision = format.precision.unwrap_or(NUMBER_SIZE - 2);
+        let per_line_size =
+            self.kcount * (precision + 2) + (self.kcount - 1) * format.delim.len() + 1;
         // pre-calculate file size
         let mut estimated_file_size = {
             let format = SeqFormat::get(&self.in_path).unwrap();
             let reader = ktio::seq::get_reader(&self.in_path).unwrap();
-            Sequences::seq_stats(format, reader).seq_count
+            Sequences::seq_stats_filtered(format, reader, filter).seq_count
         } * per_line_size;
-        let mut header = String::new();
-        if self.header {
-            header = self.get_header().join(&self.delim) + "\n";
-            estimated_file_size += header.len();
-        }
+        let header = format.header_row(&self.get_header()).unwrap_or_default();
+        estimated_file_size += header.len();
         // memmap
         let mut mmap = ktio::mmap::mmap_file_for_writing(&self.out_path, estimated_file_size)?;
         // get reader
-        let format = SeqFormat::get(&self.in_path).unwrap();
+        let seq_format = SeqFormat::get(&self.in_path).unwrap();
         let reader = ktio::seq::get_reader(&self.in_path).unwrap();
-        let records = Sequences::new(format, reader).unwrap();
+        let mut records = Sequences::new(seq_format, reader).unwrap();
+        records.set_filter(filter.cloned());
         let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
             .num_threads(self.threads)
             .build()
             .unwrap();
         let records_arc = Arc::new(Mutex::new(records));
+        // records finish out of order, stats are sorted before writing
+        let record_stats: Mutex<Vec<(usize, String, KmerStats)>> = Mutex::new(Vec::new());
 
         pool.scope(|scope| {
             let mm_slice: MMWriter<u8> = MMWriter::new(&mut mmap[..]);
-            if self.header {
+            if !header.is_empty() {
                 unsafe {
                     mm_slice.write_at(header.as_bytes(), 0);
                 }
@@ -191,17 +874,28 @@ impl OligoComputer {
             for _ in 0..self.threads {
                 let records_arc_clone = Arc::clone(&records_arc);
                 let header_len = header.len();
+                let record_stats = &record_stats;
                 scope.spawn(move |_| {
                     loop {
                         let record = { records_arc_clone.lock().unwrap().next() };
                         if let Some(record) = record {
+                            let stats =
+                                KmerStats::from_seq(&self.sequence(&record.seq), self.ksize);
+                            *self.stats.lock().unwrap() += stats;
+                            if self.options.output.record_stats {
+                                record_stats.lock().unwrap().push((
+                                    record.n,
+                                    record.id.clone(),
+                                    stats,
+                                ));
+                            }
                             let kvec = self.vectorise_one(&record.seq);
                             // optimise this with pre-sized string
                             let kvec_str: Vec<String> = kvec
                                 .iter()
-                                .map(|val| format!("{:.*}", NUMBER_SIZE - 2, val))
+                                .map(|val| format!("{:.*}", precision, val))
                                 .collect();
-                            let kvec_str = format!("{}\n", kvec_str.join(&self.delim));
+                            let kvec_str = format.row(&record.id, &kvec_str);
                             let start_pos = kvec_str.len() * record.n;
                             unsafe {
                                 mm_slice.write_at(kvec_str.as_bytes(), start_pos + header_len);
@@ -215,37 +909,277 @@ impl OligoComputer {
             }
         });
 
+        if let Some(mut stats_buffer) = self.stats_writer()? {
+            let mut record_stats = record_stats.into_inner().unwrap();
+            record_stats.sort_by_key(|(n, _, _)| *n);
+            for (_, id, stats) in record_stats {
+                writeln!(stats_buffer, "{}\t{}\t{}", id, stats.kmers, stats.skipped).unwrap();
+            }
+        }
+
         Ok(())
     }
 
+    // first pass of z-scores, mean and standard deviation of the frequency of each k-mer
+    fn column_moments(&self, input: &SeqInput) -> Result<ColumnMoments, String> {
+        let mut records = input.records()?;
+        records.set_filter(self.options.filter.clone());
+        let mut records = self.augmented(records);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+        let mut moments = ColumnMoments::new(self.dims());
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            moments = moments.merge(pool.install(|| {
+                batch
+                    .par_iter()
+                    .fold(
+                        || ColumnMoments::new(self.dims()),
+                        |moments, record| moments.push(&self.vectorise_one(&record.seq)),
+                    )
+                    .reduce(|| ColumnMoments::new(self.dims()), ColumnMoments::merge)
+            }));
+        }
+
+        Ok(moments)
+    }
+
+    // robust distance of the composition of each record from the bulk of the records,
+    // writes <id>\t<distance>\t<score>\t<outlier> where the score is the distance over the
+    // median distance and records scoring above threshold are outliers (e.g. contamination)
+    // returns the number of outliers and of records
+    pub fn compute_outliers(&self, threshold: f64) -> Result<(usize, usize), String> {
+        let mut records = SeqInput::open(&self.in_path, 1)?.records()?;
+        records.set_filter(self.options.filter.clone());
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+        let mut ids = Vec::new();
+        let mut vectors = Vec::new();
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            vectors.extend(pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|record| self.vectorise_one(&record.seq))
+                    .collect::<Vec<Vec<f64>>>()
+            }));
+            ids.extend(batch.into_iter().map(|record| record.id));
+        }
+        let model = RobustModel::fit(&vectors)?;
+        let distances: Vec<f64> = pool.install(|| {
+            vectors
+                .par_iter()
+                .map(|vector| model.distance(vector))
+                .collect()
+        });
+        let median = median(&distances);
+
+        let file = File::create(&self.out_path)
+            .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+        let mut buff = BufWriter::new(file);
+        let mut outliers = 0;
+        writeln!(buff, "id\tdistance\tscore\toutlier")
+            .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+        for (id, distance) in ids.iter().zip(distances) {
+            let score = distance / f64::max(median, f64::EPSILON);
+            let outlier = score > threshold;
+            outliers += outlier as usize;
+            writeln!(buff, "{}\t{:.6}\t{:.6}\t{}", id, distance, score, outlier)
+                .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+        }
+
+        Ok((outliers, ids.len()))
+    }
+
+    // oligo vector of one record, for feature matrices combining it with others
+    pub fn record_vector(&self, seq: &[u8]) -> Vec<f64> {
+        self.vectorise_one(seq)
+    }
+
     fn vectorise_one(&self, seq: &[u8]) -> Vec<f64> {
-        let mut vec = vec![0_f64; self.kcount];
-        let mut total = 0_f64;
+        let mut vec = self.kmer_vector(seq);
+        if self.options.output.extra_features {
+            vec.extend(base_features(seq));
+        }
+        vec
+    }
 
-        for (fmer, rmer) in KmerGenerator::new(seq, self.ksize) {
-            let min_mer = u64::min(fmer, rmer);
-            unsafe {
-                // we already know the size of the vector and
-                // min_mer is absolutely smaller than that
-                let &min_mer_pos = self.pos_map.get_unchecked(min_mer as usize);
-                *vec.get_unchecked_mut(min_mer_pos) += 1_f64;
-                total += 1_f64;
+    fn kmer_vector(&self, seq: &[u8]) -> Vec<f64> {
+        let seq = &self.sequence(seq);
+        let (mut vec, total) = if seq.len() > self.segment_size {
+            // segments start at multiples of the stride, so sampled positions are kept
+            let stride = self.options.kmers.stride;
+            let size = self.segment_size.div_ceil(stride) * stride;
+            segments(seq.len(), self.span(), size)
+                .par_iter()
+                .map(|&(start, end)| self.count_kmers(&seq[start..end]))
+                .reduce(
+                    || (vec![0_f64; self.kcount], 0_f64),
+                    |(mut vec, total), (other, other_total)| {
+                        vec.iter_mut().zip(other).for_each(|(el, val)| *el += val);
+                        (vec, total + other_total)
+                    },
+                )
+        } else {
+            self.count_kmers(seq)
+        };
+        if let Some((order, enrichment)) = self.scores() {
+            let model = MarkovModel::fit(seq, order);
+            for (pos, el) in vec.iter_mut().enumerate() {
+                let expected = model.expected(self.pos_kmer[&pos], self.ksize, total);
+                *el = enrichment.score(*el, expected);
             }
+            return vec;
         }
-        if self.norm {
-            vec.iter_mut().for_each(|el| *el /= f64::max(1_f64, total));
+        match self.options.normalisation {
+            Normalisation::Presence { .. } => vec
+                .iter_mut()
+                .for_each(|el| *el = (*el > 0_f64) as u8 as f64),
+            Normalisation::Counts => {}
+            _ => vec.iter_mut().for_each(|el| *el /= f64::max(1_f64, total)),
         }
         vec
     }
+
+    // non-zero columns of the vector of a sequence, frequencies unless counts are asked for
+    fn sparse_vector(&self, seq: &[u8]) -> Vec<(usize, f64)> {
+        let mut vec = self.sparse_kmer_vector(seq);
+        if self.options.output.extra_features {
+            vec.extend(
+                base_features(seq)
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, val)| *val != 0_f64)
+                    .map(|(col, val)| (self.kcount + col, val)),
+            );
+        }
+        vec
+    }
+
+    fn sparse_kmer_vector(&self, seq: &[u8]) -> Vec<(usize, f64)> {
+        let seq = &self.sequence(seq);
+        let (vec, total) = if seq.len() > self.segment_size {
+            // segments start at multiples of the stride, so sampled positions are kept
+            let stride = self.options.kmers.stride;
+            let size = self.segment_size.div_ceil(stride) * stride;
+            segments(seq.len(), self.span(), size)
+                .par_iter()
+                .map(|&(start, end)| self.count_kmers_sparse(&seq[start..end]))
+                .reduce(
+                    || (BTreeMap::new(), 0_f64),
+                    |(mut vec, total), (other, other_total)| {
+                        for (col, val) in other {
+                            *vec.entry(col).or_insert(0_f64) += val;
+                        }
+                        (vec, total + other_total)
+                    },
+                )
+        } else {
+            self.count_kmers_sparse(seq)
+        };
+        let scale = match self.options.normalisation {
+            Normalisation::Counts => 1_f64,
+            _ => f64::max(1_f64, total),
+        };
+        vec.into_iter()
+            .map(|(col, val)| (col, val / scale))
+            .collect()
+    }
+
+    // column of a k-mer, canonical ranks are computed when there is no lookup table
+    #[inline]
+    fn kmer_index(&self, fmer: u64, rmer: u64) -> usize {
+        let strand = self.strand();
+        if !strand.is_canonical() {
+            return strand.pick(fmer, rmer) as usize;
+        }
+        if self.options.kmers.orientation == Orientation::Hash {
+            return (strand_neutral_hash(fmer, rmer) % self.kcount as u64) as usize;
+        }
+        let min_mer = u64::min(fmer, rmer);
+        if self.pos_map.is_empty() {
+            return KmerGenerator::canonical_rank(min_mer, self.ksize);
+        }
+        // min_mer is absolutely smaller than the size of the table
+        unsafe { *self.pos_map.get_unchecked(min_mer as usize) }
+    }
+
+    // visits the column of every k-mer of a sequence, spaced k-mers when there is a seed,
+    // and returns the number of k-mers
+    fn each_kmer(&self, seq: &[u8], mut visit: impl FnMut(usize)) -> f64 {
+        let mut total = 0_f64;
+        let mut count = |(fmer, rmer)| {
+            visit(self.kmer_index(fmer, rmer));
+            total += 1_f64;
+        };
+        let kmers = &self.options.kmers;
+        match kmers.seed.as_ref() {
+            Some(seed) => SpacedKmerGenerator::new(seq, seed)
+                .with_stride(kmers.stride)
+                .for_each(&mut count),
+            // min canonical columns come straight from the position map
+            None if kmers.orientation == Orientation::Min => KmerGenerator::new(seq, self.ksize)
+                .with_stride(kmers.stride)
+                .canonical_indices(&self.pos_map)
+                .for_each(|col| {
+                    visit(col);
+                    total += 1_f64;
+                }),
+            None => KmerGenerator::new(seq, self.ksize)
+                .with_stride(kmers.stride)
+                .for_each(&mut count),
+        }
+        total
+    }
+
+    // k-mer counts of a sequence and their total
+    fn count_kmers(&self, seq: &[u8]) -> (Vec<f64>, f64) {
+        let mut vec = vec![0_f64; self.kcount];
+        let total = self.each_kmer(seq, |col| unsafe {
+            // we already know the size of the vector and
+            // every column is smaller than that
+            *vec.get_unchecked_mut(col) += 1_f64;
+        });
+        (vec, total)
+    }
+
+    // counts of the k-mers seen in a sequence by column and their total
+    fn count_kmers_sparse(&self, seq: &[u8]) -> (BTreeMap<usize, f64>, f64) {
+        let mut vec = BTreeMap::new();
+        let total = self.each_kmer(seq, |col| *vec.entry(col).or_insert(0_f64) += 1_f64);
+        (vec, total)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ktio::format::SparseFormat;
     use std::fs;
 
     const PATH_FQ: &str = "../test_data/reads.fq";
 
+    // raw counts of the given k-mers
+    fn counts(kmers: OligoKmers) -> OligoOptions {
+        OligoOptions {
+            kmers,
+            normalisation: Normalisation::Counts,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn kmer_vec_norm_test() {
         let com = OligoComputer::new(PATH_FQ.to_owned(), "../test_data/reads.kmers".to_owned(), 4);
@@ -257,12 +1191,536 @@ mod tests {
     fn kmer_vec_unnorm_test() {
         let mut com =
             OligoComputer::new(PATH_FQ.to_owned(), "../test_data/reads.kmers".to_owned(), 4);
-        com.set_norm(false);
+        com.set_options(counts(OligoKmers::default())).unwrap();
         let kvec = com.vectorise_one(b"AAAANGAGA");
         assert_eq!(kvec[0], 1.0);
         assert_eq!(kvec.iter().fold(0.0, |acc, v| acc + v), 2.0);
     }
 
+    #[test]
+    fn kmer_vec_stranded_test() {
+        let mut com =
+            OligoComputer::new(PATH_FQ.to_owned(), "../test_data/reads.kmers".to_owned(), 3);
+        let orientation = |orientation| OligoKmers {
+            orientation,
+            ..Default::default()
+        };
+        com.set_options(counts(orientation(Orientation::Reverse)))
+            .unwrap();
+        assert_eq!(com.get_header().len(), 64);
+        // AAA is counted as TTT
+        let kvec = com.vectorise_one(b"AAAA");
+        assert_eq!(kvec[63], 2.0);
+        assert_eq!(com.get_header()[63], "TTT");
+        com.set_options(counts(orientation(Orientation::Forward)))
+            .unwrap();
+        assert_eq!(com.vectorise_one(b"AAAA")[0], 2.0);
+        com.set_options(counts(orientation(Orientation::Min)))
+            .unwrap();
+        assert_eq!(com.vectorise_one(b"AAAA").len(), 32);
+        let mut com = OligoComputer::new(
+            PATH_FQ.to_owned(),
+            "../test_data/reads.kmers".to_owned(),
+            13,
+        );
+        assert!(com
+            .set_options(counts(orientation(Orientation::Forward)))
+            .is_err());
+    }
+
+    #[test]
+    fn outliers_test() {
+        let in_path = "../test_data/computed_outliers.fa";
+        let out_path = "../test_data/computed_outliers.tsv";
+        let bases = b"ACGT";
+        let mut state = 42_u64;
+        let mut fasta = String::new();
+        // AT rich contigs and a GC rich one
+        for idx in 0..40 {
+            let seq: String = (0..2000)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    let at_rich = ((state >> 33) % 10 < 7) != (idx == 39);
+                    let strand = (state >> 40) as usize % 2;
+                    bases[if at_rich { strand * 3 } else { 1 + strand }] as char
+                })
+                .collect();
+            fasta.push_str(&format!(">contig_{}\n{}\n", idx, seq));
+        }
+        fs::write(in_path, fasta).unwrap();
+        let com = OligoComputer::new(in_path.to_owned(), out_path.to_owned(), 3);
+        let (outliers, total) = com.compute_outliers(3.0).unwrap();
+        assert_eq!(total, 40);
+        assert_eq!(outliers, 1);
+        let res = fs::read_to_string(out_path).unwrap();
+        assert!(res.starts_with("id\tdistance\tscore\toutlier\n"));
+        assert!(res.lines().last().unwrap().ends_with("\ttrue"));
+    }
+
+    #[test]
+    fn kmer_vec_segments_test() {
+        let mut com =
+            OligoComputer::new(PATH_FQ.to_owned(), "../test_data/reads.kmers".to_owned(), 4);
+        com.set_options(counts(OligoKmers {
+            stride: 3,
+            ..Default::default()
+        }))
+        .unwrap();
+        let seq = b"ACGTTGCANNACGTAGCTAGCTAGGATCGATCGANACGTTTAGCA";
+        let whole = com.vectorise_one(seq);
+        // segments of 8 are rounded up to 9 to keep every third position
+        com.set_segment_size(8);
+        assert_eq!(com.vectorise_one(seq), whole);
+    }
+
+    #[test]
+    fn kmer_vec_hpc_test() {
+        let mut com =
+            OligoComputer::new(PATH_FQ.to_owned(), "../test_data/reads.kmers".to_owned(), 3);
+        com.set_options(counts(OligoKmers::default())).unwrap();
+        let compressed = com.vectorise_one(b"ACGTGCA");
+        com.set_options(counts(OligoKmers {
+            hpc: true,
+            ..Default::default()
+        }))
+        .unwrap();
+        let kvec = com.vectorise_one(b"AACCCGTTGCCA");
+        assert_eq!(kvec, compressed);
+        assert_eq!(kvec.iter().sum::<f64>(), 5.0);
+        // segments are of the compressed sequence
+        com.set_segment_size(4);
+        assert_eq!(com.vectorise_one(b"AACCCGTTGCCA"), compressed);
+    }
+
+    #[test]
+    fn kmer_vec_stride_test() {
+        let mut com = OligoComputer::new(
+            PATH_FQ.to_owned(),
+            "../test_data/computed_fa_stride.kmers".to_owned(),
+            4,
+        );
+        com.set_options(counts(OligoKmers {
+            stride: 2,
+            ..Default::default()
+        }))
+        .unwrap();
+        // AAAA at 0 and 2 are sampled, AAAA at 1 and AAAC at 3 are not
+        let kvec = com.vectorise_one(b"AAAAAAC");
+        assert_eq!(kvec[0], 2.0);
+        assert_eq!(kvec.iter().fold(0.0, |acc, v| acc + v), 2.0);
+        com.vectorise_batch().unwrap();
+        assert_eq!(
+            fs::read_to_string("../test_data/computed_fa_stride.kmers.meta").unwrap(),
+            "stride\t2\n"
+        );
+    }
+
+    #[test]
+    fn kmer_vec_hash_test() {
+        let mut com =
+            OligoComputer::new(PATH_FQ.to_owned(), "../test_data/reads.kmers".to_owned(), 4);
+        com.set_options(counts(OligoKmers {
+            orientation: Orientation::Hash,
+            ..Default::default()
+        }))
+        .unwrap();
+        let kvec = com.vectorise_one(b"AACGTTGCA");
+        assert_eq!(kvec.len(), 136);
+        assert_eq!(kvec.iter().fold(0.0, |acc, v| acc + v), 6.0);
+        // reverse complement gives the same vector
+        assert_eq!(kvec, com.vectorise_one(b"TGCAACGTT"));
+        assert_eq!(com.get_header()[135], "h135");
+    }
+
+    #[test]
+    fn kmer_vec_markov_test() {
+        let mut com =
+            OligoComputer::new(PATH_FQ.to_owned(), "../test_data/reads.kmers".to_owned(), 2);
+        let markov = |order, enrichment| OligoOptions {
+            normalisation: Normalisation::Markov { order, enrichment },
+            ..Default::default()
+        };
+        assert!(com.set_options(markov(2, Enrichment::Ratio)).is_err());
+        com.set_options(markov(0, Enrichment::Ratio)).unwrap();
+        // uniform bases, AA/TT observed twice and expected 7 * 2 / 16 times
+        let kvec = com.vectorise_one(b"AACCGGTT");
+        let aa = com.pos_map[0];
+        assert!((kvec[aa] - 2.0 / (7.0 * 2.0 / 16.0)).abs() < 1e-9);
+        com.set_options(markov(1, Enrichment::LogOdds)).unwrap();
+        // order k - 1 model reproduces the observed counts
+        let kvec = com.vectorise_one(b"AACCGGTT");
+        assert!(kvec.iter().all(|v| v.abs() < 1e-9));
+    }
+
+    #[test]
+    fn vec_with_ids_test() {
+        let out_path = "../test_data/computed_fa_with_ids.kmers";
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 3);
+        let with_lengths = |format| OligoOptions {
+            normalisation: Normalisation::Counts,
+            output: OligoOutput {
+                format,
+                with_lengths: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        com.set_options(with_lengths(OutputFormat {
+            ids: IdPolicy::First,
+            header: true,
+            ..OutputFormat::new("\t")
+        }))
+        .unwrap();
+        com.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        let mut lines = vectors.lines();
+        assert!(lines.next().unwrap().starts_with("id\tlength\tAAA\t"));
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("Read_1\t72\t4\t4\t4\t2\t"));
+        assert!(lines.next().unwrap().starts_with("Read_2\t72\t"));
+
+        com.set_options(with_lengths(OutputFormat {
+            ids: IdPolicy::First,
+            sparse: Some(SparseFormat::Pairs),
+            ..OutputFormat::default()
+        }))
+        .unwrap();
+        com.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        assert!(vectors.starts_with("Read_1 0:72 1:4 2:4 3:4 4:2 "));
+    }
+
+    #[test]
+    fn vec_augment_rc_test() {
+        let out_path = "../test_data/computed_fa_augment_rc.kmers";
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 3);
+        com.set_options(OligoOptions {
+            kmers: OligoKmers {
+                orientation: Orientation::Forward,
+                ..Default::default()
+            },
+            normalisation: Normalisation::Counts,
+            output: OligoOutput {
+                format: OutputFormat {
+                    ids: IdPolicy::First,
+                    ..OutputFormat::new("\t")
+                },
+                rows: Rows::AugmentRc,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        com.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        let rows: Vec<Vec<&str>> = vectors
+            .lines()
+            .map(|line| line.split('\t').collect())
+            .collect();
+        let ids: Vec<&str> = rows.iter().map(|row| row[0]).collect();
+        assert_eq!(ids, vec!["Read_1", "Read_1_rc", "Read_2", "Read_2_rc"]);
+        // forward k-mers of the reverse complement are the reverse complements of the
+        // forward k-mers of the record
+        let (fwd, rev) = (&rows[0][1..], &rows[1][1..]);
+        for kmer in 0..64 {
+            assert_eq!(
+                fwd[kmer],
+                rev[KmerGenerator::rev_comp(kmer as u64, 3) as usize]
+            );
+        }
+    }
+
+    #[test]
+    fn kmer_vec_skip_masked_test() {
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), "".to_owned(), 3);
+        let skip_masked = |skip_masked| OligoKmers {
+            skip_masked,
+            ..Default::default()
+        };
+        com.set_options(counts(skip_masked(true))).unwrap();
+        // only AAC and ACG are left of the 7 3-mers
+        let kvec = com.vectorise_one(b"AACGtttTT");
+        assert_eq!(kvec.iter().sum::<f64>(), 2.0);
+        com.set_options(counts(skip_masked(false))).unwrap();
+        let kvec = com.vectorise_one(b"AACGtttTT");
+        assert_eq!(kvec.iter().sum::<f64>(), 7.0);
+    }
+
+    #[test]
+    fn kmer_vec_extra_features_test() {
+        let out_path = "../test_data/computed_fa_extra.kmers";
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 3);
+        let extra_features = |format| OligoOptions {
+            output: OligoOutput {
+                format,
+                extra_features: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        com.set_options(extra_features(OutputFormat::default()))
+            .unwrap();
+        let kvec = com.vectorise_one(b"ACGTGG");
+        assert_eq!(kvec.len(), 32 + 5);
+        assert_eq!(kvec[32..36], [4.0 / 6.0, 0.5, 0.0, 6.0]);
+        let names = com.feature_names();
+        assert_eq!(
+            names[32..],
+            ["gc", "gc_skew", "at_skew", "length", "entropy"]
+        );
+
+        com.set_options(extra_features(OutputFormat {
+            header: true,
+            ..OutputFormat::default()
+        }))
+        .unwrap();
+        com.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        let mut lines = vectors.lines();
+        assert!(lines.next().unwrap().ends_with(" length entropy"));
+        let row: Vec<&str> = lines.next().unwrap().split(' ').collect();
+        assert_eq!(row.len(), 37);
+        assert_eq!(row[35], "72.000000");
+    }
+
+    #[test]
+    fn vec_windows_test() {
+        let out_path = "../test_data/computed_fa_windows.kmers";
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 3);
+        com.set_options(OligoOptions {
+            normalisation: Normalisation::Counts,
+            output: OligoOutput {
+                format: OutputFormat {
+                    header: true,
+                    ..OutputFormat::new("\t")
+                },
+                rows: Rows::Windows { size: 40, step: 20 },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        com.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        let rows: Vec<Vec<&str>> = vectors
+            .lines()
+            .map(|line| line.split('\t').collect())
+            .collect();
+        assert_eq!(rows[0][..4], ["id", "start", "end", "AAA"]);
+        // 72 bp reads have windows at 0 and 20, each of 38 3-mers
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[1][..3], ["Read_1", "0", "40"]);
+        assert_eq!(rows[2][..3], ["Read_1", "20", "60"]);
+        assert_eq!(rows[3][..3], ["Read_2", "0", "40"]);
+        for row in &rows[1..] {
+            let total: f64 = row[3..].iter().map(|val| val.parse::<f64>().unwrap()).sum();
+            assert_eq!(total, 38.0);
+        }
+    }
+
+    #[test]
+    fn vec_normalisation_test() {
+        let out_path = "../test_data/computed_fa_zscore.kmers";
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 2);
+        let normalisation = |normalisation| OligoOptions {
+            normalisation,
+            ..Default::default()
+        };
+        com.set_options(normalisation(Normalisation::OddsRatio))
+            .unwrap();
+        let kvec = com.vectorise_one(b"AACCGGTT");
+        assert!((kvec[com.pos_map[0]] - 2.0 / (7.0 * 2.0 / 16.0)).abs() < 1e-9);
+        assert!(com
+            .set_options(OligoOptions {
+                kmers: OligoKmers {
+                    orientation: Orientation::Hash,
+                    ..Default::default()
+                },
+                normalisation: Normalisation::OddsRatio,
+                ..Default::default()
+            })
+            .is_err());
+
+        // two reads, every k-mer is one standard deviation either side of the mean or 0
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 2);
+        com.set_options(normalisation(Normalisation::ZScore))
+            .unwrap();
+        assert!(com
+            .set_options(OligoOptions {
+                normalisation: Normalisation::ZScore,
+                output: OligoOutput {
+                    rows: Rows::Windows { size: 40, step: 20 },
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .is_err());
+        com.set_options(normalisation(Normalisation::ZScore))
+            .unwrap();
+        com.vectorise().unwrap();
+        let rows: Vec<Vec<f64>> = fs::read_to_string(out_path)
+            .unwrap()
+            .lines()
+            .map(|line| line.split(' ').map(|val| val.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        for (a, b) in rows[0].iter().zip(rows[1].iter()) {
+            assert_eq!(a + b, 0.0);
+            assert!(a.abs() == 1.0 || *a == 0.0);
+        }
+
+        // stdin is spooled for the first pass
+        let mut com = OligoComputer::new("-".to_owned(), out_path.to_owned(), 2);
+        assert!(com
+            .set_options(normalisation(Normalisation::ZScore))
+            .is_ok());
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 13);
+        assert!(com
+            .set_options(normalisation(Normalisation::ZScore))
+            .is_err());
+    }
+
+    #[test]
+    fn vec_scaling_test() {
+        let out_path = "../test_data/computed_fa_scaled.kmers";
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 3);
+        let scale = |scale| OligoOptions {
+            scale: Some(scale),
+            output: OligoOutput {
+                format: OutputFormat::new("\t"),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        com.set_options(scale(Rescale::Fit(Scaling::MinMax)))
+            .unwrap();
+        com.vectorise().unwrap();
+        let scaled = fs::read_to_string(out_path).unwrap();
+        let rows: Vec<Vec<f64>> = scaled
+            .lines()
+            .map(|line| line.split('\t').map(|val| val.parse().unwrap()).collect())
+            .collect();
+        // two records, each column is 0 for one and 1 for the other unless they are equal
+        for (&a, &b) in rows[0].iter().zip(rows[1].iter()) {
+            assert!((a == 0.0 && b == 1.0) || (a == 1.0 && b == 0.0) || (a == 0.0 && b == 0.0));
+        }
+
+        // the saved parameters transform the same data the same way
+        let scaler = ColumnScaler::load(&format!("{}.scaling.json", out_path)).unwrap();
+        assert_eq!(scaler.scaling(), Scaling::MinMax);
+        com.set_options(scale(Rescale::Saved(scaler.clone())))
+            .unwrap();
+        com.vectorise().unwrap();
+        assert_eq!(fs::read_to_string(out_path).unwrap(), scaled);
+
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 4);
+        com.set_options(scale(Rescale::Saved(scaler))).unwrap();
+        assert!(com.vectorise().is_err());
+        assert!(com
+            .set_options(OligoOptions {
+                normalisation: Normalisation::ZScore,
+                ..scale(Rescale::Fit(Scaling::MinMax))
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn vec_presence_test() {
+        let out_path = "../test_data/computed_fa_presence.kmers";
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 3);
+        let presence = |packed, format| OligoOptions {
+            normalisation: Normalisation::Presence { packed },
+            output: OligoOutput {
+                format,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        com.set_options(presence(false, OutputFormat::default()))
+            .unwrap();
+        // AAA twice, AAC and ACG once
+        let kvec = com.vectorise_one(b"AAAACG");
+        assert_eq!(kvec.iter().sum::<f64>(), 3.0);
+        assert!(kvec.iter().all(|val| *val == 0.0 || *val == 1.0));
+        assert_eq!(com.sparse_vector(b"AAAACG").len(), 3);
+        assert_eq!(
+            packed_bits(&[1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]),
+            "0901"
+        );
+
+        let header = OutputFormat {
+            header: true,
+            ..OutputFormat::default()
+        };
+        com.set_options(presence(false, header.clone())).unwrap();
+        com.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        let row: Vec<&str> = vectors.lines().nth(1).unwrap().split(' ').collect();
+        assert_eq!(row.len(), 32);
+        assert!(row.iter().all(|val| *val == "0" || *val == "1"));
+
+        com.set_options(presence(true, header.clone())).unwrap();
+        com.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        let mut lines = vectors.lines();
+        assert_eq!(lines.next().unwrap(), "bits");
+        let bits = lines.next().unwrap();
+        assert_eq!(bits.len(), 8);
+        let ones: u32 = (0..4)
+            .map(|i| {
+                u8::from_str_radix(&bits[2 * i..2 * i + 2], 16)
+                    .unwrap()
+                    .count_ones()
+            })
+            .sum();
+        assert_eq!(ones as usize, row.iter().filter(|val| **val == "1").count());
+
+        assert!(com
+            .set_options(presence(
+                true,
+                OutputFormat {
+                    sparse: Some(SparseFormat::Pairs),
+                    ..OutputFormat::default()
+                }
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn kmer_vec_spaced_test() {
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), "".to_owned(), 3);
+        let seed = |pattern| OligoKmers {
+            seed: Some(SpacedSeed::parse(pattern).unwrap()),
+            ..Default::default()
+        };
+        assert!(com.set_options(counts(seed("11011"))).is_err());
+        com.set_options(counts(seed("1101"))).unwrap();
+        // ACT and AGT (reverse complements) of ACGT and AGCT, one mismatch apart
+        let kvec = com.vectorise_one(b"ACGTNNAGCT");
+        assert_eq!(kvec.iter().sum::<f64>(), 2.0);
+        assert_eq!(
+            kvec[com.pos_map[kmer::kmer_to_numeric("ACT").unwrap() as usize]],
+            2.0
+        );
+        assert!(com
+            .set_options(OligoOptions {
+                kmers: seed("1101"),
+                normalisation: Normalisation::OddsRatio,
+                ..Default::default()
+            })
+            .is_err());
+        // segments overlap by the span of the seed
+        com.set_segment_size(3);
+        let seq = b"ACGTTGCATTACGGATCCA";
+        com.set_options(counts(seed("1011"))).unwrap();
+        let segmented = com.vectorise_one(seq);
+        com.set_segment_size(SEGMENT_SIZE);
+        assert_eq!(segmented, com.vectorise_one(seq));
+        assert_eq!(segmented.iter().sum::<f64>(), 16.0);
+    }
+
     #[test]
     fn vec_mmap_test() {
         let com = OligoComputer::new(
@@ -328,7 +1786,7 @@ mod tests {
                 4,
             );
             com.set_threads(8);
-            com.set_norm(false);
+            com.set_options(counts(OligoKmers::default())).unwrap();
             let _ = com.vectorise_batch();
             assert_eq!(
                 fs::read("../test_data/expected_fa_batch_unnorm.kmers").unwrap(),
@@ -337,6 +1795,94 @@ mod tests {
         }
     }
 
+    #[test]
+    fn kmer_vec_large_k_test() {
+        let com = OligoComputer::new(
+            PATH_FQ.to_owned(),
+            "../test_data/reads.kmers".to_owned(),
+            11,
+        );
+        assert!(com.pos_map.is_empty());
+        assert_eq!(com.kcount, 2_097_152);
+        let kvec = com.vectorise_one(b"AAAAAAAAAAANTTTTTTTTTTT");
+        assert_eq!(kvec[0], 1.0);
+        assert_eq!(kvec.iter().fold(0.0, |acc, v| acc + v), 1.0);
+    }
+
+    #[test]
+    fn vec_sparse_test() {
+        let out_path = "../test_data/computed_sparse.kmers";
+        let com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 15);
+        assert!(com.pos_map.is_empty());
+        com.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        assert_eq!(vectors.lines().count(), 2);
+        for line in vectors.lines() {
+            let pairs: Vec<(usize, f64)> = line
+                .split(' ')
+                .map(|pair| {
+                    let (col, val) = pair.split_once(':').unwrap();
+                    (col.parse().unwrap(), val.parse().unwrap())
+                })
+                .collect();
+            // the 58 15-mers of a 72 bp read, in column order
+            assert!(pairs.len() <= 58);
+            assert!(pairs.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert!(pairs.iter().all(|&(col, _)| col < com.kcount));
+            let total: f64 = pairs.iter().map(|&(_, val)| val).sum();
+            assert!((total - 1.0).abs() < 1e-4);
+        }
+
+        // sparse columns are those of the dense vectors
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 11);
+        let seq = b"ACGTTGCAAGGCTTAACGT";
+        let dense: Vec<(usize, f64)> = com
+            .vectorise_one(seq)
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, val)| val > 0.0)
+            .collect();
+        assert_eq!(com.sparse_vector(seq), dense);
+
+        let sparse = |normalisation, format| OligoOptions {
+            normalisation,
+            output: OligoOutput {
+                format,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(com
+            .set_options(sparse(
+                Normalisation::Freq,
+                OutputFormat {
+                    header: true,
+                    ..OutputFormat::default()
+                }
+            ))
+            .is_err());
+        assert!(com
+            .set_options(sparse(Normalisation::OddsRatio, OutputFormat::default()))
+            .is_err());
+
+        // small k written sparsely on request
+        let mut com = OligoComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 3);
+        com.set_options(sparse(
+            Normalisation::Counts,
+            OutputFormat {
+                sparse: Some(SparseFormat::Libsvm),
+                ids: IdPolicy::First,
+                ..OutputFormat::default()
+            },
+        ))
+        .unwrap();
+        com.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        let row = vectors.lines().next().unwrap();
+        assert!(row.starts_with("0 1:4 2:4 3:4 4:2 "));
+        assert!(row.ends_with(" # Read_1"));
+    }
+
     #[test]
     fn get_header_test() {
         let com = OligoComputer::new(
@@ -349,6 +1895,26 @@ mod tests {
         assert_eq!(header[135], "TTAA");
     }
 
+    #[test]
+    fn get_header_rna_test() {
+        let mut com = OligoComputer::new(
+            PATH_FQ.to_owned(),
+            "../test_data/computed_fa_batch_unnorm.kmers".to_owned(),
+            4,
+        );
+        com.set_options(OligoOptions {
+            kmers: OligoKmers {
+                molecule: Molecule::Rna,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        let header = com.get_header();
+        assert_eq!(header[0], "AAAA");
+        assert_eq!(header[135], "UUAA");
+    }
+
     #[test]
     fn vec_batch_with_header_test() {
         let mut com = OligoComputer::new(
@@ -356,7 +1922,17 @@ mod tests {
             "../test_data/computed_fa_batch_header.kmers".to_owned(),
             4,
         );
-        com.set_header(true);
+        com.set_options(OligoOptions {
+            output: OligoOutput {
+                format: OutputFormat {
+                    header: true,
+                    ..OutputFormat::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
         let _ = com.vectorise_batch();
         assert_eq!(
             fs::read("../test_data/computed_fa_batch_header.kmers").unwrap(),
@@ -364,6 +1940,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn vec_mmap_format_test() {
+        let mut format = OutputFormat::new("\t");
+        format.precision = Some(3);
+        format.header = true;
+        for (path, mmap) in [
+            ("../test_data/computed_fa_format_mmap.kmers", true),
+            ("../test_data/computed_fa_format_batch.kmers", false),
+        ] {
+            let mut com = OligoComputer::new(PATH_FQ.to_owned(), path.to_owned(), 4);
+            com.set_options(OligoOptions {
+                output: OligoOutput {
+                    format: format.clone(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .unwrap();
+            if mmap {
+                com.vectorise_mmap().unwrap();
+            } else {
+                com.vectorise_batch().unwrap();
+            }
+        }
+        let res = fs::read_to_string("../test_data/computed_fa_format_mmap.kmers").unwrap();
+        assert_eq!(
+            res,
+            fs::read_to_string("../test_data/computed_fa_format_batch.kmers").unwrap()
+        );
+        assert!(res.starts_with("AAAA\tAAAC\t"));
+        assert!(res
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("0.014\t0.014\t0.000\t"));
+    }
+
     #[test]
     fn vec_mmap_with_header_test() {
         let mut com = OligoComputer::new(
@@ -371,11 +1984,73 @@ mod tests {
             "../test_data/computed_fa_mmap_header.kmers".to_owned(),
             4,
         );
-        com.set_header(true);
+        com.set_options(OligoOptions {
+            output: OligoOutput {
+                format: OutputFormat {
+                    header: true,
+                    ..OutputFormat::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
         let _ = com.vectorise_mmap();
         assert_eq!(
             fs::read("../test_data/computed_fa_mmap_header.kmers").unwrap(),
             fs::read("../test_data/expected_fa_header.kmers").unwrap()
         );
     }
+    #[test]
+    fn record_stats_test() {
+        let in_path = "../test_data/computed_ambiguous.fa";
+        fs::write(in_path, ">seq1\nAAAANGAGA\n>seq2\nACGTACGT\n>seq3\nNNN\n").unwrap();
+        let expected = "id\tkmers\tskipped_kmers\nseq1\t2\t4\nseq2\t5\t0\nseq3\t0\t0\n";
+
+        let mut com = OligoComputer::new(
+            in_path.to_owned(),
+            "../test_data/computed_ambiguous_mmap.kmers".to_owned(),
+            4,
+        );
+        com.set_threads(4);
+        com.set_options(OligoOptions {
+            output: OligoOutput {
+                record_stats: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        com.vectorise_mmap().unwrap();
+        assert_eq!(
+            com.kmer_stats(),
+            KmerStats {
+                kmers: 7,
+                skipped: 4
+            }
+        );
+        assert_eq!(
+            fs::read_to_string("../test_data/computed_ambiguous_mmap.kmers.stats").unwrap(),
+            expected
+        );
+
+        let mut com = OligoComputer::new(
+            in_path.to_owned(),
+            "../test_data/computed_ambiguous_batch.kmers".to_owned(),
+            4,
+        );
+        com.set_options(OligoOptions {
+            output: OligoOutput {
+                record_stats: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        com.vectorise_batch().unwrap();
+        assert_eq!(
+            fs::read_to_string("../test_data/computed_ambiguous_batch.kmers.stats").unwrap(),
+            expected
+        );
+    }
 }
@@ -1,11 +1,16 @@
-use kmer::{kmer::KmerGenerator, numeric_to_kmer};
-use ktio::seq::{SeqFormat, Sequence, Sequences};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::{
-    collections::HashMap,
-    fs::File,
-    io::{BufRead, BufWriter, Write},
+use crate::render::{flip_rows, gray, png_dir, png_name, point_pixels, write_png};
+use kmer::{
+    kmer::{hard_mask, KmerGenerator},
+    numeric_to_kmer, numeric_to_kmer_in, Molecule,
+};
+use ktio::{
+    filter::RecordFilter,
+    format::{IdPolicy, OutputFormat},
+    matrix::MatrixWriter,
+    seq::{SeqInput, Sequence},
 };
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::{borrow::Cow, collections::HashMap, io::Write};
 
 const GB_4: usize = 4 * (1 << 30);
 type Point = (f64, f64);
@@ -25,6 +30,12 @@ pub struct OligoCgrComputer {
     kmers: Vec<String>,
     pos_map: Vec<usize>,
     kcount: usize,
+    filter: Option<RecordFilter>,
+    format: OutputFormat,
+    fcgr: bool,
+    png: Option<(String, usize)>,
+    skip_masked: bool,
+    molecule: Molecule,
 }
 
 impl OligoCgrComputer {
@@ -41,7 +52,7 @@ impl OligoCgrComputer {
             in_path,
             out_path,
             ksize,
-            threads: rayon::current_num_threads(),
+            threads: ktio::threads::default_threads(),
             norm: true,
             memory: GB_4,
             cgr_center,
@@ -49,6 +60,12 @@ impl OligoCgrComputer {
             kmers,
             pos_map: min_mer_pos_map,
             kcount,
+            filter: None,
+            format: OutputFormat::default(),
+            fcgr: false,
+            png: None,
+            skip_masked: false,
+            molecule: Molecule::Dna,
         }
     }
 
@@ -60,20 +77,201 @@ impl OligoCgrComputer {
         self.norm = norm;
     }
 
-    pub fn vectorise(&self) -> Result<(), String> {
-        let mut reader = ktio::seq::get_reader(&self.in_path).unwrap();
-        let buffer = reader
-            .fill_buf()
-            .map_err(|_| String::from("Invalid stream"))?;
-        let format = if buffer[0] == b'>' {
-            SeqFormat::Fasta
+    pub fn set_filter(&mut self, filter: Option<RecordFilter>) {
+        self.filter = filter;
+    }
+
+    // points are written as (x,y,frequency) with spaces, as columns with other delimiters
+    pub fn set_format(&mut self, format: OutputFormat) {
+        self.format = format;
+    }
+
+    // a column per point named by its k-mer, or a column per coordinate and frequency
+    fn point_names(&self) -> Vec<String> {
+        let kmers = self.kmers.iter().map(|kmer| self.molecule.spell(kmer));
+        if self.format.delim == " " {
+            return kmers.collect();
+        }
+        kmers
+            .flat_map(|kmer| ["x", "y", "freq"].map(|name| format!("{}_{}", kmer, name)))
+            .collect()
+    }
+
+    // frequency CGR, a 2^k x 2^k matrix of the frequencies of all forward k-mers per
+    // sequence flattened row by row, in place of the points of canonical k-mers
+    pub fn set_fcgr(&mut self, fcgr: bool) {
+        self.fcgr = fcgr;
+    }
+
+    // also write a (dir, size) size x size grayscale PNG of the points of each record,
+    // named after its ID, FCGR images are 2^k x 2^k
+    pub fn set_png(&mut self, png: Option<(String, usize)>) -> Result<(), String> {
+        if let Some((dir, _)) = &png {
+            png_dir(dir)?;
+        }
+        self.png = png;
+        Ok(())
+    }
+
+    fn write_image(&self, id: &str, points: &[(Point, f64)]) -> Result<(), String> {
+        if let Some((dir, size)) = &self.png {
+            let pixels = point_pixels(points, self.cgr_center.0 * 2.0, *size);
+            write_png(&format!("{}/{}", dir, png_name(id)), *size, &gray(&pixels))?;
+        }
+        Ok(())
+    }
+
+    fn write_fcgr_image(&self, id: &str, vec: &[f64]) -> Result<(), String> {
+        if let Some((dir, _)) = &self.png {
+            let size = 1 << self.ksize;
+            let pixels = gray(&flip_rows(vec, size));
+            write_png(&format!("{}/{}", dir, png_name(id)), size, &pixels)?;
+        }
+        Ok(())
+    }
+
+    // leave out k-mers overlapping soft-masked (lowercase) bases, such as repeats
+    pub fn set_skip_masked(&mut self, skip_masked: bool) {
+        self.skip_masked = skip_masked;
+    }
+
+    // letters of the k-mers of the header, U in place of T for RNA
+    pub fn set_molecule(&mut self, molecule: Molecule) {
+        self.molecule = molecule;
+    }
+
+    fn sequence<'a>(&self, seq: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.skip_masked {
+            Cow::Owned(hard_mask(seq))
         } else {
-            SeqFormat::Fastq
+            Cow::Borrowed(seq)
+        }
+    }
+
+    // k-mer of each cell of the flattened FCGR matrix
+    pub fn fcgr_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); 1 << (2 * self.ksize)];
+        for kmer in 0..names.len() as u64 {
+            names[Self::fcgr_cell(kmer, self.ksize)] =
+                numeric_to_kmer_in(kmer, self.ksize, self.molecule);
+        }
+        names
+    }
+
+    // the CGR point of a k-mer falls in row floor(y * 2^k) and column floor(x * 2^k), the
+    // last base picks the half of each axis so it is the most significant bit
+    fn fcgr_cell(kmer: u64, ksize: usize) -> usize {
+        let (mut row, mut col) = (0_usize, 0_usize);
+        for pos in 0..ksize {
+            // bases from the last, A=00 C=01 G=10 T=11
+            let base = ((kmer >> (2 * pos)) & 0b11) as usize;
+            let bit = ksize - 1 - pos;
+            // T and G are at x = 1, C and G at y = 1
+            col |= (base >> 1) << bit;
+            row |= ((base >> 1) ^ (base & 1)) << bit;
+        }
+        (row << ksize) | col
+    }
+
+    fn fcgr_one(&self, seq: &[u8]) -> Vec<f64> {
+        let seq = &self.sequence(seq);
+        let mut vec = vec![0_f64; 1 << (2 * self.ksize)];
+        let mut total = 0_f64;
+
+        for (fmer, _) in KmerGenerator::new(seq, self.ksize) {
+            vec[Self::fcgr_cell(fmer, self.ksize)] += 1_f64;
+            total += 1_f64;
+        }
+        if self.norm {
+            vec.iter_mut().for_each(|el| *el /= f64::max(1_f64, total));
+        }
+        vec
+    }
+
+    fn vectorise_fcgr(&self) -> Result<(), String> {
+        let mut records = SeqInput::open(&self.in_path, 1)?.records()?;
+        records.set_filter(self.filter.clone());
+        let mut matrix = match self.format.matrix {
+            Some(matrix) => Some(MatrixWriter::new(
+                &self.out_path,
+                matrix,
+                &self.fcgr_names(),
+                self.format.ids == IdPolicy::First,
+            )?),
+            None => None,
         };
-        let records = Sequences::new(format, reader).unwrap();
-        let file = File::create(&self.out_path)
-            .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
-        let mut out_buffer = BufWriter::new(file);
+        let mut out_buffer = match matrix {
+            Some(_) => None,
+            None => Some(self.format.writer(&self.out_path)?),
+        };
+        if let (Some(out_buffer), Some(header)) = (
+            out_buffer.as_mut(),
+            self.format.header_row(&self.fcgr_names()),
+        ) {
+            out_buffer
+                .write_all(header.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+        }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let vecs: Vec<Vec<f64>> = pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|seq| {
+                        let vec = self.fcgr_one(&seq.seq);
+                        self.write_fcgr_image(&seq.id, &vec)?;
+                        Ok(vec)
+                    })
+                    .collect::<Result<Vec<Vec<f64>>, String>>()
+            })?;
+            if let Some(matrix) = matrix.as_mut() {
+                for (seq, vec) in batch.iter().zip(vecs.iter()) {
+                    matrix.write_row(&seq.id, vec)?;
+                }
+            } else if let Some(out_buffer) = out_buffer.as_mut() {
+                let result: String = batch
+                    .iter()
+                    .zip(vecs.iter())
+                    .map(|(seq, vec)| {
+                        let fields: Vec<String> = vec
+                            .iter()
+                            .map(|val| self.format.number(*val, None))
+                            .collect();
+                        self.format.row(&seq.id, &fields)
+                    })
+                    .collect();
+                out_buffer
+                    .write_all(result.as_bytes())
+                    .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+            }
+        }
+        if let Some(matrix) = matrix {
+            matrix.finish()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn vectorise(&self) -> Result<(), String> {
+        if self.fcgr {
+            return self.vectorise_fcgr();
+        }
+        let mut records = SeqInput::open(&self.in_path, 1)?.records()?;
+        records.set_filter(self.filter.clone());
+        let mut out_buffer = self.format.writer(&self.out_path)?;
+        if let Some(header) = self.format.header_row(&self.point_names()) {
+            out_buffer
+                .write_all(header.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+        }
         let pool = rayon::ThreadPoolBuilder::new()
             .num_threads(self.threads)
             .build()
@@ -90,15 +288,23 @@ impl OligoCgrComputer {
                         .par_iter()
                         .map(|seq| {
                             let kvec = self.vectorise_one(&seq.seq).unwrap();
+                            self.write_image(&seq.id, &kvec)?;
                             let kvec_str: Vec<String> = kvec
                                 .iter()
-                                .map(|val| format!("({},{},{})", val.0 .0, val.0 .1, val.1))
+                                .map(|val| {
+                                    self.format.point(&[
+                                        self.format.number(val.0 .0, None),
+                                        self.format.number(val.0 .1, None),
+                                        self.format.number(val.1, None),
+                                    ])
+                                })
                                 .collect();
-                            format!("{}\n", kvec_str.join(" "))
+                            Ok(self.format.row(&seq.id, &kvec_str))
                         })
-                        .collect::<Vec<String>>()
+                        .collect::<Result<Vec<String>, String>>()?
                         .join("");
                     out_buffer.write_all(result.as_bytes()).unwrap();
+                    Ok::<(), String>(())
                 };
 
                 for record in records {
@@ -106,18 +312,18 @@ impl OligoCgrComputer {
                     buffer.push(record);
 
                     if total >= self.memory {
-                        process_buffer(&buffer);
+                        process_buffer(&buffer)?;
                         buffer.clear();
                         total = 0;
                     }
                 }
 
                 if !buffer.is_empty() {
-                    process_buffer(&buffer);
+                    process_buffer(&buffer)?;
                 }
-            });
-        });
-        Ok(())
+                Ok(())
+            })
+        })
     }
 
     fn vectorise_one(&self, seq: &[u8]) -> Result<Vec<(Point, f64)>, String> {
@@ -143,11 +349,11 @@ impl OligoCgrComputer {
     }
 
     fn seq_to_kmer(&self, seq: &[u8]) -> Vec<f64> {
+        let seq = &self.sequence(seq);
         let mut vec = vec![0_f64; self.kcount];
         let mut total = 0_f64;
 
-        for (fmer, rmer) in KmerGenerator::new(seq, self.ksize) {
-            let min_mer = u64::min(fmer, rmer);
+        for (min_mer, _) in KmerGenerator::new(seq, self.ksize).canonical() {
             unsafe {
                 // we already know the size of the vector and
                 // min_mer is absolutely smaller than that
@@ -219,6 +425,55 @@ mod tests {
         assert_eq!(res[0].1, 1.0);
     }
 
+    #[test]
+    fn oligo_cgr_header_test() {
+        let out_path = "../test_data/computed_reads.k3.header.cgr";
+        let mut cgr = OligoCgrComputer::new(PATH_FQ.to_owned(), out_path.to_owned(), 3, 8);
+        let mut format = OutputFormat::new(",");
+        format.header = true;
+        format.ids = IdPolicy::First;
+        cgr.set_format(format);
+        cgr.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        let mut lines = vectors.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("id,AAA_x,AAA_y,AAA_freq,AAC_x,"));
+        let row = lines.next().unwrap();
+        assert!(row.starts_with("Read_1,"));
+        assert_eq!(row.split(',').count(), 1 + 32 * 3);
+
+        cgr.set_fcgr(true);
+        cgr.vectorise().unwrap();
+        let vectors = fs::read_to_string(out_path).unwrap();
+        assert!(vectors.starts_with("id,AAA,TAA,ATA,TTA,"));
+        assert!(vectors.lines().nth(2).unwrap().starts_with("Read_2,"));
+    }
+
+    #[test]
+    fn fcgr_test() {
+        let mut cgr = OligoCgrComputer::new(PATH_FQ.to_owned(), "".to_owned(), 2, 4);
+        cgr.set_norm(false);
+        // A, T, C and G corners of the 4 x 4 matrix
+        let names = cgr.fcgr_names();
+        assert_eq!(names[0], "AA");
+        assert_eq!(names[3], "TT");
+        assert_eq!(names[12], "CC");
+        assert_eq!(names[15], "GG");
+        // AT ends in the T quadrant, closest to A within it
+        assert_eq!(names[2], "AT");
+        let vec = cgr.fcgr_one(b"AAATTGG");
+        assert_eq!(vec[0], 2.0);
+        assert_eq!(vec[2], 1.0);
+        assert_eq!(vec[3], 1.0);
+        assert_eq!(vec.iter().sum::<f64>(), 6.0);
+        // TG and GG overlap masked bases
+        cgr.set_skip_masked(true);
+        let vec = cgr.fcgr_one(b"AAATTgg");
+        assert_eq!(vec.iter().sum::<f64>(), 4.0);
+    }
+
     #[test]
     fn oligo_cgr_complete_unnorm_test() {
         let mut cgr = OligoCgrComputer::new(
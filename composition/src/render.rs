@@ -0,0 +1,96 @@
+use ktio::fops::create_directory;
+
+type Point = (f64, f64);
+
+// file name of the image of a record, characters other than letters, digits, '.', '-'
+// and '_' become '_'
+pub fn png_name(id: &str) -> String {
+    let name: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.png", name)
+}
+
+// weights of the points summed over a size x size grid of the square [0, extent], rows
+// from the top so that y grows upwards as in the CGR square
+pub fn point_pixels(points: &[(Point, f64)], extent: f64, size: usize) -> Vec<f64> {
+    let mut pixels = vec![0_f64; size * size];
+    let cell = |val: f64| usize::min(size - 1, (val / extent * size as f64) as usize);
+    for &((x, y), weight) in points {
+        pixels[(size - 1 - cell(y)) * size + cell(x)] += weight;
+    }
+    pixels
+}
+
+// rows of a flattened square matrix of values in reverse, so that row 0 is at the bottom
+pub fn flip_rows(values: &[f64], size: usize) -> Vec<f64> {
+    values
+        .chunks(size)
+        .rev()
+        .flat_map(|row| row.iter().copied())
+        .collect()
+}
+
+// shades of gray from black at 0 to white at the largest value
+pub fn gray(values: &[f64]) -> Vec<u8> {
+    let max = values.iter().copied().fold(0_f64, f64::max);
+    if max <= 0_f64 {
+        return vec![0; values.len()];
+    }
+    values
+        .iter()
+        .map(|val| (f64::max(0_f64, *val) / max * 255_f64).round() as u8)
+        .collect()
+}
+
+// images are written to this directory, an error when built without the png feature
+pub fn png_dir(dir: &str) -> Result<(), String> {
+    if cfg!(not(feature = "png")) {
+        return Err("kmertools was built without the png feature".to_string());
+    }
+    create_directory(dir).map_err(|_| format!("Unable to create directory: {}", dir))
+}
+
+#[cfg(not(feature = "png"))]
+pub fn write_png(_path: &str, _size: usize, _pixels: &[u8]) -> Result<(), String> {
+    unreachable!("PNG images cannot be requested without the png feature")
+}
+
+// size x size 8-bit grayscale image
+#[cfg(feature = "png")]
+pub fn write_png(path: &str, size: usize, pixels: &[u8]) -> Result<(), String> {
+    image::save_buffer_with_format(
+        path,
+        pixels,
+        size as u32,
+        size as u32,
+        image::ColorType::L8,
+        image::ImageFormat::Png,
+    )
+    .map_err(|_| format!("Unable to write to file: {}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_test() {
+        assert_eq!(png_name("NC_001.1 phage/x"), "NC_001.1_phage_x.png");
+        // corners A (0, 0) and G (1, 1), A is at the bottom left
+        let pixels = point_pixels(&[((0.0, 0.0), 1.0), ((1.0, 1.0), 3.0)], 1.0, 2);
+        assert_eq!(pixels, vec![0.0, 3.0, 1.0, 0.0]);
+        assert_eq!(gray(&pixels), vec![0, 255, 85, 0]);
+        assert_eq!(
+            flip_rows(&[1.0, 2.0, 3.0, 4.0], 2),
+            vec![3.0, 4.0, 1.0, 2.0]
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use ktio::{
+    bundle::json_string,
+    json::{parse_object, Value},
+};
+use std::fs;
+
+// fraction of the points used for the robust covariance, the others are left out as possible outliers
+const SUPPORT: f64 = 0.75;
+// concentration steps refitting on the closest points
+const C_STEPS: usize = 5;
+
+pub fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0_f64;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2_f64
+    } else {
+        sorted[mid]
+    }
+}
+
+// lower triangular l with l * l^T = a, a must be symmetric positive definite
+fn cholesky(a: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let dims = a.len();
+    let mut l = vec![vec![0_f64; dims]; dims];
+    for i in 0..dims {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|k| l[i][k] * l[j][k]).sum();
+            if i == j {
+                let diag = a[i][i] - sum;
+                if diag <= 0_f64 {
+                    return None;
+                }
+                l[i][j] = diag.sqrt();
+            } else {
+                l[i][j] = (a[i][j] - sum) / l[j][j];
+            }
+        }
+    }
+    Some(l)
+}
+
+// centroid and covariance of the points, shrunk towards a scaled identity by
+// dims / (dims + points) as there are often fewer contigs than k-mers, and
+// compositions sum to one so their covariance is singular without it
+fn fit_gaussian(points: &[&Vec<f64>]) -> Option<(Vec<f64>, Vec<Vec<f64>>)> {
+    let dims = points[0].len();
+    let n = points.len() as f64;
+    let mut centroid = vec![0_f64; dims];
+    for point in points.iter() {
+        centroid
+            .iter_mut()
+            .zip(point.iter())
+            .for_each(|(c, val)| *c += val / n);
+    }
+    let mut cov = vec![vec![0_f64; dims]; dims];
+    for point in points.iter() {
+        for i in 0..dims {
+            let di = point[i] - centroid[i];
+            for j in 0..dims {
+                cov[i][j] += di * (point[j] - centroid[j]) / f64::max(1_f64, n - 1_f64);
+            }
+        }
+    }
+    let shrinkage = dims as f64 / (dims as f64 + n);
+    let scale = (0..dims).map(|i| cov[i][i]).sum::<f64>() / dims as f64 + 1e-12;
+    for (i, row) in cov.iter_mut().enumerate() {
+        row.iter_mut().for_each(|val| *val *= 1_f64 - shrinkage);
+        row[i] += shrinkage * scale;
+    }
+    let l = cholesky(&cov)?;
+    Some((centroid, l))
+}
+
+// centroid and covariance fitted to the most central points (concentration steps of
+// FAST-MCD started from the coordinate-wise median), so outliers do not shift the fit
+pub struct RobustModel {
+    centroid: Vec<f64>,
+    // Cholesky factor of the covariance
+    chol: Vec<Vec<f64>>,
+}
+
+impl RobustModel {
+    pub fn fit(points: &[Vec<f64>]) -> Result<Self, String> {
+        if points.len() < 3 {
+            return Err(format!("At least 3 vectors required, got {}", points.len()));
+        }
+        let dims = points[0].len();
+        if points.iter().any(|point| point.len() != dims) {
+            return Err("Vectors must have the same length".to_string());
+        }
+        let support = usize::max(2, (points.len() as f64 * SUPPORT).ceil() as usize);
+        let start: Vec<f64> = (0..dims)
+            .map(|i| median(&points.iter().map(|point| point[i]).collect::<Vec<f64>>()))
+            .collect();
+        let mut distances: Vec<f64> = points
+            .iter()
+            .map(|point| {
+                point
+                    .iter()
+                    .zip(start.iter())
+                    .map(|(a, b)| (a - b) * (a - b))
+                    .sum()
+            })
+            .collect();
+        let mut model = None;
+        for _ in 0..C_STEPS {
+            let mut order: Vec<usize> = (0..points.len()).collect();
+            order.sort_by(|&a, &b| distances[a].total_cmp(&distances[b]));
+            let closest: Vec<&Vec<f64>> = order[..support].iter().map(|&i| &points[i]).collect();
+            let (centroid, chol) =
+                fit_gaussian(&closest).ok_or("Unable to fit the covariance of the vectors")?;
+            let fitted = RobustModel { centroid, chol };
+            distances = points.iter().map(|point| fitted.distance(point)).collect();
+            model = Some(fitted);
+        }
+        Ok(model.unwrap())
+    }
+
+    // Mahalanobis distance from the robust centroid
+    pub fn distance(&self, point: &[f64]) -> f64 {
+        // solves chol * y = point - centroid, the distance is the norm of y
+        let dims = self.centroid.len();
+        let mut y = vec![0_f64; dims];
+        for i in 0..dims {
+            let sum: f64 = (0..i).map(|k| self.chol[i][k] * y[k]).sum();
+            y[i] = (point[i] - self.centroid[i] - sum) / self.chol[i][i];
+        }
+        y.iter().map(|val| val * val).sum::<f64>().sqrt()
+    }
+}
+
+// running sums and ranges of the columns of vectors, for their mean, standard deviation,
+// minimum and maximum
+#[derive(Debug, Clone)]
+pub struct ColumnMoments {
+    count: f64,
+    sums: Vec<f64>,
+    squares: Vec<f64>,
+    mins: Vec<f64>,
+    maxs: Vec<f64>,
+}
+
+impl ColumnMoments {
+    pub fn new(dims: usize) -> Self {
+        Self {
+            count: 0_f64,
+            sums: vec![0_f64; dims],
+            squares: vec![0_f64; dims],
+            mins: vec![f64::INFINITY; dims],
+            maxs: vec![f64::NEG_INFINITY; dims],
+        }
+    }
+
+    pub fn push(mut self, point: &[f64]) -> Self {
+        self.count += 1_f64;
+        for (i, val) in point.iter().enumerate() {
+            self.sums[i] += val;
+            self.squares[i] += val * val;
+            self.mins[i] = f64::min(self.mins[i], *val);
+            self.maxs[i] = f64::max(self.maxs[i], *val);
+        }
+        self
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        self.count += other.count;
+        self.sums
+            .iter_mut()
+            .zip(other.sums)
+            .for_each(|(a, b)| *a += b);
+        self.squares
+            .iter_mut()
+            .zip(other.squares)
+            .for_each(|(a, b)| *a += b);
+        self.mins
+            .iter_mut()
+            .zip(other.mins)
+            .for_each(|(a, b)| *a = f64::min(*a, b));
+        self.maxs
+            .iter_mut()
+            .zip(other.maxs)
+            .for_each(|(a, b)| *a = f64::max(*a, b));
+        self
+    }
+
+    pub fn mean(&self, col: usize) -> f64 {
+        self.sums[col] / f64::max(1_f64, self.count)
+    }
+
+    // population standard deviation
+    pub fn sd(&self, col: usize) -> f64 {
+        let mean = self.mean(col);
+        let var = self.squares[col] / f64::max(1_f64, self.count) - mean * mean;
+        f64::max(0_f64, var).sqrt()
+    }
+
+    // smallest and largest value of a column, 0 and 0 without vectors
+    pub fn range(&self, col: usize) -> (f64, f64) {
+        if self.count == 0_f64 {
+            return (0_f64, 0_f64);
+        }
+        (self.mins[col], self.maxs[col])
+    }
+
+    // z-scores of a vector, columns without variance are 0
+    pub fn standardise(&self, point: &mut [f64]) {
+        for (col, val) in point.iter_mut().enumerate() {
+            let sd = self.sd(col);
+            *val = if sd > 1e-12 {
+                (*val - self.mean(col)) / sd
+            } else {
+                0_f64
+            };
+        }
+    }
+}
+
+// rescaling of each column of the vectors across the records of a dataset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scaling {
+    // zero mean and unit variance
+    Standard,
+    // from the range of the column to 0..1
+    MinMax,
+}
+
+impl Scaling {
+    fn name(self) -> &'static str {
+        match self {
+            Scaling::Standard => "standard",
+            Scaling::MinMax => "minmax",
+        }
+    }
+}
+
+// columns become (value - shift) / scale, columns without spread become 0, the parameters
+// fitted to one dataset are saved as JSON to transform new data the same way
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnScaler {
+    scaling: Scaling,
+    features: Vec<String>,
+    shift: Vec<f64>,
+    scale: Vec<f64>,
+}
+
+impl ColumnScaler {
+    pub fn fit(scaling: Scaling, features: Vec<String>, moments: &ColumnMoments) -> Self {
+        let (shift, scale) = (0..features.len())
+            .map(|col| match scaling {
+                Scaling::Standard => (moments.mean(col), moments.sd(col)),
+                Scaling::MinMax => {
+                    let (min, max) = moments.range(col);
+                    (min, max - min)
+                }
+            })
+            .unzip();
+        Self {
+            scaling,
+            features,
+            shift,
+            scale,
+        }
+    }
+
+    pub fn features(&self) -> &[String] {
+        &self.features
+    }
+
+    pub fn scaling(&self) -> Scaling {
+        self.scaling
+    }
+
+    pub fn apply(&self, point: &mut [f64]) {
+        for (col, val) in point.iter_mut().enumerate() {
+            let scale = self.scale[col];
+            *val = if scale > 1e-12 {
+                (*val - self.shift[col]) / scale
+            } else {
+                0_f64
+            };
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let numbers = |values: &[f64]| {
+            let values: Vec<String> = values.iter().map(|val| val.to_string()).collect();
+            format!("[{}]", values.join(", "))
+        };
+        let features: Vec<String> = self.features.iter().map(|name| json_string(name)).collect();
+        format!(
+            "{{\n  \"scaling\": {},\n  \"features\": [{}],\n  \"shift\": {},\n  \"scale\": {}\n}}\n",
+            json_string(self.scaling.name()),
+            features.join(", "),
+            numbers(&self.shift),
+            numbers(&self.scale)
+        )
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_json()).map_err(|_| format!("Unable to write to file: {}", path))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|_| format!("Unable to read file: {}", path))?;
+        let fields =
+            parse_object(&text).map_err(|e| format!("Invalid scaling file {}: {}", path, e))?;
+        let invalid = |field: &str| format!("Invalid scaling file {}: {}", path, field);
+        let list = |field: &str| match fields.get(field) {
+            Some(Value::List(values)) => Ok(values),
+            _ => Err(invalid(field)),
+        };
+        let numbers = |field: &str| {
+            list(field)?
+                .iter()
+                .map(|value| match value {
+                    Value::Num(val) => Ok(*val),
+                    _ => Err(invalid(field)),
+                })
+                .collect::<Result<Vec<f64>, String>>()
+        };
+        let scaling = match fields.get("scaling") {
+            Some(Value::Str(name)) if name == "standard" => Scaling::Standard,
+            Some(Value::Str(name)) if name == "minmax" => Scaling::MinMax,
+            _ => return Err(invalid("scaling")),
+        };
+        let features = list("features")?
+            .iter()
+            .map(|value| match value {
+                Value::Str(name) => Ok(name.clone()),
+                _ => Err(invalid("features")),
+            })
+            .collect::<Result<Vec<String>, String>>()?;
+        let (shift, scale) = (numbers("shift")?, numbers("scale")?);
+        if shift.len() != features.len() || scale.len() != features.len() {
+            return Err(invalid("a shift and scale per feature"));
+        }
+        Ok(Self {
+            scaling,
+            features,
+            shift,
+            scale,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_test() {
+        assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&[4.0, 1.0, 2.0, 3.0]), 2.5);
+        assert_eq!(median(&[]), 0.0);
+    }
+
+    #[test]
+    fn column_moments_test() {
+        let moments = ColumnMoments::new(2)
+            .push(&[1.0, 5.0])
+            .merge(ColumnMoments::new(2).push(&[3.0, 5.0]));
+        assert_eq!(moments.mean(0), 2.0);
+        assert_eq!(moments.sd(0), 1.0);
+        let mut point = vec![3.0, 5.0];
+        moments.standardise(&mut point);
+        assert_eq!(point, vec![1.0, 0.0]);
+        assert_eq!(moments.range(0), (1.0, 3.0));
+        assert_eq!(ColumnMoments::new(1).range(0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn column_scaler_test() {
+        let moments = ColumnMoments::new(2)
+            .push(&[1.0, 5.0])
+            .push(&[3.0, 5.0])
+            .push(&[2.0, 5.0]);
+        let features = vec!["AAA".to_string(), "AAC".to_string()];
+        let scaler = ColumnScaler::fit(Scaling::MinMax, features.clone(), &moments);
+        let mut point = vec![2.5, 7.0];
+        scaler.apply(&mut point);
+        assert_eq!(point, vec![0.75, 0.0]);
+
+        let path = "../test_data/computed_scaling.json";
+        let scaler = ColumnScaler::fit(Scaling::Standard, features, &moments);
+        scaler.save(path).unwrap();
+        assert_eq!(ColumnScaler::load(path).unwrap(), scaler);
+        fs::write(path, "{\"scaling\": \"minmax\", \"features\": [\"AAA\"]}").unwrap();
+        assert!(ColumnScaler::load(path).is_err());
+    }
+
+    #[test]
+    fn robust_model_test() {
+        // points around (0.3, 0.7) with one far away
+        let mut points: Vec<Vec<f64>> = (0..20)
+            .map(|i| {
+                let noise = ((i * 7919) % 13) as f64 / 1000.0 - 0.006;
+                vec![0.3 + noise, 0.7 - noise + ((i % 3) as f64 - 1.0) / 1000.0]
+            })
+            .collect();
+        points.push(vec![0.8, 0.2]);
+        let model = RobustModel::fit(&points).unwrap();
+        let distances: Vec<f64> = points.iter().map(|point| model.distance(point)).collect();
+        assert!(distances[20] > 10.0 * median(&distances));
+        assert!(RobustModel::fit(&points[..2]).is_err());
+    }
+}
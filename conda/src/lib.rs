@@ -1,5 +1,9 @@
 use pybindings::{
-    cgr::CgrComputer, kmer::KmerGenerator, min::MinimiserGenerator, oligo::OligoComputer,
+    cgr::CgrComputer,
+    kmer::{hamming_neighbours, CanonicalIndexGenerator, CanonicalKmerGenerator, KmerGenerator},
+    min::MinimiserGenerator,
+    oligo::OligoComputer,
+    utils::add_utils,
 };
 use pyo3::prelude::*;
 
@@ -10,14 +14,22 @@ use pyo3::prelude::*;
 ///     CgrComputer        - computing chaos game representations
 ///                           for DNA sequences
 ///     KmerGenerator      - an iterator object to generate k-mers
-///                          as (forward, reverse) numeric kmer tuples
+///                          as (forward, reverse) numeric kmer tuples,
+///                          canonical() yields (canonical, is_forward) tuples,
+///                          canonical_indices() OligoComputer columns
 ///     MinimiserGenerator - an iterator object to iterate minimisers
-///                          as (kmer, start, end) numeric minimiser tuples
+///                          as (kmer, start, end, is_forward) minimiser tuples
+///     hamming_neighbours - numeric k-mers within a Hamming distance of a k-mer
+///     utils              - reverse complements of sequences
 #[pymodule]
 fn pykmertools(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<OligoComputer>()?;
     m.add_class::<CgrComputer>()?;
     m.add_class::<KmerGenerator>()?;
+    m.add_class::<CanonicalKmerGenerator>()?;
+    m.add_class::<CanonicalIndexGenerator>()?;
     m.add_class::<MinimiserGenerator>()?;
+    m.add_function(wrap_pyfunction!(hamming_neighbours, m)?)?;
+    add_utils(m)?;
     Ok(())
 }
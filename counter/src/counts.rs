@@ -0,0 +1,410 @@
+use crate::width::{Count, CounterWidth};
+use kmer::{superkmer::kmer_signature, Kmer, KmerInt};
+use ktio::mmap::mmap_file_for_reading;
+use memmap2::Mmap;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+};
+
+// binary layout
+// magic (8 bytes), ksize (u64), n_parts (u64), signature size (u64), count bytes (u64),
+// n_parts + 1 record offsets (u64) followed by partitions of (kmer, count) records sorted by kmer
+// kmer is u64, or u128 when ksize > 32, count is u32 or u64 as of the counter width
+// every value is little endian, partitions are as given by Partitioning
+const MAGIC: &[u8; 8] = b"KTCOUNT2";
+// files before signatures have no signature size or count bytes,
+// partitions are always kmer % n_parts and counts u32
+const MAGIC_V1: &[u8; 8] = b"KTCOUNTS";
+const HEADER_LEN: usize = 40;
+
+// how k-mers are assigned to partitions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Partitioning {
+    // kmer % n_parts
+    Modulo,
+    // signature (smallest hashed canonical m-mer of this size) % n_parts, as super-k-mers
+    Signature(usize),
+}
+
+impl Partitioning {
+    #[inline]
+    pub fn part<K: KmerInt>(self, kmer: K, ksize: usize, n_parts: u64) -> usize {
+        match self {
+            Partitioning::Modulo => (kmer % K::from_u64(n_parts)).as_u64() as usize,
+            Partitioning::Signature(msize) => {
+                (kmer_signature(kmer, ksize, msize) % n_parts) as usize
+            }
+        }
+    }
+
+    fn signature_size(self) -> usize {
+        match self {
+            Partitioning::Modulo => 0,
+            Partitioning::Signature(msize) => msize,
+        }
+    }
+}
+
+fn kmer_bytes(ksize: usize) -> usize {
+    if ksize > Kmer::MAX_KSIZE {
+        <u128 as KmerInt>::BYTES
+    } else {
+        <Kmer as KmerInt>::BYTES
+    }
+}
+
+pub struct CountsWriter {
+    buff: BufWriter<File>,
+    offsets: Vec<u64>,
+    ksize: usize,
+    count_bytes: usize,
+}
+
+impl CountsWriter {
+    pub fn new(path: &str, ksize: usize, n_parts: u64) -> Result<Self, String> {
+        Self::with_layout(
+            path,
+            ksize,
+            n_parts,
+            Partitioning::Modulo,
+            CounterWidth::U32,
+        )
+    }
+
+    // partitions must be written in order, each holding the k-mers partitioning assigns to it
+    pub fn with_layout(
+        path: &str,
+        ksize: usize,
+        n_parts: u64,
+        partitioning: Partitioning,
+        width: CounterWidth,
+    ) -> Result<Self, String> {
+        let file = File::create(path).map_err(|_| format!("Unable to write to file: {}", path))?;
+        let mut buff = BufWriter::new(file);
+        let mut header = Vec::with_capacity(HEADER_LEN + 8 * (n_parts as usize + 1));
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&(ksize as u64).to_le_bytes());
+        header.extend_from_slice(&n_parts.to_le_bytes());
+        header.extend_from_slice(&(partitioning.signature_size() as u64).to_le_bytes());
+        header.extend_from_slice(&(width.bytes() as u64).to_le_bytes());
+        // offsets are filled once all partitions are written
+        header.resize(header.capacity(), 0);
+        buff.write_all(&header)
+            .map_err(|_| format!("Unable to write to file: {}", path))?;
+
+        Ok(Self {
+            buff,
+            offsets: vec![0],
+            ksize,
+            count_bytes: width.bytes(),
+        })
+    }
+
+    pub fn write_partition<K: KmerInt, C: Count>(
+        &mut self,
+        entries: &mut [(K, C)],
+    ) -> Result<(), String> {
+        entries.sort_unstable();
+        self.write_sorted_partition(entries.iter().copied())
+    }
+
+    // entries must come in ascending k-mer order, so they can be streamed from a merge
+    pub fn write_sorted_partition<K: KmerInt, C: Count>(
+        &mut self,
+        entries: impl Iterator<Item = (K, C)>,
+    ) -> Result<(), String> {
+        assert_eq!(
+            K::BYTES,
+            kmer_bytes(self.ksize),
+            "k-mer width must match ksize"
+        );
+        assert_eq!(
+            C::BYTES,
+            self.count_bytes,
+            "counts must match counter width"
+        );
+        let written = write_records(&mut self.buff, entries)
+            .map_err(|_| String::from("Unable to write counts"))?;
+        let last = *self.offsets.last().unwrap();
+        self.offsets.push(last + written);
+        Ok(())
+    }
+
+    // next partition from a file of `entries` records written by write_records
+    pub fn append_partition(&mut self, path: &str, entries: u64) -> Result<(), String> {
+        let mut file = File::open(path).map_err(|_| format!("Unable to open: {}", path))?;
+        io::copy(&mut file, &mut self.buff).map_err(|_| String::from("Unable to write counts"))?;
+        let last = *self.offsets.last().unwrap();
+        self.offsets.push(last + entries);
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        let mut file = self
+            .buff
+            .into_inner()
+            .map_err(|_| String::from("Unable to write counts"))?;
+        let offsets: Vec<u8> = self
+            .offsets
+            .iter()
+            .flat_map(|offset| offset.to_le_bytes())
+            .collect();
+        file.seek(SeekFrom::Start(HEADER_LEN as u64))
+            .and_then(|_| file.write_all(&offsets))
+            .map_err(|_| String::from("Unable to write counts"))
+    }
+}
+
+// (kmer, count) records of a partition, returns how many were written
+pub fn write_records<K: KmerInt, C: Count>(
+    writer: &mut impl Write,
+    entries: impl Iterator<Item = (K, C)>,
+) -> io::Result<u64> {
+    let mut record = Vec::with_capacity(K::BYTES + C::BYTES);
+    let mut written = 0;
+    for (kmer, count) in entries {
+        record.clear();
+        kmer.write_le(&mut record);
+        count.write_le(&mut record);
+        writer.write_all(&record)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+// whether the file starts as a binary counts file of any version
+pub fn is_binary_counts(path: &str) -> bool {
+    let mut magic = [0; 8];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && (&magic == MAGIC || &magic == MAGIC_V1)
+}
+
+// read only view of the binary counts, shared between threads
+pub struct CountsReader {
+    mmap: Mmap,
+    ksize: usize,
+    kmer_bytes: usize,
+    count_bytes: usize,
+    n_parts: u64,
+    partitioning: Partitioning,
+    offsets: Vec<usize>,
+    data_start: usize,
+}
+
+impl CountsReader {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let mmap = mmap_file_for_reading(path)?;
+        let header_len = if mmap.len() >= HEADER_LEN && &mmap[..8] == MAGIC {
+            HEADER_LEN
+        } else if mmap.len() >= 24 && &mmap[..8] == MAGIC_V1 {
+            24
+        } else {
+            return Err(format!("Not a binary counts file: {}", path));
+        };
+        let ksize = u64::from_le_bytes(mmap[8..16].try_into().unwrap()) as usize;
+        let n_parts = u64::from_le_bytes(mmap[16..24].try_into().unwrap());
+        let (partitioning, count_bytes) = match header_len {
+            HEADER_LEN => (
+                match u64::from_le_bytes(mmap[24..32].try_into().unwrap()) as usize {
+                    0 => Partitioning::Modulo,
+                    msize => Partitioning::Signature(msize),
+                },
+                u64::from_le_bytes(mmap[32..40].try_into().unwrap()) as usize,
+            ),
+            _ => (Partitioning::Modulo, 4),
+        };
+        if count_bytes != 4 && count_bytes != 8 {
+            return Err(format!("Corrupted binary counts file: {}", path));
+        }
+        let data_start = header_len + 8 * (n_parts as usize + 1);
+        if n_parts == 0 || mmap.len() < data_start {
+            return Err(format!("Corrupted binary counts file: {}", path));
+        }
+        let offsets: Vec<usize> = mmap[header_len..data_start]
+            .chunks_exact(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()) as usize)
+            .collect();
+        let kmer_bytes = kmer_bytes(ksize);
+        if mmap.len() != data_start + offsets[n_parts as usize] * (kmer_bytes + count_bytes) {
+            return Err(format!("Corrupted binary counts file: {}", path));
+        }
+
+        Ok(Self {
+            mmap,
+            ksize,
+            kmer_bytes,
+            count_bytes,
+            n_parts,
+            partitioning,
+            offsets,
+            data_start,
+        })
+    }
+
+    pub fn ksize(&self) -> usize {
+        self.ksize
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets[self.n_parts as usize]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn record<K: KmerInt>(&self, idx: usize) -> (K, u64) {
+        let start = self.data_start + idx * (self.kmer_bytes + self.count_bytes);
+        let kmer = K::read_le(&self.mmap[start..start + self.kmer_bytes]);
+        let count = &self.mmap[start + self.kmer_bytes..];
+        let count = if self.count_bytes == 8 {
+            <u64 as Count>::read_le(count)
+        } else {
+            <u32 as Count>::read_le(count) as u64
+        };
+        (kmer, count)
+    }
+
+    // K must be u128 when ksize > 32 and u64 otherwise
+    pub fn get<K: KmerInt>(&self, kmer: K) -> Option<u64> {
+        debug_assert_eq!(K::BYTES, self.kmer_bytes);
+        let part = self.partitioning.part(kmer, self.ksize, self.n_parts);
+        let (mut lo, mut hi) = (self.offsets[part], self.offsets[part + 1]);
+        // binary search within the partition
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let (mid_kmer, count) = self.record::<K>(mid);
+            match mid_kmer.cmp(&kmer) {
+                std::cmp::Ordering::Equal => return Some(count),
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+            }
+        }
+        None
+    }
+
+    pub fn iter<K: KmerInt>(&self) -> impl Iterator<Item = (K, u64)> + '_ {
+        debug_assert_eq!(K::BYTES, self.kmer_bytes);
+        (0..self.len()).map(|idx| self.record::<K>(idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_roundtrip_test() {
+        let path = "../test_data/computed_counts_roundtrip.bin";
+        let mut writer = CountsWriter::new(path, 15, 2).unwrap();
+        writer
+            .write_partition(&mut [(4_u64, 1_u32), (2, 7), (0, 3)])
+            .unwrap();
+        writer
+            .write_partition(&mut [(5_u64, 2_u32), (1, 9)])
+            .unwrap();
+        writer.finish().unwrap();
+
+        assert!(is_binary_counts(path));
+        let reader = CountsReader::open(path).unwrap();
+        assert_eq!(reader.ksize(), 15);
+        assert_eq!(reader.len(), 5);
+        assert_eq!(reader.get(0_u64), Some(3));
+        assert_eq!(reader.get(2_u64), Some(7));
+        assert_eq!(reader.get(4_u64), Some(1));
+        assert_eq!(reader.get(1_u64), Some(9));
+        assert_eq!(reader.get(5_u64), Some(2));
+        assert_eq!(reader.get(3_u64), None);
+        assert_eq!(reader.get(6_u64), None);
+        assert_eq!(
+            reader.iter::<u64>().collect::<Vec<_>>(),
+            vec![(0, 3), (2, 7), (4, 1), (1, 9), (5, 2)]
+        );
+    }
+
+    #[test]
+    fn counts_wide_roundtrip_test() {
+        let path = "../test_data/computed_counts_roundtrip_wide.bin";
+        // 2 ^ 90 % 3 == 1
+        let big = 1_u128 << 90;
+        let mut writer = CountsWriter::new(path, 50, 3).unwrap();
+        writer.write_partition(&mut [(3_u128, 1_u32)]).unwrap();
+        writer
+            .write_partition(&mut [(big + 3, 4_u32), (big, 5)])
+            .unwrap();
+        writer.write_partition(&mut [(big + 1, 2_u32)]).unwrap();
+        writer.finish().unwrap();
+
+        let reader = CountsReader::open(path).unwrap();
+        assert_eq!(reader.ksize(), 50);
+        assert_eq!(reader.len(), 4);
+        assert_eq!(reader.get(3_u128), Some(1));
+        assert_eq!(reader.get(big + 1), Some(2));
+        assert_eq!(reader.get(big + 3), Some(4));
+        assert_eq!(reader.get(big), Some(5));
+        assert_eq!(reader.get(big + 2), None);
+    }
+
+    #[test]
+    fn counts_wide_counter_test() {
+        let path = "../test_data/computed_counts_roundtrip_u64.bin";
+        let big = u32::MAX as u64 + 7;
+        let mut writer =
+            CountsWriter::with_layout(path, 15, 2, Partitioning::Signature(9), CounterWidth::U64)
+                .unwrap();
+        let part = Partitioning::Signature(9).part(42_u64, 15, 2);
+        for idx in 0..2 {
+            let mut entries = if idx == part {
+                vec![(42_u64, big)]
+            } else {
+                vec![]
+            };
+            writer.write_partition(&mut entries).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = CountsReader::open(path).unwrap();
+        assert_eq!(reader.get(42_u64), Some(big));
+        assert_eq!(reader.get(43_u64), None);
+    }
+
+    #[test]
+    fn counts_append_partition_test() {
+        let path = "../test_data/computed_counts_append.bin";
+        let mut writer = CountsWriter::new(path, 15, 2).unwrap();
+        for (idx, entries) in [vec![(0_u64, 3_u32), (2, 7)], vec![(1_u64, 9_u32)]]
+            .into_iter()
+            .enumerate()
+        {
+            let part_path = format!("../test_data/computed_counts_append.part_{}", idx);
+            let mut part_file = File::create(&part_path).unwrap();
+            let written = write_records(&mut part_file, entries.into_iter()).unwrap();
+            writer.append_partition(&part_path, written).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = CountsReader::open(path).unwrap();
+        assert_eq!(reader.len(), 3);
+        assert_eq!(reader.get(0_u64), Some(3));
+        assert_eq!(reader.get(2_u64), Some(7));
+        assert_eq!(reader.get(1_u64), Some(9));
+        assert_eq!(reader.get(3_u64), None);
+
+        let mut writer =
+            CountsWriter::new("../test_data/computed_counts_missing.bin", 15, 1).unwrap();
+        assert!(writer
+            .append_partition("../test_data/doesnotexist.part", 1)
+            .is_err());
+    }
+
+    #[test]
+    fn counts_bad_file_test() {
+        assert!(CountsReader::open("../test_data/reads.fa").is_err());
+        assert!(CountsReader::open("../test_data/doesnotexist.bin").is_err());
+        assert!(!is_binary_counts("../test_data/reads.fa"));
+        assert!(!is_binary_counts("../test_data/doesnotexist.bin"));
+    }
+}
@@ -0,0 +1,234 @@
+use kmer::strand::Strand;
+#[cfg(feature = "gpu")]
+use {
+    cudarc::{
+        driver::{CudaDevice, CudaFunction, LaunchAsync, LaunchConfig},
+        nvrtc::compile_ptx,
+    },
+    libloading::{library_filename, Library},
+    std::{
+        env::consts::{DLL_PREFIX, DLL_SUFFIX},
+        ffi::OsString,
+        sync::Arc,
+    },
+};
+
+// partition of positions whose k-mer has a base other than ACGT(U)
+pub const INVALID: u32 = u32::MAX;
+
+// one thread per k-mer start, k-mers are 2-bit encoded as by GenericKmerGenerator and
+// partitions are as of counts::Partitioning (kmer % n_parts when msize is 0)
+#[cfg(feature = "gpu")]
+const KERNEL: &str = r#"
+__device__ unsigned long long hash64(unsigned long long key, unsigned long long mask) {
+    key = (~key + (key << 21)) & mask;
+    key = key ^ key >> 24;
+    key = ((key + (key << 3)) + (key << 8)) & mask;
+    key = key ^ key >> 14;
+    key = ((key + (key << 2)) + (key << 4)) & mask;
+    key = key ^ key >> 28;
+    key = (key + (key << 31)) & mask;
+    return key;
+}
+
+__device__ unsigned long long rev_comp(unsigned long long kmer, unsigned int size) {
+    unsigned long long rkmer = 0;
+    for (unsigned int i = 0; i < size; i++) {
+        rkmer = (rkmer << 2) | ((kmer & 3) ^ 3);
+        kmer >>= 2;
+    }
+    return rkmer;
+}
+
+extern "C" __global__ void kmers(
+    const unsigned char *seq, unsigned long long n, unsigned int ksize, unsigned int msize,
+    unsigned int strand, unsigned long long n_parts,
+    unsigned long long *out_kmers, unsigned int *out_parts
+) {
+    unsigned long long pos = (unsigned long long)blockIdx.x * blockDim.x + threadIdx.x;
+    if (pos + ksize > n) {
+        return;
+    }
+    unsigned long long mask = ksize == 32 ? ~0ull : (1ull << (2 * ksize)) - 1;
+    unsigned long long fmer = 0, rmer = 0;
+    for (unsigned int i = 0; i < ksize; i++) {
+        unsigned long long code;
+        switch (seq[pos + i]) {
+            case 'A': case 'a': code = 0; break;
+            case 'C': case 'c': code = 1; break;
+            case 'G': case 'g': code = 2; break;
+            case 'T': case 't': case 'U': case 'u': code = 3; break;
+            default: out_parts[pos] = 0xffffffffu; return;
+        }
+        fmer = ((fmer << 2) | code) & mask;
+        rmer = (rmer >> 2) | ((code ^ 3) << (2 * (ksize - 1)));
+    }
+    unsigned long long kmer = strand == 1 ? fmer : strand == 2 ? rmer : (fmer < rmer ? fmer : rmer);
+    out_kmers[pos] = kmer;
+    if (msize == 0) {
+        out_parts[pos] = (unsigned int)(kmer % n_parts);
+        return;
+    }
+    unsigned long long m_mask = (1ull << (2 * msize)) - 1, signature = ~0ull;
+    for (unsigned int shift = 0; shift + msize <= ksize; shift++) {
+        unsigned long long mmer = (fmer >> (2 * shift)) & m_mask;
+        unsigned long long rc = rev_comp(mmer, msize);
+        unsigned long long hash = hash64(mmer < rc ? mmer : rc, m_mask);
+        signature = hash < signature ? hash : signature;
+    }
+    out_parts[pos] = (unsigned int)(signature % n_parts);
+}
+"#;
+
+// records laid out for extraction, separated so that no k-mer spans two of them
+pub struct Batch {
+    pub bases: Vec<u8>,
+    // (offset, length) of each record in bases
+    pub records: Vec<(usize, usize)>,
+}
+
+impl Batch {
+    pub fn new<S: AsRef<[u8]>>(seqs: &[S]) -> Self {
+        let mut bases = Vec::with_capacity(seqs.iter().map(|seq| seq.as_ref().len() + 1).sum());
+        let mut records = Vec::with_capacity(seqs.len());
+        for seq in seqs {
+            records.push((bases.len(), seq.as_ref().len()));
+            bases.extend_from_slice(seq.as_ref());
+            bases.push(b'N');
+        }
+        Self { bases, records }
+    }
+}
+
+// k-mers of every position of a batch with their partitions, INVALID where there is no k-mer
+pub struct Extracted {
+    pub kmers: Vec<u64>,
+    pub parts: Vec<u32>,
+}
+
+// whether one of the libraries loads, the names are among those cudarc looks up so that it
+// finds a library when one of them loads, as it panics instead of erring without one
+#[cfg(feature = "gpu")]
+fn loadable(names: &[OsString]) -> bool {
+    names
+        .iter()
+        .any(|name| unsafe { Library::new(name) }.is_ok())
+}
+
+// CUDA driver and NVRTC (CUDA 12, 11 or 10) libraries
+#[cfg(feature = "gpu")]
+fn cuda_libraries() -> Result<(), String> {
+    if !loadable(&[library_filename("cuda"), library_filename("nvcuda")]) {
+        return Err("CUDA driver library not found".to_string());
+    }
+    let nvrtc = [
+        library_filename("nvrtc"),
+        format!("{}nvrtc{}.12", DLL_PREFIX, DLL_SUFFIX).into(),
+        format!("{}nvrtc{}.11", DLL_PREFIX, DLL_SUFFIX).into(),
+        format!("{}nvrtc{}.10", DLL_PREFIX, DLL_SUFFIX).into(),
+        format!("{}nvrtc64_120_0{}", DLL_PREFIX, DLL_SUFFIX).into(),
+    ];
+    if !loadable(&nvrtc) {
+        return Err("NVRTC library not found".to_string());
+    }
+    Ok(())
+}
+
+// k-mer extraction and partitioning on the first CUDA device, for k up to 32
+pub struct GpuKmers {
+    #[cfg(feature = "gpu")]
+    device: Arc<CudaDevice>,
+    #[cfg(feature = "gpu")]
+    kernel: CudaFunction,
+}
+
+impl GpuKmers {
+    #[cfg(not(feature = "gpu"))]
+    pub fn new() -> Result<Self, String> {
+        Err("kmertools was built without the gpu feature".to_string())
+    }
+
+    // an error without a CUDA driver, device or NVRTC, so that counting stays on the CPU
+    #[cfg(feature = "gpu")]
+    pub fn new() -> Result<Self, String> {
+        // cudarc panics when the CUDA libraries cannot be loaded
+        cuda_libraries()?;
+        let device = CudaDevice::new(0).map_err(|e| format!("No CUDA device: {}", e))?;
+        let ptx = compile_ptx(KERNEL).map_err(|e| format!("Unable to compile kernel: {}", e))?;
+        device
+            .load_ptx(ptx, "kmers", &["kmers"])
+            .map_err(|e| format!("Unable to load kernel: {}", e))?;
+        let kernel = device.get_func("kmers", "kmers").unwrap();
+        Ok(Self { device, kernel })
+    }
+
+    #[cfg(not(feature = "gpu"))]
+    pub fn extract(
+        &self,
+        _batch: &Batch,
+        _ksize: usize,
+        _msize: usize,
+        _strand: Strand,
+        _n_parts: u64,
+    ) -> Result<Extracted, String> {
+        unreachable!("GpuKmers cannot be created without the gpu feature")
+    }
+
+    // msize is the signature size of signature partitioning, 0 for modulo partitioning
+    #[cfg(feature = "gpu")]
+    pub fn extract(
+        &self,
+        batch: &Batch,
+        ksize: usize,
+        msize: usize,
+        strand: Strand,
+        n_parts: u64,
+    ) -> Result<Extracted, String> {
+        let error = |e: cudarc::driver::DriverError| format!("CUDA error: {}", e);
+        let n = batch.bases.len();
+        let strand: u32 = match strand {
+            Strand::Canonical => 0,
+            Strand::Forward => 1,
+            Strand::Reverse => 2,
+        };
+        let bases = self.device.htod_sync_copy(&batch.bases).map_err(error)?;
+        let mut kmers = self.device.alloc_zeros::<u64>(n).map_err(error)?;
+        let mut parts = self
+            .device
+            .htod_sync_copy(&vec![INVALID; n])
+            .map_err(error)?;
+        unsafe {
+            self.kernel.clone().launch(
+                LaunchConfig::for_num_elems(n as u32),
+                (
+                    &bases,
+                    n as u64,
+                    ksize as u32,
+                    msize as u32,
+                    strand,
+                    n_parts,
+                    &mut kmers,
+                    &mut parts,
+                ),
+            )
+        }
+        .map_err(error)?;
+        Ok(Extracted {
+            kmers: self.device.dtoh_sync_copy(&kmers).map_err(error)?,
+            parts: self.device.dtoh_sync_copy(&parts).map_err(error)?,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "gpu"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpu_kmers_new_test() {
+        // without the libraries creating fails with their error instead of panicking
+        if let Err(e) = cuda_libraries() {
+            assert_eq!(GpuKmers::new().err(), Some(e));
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::counts::CountsWriter;
+use kmer::{kmer::GenericKmerGenerator, numeric_to_kmer, strand::Strand, Kmer, KmerInt};
+use ktio::seq::get_reader;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, Read},
+    path::Path,
+};
+
+// Jellyfish .jf and KMC .kmc_pre/.kmc_suf databases are binary, only their text dumps are read
+fn reject_database(in_path: &str) -> Result<(), String> {
+    let mut head = Vec::new();
+    if let Ok(file) = File::open(in_path) {
+        let _ = file.take(10).read_to_end(&mut head);
+    }
+    // jellyfish 2 files start with the 9 digit length of their JSON header
+    if head.len() == 10 && head[..9].iter().all(u8::is_ascii_digit) && head[9] == b'{' {
+        return Err(format!(
+            "{} is a Jellyfish database, import its text dump (jellyfish dump) instead",
+            in_path
+        ));
+    }
+    // KMC databases are given by their prefix or by one of their two files
+    if head.starts_with(b"KMCP")
+        || head.starts_with(b"KMCS")
+        || Path::new(&format!("{}.kmc_pre", in_path)).exists()
+    {
+        return Err(format!(
+            "{} is a KMC database, import its text dump (kmc_dump) instead",
+            in_path
+        ));
+    }
+    Ok(())
+}
+
+// k-mer and count of one dump entry, both strands are merged as kmertools counts them
+// numeric k-mers are those of kmertools counts tables
+fn parse_entry<K: KmerInt>(
+    kmer: &str,
+    count: &str,
+    ksize: usize,
+    strand: Strand,
+) -> Result<(K, u32), String> {
+    let numeric;
+    let kmer = if !kmer.is_empty() && kmer.bytes().all(|c| c.is_ascii_digit()) {
+        let value: K = kmer
+            .parse()
+            .map_err(|_| format!("Not a {}-mer: {}", ksize, kmer))?;
+        if ksize < K::MAX_KSIZE && value >> (2 * ksize) != K::default() {
+            return Err(format!("Not a {}-mer: {}", ksize, kmer));
+        }
+        numeric = numeric_to_kmer(value, ksize);
+        numeric.as_str()
+    } else {
+        kmer
+    };
+    if kmer.len() != ksize {
+        return Err(format!("Expected a {}-mer, got: {}", ksize, kmer));
+    }
+    let (fmer, rmer) = GenericKmerGenerator::<K>::new(kmer.as_bytes(), ksize)
+        .next()
+        .ok_or(format!("Not an ACGT k-mer: {}", kmer))?;
+    let count = count
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid count: {}", count))?;
+    Ok((strand.pick(fmer, rmer), count))
+}
+
+fn import<K: KmerInt>(
+    in_path: &str,
+    out_path: &str,
+    ksize: usize,
+    strand: Strand,
+) -> Result<usize, String> {
+    reject_database(in_path)?;
+    let reader = get_reader(in_path)?;
+    let mut counts: HashMap<K, u32> = HashMap::new();
+    let mut lines = reader.lines().map_while(Result::ok);
+
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        // jellyfish dump: >count followed by the k-mer
+        let (kmer, count) = if let Some(count) = line.strip_prefix('>') {
+            let kmer = lines
+                .next()
+                .ok_or(format!("Missing k-mer after: {}", line))?;
+            parse_entry::<K>(kmer.trim(), count, ksize, strand)?
+        } else {
+            // jellyfish dump -c, kmc_dump and kmertools counts: k-mer first and count last,
+            // separated by whitespace
+            let mut parts = line.split_whitespace();
+            let kmer = parts.next().unwrap();
+            let count = parts
+                .next_back()
+                .ok_or(format!("Missing count after: {}", kmer))?;
+            parse_entry::<K>(kmer, count, ksize, strand)?
+        };
+        *counts.entry(kmer).or_insert(0) += count;
+    }
+
+    let mut entries: Vec<(K, u32)> = counts.into_iter().collect();
+    let mut writer = CountsWriter::new(out_path, ksize, 1)?;
+    writer.write_partition(&mut entries)?;
+    writer.finish()?;
+
+    Ok(entries.len())
+}
+
+// Jellyfish (dump, dump -c), KMC (kmc_dump) or kmertools text counts into a binary counts table,
+// returns the number of distinct k-mers, their binary databases are rejected
+pub fn import_counts(
+    in_path: &str,
+    out_path: &str,
+    ksize: usize,
+    strand: Strand,
+) -> Result<usize, String> {
+    if ksize > Kmer::MAX_KSIZE {
+        import::<u128>(in_path, out_path, ksize, strand)
+    } else {
+        import::<Kmer>(in_path, out_path, ksize, strand)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counts::CountsReader;
+    use kmer::kmer_to_numeric;
+    use std::fs;
+
+    #[test]
+    fn import_counts_test() {
+        let out_path = "../test_data/computed_imported.counts.bin";
+        // ACGG and its reverse complement CCGT are one canonical k-mer
+        let dumps = [
+            (
+                "../test_data/computed_jellyfish.fa",
+                ">3\nAAAA\n>2\nACGG\n>1\nCCGT\n",
+            ),
+            (
+                "../test_data/computed_jellyfish.txt",
+                "AAAA 3\nACGG 2\nCCGT 1\n",
+            ),
+            (
+                "../test_data/computed_kmc.txt",
+                "AAAA\t3\nACGG\t2\nCCGT\t1\n",
+            ),
+            // kmertools counts, numeric and with --acgt-column
+            ("../test_data/computed_kmertools.counts", "0\t3\n26\t3\n"),
+            (
+                "../test_data/computed_kmertools_acgt.counts",
+                "0\tAAAA\t3\n26\tACGG\t2\n91\tCCGT\t1\n",
+            ),
+        ];
+        for (path, dump) in dumps {
+            fs::write(path, dump).unwrap();
+            assert_eq!(import_counts(path, out_path, 4, Strand::Canonical), Ok(2));
+            let counts = CountsReader::open(out_path).unwrap();
+            assert_eq!(counts.get(kmer_to_numeric("AAAA").unwrap()), Some(3));
+            assert_eq!(counts.get(kmer_to_numeric("ACGG").unwrap()), Some(3));
+            assert_eq!(counts.get(kmer_to_numeric("CCGT").unwrap()), None);
+        }
+        assert_eq!(
+            import_counts(dumps[1].0, out_path, 4, Strand::Forward),
+            Ok(3)
+        );
+        assert!(import_counts(dumps[1].0, out_path, 5, Strand::Canonical).is_err());
+        fs::write(dumps[3].0, "256\t1\n").unwrap();
+        assert!(import_counts(dumps[3].0, out_path, 4, Strand::Canonical).is_err());
+    }
+
+    #[test]
+    fn import_database_test() {
+        let out_path = "../test_data/computed_imported_db.counts.bin";
+        // binary databases are rejected by their headers
+        let jellyfish = "../test_data/computed_jellyfish.jf";
+        fs::write(jellyfish, b"000000042{\"alignment\":8}\0\0").unwrap();
+        let e = import_counts(jellyfish, out_path, 4, Strand::Canonical).unwrap_err();
+        assert!(e.contains("Jellyfish database"), "{}", e);
+        let kmc = "../test_data/computed_kmc_db";
+        fs::write(format!("{}.kmc_pre", kmc), b"KMCP\0\0\0\0").unwrap();
+        fs::write(format!("{}.kmc_suf", kmc), b"KMCS\0\0\0\0").unwrap();
+        for path in [kmc.to_string(), format!("{}.kmc_suf", kmc)] {
+            let e = import_counts(&path, out_path, 4, Strand::Canonical).unwrap_err();
+            assert!(e.contains("KMC database"), "{}", e);
+        }
+        // text counts starting with a numeric k-mer are not taken for a database
+        let counts = "../test_data/computed_kmertools_db.counts";
+        fs::write(counts, "123456789\t1\n").unwrap();
+        assert_eq!(import_counts(counts, out_path, 15, Strand::Forward), Ok(1));
+    }
+}
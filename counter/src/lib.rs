@@ -1,26 +1,218 @@
+pub mod counts;
+pub mod gpu;
+pub mod import;
+pub mod matrix;
+pub mod memory;
+pub mod ops;
+pub mod rescale;
+pub mod shards;
+pub mod spill;
+pub mod whitelist;
+pub mod width;
+use counts::{CountsWriter, Partitioning};
+use gpu::{Batch, Extracted, GpuKmers};
 use indicatif::{ProgressBar, ProgressStyle};
-use kmer::{kmer::KmerGenerator, numeric_to_kmer, Kmer};
+use kmer::{
+    kmer::{compress_homopolymers, GenericKmerGenerator},
+    numeric_to_kmer_in,
+    sketch::{hash64, HyperLogLog},
+    stats::KmerStats,
+    strand::Strand,
+    superkmer::{signature_size, super_kmers},
+    Kmer, KmerInt, Molecule,
+};
 use ktio::{
+    filter::RecordFilter,
     fops::delete_file_if_exists,
-    seq::{get_reader, SeqFormat, Sequences},
+    seq::{get_reader, SeqFormat, Sequence, Sequences},
 };
+use memory::MemoryPolicy;
 use rayon::prelude::*;
 use scc::HashMap as SccMap;
+use shards::ShardWriter;
+use spill::{MergedRuns, SpillCompression};
 use std::{
+    borrow::Cow,
     cmp::{max, min},
+    collections::BTreeMap,
     fs,
-    io::{BufRead, BufReader, BufWriter, Read, Write},
+    io::{self, BufReader, BufWriter, Read, Write},
+    ops::RangeInclusive,
+    path::Path,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, Mutex,
     },
+    thread,
+    time::{Duration, Instant},
 };
+use whitelist::Whitelist;
+use width::{Count, CounterWidth};
+
+// k-mer sizes ctr counts, k > 32 uses 128-bit k-mers
+pub const KSIZES: RangeInclusive<u64> = 10..=63;
+// registers of the distinct k-mer estimate are 2^HLL_PRECISION, about 0.8% error
+const HLL_PRECISION: u32 = 14;
+// bases a worker extracts at once on the GPU, 12 bytes of k-mer and partition per base
+const GPU_BATCH_BASES: usize = 1 << 20;
 
 // only to make code more readable
-type SeqArc = Arc<Mutex<Sequences<BufReader<Box<dyn Read + Sync + Send>>>>>;
+type SeqReader = BufReader<Box<dyn Read + Sync + Send>>;
+type SeqArc = Arc<Mutex<Sequences<SeqReader>>>;
+
+// a partition merged in memory, or as sorted runs on disk once it outgrew the merge limit
+enum MergedPartition<K, C> {
+    Map(SccMap<K, C>),
+    Runs(Vec<String>),
+}
+
+// one line of the kmers.chunks checkpoint log
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChunkStats {
+    pub records: u64,
+    pub kmers: u64,
+    pub distinct: u64,
+    pub seconds: f64,
+    // partitions spilled early to stay under the memory ceiling
+    pub spills: u64,
+    // chunk was cut short by the memory ceiling
+    pub memory_limit: bool,
+}
+
+// how k-mers are written to text counts
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum KmerText {
+    // numeric k-mers, sortable and searchable
+    #[default]
+    Numeric,
+    // ACGT k-mers, with U in place of T for RNA
+    Acgt(Molecule),
+    // numeric k-mer followed by its ACGT form
+    NumericAcgt(Molecule),
+}
+
+// where merged counts are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CountsOutput {
+    // <kmer>\t<count> lines in kmers.counts, ascending k-mers when sorted, numeric order
+    // is also the ACGT lexicographic order
+    Text { kmers: KmerText, sorted: bool },
+    // sorted and searchable binary counts in kmers.counts.bin
+    Binary,
+    // sorted binary shard per partition in kmers.shards, with an index of their k-mer ranges
+    Shards,
+}
+
+impl Default for CountsOutput {
+    fn default() -> Self {
+        CountsOutput::Text {
+            kmers: KmerText::Numeric,
+            sorted: false,
+        }
+    }
+}
+
+// which k-mers of the records are counted, the default is every canonical k-mer
+#[derive(Debug, Clone)]
+pub struct KmerOptions {
+    // forward or reverse k-mers only for stranded libraries
+    pub strand: Strand,
+    // count every stride-th k-mer position only, for approximate profiles
+    pub stride: usize,
+    // k-mers of homopolymer compressed reads, for long reads with homopolymer length errors
+    pub hpc: bool,
+    // only whitelisted k-mers are counted, memory stays proportional to the whitelist
+    pub whitelist: Option<Whitelist>,
+    pub filter: Option<RecordFilter>,
+}
+
+impl Default for KmerOptions {
+    fn default() -> Self {
+        Self {
+            strand: Strand::Canonical,
+            stride: 1,
+            hpc: false,
+            whitelist: None,
+            filter: None,
+        }
+    }
+}
+
+// what is written once counting is done, the default is every k-mer in text kmers.counts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputOptions {
+    pub counts: CountsOutput,
+    // 64-bit counters for deep data, or 32-bit ones held at u32::MAX instead of wrapping
+    pub width: CounterWidth,
+    // abundance spectrum written to kmers.histo while merging
+    pub histogram: bool,
+    // k-mers counted fewer than min_count or more than max_count times are not written
+    pub min_count: u64,
+    pub max_count: u64,
+    // k-mers seen and skipped in kmers.stats and a line per chunk in kmers.chunks
+    pub stats: bool,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            counts: CountsOutput::default(),
+            width: CounterWidth::default(),
+            histogram: false,
+            min_count: 1,
+            max_count: u64::MAX,
+            stats: false,
+        }
+    }
+}
+
+// how k-mers are split into partitions, spilled and merged, 0 derives a value from the
+// threads or the memory ceiling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PartitionOptions {
+    // by the super-k-mer signature of the k-mer size unless set
+    pub partitioning: Option<Partitioning>,
+    // fraction of the memory ceiling after which workers spill the largest partition
+    // before reading on, values of 1 or more only cut chunks at the ceiling
+    pub soft_limit: f64,
+    pub memory_policy: MemoryPolicy,
+    // trade some CPU for less temporary disk space
+    pub compress_tmp: SpillCompression,
+    pub parts_in_flight: usize,
+    // k-mers of the partitions in flight held in memory while merging, larger partitions
+    // go through sorted runs on disk
+    pub merge_limit: u64,
+}
+
+impl Default for PartitionOptions {
+    fn default() -> Self {
+        Self {
+            partitioning: None,
+            soft_limit: 0.75,
+            memory_policy: MemoryPolicy::default(),
+            compress_tmp: SpillCompression::default(),
+            parts_in_flight: 0,
+            merge_limit: 0,
+        }
+    }
+}
+
+// what is counted and written, the default counts canonical k-mers into text kmers.counts
+#[derive(Debug, Clone, Default)]
+pub struct CountOptions {
+    pub kmers: KmerOptions,
+    pub output: OutputOptions,
+    pub partitions: PartitionOptions,
+}
 
 pub struct CountComputer {
     in_path: String,
+    // second file of paired reads, counted after in_path
+    mate_path: Option<String>,
+    format: SeqFormat,
+    // input is read once, partitions grow with the bases read so far
+    streaming: bool,
+    streamed: AtomicU64,
     out_dir: String,
     ksize: usize,
     threads: usize,
@@ -30,27 +222,77 @@ pub struct CountComputer {
     memory_ceil_gb: f64,
     seq_count: u64,
     debug: bool,
-    acgt: bool,
+    options: CountOptions,
+    stats: Mutex<KmerStats>,
+    histogram: Mutex<BTreeMap<u64, u64>>,
+    // samples of a matrix share the same partitions
+    min_parts: u64,
+    chunk_stats: Vec<ChunkStats>,
+    saturated: AtomicBool,
+    gpu: Option<GpuKmers>,
+    gpu_failed: AtomicBool,
 }
 
 impl CountComputer {
     pub fn new(in_path: String, out_dir: String, ksize: usize) -> Self {
         let format = SeqFormat::get(&in_path).unwrap();
         let reader = ktio::seq::get_reader(&in_path).unwrap();
+        Self::with_reader(in_path, format, reader, out_dir, ksize)
+    }
+
+    // counts the reads of both files of a pair into one table
+    pub fn from_mates(in_path: String, mate_path: String, out_dir: String, ksize: usize) -> Self {
+        let format = SeqFormat::get(&in_path).unwrap();
+        let reader = ktio::seq::get_mates_reader(&in_path, &mate_path).unwrap();
+        let mut ctr = Self::with_reader(in_path, format, reader, out_dir, ksize);
+        ctr.mate_path = Some(mate_path);
+        ctr
+    }
+
+    // counts a stream such as stdin in a single pass, the format cannot be told from a path
+    pub fn from_stream(
+        format: SeqFormat,
+        reader: SeqReader,
+        out_dir: String,
+        ksize: usize,
+    ) -> Self {
+        let mut ctr = Self::with_reader("-".to_string(), format, reader, out_dir, ksize);
+        ctr.streaming = true;
+        ctr
+    }
+
+    fn with_reader(
+        in_path: String,
+        format: SeqFormat,
+        reader: SeqReader,
+        out_dir: String,
+        ksize: usize,
+    ) -> Self {
         let records = Sequences::new(format, reader).unwrap();
 
         Self {
             in_path,
+            mate_path: None,
+            format,
+            streaming: false,
+            streamed: AtomicU64::new(0),
             out_dir,
             ksize,
-            threads: rayon::current_num_threads(),
+            threads: ktio::threads::default_threads(),
             records: Arc::new(Mutex::new(records)),
             chunks: 0,
             n_parts: 0,
             seq_count: 0,
             memory_ceil_gb: 6_f64,
             debug: false,
-            acgt: false,
+            options: CountOptions::default(),
+            stats: Mutex::new(KmerStats::default()),
+            histogram: Mutex::new(BTreeMap::new()),
+            min_parts: 0,
+            chunk_stats: Vec::new(),
+            saturated: AtomicBool::new(false),
+            gpu: None,
+            gpu_failed: AtomicBool::new(false),
         }
     }
 
@@ -62,43 +304,222 @@ impl CountComputer {
         self.memory_ceil_gb = memory_ceil_gb;
     }
 
-    pub fn set_acgt_output(&mut self, acgt: bool) {
-        self.acgt = acgt;
+    // k-mers go to partitions by their super-k-mer signature unless set otherwise
+    fn partitioning(&self) -> Partitioning {
+        self.options
+            .partitions
+            .partitioning
+            .unwrap_or(Partitioning::Signature(signature_size(self.ksize)))
+    }
+
+    // what is counted and written, the record filter applies to records not read yet
+    pub fn set_options(&mut self, options: CountOptions) {
+        self.records
+            .lock()
+            .unwrap()
+            .set_filter(options.kmers.filter.clone());
+        self.options = options;
+        self.options.kmers.stride = max(1, self.options.kmers.stride);
+    }
+
+    // k-mers are extracted and partitioned on a CUDA GPU (gpu feature), an error leaves
+    // counting on the CPU, as does a failure while counting
+    pub fn set_gpu(&mut self, gpu: bool) -> Result<(), String> {
+        self.gpu = None;
+        if gpu {
+            if self.ksize > Kmer::MAX_KSIZE {
+                return Err(format!("GPU counting supports k up to {}", Kmer::MAX_KSIZE));
+            }
+            self.gpu = Some(GpuKmers::new()?);
+        }
+        Ok(())
+    }
+
+    // (count, distinct k-mers with that count) of the last merge
+    pub fn histogram(&self) -> Vec<(u64, u64)> {
+        self.histogram
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(count, kmers)| (*count, *kmers))
+            .collect()
+    }
+
+    pub fn kmer_stats(&self) -> KmerStats {
+        *self.stats.lock().unwrap()
+    }
+
+    // HyperLogLog sketch of the counted k-mers and the number of k-mers, streamed
+    // without counting tables or temporary files, the input is consumed
+    pub fn estimate(&self) -> Result<(HyperLogLog, u64), String> {
+        let mut records = self.records.lock().unwrap();
+        let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+        let empty = || (HyperLogLog::new(HLL_PRECISION), 0_u64);
+        let (mut hll, mut total) = empty();
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let (sketch, kmers) = pool.install(|| {
+                batch
+                    .par_iter()
+                    .fold(empty, |(mut sketch, kmers), record| {
+                        // k-mers longer than 32 bases need 128 bits
+                        let added = if self.ksize > Kmer::MAX_KSIZE {
+                            self.sketch_record::<u128>(&self.sequence(&record.seq), &mut sketch)
+                        } else {
+                            self.sketch_record::<Kmer>(&self.sequence(&record.seq), &mut sketch)
+                        };
+                        (sketch, kmers + added)
+                    })
+                    .reduce(empty, |(mut sketch, kmers), (other, other_kmers)| {
+                        sketch.merge(&other);
+                        (sketch, kmers + other_kmers)
+                    })
+            });
+            hll.merge(&sketch);
+            total += kmers;
+        }
+
+        Ok((hll, total))
+    }
+
+    fn sketch_record<K: KmerInt>(&self, seq: &[u8], hll: &mut HyperLogLog) -> u64 {
+        let mut kmers = 0;
+        for (fmer, rmer) in
+            GenericKmerGenerator::<K>::new(seq, self.ksize).with_stride(self.options.kmers.stride)
+        {
+            let kmer = self.options.kmers.strand.pick(fmer, rmer);
+            if self
+                .options
+                .kmers
+                .whitelist
+                .as_ref()
+                .is_some_and(|whitelist| !whitelist.contains(kmer))
+            {
+                continue;
+            }
+            let kmer = kmer.as_u128();
+            hll.add_hash(hash64(
+                kmer as u64 ^ hash64((kmer >> 64) as u64, u64::MAX),
+                u64::MAX,
+            ));
+            kmers += 1;
+        }
+        kmers
+    }
+
+    pub fn chunk_stats(&self) -> &[ChunkStats] {
+        &self.chunk_stats
+    }
+
+    // appended and flushed after every chunk, shows balance of chunks and early memory cut offs
+    fn log_chunk(&self, log: &mut fs::File, stats: &ChunkStats) {
+        writeln!(
+            log,
+            "{}\t{}\t{}\t{}\t{:.3}\t{}\t{}",
+            self.chunks,
+            stats.records,
+            stats.kmers,
+            stats.distinct,
+            stats.seconds,
+            stats.spills,
+            stats.memory_limit
+        )
+        .unwrap();
+        log.flush().unwrap();
     }
 
     pub fn count(&mut self) {
         self.init();
         let pbar = ProgressBar::new(self.seq_count);
-        pbar.set_style(
+        pbar.set_style(if self.streaming {
+            // the number of records of a stream is not known
+            ProgressStyle::with_template("[{elapsed_precise}] {spinner} {pos:>7} records {msg}")
+                .unwrap()
+        } else {
             ProgressStyle::with_template(
                 "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} ({percent}%) {msg}",
             )
             .unwrap()
-            .progress_chars("#>-"),
-        );
+            .progress_chars("#>-")
+        });
+        let mut log = self.options.output.stats.then(|| {
+            let mut log = fs::File::create(format!("{}/kmers.chunks", self.out_dir)).unwrap();
+            writeln!(
+                log,
+                "chunk\trecords\tkmers\tdistinct\tseconds\tspills\tmemory_limit"
+            )
+            .unwrap();
+            log
+        });
         loop {
             // TODO have to fix below line being called even the next chunk does not exist
             pbar.set_message(format!("Processing chunk: {}", self.chunks + 1));
-            let records = self.count_chunk(&pbar);
-            if records > 0 {
+            // k-mers longer than 32 bases need 128 bits
+            let stats = match (self.ksize > Kmer::MAX_KSIZE, self.options.output.width) {
+                (true, CounterWidth::U64) => self.count_chunk::<u128, u64>(&pbar),
+                (true, _) => self.count_chunk::<u128, u32>(&pbar),
+                (false, CounterWidth::U64) => self.count_chunk::<Kmer, u64>(&pbar),
+                (false, _) => self.count_chunk::<Kmer, u32>(&pbar),
+            };
+            if stats.records > 0 {
+                if let Some(log) = log.as_mut() {
+                    self.log_chunk(log, &stats);
+                }
+                self.chunk_stats.push(stats);
                 self.chunks += 1;
+                if self.streaming {
+                    match (self.ksize > Kmer::MAX_KSIZE, self.options.output.width) {
+                        (true, CounterWidth::U64) => self.grow_parts::<u128, u64>(),
+                        (true, _) => self.grow_parts::<u128, u32>(),
+                        (false, CounterWidth::U64) => self.grow_parts::<Kmer, u64>(),
+                        (false, _) => self.grow_parts::<Kmer, u32>(),
+                    }
+                }
             } else {
                 break;
             }
         }
         pbar.finish();
+        if !self.options.output.stats {
+            return;
+        }
+        // stride is recorded so that sampled counts can be rescaled, k so that the counts
+        // are not taken for counts of another k-mer size
+        fs::write(
+            format!("{}/kmers.stats", self.out_dir),
+            format!(
+                "{}stride\t{}\nksize\t{}\n",
+                self.kmer_stats(),
+                self.options.kmers.stride,
+                self.ksize
+            ),
+        )
+        .unwrap();
     }
 
-    fn count_chunk(&self, pbar: &ProgressBar) -> u64 {
+    fn count_chunk<K: KmerInt, C: Count>(&self, pbar: &ProgressBar) -> ChunkStats {
+        let start = Instant::now();
         let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
             .num_threads(self.threads)
             .build()
             .unwrap();
         let total_records = Arc::new(AtomicU64::new(0));
-        // an estimate of worse case kmer count
-        let total_kmers_so_far = Arc::new(AtomicU64::new(0));
-        let counts_table: Vec<SccMap<Kmer, u32>> = vec![SccMap::new(); self.n_parts as usize];
+        let total_kmers = AtomicU64::new(0);
+        // bytes held by the partition tables, as measured by the memory policy
+        let held = Arc::new(AtomicU64::new(0));
+        let counts_table: Vec<SccMap<K, C>> = vec![SccMap::new(); self.n_parts as usize];
         let counts_table_arc = Arc::new(counts_table);
+        let budget = (1_000_000_000_f64 * self.memory_ceil_gb) as u64;
+        // early spills of each partition, held by the spilling worker
+        let spills = Mutex::new(vec![0_u64; self.n_parts as usize]);
+        let spilled = AtomicU64::new(0);
         // make pbar for all bases struct wide
 
         pool.scope(|scope| {
@@ -106,38 +527,88 @@ impl CountComputer {
                 let records_arc_clone = Arc::clone(&self.records);
                 let total_records_clone = Arc::clone(&total_records);
                 let counts_table_arc_clone = Arc::clone(&counts_table_arc);
-                let total_kmers_so_far_clone = Arc::clone(&total_kmers_so_far);
+                let held_clone = Arc::clone(&held);
+                let total_kmers = &total_kmers;
+                let spills = &spills;
+                let spilled = &spilled;
 
                 scope.spawn(move |_| {
+                    let mut stats = KmerStats::default();
+                    let mut inserted = 0;
+                    let mut bases = 0;
                     loop {
-                        // when limit reached exit without further reads
-                        if total_kmers_so_far_clone.load(Ordering::Relaxed)
-                            > (1_000_000_000_f64 * self.memory_ceil_gb / 8.0) as u64
+                        // past the soft limit one worker spills while the others hold their reads
+                        if held_clone.load(Ordering::Relaxed)
+                            > (budget as f64 * self.options.partitions.soft_limit) as u64
                         {
+                            if let Ok(mut spills) = spills.try_lock() {
+                                spilled.fetch_add(
+                                    self.spill_largest(&counts_table_arc_clone, &mut spills),
+                                    Ordering::Relaxed,
+                                );
+                                held_clone.store(
+                                    self.options
+                                        .partitions
+                                        .memory_policy
+                                        .held(&counts_table_arc_clone),
+                                    Ordering::Relaxed,
+                                );
+                            } else {
+                                thread::sleep(Duration::from_millis(1));
+                                continue;
+                            }
+                        }
+                        // when limit reached exit without further reads
+                        if held_clone.load(Ordering::Relaxed) > budget {
                             break;
                         }
-                        let record = { records_arc_clone.lock().unwrap().next() };
-                        if let Some(record) = record {
+                        let batch = self.take_records(&records_arc_clone);
+                        if batch.is_empty() {
+                            // end of iteration
+                            break;
+                        }
+                        let seqs: Vec<Cow<[u8]>> = batch
+                            .iter()
+                            .map(|record| self.sequence(&record.seq))
+                            .collect();
+                        let extracted = self.extract_batch(&seqs);
+                        for (n, seq) in seqs.iter().enumerate() {
                             pbar.inc(1);
                             total_records_clone.fetch_add(1, Ordering::Acquire);
-                            for (fmer, rmer) in KmerGenerator::new(&record.seq, self.ksize) {
-                                let min_mer = min(fmer, rmer);
-                                unsafe {
-                                    counts_table_arc_clone
-                                        .get_unchecked((min_mer % self.n_parts) as usize)
-                                        .entry(min_mer)
-                                        .and_modify(|v| *v += 1)
-                                        .or_insert(1);
-                                }
-                            }
+                            let kmers = match &extracted {
+                                Some((gpu_batch, extracted)) => self.count_extracted(
+                                    gpu_batch.records[n],
+                                    extracted,
+                                    &counts_table_arc_clone,
+                                ),
+                                None => self.count_record(seq, &counts_table_arc_clone),
+                            };
+                            inserted += kmers;
+                            bases += seq.len() as u64;
+                            // statistics are of all k-mer positions, not only the sampled or whitelisted ones
+                            stats += if self.options.kmers.stride == 1
+                                && self.options.kmers.whitelist.is_none()
+                            {
+                                KmerStats::new(seq.len(), self.ksize, kmers)
+                            } else {
+                                KmerStats::from_seq(seq, self.ksize)
+                            };
 
-                            total_kmers_so_far_clone
-                                .fetch_add(record.seq.len() as u64, Ordering::Relaxed);
-                        } else {
-                            // end of iteration
-                            break;
+                            match self.options.partitions.memory_policy.record(seq.len()) {
+                                Some(bytes) => held_clone.fetch_add(bytes, Ordering::Relaxed),
+                                None => held_clone.swap(
+                                    self.options
+                                        .partitions
+                                        .memory_policy
+                                        .held(&counts_table_arc_clone),
+                                    Ordering::Relaxed,
+                                ),
+                            };
                         }
                     }
+                    total_kmers.fetch_add(inserted, Ordering::Relaxed);
+                    self.streamed.fetch_add(bases, Ordering::Relaxed);
+                    *self.stats.lock().unwrap() += stats;
                 });
             }
         });
@@ -145,7 +616,7 @@ impl CountComputer {
         let recs = total_records.load(Ordering::Acquire);
 
         if recs == 0 {
-            return 0;
+            return ChunkStats::default();
         }
 
         pool.scope(|_| {
@@ -153,29 +624,357 @@ impl CountComputer {
                 .par_iter()
                 .enumerate()
                 .for_each(|(part, map)| {
-                    let outf = fs::File::create(format!(
-                        "{}/temp_kmers.part_{}_chunk_{}",
-                        self.out_dir, part, self.chunks
-                    ))
-                    .unwrap();
-                    let mut buff = BufWriter::new(outf);
+                    let mut buff = self
+                        .options
+                        .partitions
+                        .compress_tmp
+                        .writer(&format!(
+                            "{}/temp_kmers.part_{}_chunk_{}",
+                            self.out_dir, part, self.chunks
+                        ))
+                        .unwrap();
                     map.scan(|k, v| {
-                        buff.write_all(format!("{}\t{:?}\n", k, v).as_bytes())
+                        self.options
+                            .partitions
+                            .compress_tmp
+                            .write_entry(&mut buff, *k, *v)
                             .unwrap();
                     });
                 })
         });
 
-        total_records.load(Ordering::Acquire)
+        let early_spills = spills.lock().unwrap().iter().sum();
+        ChunkStats {
+            records: recs,
+            kmers: total_kmers.load(Ordering::Relaxed),
+            // k-mers spilled more than once are counted for each spill
+            distinct: counts_table_arc
+                .iter()
+                .map(|map| map.len() as u64)
+                .sum::<u64>()
+                + spilled.load(Ordering::Relaxed),
+            seconds: start.elapsed().as_secs_f64(),
+            spills: early_spills,
+            memory_limit: held.load(Ordering::Relaxed) > budget,
+        }
+    }
+
+    // moves the largest partition of the chunk to disk, returns the k-mers spilled
+    fn spill_largest<K: KmerInt, C: Count>(
+        &self,
+        counts_table: &[SccMap<K, C>],
+        spills: &mut [u64],
+    ) -> u64 {
+        let (part, map) = counts_table
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, map)| map.len())
+            .unwrap();
+        let path = format!(
+            "{}/temp_kmers.part_{}_chunk_{}.{}",
+            self.out_dir, part, self.chunks, spills[part]
+        );
+        spills[part] += 1;
+        let mut buff = self.options.partitions.compress_tmp.writer(&path).unwrap();
+        let mut entries = 0;
+        // entries are removed as they are written, concurrent counts land in new entries
+        map.retain(|k, v| {
+            self.options
+                .partitions
+                .compress_tmp
+                .write_entry(&mut buff, *k, *v)
+                .unwrap();
+            entries += 1;
+            false
+        });
+        entries
+    }
+
+    // partitions of a stream double until they suit the bases read so far, k-mers of
+    // partition p can only move to p + i * n_parts so each partition is split on its own
+    fn grow_parts<K: KmerInt, C: Count>(&mut self) {
+        let target = self.parts_for(self.streamed.load(Ordering::Relaxed));
+        let mut n_parts = self.n_parts;
+        while n_parts < target {
+            n_parts *= 2;
+        }
+        if n_parts == self.n_parts {
+            return;
+        }
+        let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            (0..self.n_parts).into_par_iter().for_each(|part| {
+                for chunk in 0..self.chunks {
+                    self.split_part::<K, C>(part, chunk, n_parts);
+                }
+            })
+        });
+        self.n_parts = n_parts;
+    }
+
+    fn split_part<K: KmerInt, C: Count>(&self, part: u64, chunk: u64, n_parts: u64) {
+        let split_path = |part: u64| {
+            format!(
+                "{}/temp_kmers.part_{}_chunk_{}.split",
+                self.out_dir, part, chunk
+            )
+        };
+        let new_parts: Vec<u64> = (part..n_parts).step_by(self.n_parts as usize).collect();
+        let mut buffs: Vec<_> = new_parts
+            .iter()
+            .map(|&new_part| {
+                self.options
+                    .partitions
+                    .compress_tmp
+                    .writer(&split_path(new_part))
+                    .unwrap()
+            })
+            .collect();
+        // early spills are folded into the chunk files, the merge adds repeated k-mers
+        for path in self.chunk_paths(part, chunk) {
+            for (kmer, count) in self
+                .options
+                .partitions
+                .compress_tmp
+                .entries::<K, C>(&path)
+                .unwrap()
+            {
+                let new_part = self.partitioning().part(kmer, self.ksize, n_parts) as u64;
+                let buff = &mut buffs[(new_part / self.n_parts) as usize];
+                self.options
+                    .partitions
+                    .compress_tmp
+                    .write_entry(buff, kmer, count)
+                    .unwrap();
+            }
+            delete_file_if_exists(&path).expect("file must be removable");
+        }
+        drop(buffs);
+        for new_part in new_parts {
+            fs::rename(
+                split_path(new_part),
+                format!(
+                    "{}/temp_kmers.part_{}_chunk_{}",
+                    self.out_dir, new_part, chunk
+                ),
+            )
+            .unwrap();
+        }
+    }
+
+    // temporary files of a partition in a chunk, the chunk file and its early spills
+    pub(crate) fn chunk_paths(&self, part: u64, chunk: u64) -> Vec<String> {
+        let path = format!("{}/temp_kmers.part_{}_chunk_{}", self.out_dir, part, chunk);
+        let mut paths = vec![path.clone()];
+        paths.extend(
+            (0..)
+                .map(|spill| format!("{}.{}", path, spill))
+                .take_while(|spill| Path::new(spill).exists()),
+        );
+        paths
+    }
+
+    // reads are compressed up front so that super-k-mers and statistics see the counted bases
+    fn sequence<'a>(&self, seq: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.options.kmers.hpc {
+            Cow::Owned(compress_homopolymers(seq))
+        } else {
+            Cow::Borrowed(seq)
+        }
+    }
+
+    // records counted by a worker at once, a batch of bases when k-mers are extracted on the GPU
+    fn take_records(&self, records: &SeqArc) -> Vec<Sequence> {
+        let mut records = records.lock().unwrap();
+        if self.gpu.is_none() || self.gpu_failed.load(Ordering::Relaxed) {
+            return records.next().into_iter().collect();
+        }
+        let mut batch = Vec::new();
+        let mut bases = 0;
+        while bases < GPU_BATCH_BASES {
+            match records.next() {
+                Some(record) => {
+                    bases += record.seq.len();
+                    batch.push(record);
+                }
+                None => break,
+            }
+        }
+        batch
+    }
+
+    // k-mers of a batch extracted on the GPU, None to count it on the CPU
+    fn extract_batch(&self, seqs: &[Cow<[u8]>]) -> Option<(Batch, Extracted)> {
+        let gpu = self.gpu.as_ref()?;
+        if self.gpu_failed.load(Ordering::Relaxed) {
+            return None;
+        }
+        let msize = match self.partitioning() {
+            Partitioning::Modulo => 0,
+            Partitioning::Signature(msize) => msize,
+        };
+        let batch = Batch::new(seqs);
+        match gpu.extract(
+            &batch,
+            self.ksize,
+            msize,
+            self.options.kmers.strand,
+            self.n_parts,
+        ) {
+            Ok(extracted) => Some((batch, extracted)),
+            Err(e) => {
+                // the rest of the input is counted on the CPU
+                if !self.gpu_failed.swap(true, Ordering::Relaxed) {
+                    eprintln!("Warning: {}, counting on the CPU", e);
+                }
+                None
+            }
+        }
+    }
+
+    // inserts the k-mers extracted on the GPU of the record at (offset, length) of its batch
+    fn count_extracted<K: KmerInt, C: Count>(
+        &self,
+        (offset, len): (usize, usize),
+        extracted: &Extracted,
+        counts_table: &[SccMap<K, C>],
+    ) -> u64 {
+        let mut kmers = 0;
+        for pos in (0..(len + 1).saturating_sub(self.ksize)).step_by(self.options.kmers.stride) {
+            let part = extracted.parts[offset + pos];
+            if part != gpu::INVALID
+                && self.insert_kmer(
+                    part as usize,
+                    K::from_u64(extracted.kmers[offset + pos]),
+                    counts_table,
+                )
+            {
+                kmers += 1;
+            }
+        }
+        kmers
+    }
+
+    // counts a k-mer already picked by strand unless it is not whitelisted
+    #[inline]
+    fn insert_kmer<K: KmerInt, C: Count>(
+        &self,
+        part: usize,
+        kmer: K,
+        counts_table: &[SccMap<K, C>],
+    ) -> bool {
+        if self
+            .options
+            .kmers
+            .whitelist
+            .as_ref()
+            .is_some_and(|whitelist| !whitelist.contains(kmer))
+        {
+            return false;
+        }
+        unsafe {
+            counts_table
+                .get_unchecked(part)
+                .entry(kmer)
+                .and_modify(|v| *v = self.add_count(*v, C::one()))
+                .or_insert(C::one());
+        }
+        true
+    }
+
+    // inserts the sampled k-mers of a sequence, returns how many were inserted
+    fn count_record<K: KmerInt, C: Count>(&self, seq: &[u8], counts_table: &[SccMap<K, C>]) -> u64 {
+        let mut kmers = 0;
+        let mut insert = |part: usize, fmer: K, rmer: K| {
+            if self.insert_kmer(
+                part,
+                self.options.kmers.strand.pick(fmer, rmer),
+                counts_table,
+            ) {
+                kmers += 1;
+            }
+        };
+
+        match self.partitioning() {
+            Partitioning::Modulo => {
+                for (fmer, rmer) in GenericKmerGenerator::<K>::new(seq, self.ksize)
+                    .with_stride(self.options.kmers.stride)
+                {
+                    let part = self.partitioning().part(
+                        self.options.kmers.strand.pick(fmer, rmer),
+                        self.ksize,
+                        self.n_parts,
+                    );
+                    insert(part, fmer, rmer);
+                }
+            }
+            Partitioning::Signature(msize) => {
+                // a whole super-k-mer shares its partition, no per k-mer signature needed
+                for (signature, start, end) in super_kmers(seq, self.ksize, msize) {
+                    let part = (signature % self.n_parts) as usize;
+                    for (pos, (fmer, rmer)) in
+                        GenericKmerGenerator::<K>::new(&seq[start..end], self.ksize).enumerate()
+                    {
+                        // stride is of positions in the whole sequence
+                        if (start + pos) % self.options.kmers.stride == 0 {
+                            insert(part, fmer, rmer);
+                        }
+                    }
+                }
+            }
+        }
+
+        kmers
+    }
+
+    // wrapping or saturating sum as of the counter width, saturation is remembered for a warning
+    #[inline]
+    fn add_count<C: Count>(&self, count: C, other: C) -> C {
+        let sum = count.add(other, self.options.output.width.saturates());
+        if self.options.output.width.saturates() && sum == C::MAX {
+            self.saturated.store(true, Ordering::Relaxed);
+        }
+        sum
     }
 
     pub fn merge(&self, delete: bool) {
+        match (self.ksize > Kmer::MAX_KSIZE, self.options.output.width) {
+            (true, CounterWidth::U64) => self.merge_kmers::<u128, u64>(delete),
+            (true, _) => self.merge_kmers::<u128, u32>(delete),
+            (false, CounterWidth::U64) => self.merge_kmers::<Kmer, u64>(delete),
+            (false, _) => self.merge_kmers::<Kmer, u32>(delete),
+        }
+        if self.saturated.load(Ordering::Relaxed) {
+            eprintln!(
+                "Warning: some k-mer counts reached {} and were capped, use 64-bit counters",
+                u32::MAX
+            );
+        }
+    }
+
+    fn merge_kmers<K: KmerInt, C: Count>(&self, delete: bool) {
         let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
             .num_threads(self.threads)
             .build()
             .unwrap();
-        let outf = fs::File::create(format!("{}/kmers.counts", self.out_dir)).unwrap();
-        let mut buff = BufWriter::new(outf);
+        let counts = self.options.output.counts;
+        let shard_writer = if counts == CountsOutput::Shards {
+            Some(
+                ShardWriter::new(
+                    &format!("{}/kmers.shards", self.out_dir),
+                    self.ksize,
+                    self.n_parts,
+                    self.partitioning(),
+                    self.options.output.width,
+                )
+                .unwrap(),
+            )
+        } else {
+            None
+        };
         let pbar = ProgressBar::new(self.n_parts * self.chunks);
         pbar.set_style(
             ProgressStyle::with_template(
@@ -184,67 +983,324 @@ impl CountComputer {
             .unwrap()
             .progress_chars("#>-"),
         );
+        // number of partitions held in memory at once
+        let in_flight = if self.options.partitions.parts_in_flight == 0 {
+            self.threads
+        } else {
+            self.options.partitions.parts_in_flight
+        };
+        let in_flight = max(1, in_flight) as u64;
+        // assuming 8 bytes per k-mer as when counting
+        let limit = match self.options.partitions.merge_limit {
+            0 => (1_000_000_000_f64 * self.memory_ceil_gb / 8.0) as u64,
+            limit => limit,
+        } / in_flight;
+        pbar.set_message("Merging partitions");
+        // each worker merges a partition and writes it to a file of its own, then takes
+        // the next one, so reading chunks and writing partitions overlap
+        let next_part = AtomicU64::new(0);
+        let written = Mutex::new(vec![0_u64; self.n_parts as usize]);
+        let histogram: Mutex<BTreeMap<u64, u64>> = Mutex::new(BTreeMap::new());
+        pool.scope(|scope| {
+            for _ in 0..min(in_flight, self.n_parts) {
+                scope.spawn(|_| loop {
+                    let part = next_part.fetch_add(1, Ordering::Relaxed);
+                    if part >= self.n_parts {
+                        break;
+                    }
+                    let merged = self.merge_partition::<K, C>(part, delete, limit, &pbar);
+                    let (entries, part_histogram) =
+                        self.write_part(part, merged, shard_writer.as_ref());
+                    written.lock().unwrap()[part as usize] = entries;
+                    let mut histogram = histogram.lock().unwrap();
+                    for (count, kmers) in part_histogram {
+                        *histogram.entry(count).or_insert(0) += kmers;
+                    }
+                });
+            }
+        });
+        let written = written.into_inner().unwrap();
+        let histogram = histogram.into_inner().unwrap();
 
-        for part in 0..self.n_parts {
-            let completed = Arc::new(AtomicU64::new(0));
-            let map: SccMap<Kmer, u32> = SccMap::new();
-            let map_arc = Arc::new(map);
-            pbar.set_message(format!("Merging partition: {}", part + 1));
+        pbar.set_message("Writing partitions");
+        let part_paths: Vec<String> = (0..self.n_parts)
+            .map(|part| format!("{}/temp_out.part_{}", self.out_dir, part))
+            .collect();
+        if let Some(shard_writer) = shard_writer {
+            shard_writer.finish().unwrap();
+        } else if counts == CountsOutput::Binary {
+            // binary output goes to kmers.counts.bin, sorted and searchable
+            let mut counts_writer = CountsWriter::with_layout(
+                &format!("{}/kmers.counts.bin", self.out_dir),
+                self.ksize,
+                self.n_parts,
+                self.partitioning(),
+                self.options.output.width,
+            )
+            .unwrap();
+            for (path, entries) in part_paths.iter().zip(written) {
+                counts_writer.append_partition(path, entries).unwrap();
+                delete_file_if_exists(path).expect("file must be removable");
+            }
+            counts_writer.finish().unwrap();
+        } else {
+            let outf = fs::File::create(format!("{}/kmers.counts", self.out_dir)).unwrap();
+            let mut buff = BufWriter::new(outf);
+            if matches!(counts, CountsOutput::Text { sorted: true, .. }) {
+                // sorted partitions are merged once all are done
+                let sorted_parts: Vec<String> = (0..self.n_parts)
+                    .map(|part| format!("{}/temp_sorted.part_{}", self.out_dir, part))
+                    .collect();
+                self.merge_sorted::<K, C>(&sorted_parts, &mut buff);
+            } else {
+                for path in part_paths.iter() {
+                    let mut part_file = fs::File::open(path).unwrap();
+                    io::copy(&mut part_file, &mut buff).unwrap();
+                    delete_file_if_exists(path).expect("file must be removable");
+                }
+            }
+        }
 
-            pool.scope(|scope| {
-                for chunk in 0..self.chunks {
-                    let map_arc_clone = Arc::clone(&map_arc);
-                    let pbar_clone = pbar.clone();
-                    let completed_clone = Arc::clone(&completed);
-
-                    scope.spawn(move |_| {
-                        let path =
-                            format!("{}/temp_kmers.part_{}_chunk_{}", self.out_dir, part, chunk);
-                        let file = fs::File::open(&path).unwrap();
-                        let buff = BufReader::new(file);
-                        for line in buff.lines().map_while(Result::ok) {
-                            let mut parts = line.trim().split('\t');
-                            let kmer: Kmer = parts.next().unwrap().parse().unwrap();
-                            let count: u32 = parts.next().unwrap().parse().unwrap();
-                            *map_arc_clone.entry(kmer).or_insert(0) += count;
-                        }
-                        if delete {
-                            delete_file_if_exists(&path).expect("file must be removable");
-                        }
-                        completed_clone.fetch_add(1, Ordering::Acquire);
-                        pbar_clone.inc(1);
-                    });
+        if self.options.output.histogram {
+            let outf = fs::File::create(format!("{}/kmers.histo", self.out_dir)).unwrap();
+            let mut buff = BufWriter::new(outf);
+            for (count, kmers) in histogram.iter() {
+                buff.write_all(format!("{}\t{}\n", count, kmers).as_bytes())
+                    .unwrap();
+            }
+        }
+        *self.histogram.lock().unwrap() = histogram;
+        pbar.finish();
+    }
+
+    // writes a merged partition to a file of its own, in ascending k-mer order unless
+    // it is text output, returns the k-mers written and the histogram of the partition
+    fn write_part<K: KmerInt, C: Count>(
+        &self,
+        part: u64,
+        merged: MergedPartition<K, C>,
+        shard_writer: Option<&ShardWriter>,
+    ) -> (u64, BTreeMap<u64, u64>) {
+        let ordered = !matches!(
+            self.options.output.counts,
+            CountsOutput::Text { sorted: false, .. }
+        );
+        let mut histogram = BTreeMap::new();
+        let mut runs = Vec::new();
+        let entries: Box<dyn Iterator<Item = (K, C)> + '_> = match merged {
+            MergedPartition::Map(map) => {
+                let mut entries = Vec::with_capacity(map.len());
+                map.scan(|k, v| entries.push((*k, *v)));
+                drop(map);
+                if ordered {
+                    entries.sort_unstable();
                 }
-            });
+                Box::new(entries.into_iter())
+            }
+            MergedPartition::Runs(paths) => {
+                runs = paths;
+                Box::new(self.merged_runs::<K, C>(&runs))
+            }
+        };
+        let entries = entries
+            .inspect(|(_, v)| {
+                if self.options.output.histogram {
+                    *histogram.entry(v.as_u64()).or_insert(0) += 1;
+                }
+            })
+            .filter(|(_, v)| self.keep(*v));
+        let mut written = 0;
+        let part_file = || {
+            let path = format!("{}/temp_out.part_{}", self.out_dir, part);
+            BufWriter::new(fs::File::create(path).unwrap())
+        };
+        if let Some(shard_writer) = shard_writer {
+            shard_writer.write_shard(part, entries).unwrap();
+        } else if self.options.output.counts == CountsOutput::Binary {
+            written = counts::write_records(&mut part_file(), entries).unwrap();
+        } else if ordered {
+            let path = format!("{}/temp_sorted.part_{}", self.out_dir, part);
+            let mut spill = self.options.partitions.compress_tmp.writer(&path).unwrap();
+            for (k, v) in entries {
+                self.options
+                    .partitions
+                    .compress_tmp
+                    .write_entry(&mut spill, k, v)
+                    .unwrap();
+            }
+        } else {
+            let mut buff = part_file();
+            for (k, v) in entries {
+                self.write_count(&mut buff, k, v);
+            }
+        }
+        for path in runs.iter() {
+            delete_file_if_exists(path).expect("file must be removable");
+        }
+        (written, histogram)
+    }
+
+    fn write_count<K: KmerInt, C: Count>(&self, buff: &mut impl Write, kmer: K, count: C) {
+        let line = match self.kmer_text() {
+            KmerText::Numeric => format!("{}\t{}\n", kmer, count),
+            KmerText::Acgt(molecule) => format!(
+                "{}\t{}\n",
+                numeric_to_kmer_in(kmer, self.ksize, molecule),
+                count
+            ),
+            KmerText::NumericAcgt(molecule) => format!(
+                "{}\t{}\t{}\n",
+                kmer,
+                numeric_to_kmer_in(kmer, self.ksize, molecule),
+                count
+            ),
+        };
+        buff.write_all(line.as_bytes()).unwrap();
+    }
+
+    // k-mers of text counts and matrices
+    fn kmer_text(&self) -> KmerText {
+        match self.options.output.counts {
+            CountsOutput::Text { kmers, .. } => kmers,
+            _ => KmerText::Numeric,
+        }
+    }
+
+    fn merged_runs<K: KmerInt, C: Count>(
+        &self,
+        paths: &[String],
+    ) -> impl Iterator<Item = (K, C)> + '_ {
+        let readers = paths
+            .iter()
+            .map(|path| {
+                self.options
+                    .partitions
+                    .compress_tmp
+                    .entries::<K, C>(path)
+                    .unwrap()
+            })
+            .collect();
+        MergedRuns::new(readers, |count, other| self.add_count(count, other))
+    }
+
+    // k-way merge of sorted partitions into one ascending output
+    fn merge_sorted<K: KmerInt, C: Count>(&self, paths: &[String], buff: &mut impl Write) {
+        for (kmer, count) in self.merged_runs::<K, C>(paths) {
+            self.write_count(buff, kmer, count);
+        }
+        for path in paths {
+            delete_file_if_exists(path).expect("file must be removable");
+        }
+    }
+
+    fn keep<C: Count>(&self, count: C) -> bool {
+        self.options.output.min_count <= count.as_u64()
+            && count.as_u64() <= self.options.output.max_count
+    }
 
-            map_arc.scan(|k, v| {
-                if self.acgt {
-                    buff.write_all(
-                        format!("{}\t{:?}\n", numeric_to_kmer(*k, self.ksize), v).as_bytes(),
-                    )
+    fn merge_partition<K: KmerInt, C: Count>(
+        &self,
+        part: u64,
+        delete: bool,
+        limit: u64,
+        pbar: &ProgressBar,
+    ) -> MergedPartition<K, C> {
+        let map: SccMap<K, C> = SccMap::new();
+        let runs = Mutex::new(Vec::new());
+        // one worker writes a run while the others keep merging
+        let write_run = || {
+            if let Ok(mut runs) = runs.try_lock() {
+                if map.len() as u64 > limit {
+                    self.write_run(part, &map, &mut runs);
+                }
+            }
+        };
+
+        (0..self.chunks).into_par_iter().for_each(|chunk| {
+            for path in self.chunk_paths(part, chunk) {
+                let entries = self
+                    .options
+                    .partitions
+                    .compress_tmp
+                    .entries::<K, C>(&path)
                     .unwrap();
-                } else {
-                    buff.write_all(format!("{}\t{:?}\n", k, v).as_bytes())
-                        .unwrap();
+                for (idx, (kmer, count)) in entries.enumerate() {
+                    map.entry(kmer)
+                        .and_modify(|v| *v = self.add_count(*v, count))
+                        .or_insert(count);
+                    // len() visits the whole table, so it is only checked now and then
+                    if idx % 65_536 == 65_535 {
+                        write_run();
+                    }
                 }
-            });
+                write_run();
+                if delete {
+                    delete_file_if_exists(&path).expect("file must be removable");
+                }
+            }
+            pbar.inc(1);
+        });
+
+        let mut runs = runs.into_inner().unwrap();
+        if runs.is_empty() {
+            return MergedPartition::Map(map);
         }
+        self.write_run(part, &map, &mut runs);
+        MergedPartition::Runs(runs)
+    }
 
-        pbar.finish();
+    // moves the k-mers of a partition being merged to a run on disk, sorted by k-mer
+    fn write_run<K: KmerInt, C: Count>(
+        &self,
+        part: u64,
+        map: &SccMap<K, C>,
+        runs: &mut Vec<String>,
+    ) {
+        let path = format!("{}/temp_run.part_{}.{}", self.out_dir, part, runs.len());
+        let mut entries = Vec::with_capacity(map.len());
+        // entries are removed as they are taken, concurrent merges land in new entries
+        map.retain(|k, v| {
+            entries.push((*k, *v));
+            false
+        });
+        entries.sort_unstable();
+        let mut buff = self.options.partitions.compress_tmp.writer(&path).unwrap();
+        for (k, v) in entries {
+            self.options
+                .partitions
+                .compress_tmp
+                .write_entry(&mut buff, k, v)
+                .unwrap();
+        }
+        runs.push(path);
     }
 
-    pub fn init(&mut self) {
-        let reader = get_reader(&self.in_path).unwrap();
-        let format = SeqFormat::get(&self.in_path).unwrap();
-        let stats = Sequences::seq_stats(format, reader);
-        let data_size_gb = stats.total_length as f64 / (1 << 30) as f64;
+    // partitions for the given bases to merge within the memory ceiling
+    fn parts_for(&self, bases: u64) -> u64 {
+        let data_size_gb = bases as f64 / (1 << 30) as f64;
         // assuming 8 bytes per kmer
+        (8_f64 * data_size_gb / (2_f64 * self.memory_ceil_gb)).ceil() as u64
+    }
+
+    pub fn init(&mut self) {
         // at least this should be the num threads for fastest possible merging
-        let n_parts = max(
+        let min_parts = max(
             if self.debug { 1 } else { self.threads as u64 },
-            (8_f64 * data_size_gb / (2_f64 * self.memory_ceil_gb)).ceil() as u64,
+            self.min_parts,
         );
-        self.n_parts = n_parts;
+        if self.streaming {
+            // a stream cannot be read twice, partitions grow as it is counted
+            self.n_parts = min_parts;
+            return;
+        }
+        let reader = match &self.mate_path {
+            Some(mate_path) => ktio::seq::get_mates_reader(&self.in_path, mate_path),
+            None => get_reader(&self.in_path),
+        }
+        .unwrap();
+        let stats =
+            Sequences::seq_stats_filtered(self.format, reader, self.options.kmers.filter.as_ref());
+        self.n_parts = max(min_parts, self.parts_for(stats.total_length as u64));
         self.seq_count = stats.seq_count as u64;
     }
 }
@@ -252,10 +1308,26 @@ impl CountComputer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use counts::CountsReader;
+    use kmer::{kmer::KmerGenerator, kmer_to_numeric, numeric_to_kmer};
     use ktio::fops::{create_directory, load_lines_sorted};
+    use std::{collections::HashMap, path::Path};
 
     const PATH_FQ: &str = "../test_data/reads.fq";
 
+    // chunks of merge_test in their own directory, so that merges do not race
+    fn copy_test_chunks(out_dir: &str) {
+        create_directory(out_dir).expect("Directory must be creatable");
+        for (part, chunk) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let name = format!("temp_kmers.part_{}_chunk_{}", part, chunk);
+            fs::copy(
+                format!("../test_data/computed_counts_test/{}", name),
+                format!("{}/{}", out_dir, name),
+            )
+            .unwrap();
+        }
+    }
+
     #[test]
     fn count_test() {
         create_directory("../test_data/computed_counts").expect("Directory must be creatable");
@@ -275,6 +1347,49 @@ mod tests {
         assert_eq!(exp, res);
     }
 
+    #[test]
+    fn count_stats_test() {
+        let out_dir = "../test_data/computed_counts_stats";
+        let _ = fs::remove_dir_all(out_dir);
+        create_directory(out_dir).expect("Directory must be creatable");
+        // statistics are only written when asked for
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.count();
+        assert!(fs::metadata(format!("{}/kmers.stats", out_dir)).is_err());
+        assert!(fs::metadata(format!("{}/kmers.chunks", out_dir)).is_err());
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.debug = true;
+        ctr.options.output.stats = true;
+        ctr.count();
+        let exp = load_lines_sorted("../test_data/expected_counts.part_0_chunk_0");
+        // no ambiguous bases in reads
+        let total: u64 = exp
+            .iter()
+            .map(|line| line.split('\t').nth(1).unwrap().parse::<u64>().unwrap())
+            .sum();
+        let stats = ctr.kmer_stats();
+        assert_eq!(
+            stats,
+            KmerStats {
+                kmers: total,
+                skipped: 0
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}/kmers.stats", out_dir)).unwrap(),
+            format!("{}stride\t1\nksize\t15\n", stats)
+        );
+        let chunk = ctr.chunk_stats()[0];
+        assert_eq!(chunk.records, 2);
+        assert_eq!(chunk.kmers, total);
+        assert_eq!(chunk.distinct, exp.len() as u64);
+        assert!(!chunk.memory_limit);
+        let log = load_lines_sorted(format!("{}/kmers.chunks", out_dir));
+        assert_eq!(log.len(), 2);
+        assert!(log[0].starts_with(&format!("0\t2\t{}\t{}\t", total, exp.len())));
+        assert!(log[0].ends_with("\tfalse"));
+    }
+
     #[test]
     fn merge_test() {
         let mut ctr = CountComputer::new(
@@ -292,6 +1407,522 @@ mod tests {
         assert_eq!(exp, res);
     }
 
+    #[test]
+    fn merge_parts_in_flight_test() {
+        // chunks of merge_test, merged apart from it
+        let out_dir = "../test_data/computed_counts_in_flight";
+        create_directory(out_dir).expect("Directory must be creatable");
+        for (part, chunk) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let name = format!("temp_kmers.part_{}_chunk_{}", part, chunk);
+            fs::copy(
+                format!("../test_data/computed_counts_test/{}", name),
+                format!("{}/{}", out_dir, name),
+            )
+            .unwrap();
+        }
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.chunks = 2;
+        ctr.n_parts = 2;
+        let exp = load_lines_sorted("../test_data/expected_counts_test.counts");
+        // sequential, windowed and fully parallel partition merging
+        for parts in 1..=3 {
+            ctr.options.partitions.parts_in_flight = parts;
+            ctr.merge(false);
+            let res = load_lines_sorted(format!("{}/kmers.counts", out_dir));
+            assert_eq!(exp, res);
+        }
+    }
+
+    #[test]
+    fn merge_parts_in_order_test() {
+        let out_dir = "../test_data/computed_counts_in_order";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.set_threads(4);
+        ctr.count();
+        assert!(ctr.n_parts > 1);
+        ctr.options.partitions.parts_in_flight = 1;
+        ctr.merge(false);
+        let exp = load_lines_sorted(format!("{}/kmers.counts", out_dir));
+        // partitions merged by several workers still come out in partition order
+        ctr.options.partitions.parts_in_flight = 4;
+        ctr.merge(false);
+        assert_eq!(exp, load_lines_sorted(format!("{}/kmers.counts", out_dir)));
+        let res = fs::read_to_string(format!("{}/kmers.counts", out_dir)).unwrap();
+        let kmers: Vec<Kmer> = res
+            .lines()
+            .map(|line| line.split('\t').next().unwrap().parse().unwrap())
+            .collect();
+        let parts: Vec<usize> = kmers
+            .iter()
+            .map(|&kmer| ctr.partitioning().part(kmer, ctr.ksize, ctr.n_parts))
+            .collect();
+        assert!(parts.windows(2).all(|w| w[0] <= w[1]));
+        for part in 0..ctr.n_parts {
+            assert!(!Path::new(&format!("{}/temp_out.part_{}", out_dir, part)).exists());
+        }
+    }
+
+    #[test]
+    fn count_stranded_test() {
+        let out_dir = "../test_data/computed_counts_stranded";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.options.kmers.strand = Strand::Forward;
+        ctr.count();
+        ctr.merge(true);
+        let reader = get_reader(PATH_FQ).unwrap();
+        let mut expected: HashMap<Kmer, u32> = HashMap::new();
+        for record in Sequences::new(SeqFormat::Fastq, reader).unwrap() {
+            for (fmer, _) in KmerGenerator::new(&record.seq, 15) {
+                *expected.entry(fmer).or_insert(0) += 1;
+            }
+        }
+        let mut expected: Vec<String> = expected
+            .iter()
+            .map(|(kmer, count)| format!("{}\t{}", kmer, count))
+            .collect();
+        expected.sort();
+        assert_eq!(
+            load_lines_sorted(format!("{}/kmers.counts", out_dir)),
+            expected
+        );
+    }
+
+    #[test]
+    fn count_hpc_test() {
+        let out_dir = "../test_data/computed_counts_hpc";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.options.kmers.hpc = true;
+        ctr.count();
+        ctr.merge(true);
+        let reader = get_reader(PATH_FQ).unwrap();
+        let mut expected: HashMap<Kmer, u32> = HashMap::new();
+        let mut total = 0;
+        for record in Sequences::new(SeqFormat::Fastq, reader).unwrap() {
+            for (fmer, rmer) in KmerGenerator::new(&record.seq, 15).with_hpc(true) {
+                *expected.entry(u64::min(fmer, rmer)).or_insert(0) += 1;
+                total += 1;
+            }
+        }
+        // reads have homopolymers, so fewer k-mers are counted
+        assert!(total < 116);
+        let mut expected: Vec<String> = expected
+            .iter()
+            .map(|(kmer, count)| format!("{}\t{}", kmer, count))
+            .collect();
+        expected.sort();
+        assert_eq!(
+            load_lines_sorted(format!("{}/kmers.counts", out_dir)),
+            expected
+        );
+        assert_eq!(ctr.kmer_stats().kmers, total);
+    }
+
+    #[test]
+    fn count_extracted_test() {
+        let mut ctr = CountComputer::new(
+            PATH_FQ.to_owned(),
+            "../test_data/computed_counts".to_owned(),
+            15,
+        );
+        ctr.n_parts = 3;
+        let reader = get_reader(PATH_FQ).unwrap();
+        let mut seqs: Vec<Vec<u8>> = Sequences::new(SeqFormat::Fastq, reader)
+            .unwrap()
+            .map(|record| record.seq)
+            .collect();
+        seqs.push(b"ACGTNACGTTGCATGCATTAGCTAGCA".to_vec());
+        // k-mers and partitions of every position as the GPU kernel gives them
+        let batch = Batch::new(&seqs);
+        let mut extracted = Extracted {
+            kmers: vec![0; batch.bases.len()],
+            parts: vec![gpu::INVALID; batch.bases.len()],
+        };
+        for pos in 0..=batch.bases.len() - 15 {
+            if let Some((fmer, rmer)) = KmerGenerator::new(&batch.bases[pos..pos + 15], 15).next() {
+                let kmer = u64::min(fmer, rmer);
+                extracted.kmers[pos] = kmer;
+                extracted.parts[pos] = ctr.partitioning().part(kmer, 15, 3) as u32;
+            }
+        }
+        for stride in [1, 3] {
+            ctr.options.kmers.stride = stride;
+            let gpu_table: Vec<SccMap<Kmer, u32>> = vec![SccMap::new(); 3];
+            let cpu_table: Vec<SccMap<Kmer, u32>> = vec![SccMap::new(); 3];
+            for (n, seq) in seqs.iter().enumerate() {
+                assert_eq!(
+                    ctr.count_extracted(batch.records[n], &extracted, &gpu_table),
+                    ctr.count_record(seq, &cpu_table)
+                );
+            }
+            for (gpu_map, cpu_map) in gpu_table.iter().zip(cpu_table.iter()) {
+                let entries = |map: &SccMap<Kmer, u32>| {
+                    let mut entries = Vec::new();
+                    map.scan(|k, v| entries.push((*k, *v)));
+                    entries.sort();
+                    entries
+                };
+                assert_eq!(entries(gpu_map), entries(cpu_map));
+            }
+        }
+        #[cfg(not(feature = "gpu"))]
+        assert!(ctr.set_gpu(true).is_err());
+        let mut ctr = CountComputer::new(
+            PATH_FQ.to_owned(),
+            "../test_data/computed_counts".to_owned(),
+            45,
+        );
+        assert!(ctr.set_gpu(true).is_err());
+    }
+
+    #[test]
+    fn count_gpu_fallback_test() {
+        let counts = |out_dir: &str, gpu: bool| {
+            create_directory(out_dir).expect("Directory must be creatable");
+            let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+            ctr.set_threads(2);
+            let fallback = gpu && ctr.set_gpu(true).is_err();
+            // builds without the gpu feature always count on the CPU
+            #[cfg(not(feature = "gpu"))]
+            assert_eq!(fallback, gpu);
+            assert_eq!(ctr.gpu.is_none(), !gpu || fallback);
+            ctr.count();
+            ctr.merge(true);
+            (
+                load_lines_sorted(format!("{}/kmers.counts", out_dir)),
+                ctr.kmer_stats(),
+            )
+        };
+        let cpu = counts("../test_data/computed_counts_cpu", false);
+        assert!(!cpu.0.is_empty());
+        assert_eq!(counts("../test_data/computed_counts_gpu", true), cpu);
+    }
+
+    #[test]
+    fn count_soft_limit_test() {
+        let out_dir = "../test_data/computed_counts_soft_limit";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.set_threads(1);
+        // any k-mer is over the soft limit, every record is spilled within one chunk
+        ctr.options.partitions.soft_limit = 0_f64;
+        ctr.options.partitions.memory_policy = MemoryPolicy::SequenceLength;
+        ctr.count();
+        assert_eq!(ctr.chunks, 1);
+        assert_eq!(ctr.chunk_stats()[0].spills, 2);
+        assert!(!ctr.chunk_stats()[0].memory_limit);
+        ctr.merge(true);
+        let reader = get_reader(PATH_FQ).unwrap();
+        let mut expected: HashMap<Kmer, u32> = HashMap::new();
+        for record in Sequences::new(SeqFormat::Fastq, reader).unwrap() {
+            for (fmer, rmer) in KmerGenerator::new(&record.seq, 15) {
+                *expected.entry(min(fmer, rmer)).or_insert(0) += 1;
+            }
+        }
+        let mut expected: Vec<String> = expected
+            .iter()
+            .map(|(kmer, count)| format!("{}\t{}", kmer, count))
+            .collect();
+        expected.sort();
+        assert_eq!(
+            load_lines_sorted(format!("{}/kmers.counts", out_dir)),
+            expected
+        );
+        assert!(!Path::new(&format!("{}/temp_kmers.part_0_chunk_0.0", out_dir)).exists());
+    }
+
+    #[test]
+    fn count_memory_policy_test() {
+        let out_dir = "../test_data/computed_counts_memory";
+        let in_path = "../test_data/computed_counts_memory.fa";
+        create_directory(out_dir).expect("Directory must be creatable");
+        // 20 copies of one record, 11520 bytes by sequence length but only 58 distinct k-mers
+        let record = &fs::read_to_string("../test_data/reads.fa").unwrap()[..83];
+        fs::write(in_path, record.repeat(20)).unwrap();
+        let mut chunks = Vec::new();
+        for policy in [MemoryPolicy::SequenceLength, MemoryPolicy::Occupancy] {
+            let mut ctr = CountComputer::new(in_path.to_owned(), out_dir.to_owned(), 15);
+            ctr.set_threads(1);
+            ctr.set_max_memory(8e-6);
+            ctr.options.partitions.soft_limit = 2_f64;
+            ctr.options.partitions.memory_policy = policy;
+            ctr.count();
+            ctr.merge(true);
+            chunks.push(ctr.chunks);
+            assert_eq!(
+                load_lines_sorted(format!("{}/kmers.counts", out_dir)).len(),
+                58
+            );
+        }
+        assert!(chunks[0] > 1);
+        assert_eq!(chunks[1], 1);
+    }
+
+    #[test]
+    fn count_stream_test() {
+        let out_dir = "../test_data/computed_counts_stream";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut ctr = CountComputer::from_stream(
+            SeqFormat::Fastq,
+            get_reader(PATH_FQ).unwrap(),
+            out_dir.to_owned(),
+            15,
+        );
+        ctr.debug = true;
+        ctr.set_threads(1);
+        // a chunk per read, after which the partitions are grown to 4 and then 8
+        ctr.set_max_memory(1e-7);
+        ctr.options.partitions.soft_limit = 100_f64;
+        ctr.count();
+        assert_eq!(ctr.chunks, 2);
+        assert_eq!(ctr.n_parts, 8);
+        ctr.merge(true);
+        let res = load_lines_sorted(format!("{}/kmers.counts", out_dir));
+
+        let exp_dir = "../test_data/computed_counts_stream_file";
+        create_directory(exp_dir).expect("Directory must be creatable");
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), exp_dir.to_owned(), 15);
+        ctr.count();
+        ctr.merge(true);
+        assert_eq!(load_lines_sorted(format!("{}/kmers.counts", exp_dir)), res);
+    }
+
+    #[test]
+    fn estimate_test() {
+        let ctr = CountComputer::new(PATH_FQ.to_owned(), "../test_data".to_owned(), 15);
+        let (hll, total) = ctr.estimate().unwrap();
+        // 116 distinct canonical 15-mers, each seen once
+        assert_eq!(total, 116);
+        assert!((hll.estimate() - 116.0).abs() < 3.0);
+    }
+
+    #[test]
+    fn count_whitelist_test() {
+        let out_dir = "../test_data/computed_counts_whitelist";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let reader = get_reader(PATH_FQ).unwrap();
+        let record = Sequences::new(SeqFormat::Fastq, reader)
+            .unwrap()
+            .next()
+            .unwrap();
+        let targets = format!("{}/targets.fa", out_dir);
+        fs::write(
+            &targets,
+            format!(">t\n{}\n", String::from_utf8_lossy(&record.seq[..20])),
+        )
+        .unwrap();
+        let whitelist = Whitelist::load(&targets, 15, Strand::Canonical).unwrap();
+        assert_eq!(whitelist.len(), 6);
+
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.count();
+        ctr.merge(true);
+        let all = load_lines_sorted(format!("{}/kmers.counts", out_dir));
+        ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.set_options(CountOptions {
+            kmers: KmerOptions {
+                whitelist: Some(whitelist.clone()),
+                ..KmerOptions::default()
+            },
+            ..CountOptions::default()
+        });
+        ctr.count();
+        ctr.merge(true);
+        let res = load_lines_sorted(format!("{}/kmers.counts", out_dir));
+        let expected: Vec<String> = all
+            .into_iter()
+            .filter(|line| {
+                whitelist.contains(line.split('\t').next().unwrap().parse::<Kmer>().unwrap())
+            })
+            .collect();
+        assert_eq!(res.len(), 6);
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn count_stride_test() {
+        create_directory("../test_data/computed_counts_stride")
+            .expect("Directory must be creatable");
+        let mut ctr = CountComputer::new(
+            PATH_FQ.to_owned(),
+            "../test_data/computed_counts_stride".to_owned(),
+            15,
+        );
+        ctr.set_options(CountOptions {
+            kmers: KmerOptions {
+                stride: 3,
+                ..KmerOptions::default()
+            },
+            output: OutputOptions {
+                stats: true,
+                ..OutputOptions::default()
+            },
+            ..CountOptions::default()
+        });
+        ctr.count();
+        ctr.merge(true);
+        let total: u64 = load_lines_sorted("../test_data/computed_counts_stride/kmers.counts")
+            .iter()
+            .map(|line| line.split('\t').nth(1).unwrap().parse::<u64>().unwrap())
+            .sum();
+        let reader = get_reader(PATH_FQ).unwrap();
+        let expected: u64 = Sequences::new(SeqFormat::Fastq, reader)
+            .unwrap()
+            .map(|record| KmerGenerator::new(&record.seq, 15).with_stride(3).count() as u64)
+            .sum();
+        assert_eq!(total, expected);
+        // statistics and recorded stride cover all positions
+        let stats = fs::read_to_string("../test_data/computed_counts_stride/kmers.stats").unwrap();
+        assert!(stats.ends_with("skipped_kmers\t0\nstride\t3\nksize\t15\n"));
+        assert!(ctr.kmer_stats().kmers > 2 * total);
+    }
+
+    #[test]
+    fn count_compress_tmp_test() {
+        let mut results = Vec::new();
+        for (codec, out_dir) in [
+            (
+                SpillCompression::None,
+                "../test_data/computed_counts_spill_none",
+            ),
+            (
+                SpillCompression::Lz4,
+                "../test_data/computed_counts_spill_lz4",
+            ),
+            (
+                SpillCompression::Zstd,
+                "../test_data/computed_counts_spill_zstd",
+            ),
+            (
+                SpillCompression::Gzip,
+                "../test_data/computed_counts_spill_gzip",
+            ),
+        ] {
+            create_directory(out_dir).expect("Directory must be creatable");
+            let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+            ctr.options.partitions.compress_tmp = codec;
+            ctr.count();
+            ctr.merge(true);
+            results.push(load_lines_sorted(format!("{}/kmers.counts", out_dir)));
+        }
+        assert_eq!(results[0], results[1]);
+        assert_eq!(results[0], results[2]);
+        assert_eq!(results[0], results[3]);
+    }
+
+    #[test]
+    fn count_wide_test() {
+        let out_dir = "../test_data/computed_counts_wide";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 45);
+        ctr.set_threads(4);
+        ctr.count();
+        ctr.merge(true);
+        let reader = get_reader(PATH_FQ).unwrap();
+        let mut expected: HashMap<u128, u32> = HashMap::new();
+        for record in Sequences::new(SeqFormat::Fastq, reader).unwrap() {
+            for (fmer, rmer) in GenericKmerGenerator::<u128>::new(&record.seq, 45) {
+                *expected.entry(min(fmer, rmer)).or_insert(0) += 1;
+            }
+        }
+        let mut expected: Vec<String> = expected
+            .iter()
+            .map(|(kmer, count)| format!("{}\t{}", kmer, count))
+            .collect();
+        expected.sort();
+        // k-mers do not fit in 64 bits
+        assert!(expected.iter().any(|line| line.len() > 25));
+        assert_eq!(
+            load_lines_sorted(format!("{}/kmers.counts", out_dir)),
+            expected
+        );
+
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 45);
+        ctr.options.output.counts = CountsOutput::Binary;
+        ctr.count();
+        ctr.merge(true);
+        let counts = CountsReader::open(&format!("{}/kmers.counts.bin", out_dir)).unwrap();
+        assert_eq!(counts.len(), expected.len());
+        for line in expected {
+            let (kmer, count) = line.split_once('\t').unwrap();
+            assert_eq!(
+                counts.get(kmer.parse::<u128>().unwrap()),
+                Some(count.parse().unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn merge_counter_width_test() {
+        let out_dir = "../test_data/computed_counts_width";
+        copy_test_chunks(out_dir);
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.chunks = 2;
+        ctr.n_parts = 2;
+        ctr.options.output.width = CounterWidth::U64;
+        ctr.merge(false);
+        assert_eq!(
+            load_lines_sorted(format!("{}/kmers.counts", out_dir)),
+            load_lines_sorted("../test_data/expected_counts_test.counts")
+        );
+
+        ctr.options.output.width = CounterWidth::Saturating;
+        assert_eq!(ctr.add_count(u32::MAX - 1, 3), u32::MAX);
+        assert!(ctr.saturated.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn merge_count_range_test() {
+        let out_dir = "../test_data/computed_counts_range";
+        copy_test_chunks(out_dir);
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.chunks = 2;
+        ctr.n_parts = 2;
+        ctr.options.output.min_count = 2;
+        ctr.options.output.max_count = 10;
+        ctr.merge(false);
+        let exp: Vec<String> = load_lines_sorted("../test_data/expected_counts_test.counts")
+            .into_iter()
+            .filter(|line| {
+                let count: u32 = line.split('\t').nth(1).unwrap().parse().unwrap();
+                (2..=10).contains(&count)
+            })
+            .collect();
+        let res = load_lines_sorted(format!("{}/kmers.counts", out_dir));
+        assert!(!exp.is_empty());
+        assert_eq!(exp, res);
+    }
+
+    #[test]
+    fn merge_histogram_test() {
+        let out_dir = "../test_data/computed_counts_histo";
+        copy_test_chunks(out_dir);
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.chunks = 2;
+        ctr.n_parts = 2;
+        ctr.options.output.histogram = true;
+        ctr.merge(false);
+        let mut expected: BTreeMap<u64, u64> = BTreeMap::new();
+        for line in load_lines_sorted("../test_data/expected_counts_test.counts") {
+            let count: u64 = line.split('\t').nth(1).unwrap().parse().unwrap();
+            *expected.entry(count).or_insert(0) += 1;
+        }
+        let expected: Vec<(u64, u64)> = expected.into_iter().collect();
+        assert_eq!(ctr.histogram(), expected);
+        let res: Vec<String> = fs::read_to_string(format!("{}/kmers.histo", out_dir))
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        let exp: Vec<String> = expected
+            .iter()
+            .map(|(count, kmers)| format!("{}\t{}", count, kmers))
+            .collect();
+        assert_eq!(res, exp);
+    }
+
     #[test]
     fn merge_acgt_test() {
         let mut ctr = CountComputer::new(
@@ -301,7 +1932,10 @@ mod tests {
         );
         ctr.chunks = 2;
         ctr.n_parts = 2;
-        ctr.set_acgt_output(true);
+        ctr.options.output.counts = CountsOutput::Text {
+            kmers: KmerText::Acgt(Molecule::Dna),
+            sorted: false,
+        };
         ctr.merge(false);
         let exp = load_lines_sorted("../test_data/expected_counts_acgt_test.counts");
         let res = load_lines_sorted("../test_data/computed_counts_acgt_test/kmers.counts");
@@ -309,4 +1943,182 @@ mod tests {
         println!("Expected: {:?}", exp);
         assert_eq!(exp, res);
     }
+
+    #[test]
+    fn merge_acgt_column_test() {
+        let out_dir = "../test_data/computed_counts_acgt_column";
+        copy_test_chunks(out_dir);
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.chunks = 2;
+        ctr.n_parts = 2;
+        ctr.options.output.counts = CountsOutput::Text {
+            kmers: KmerText::NumericAcgt(Molecule::Dna),
+            sorted: false,
+        };
+        ctr.merge(false);
+        let exp = load_lines_sorted("../test_data/expected_counts_test.counts");
+        let res = load_lines_sorted(format!("{}/kmers.counts", out_dir));
+        assert_eq!(exp.len(), res.len());
+        for line in res {
+            let values: Vec<&str> = line.split('\t').collect();
+            assert_eq!(values.len(), 3);
+            assert_eq!(
+                numeric_to_kmer(values[0].parse::<Kmer>().unwrap(), 15),
+                values[1]
+            );
+            assert!(exp.contains(&format!("{}\t{}", values[0], values[2])));
+        }
+    }
+
+    #[test]
+    fn merge_rna_test() {
+        let out_dir = "../test_data/computed_counts_rna";
+        copy_test_chunks(out_dir);
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.chunks = 2;
+        ctr.n_parts = 2;
+        ctr.options.output.counts = CountsOutput::Text {
+            kmers: KmerText::Acgt(Molecule::Rna),
+            sorted: false,
+        };
+        ctr.merge(false);
+        let exp = load_lines_sorted("../test_data/expected_counts_test.counts");
+        let res = load_lines_sorted(format!("{}/kmers.counts", out_dir));
+        assert_eq!(exp.len(), res.len());
+        for line in res {
+            let (kmer, count) = line.split_once('\t').unwrap();
+            assert!(!kmer.contains('T'));
+            let kmer = kmer_to_numeric(kmer).unwrap();
+            assert!(exp.contains(&format!("{}\t{}", kmer, count)));
+        }
+    }
+
+    #[test]
+    fn merge_sorted_test() {
+        let out_dir = "../test_data/computed_counts_sorted";
+        copy_test_chunks(out_dir);
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.chunks = 2;
+        ctr.n_parts = 2;
+        ctr.options.output.counts = CountsOutput::Text {
+            kmers: KmerText::Numeric,
+            sorted: true,
+        };
+        ctr.merge(false);
+        let res: Vec<Kmer> = fs::read_to_string(format!("{}/kmers.counts", out_dir))
+            .unwrap()
+            .lines()
+            .map(|line| line.split('\t').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(
+            res.len(),
+            load_lines_sorted("../test_data/expected_counts_test.counts").len()
+        );
+        assert!(res.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(!Path::new(&format!("{}/temp_sorted.part_0", out_dir)).exists());
+        // ACGT order follows the numeric order
+        ctr.options.output.counts = CountsOutput::Text {
+            kmers: KmerText::Acgt(Molecule::Dna),
+            sorted: true,
+        };
+        ctr.merge(false);
+        let res = fs::read_to_string(format!("{}/kmers.counts", out_dir)).unwrap();
+        let mut exp: Vec<&str> = res.lines().collect();
+        exp.sort();
+        assert_eq!(res.lines().collect::<Vec<&str>>(), exp);
+    }
+
+    #[test]
+    fn merge_runs_test() {
+        let out_dir = "../test_data/computed_counts_runs";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.set_threads(2);
+        ctr.count();
+        ctr.merge(false);
+        let exp = load_lines_sorted(format!("{}/kmers.counts", out_dir));
+        // partitions outgrow the limit and are merged from sorted runs
+        ctr.options.partitions.merge_limit = 20;
+        ctr.options.output.histogram = true;
+        ctr.merge(false);
+        assert_eq!(load_lines_sorted(format!("{}/kmers.counts", out_dir)), exp);
+        assert_eq!(ctr.histogram(), vec![(1, exp.len() as u64)]);
+        assert!(!Path::new(&format!("{}/temp_run.part_0.0", out_dir)).exists());
+
+        ctr.options.output.counts = CountsOutput::Text {
+            kmers: KmerText::Numeric,
+            sorted: true,
+        };
+        ctr.merge(false);
+        let res = fs::read_to_string(format!("{}/kmers.counts", out_dir)).unwrap();
+        let res: Vec<&str> = res.lines().collect();
+        let kmers: Vec<Kmer> = res
+            .iter()
+            .map(|line| line.split('\t').next().unwrap().parse().unwrap())
+            .collect();
+        assert!(kmers.windows(2).all(|pair| pair[0] < pair[1]));
+        let mut sorted = res.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+        sorted.sort();
+        assert_eq!(sorted, exp);
+
+        ctr.options.output.counts = CountsOutput::Binary;
+        ctr.merge(true);
+        let reader = counts::CountsReader::open(&format!("{}/kmers.counts.bin", out_dir)).unwrap();
+        assert_eq!(reader.len(), exp.len());
+        for line in exp {
+            let (kmer, count) = line.split_once('\t').unwrap();
+            let kmer: Kmer = kmer.parse().unwrap();
+            assert_eq!(reader.get(kmer), Some(count.parse().unwrap()));
+        }
+    }
+
+    #[test]
+    fn merge_binary_test() {
+        create_directory("../test_data/computed_counts_binary")
+            .expect("Directory must be creatable");
+        let mut ctr = CountComputer::new(
+            PATH_FQ.to_owned(),
+            "../test_data/computed_counts_binary".to_owned(),
+            15,
+        );
+        ctr.set_threads(4);
+        ctr.count();
+        ctr.merge(false);
+        ctr.options.output.counts = CountsOutput::Binary;
+        ctr.merge(true);
+        let exp = load_lines_sorted("../test_data/computed_counts_binary/kmers.counts");
+        let reader =
+            counts::CountsReader::open("../test_data/computed_counts_binary/kmers.counts.bin")
+                .unwrap();
+        assert_eq!(reader.ksize(), 15);
+        assert_eq!(reader.len(), exp.len());
+        for line in exp {
+            let mut parts = line.split('\t');
+            let kmer: Kmer = parts.next().unwrap().parse().unwrap();
+            let count: u64 = parts.next().unwrap().parse().unwrap();
+            assert_eq!(reader.get(kmer), Some(count));
+        }
+    }
+
+    #[test]
+    fn merge_shards_test() {
+        let out_dir = "../test_data/computed_counts_shards";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut ctr = CountComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15);
+        ctr.set_threads(3);
+        ctr.count();
+        ctr.merge(false);
+        ctr.options.output.counts = CountsOutput::Shards;
+        ctr.merge(true);
+        let index = shards::ShardIndex::open(&format!("{}/kmers.shards", out_dir)).unwrap();
+        for line in load_lines_sorted(format!("{}/kmers.counts", out_dir)) {
+            let (kmer, count) = line.split_once('\t').unwrap();
+            let kmer: Kmer = kmer.parse().unwrap();
+            assert_eq!(index.get(kmer).unwrap(), Some(count.parse().unwrap()));
+        }
+        assert_eq!(
+            load_lines_sorted(format!("{}/kmers.shards/index.tsv", out_dir)).len(),
+            4
+        );
+    }
 }
@@ -0,0 +1,219 @@
+use crate::{
+    width::{Count, CounterWidth},
+    CountComputer, KmerText,
+};
+use kmer::{numeric_to_kmer_in, stats::KmerStats, Kmer, KmerInt};
+use ktio::fops::{create_directory, delete_file_if_exists};
+use rayon::prelude::*;
+use std::{
+    cmp::max,
+    collections::HashMap,
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+// file-of-files, one <path>[\t<name>] per line, names default to the file stem
+pub fn load_samples(path: &str) -> Result<Vec<(String, String)>, String> {
+    let text = fs::read_to_string(path).map_err(|_| format!("Unable to read file: {}", path))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut parts = line.split('\t');
+            let path = parts.next().unwrap().to_string();
+            let name = parts
+                .next()
+                .map(String::from)
+                .unwrap_or_else(|| sample_name(&path));
+            (name, path)
+        })
+        .collect())
+}
+
+pub fn sample_name(path: &str) -> String {
+    let name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or(path.to_string());
+    // reads.fq.gz -> reads
+    name.split('.').next().unwrap_or(&name).to_string()
+}
+
+// counts every (name, path) sample and writes a k-mer x sample table to <out_dir>/kmers.matrix,
+// each counter is set up by configure, returns the statistics of all samples
+pub fn count_matrix(
+    samples: &[(String, String)],
+    out_dir: &str,
+    ksize: usize,
+    configure: impl Fn(&mut CountComputer),
+) -> Result<KmerStats, String> {
+    let mut ctrs = Vec::with_capacity(samples.len());
+    for (n, (_, path)) in samples.iter().enumerate() {
+        let sample_dir = format!("{}/sample_{}", out_dir, n);
+        create_directory(&sample_dir)
+            .map_err(|_| format!("Unable to create directory: {}", sample_dir))?;
+        let mut ctr = CountComputer::new(path.clone(), sample_dir, ksize);
+        configure(&mut ctr);
+        ctr.init();
+        ctrs.push(ctr);
+    }
+    // k-mers of a partition are found in the same partition of every sample
+    let n_parts = ctrs.iter().map(|ctr| ctr.n_parts).max().unwrap_or(1);
+    let mut stats = KmerStats::default();
+    for ctr in ctrs.iter_mut() {
+        ctr.min_parts = n_parts;
+        ctr.count();
+        stats += ctr.kmer_stats();
+    }
+
+    let names: Vec<&str> = samples.iter().map(|(name, _)| name.as_str()).collect();
+    match (ksize > Kmer::MAX_KSIZE, ctrs[0].options.output.width) {
+        (true, CounterWidth::U64) => merge_samples::<u128, u64>(&ctrs, &names, out_dir, n_parts)?,
+        (true, _) => merge_samples::<u128, u32>(&ctrs, &names, out_dir, n_parts)?,
+        (false, CounterWidth::U64) => merge_samples::<Kmer, u64>(&ctrs, &names, out_dir, n_parts)?,
+        (false, _) => merge_samples::<Kmer, u32>(&ctrs, &names, out_dir, n_parts)?,
+    }
+    for ctr in ctrs.iter() {
+        fs::remove_dir_all(&ctr.out_dir)
+            .map_err(|_| format!("Unable to remove directory: {}", ctr.out_dir))?;
+    }
+
+    Ok(stats)
+}
+
+fn merge_samples<K: KmerInt, C: Count>(
+    ctrs: &[CountComputer],
+    names: &[&str],
+    out_dir: &str,
+    n_parts: u64,
+) -> Result<(), String> {
+    let out_path = format!("{}/kmers.matrix", out_dir);
+    let outf = fs::File::create(&out_path)
+        .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    let kmers = ctrs[0].kmer_text();
+    let header = if matches!(kmers, KmerText::NumericAcgt(_)) {
+        format!("kmer\tacgt\t{}\n", names.join("\t"))
+    } else {
+        format!("kmer\t{}\n", names.join("\t"))
+    };
+    buff.write_all(header.as_bytes())
+        .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let threads = max(1, ctrs[0].threads);
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let ksize = ctrs[0].ksize;
+    let mut part = 0;
+
+    // partitions are loaded concurrently, but written in order
+    while part < n_parts {
+        let last = (part + threads as u64).min(n_parts);
+        let rows: Vec<Result<String, String>> = pool.install(|| {
+            (part..last)
+                .into_par_iter()
+                .map(|part| {
+                    let mut table: HashMap<K, Vec<u64>> = HashMap::new();
+                    for (sample, ctr) in ctrs.iter().enumerate() {
+                        for chunk in 0..ctr.chunks {
+                            for path in ctr.chunk_paths(part, chunk) {
+                                for (kmer, count) in
+                                    ctr.options.partitions.compress_tmp.entries::<K, C>(&path)?
+                                {
+                                    table.entry(kmer).or_insert(vec![0; ctrs.len()])[sample] +=
+                                        count.as_u64();
+                                }
+                                delete_file_if_exists(&path)
+                                    .map_err(|_| format!("Unable to remove file: {}", path))?;
+                            }
+                        }
+                    }
+                    let mut rows = String::new();
+                    for (kmer, counts) in table {
+                        let counts: Vec<String> =
+                            counts.iter().map(|count| count.to_string()).collect();
+                        rows += &match kmers {
+                            KmerText::Numeric => format!("{}\t{}\n", kmer, counts.join("\t")),
+                            KmerText::Acgt(molecule) => format!(
+                                "{}\t{}\n",
+                                numeric_to_kmer_in(kmer, ksize, molecule),
+                                counts.join("\t")
+                            ),
+                            KmerText::NumericAcgt(molecule) => format!(
+                                "{}\t{}\t{}\n",
+                                kmer,
+                                numeric_to_kmer_in(kmer, ksize, molecule),
+                                counts.join("\t")
+                            ),
+                        };
+                    }
+                    Ok(rows)
+                })
+                .collect()
+        });
+        for rows in rows {
+            buff.write_all(rows?.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+        }
+        part = last;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ktio::fops::load_lines_sorted;
+
+    #[test]
+    fn count_matrix_test() {
+        let out_dir = "../test_data/computed_counts_matrix";
+        create_directory(out_dir).unwrap();
+        let samples = vec![
+            ("fq".to_string(), "../test_data/reads.fq".to_string()),
+            ("fa".to_string(), "../test_data/reads.fa".to_string()),
+        ];
+        count_matrix(&samples, out_dir, 15, |ctr| ctr.set_threads(4)).unwrap();
+        let res = load_lines_sorted(format!("{}/kmers.matrix", out_dir));
+        assert!(res.contains(&"kmer\tfq\tfa".to_string()));
+        assert!(!Path::new(&format!("{}/sample_0", out_dir)).exists());
+
+        // each column matches counting the sample on its own
+        for (column, (name, path)) in samples.iter().enumerate() {
+            let sample_dir = format!("{}/single_{}", out_dir, name);
+            create_directory(&sample_dir).unwrap();
+            let mut ctr = CountComputer::new(path.clone(), sample_dir.clone(), 15);
+            ctr.count();
+            ctr.merge(true);
+            let exp = load_lines_sorted(format!("{}/kmers.counts", sample_dir));
+            let mut col: Vec<String> = res
+                .iter()
+                .filter(|line| !line.starts_with("kmer"))
+                .filter_map(|line| {
+                    let values: Vec<&str> = line.split('\t').collect();
+                    (values[column + 1] != "0")
+                        .then(|| format!("{}\t{}", values[0], values[column + 1]))
+                })
+                .collect();
+            col.sort();
+            assert_eq!(exp, col);
+        }
+    }
+
+    #[test]
+    fn load_samples_test() {
+        let path = "../test_data/computed_samples.txt";
+        fs::write(path, "a/reads.fq.gz\n# comment\nb/other.fa\tsample_b\n").unwrap();
+        assert_eq!(
+            load_samples(path).unwrap(),
+            vec![
+                ("reads".to_string(), "a/reads.fq.gz".to_string()),
+                ("sample_b".to_string(), "b/other.fa".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use crate::width::Count;
+use kmer::KmerInt;
+use scc::HashMap as SccMap;
+use std::mem::size_of;
+
+// how counting measures memory use against the ceiling
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum MemoryPolicy {
+    // slots allocated by the partition tables times the size of an entry
+    #[default]
+    Occupancy,
+    // 8 bytes per base read into the chunk, overestimates repetitive data
+    // and underestimates diverse data
+    SequenceLength,
+}
+
+impl MemoryPolicy {
+    // bytes held by the partition tables, with k-mers still in memory when
+    // sequence length is the measure
+    pub fn held<K: KmerInt, C: Count>(self, tables: &[SccMap<K, C>]) -> u64 {
+        match self {
+            MemoryPolicy::Occupancy => {
+                tables.iter().map(|map| map.capacity() as u64).sum::<u64>()
+                    * size_of::<(K, C)>() as u64
+            }
+            MemoryPolicy::SequenceLength => {
+                tables.iter().map(|map| map.len() as u64).sum::<u64>() * 8
+            }
+        }
+    }
+
+    // bytes a record adds, occupancy is measured again instead
+    pub fn record(self, seq_len: usize) -> Option<u64> {
+        match self {
+            MemoryPolicy::Occupancy => None,
+            MemoryPolicy::SequenceLength => Some(seq_len as u64 * 8),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_policy_test() {
+        let tables: Vec<SccMap<u64, u32>> = vec![SccMap::new(); 2];
+        assert_eq!(MemoryPolicy::Occupancy.held(&tables), 0);
+        for kmer in 0..100 {
+            let _ = tables[(kmer % 2) as usize].insert(kmer, 1);
+        }
+        let slots: u64 = tables.iter().map(|map| map.capacity() as u64).sum();
+        assert!(slots >= 100);
+        assert_eq!(MemoryPolicy::Occupancy.held(&tables), slots * 16);
+        assert_eq!(MemoryPolicy::SequenceLength.held(&tables), 800);
+        assert_eq!(MemoryPolicy::SequenceLength.record(150), Some(1200));
+        assert_eq!(MemoryPolicy::Occupancy.record(150), None);
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufWriter, Write},
+};
+
+// set operation on the k-mers of two kmers.counts files a and b
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetOp {
+    // k-mers of both, with the smaller count
+    Intersect,
+    // k-mers of either, with summed counts
+    Union,
+    // k-mers of a missing from b, with their count in a
+    Subtract,
+    // k-mers of a also in b, with their count in a, reported as a fraction of a
+    Containment,
+}
+
+// distinct k-mers of the inputs, shared between them and written
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OpStats {
+    pub a: u64,
+    pub b: u64,
+    pub shared: u64,
+    pub written: u64,
+}
+
+impl OpStats {
+    // fraction of the k-mers of a found in b
+    pub fn containment(&self) -> f64 {
+        self.shared as f64 / u64::max(1, self.a) as f64
+    }
+
+    // shared k-mers over k-mers of either input
+    pub fn jaccard(&self) -> f64 {
+        self.shared as f64 / u64::max(1, self.a + self.b - self.shared) as f64
+    }
+}
+
+// k-mer (first column) and count (last column) of a kmers.counts line,
+// numeric, ACGT or with --acgt-column
+fn parse_line(line: &str) -> Result<(&str, u64), String> {
+    let (kmer, rest) = line
+        .trim()
+        .split_once('\t')
+        .ok_or(format!("Invalid counts line: {}", line))?;
+    let count = rest
+        .rsplit('\t')
+        .next()
+        .and_then(|count| count.parse().ok())
+        .ok_or(format!("Invalid counts line: {}", line))?;
+    Ok((kmer, count))
+}
+
+fn is_numeric(kmer: &str) -> bool {
+    kmer.bytes().all(|c| c.is_ascii_digit())
+}
+
+// k-mers and counts in file order, with the index of each k-mer
+type CountsTable = (Vec<(String, u64)>, HashMap<String, usize>);
+
+fn load_counts(path: &str) -> Result<CountsTable, String> {
+    let mut entries = Vec::new();
+    let mut index = HashMap::new();
+    for line in ktio::seq::get_reader(path)?.lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (kmer, count) = parse_line(&line)?;
+        index.insert(kmer.to_string(), entries.len());
+        entries.push((kmer.to_string(), count));
+    }
+    Ok((entries, index))
+}
+
+// k-mers are matched as written, so both files need the same k and representation
+// a is streamed in file order and b is held in memory, the union writes k-mers
+// of a then those only in b
+pub fn set_operation(
+    a_path: &str,
+    b_path: &str,
+    out_path: &str,
+    op: SetOp,
+) -> Result<OpStats, String> {
+    let (b_entries, b_index) = load_counts(b_path)?;
+    let mut b_seen = vec![false; b_entries.len()];
+    let b_numeric = b_entries.first().map(|(kmer, _)| is_numeric(kmer));
+    let outf =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    let mut stats = OpStats {
+        b: b_entries.len() as u64,
+        ..OpStats::default()
+    };
+
+    let mut write = |kmer: &str, count: u64, stats: &mut OpStats| {
+        stats.written += 1;
+        writeln!(buff, "{}\t{}", kmer, count)
+            .map_err(|_| format!("Unable to write to file: {}", out_path))
+    };
+    for line in ktio::seq::get_reader(a_path)?.lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (kmer, count) = parse_line(&line)?;
+        if stats.a == 0 && b_numeric.is_some_and(|numeric| numeric != is_numeric(kmer)) {
+            return Err("Inputs must both have numeric or both have ACGT k-mers".to_string());
+        }
+        stats.a += 1;
+        let other = b_index.get(kmer).map(|&idx| {
+            b_seen[idx] = true;
+            b_entries[idx].1
+        });
+        if other.is_some() {
+            stats.shared += 1;
+        }
+        match (op, other) {
+            (SetOp::Intersect, Some(other)) => write(kmer, u64::min(count, other), &mut stats)?,
+            (SetOp::Union, other) => {
+                write(kmer, count.saturating_add(other.unwrap_or(0)), &mut stats)?
+            }
+            (SetOp::Subtract, None) | (SetOp::Containment, Some(_)) => {
+                write(kmer, count, &mut stats)?
+            }
+            _ => {}
+        }
+    }
+    if op == SetOp::Union {
+        for ((kmer, count), seen) in b_entries.iter().zip(b_seen) {
+            if !seen {
+                write(kmer, *count, &mut stats)?;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_operation_test() {
+        let a_path = "../test_data/computed_ops_a.counts";
+        let b_path = "../test_data/computed_ops_b.counts";
+        let out_path = "../test_data/computed_ops.counts";
+        fs::write(a_path, "1\t3\n2\t5\n4\t1\n").unwrap();
+        fs::write(b_path, "4\t2\n8\t7\n2\t1\n").unwrap();
+
+        let stats = set_operation(a_path, b_path, out_path, SetOp::Intersect).unwrap();
+        assert_eq!(fs::read_to_string(out_path).unwrap(), "2\t1\n4\t1\n");
+        assert_eq!(
+            (stats.a, stats.b, stats.shared, stats.written),
+            (3, 3, 2, 2)
+        );
+        assert_eq!(stats.jaccard(), 0.5);
+
+        set_operation(a_path, b_path, out_path, SetOp::Union).unwrap();
+        assert_eq!(
+            fs::read_to_string(out_path).unwrap(),
+            "1\t3\n2\t6\n4\t3\n8\t7\n"
+        );
+
+        set_operation(a_path, b_path, out_path, SetOp::Subtract).unwrap();
+        assert_eq!(fs::read_to_string(out_path).unwrap(), "1\t3\n");
+
+        let stats = set_operation(a_path, b_path, out_path, SetOp::Containment).unwrap();
+        assert_eq!(fs::read_to_string(out_path).unwrap(), "2\t5\n4\t1\n");
+        assert_eq!(stats.containment(), 2.0 / 3.0);
+
+        fs::write(b_path, "AAAC\t1\n").unwrap();
+        assert!(set_operation(a_path, b_path, out_path, SetOp::Union).is_err());
+    }
+}
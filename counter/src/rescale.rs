@@ -0,0 +1,181 @@
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufWriter, Write},
+};
+
+const BATCH_LINES: usize = 100_000;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Rounding {
+    // round to nearest
+    Deterministic,
+    // round up with probability of the fractional part, seeded
+    Stochastic(u64),
+}
+
+// https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+// random draw depends only on the k-mer and the seed, so output is reproducible
+fn uniform(kmer: &str, seed: u64) -> f64 {
+    let hash = kmer.bytes().fold(0xcbf29ce484222325_u64, |acc, b| {
+        (acc ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    (splitmix64(hash ^ seed) >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+pub fn scale_count(kmer: &str, count: u32, factor: f64, rounding: Rounding) -> u32 {
+    let scaled = count as f64 * factor;
+    match rounding {
+        Rounding::Deterministic => scaled.round() as u32,
+        Rounding::Stochastic(seed) => {
+            let floor = scaled.floor();
+            if uniform(kmer, seed) < scaled - floor {
+                floor as u32 + 1
+            } else {
+                floor as u32
+            }
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Result<(&str, u32), String> {
+    let mut parts = line.trim().split('\t');
+    let kmer = parts.next().unwrap();
+    let count = parts
+        .next()
+        .and_then(|count| count.parse().ok())
+        .ok_or(format!("Invalid counts line: {}", line))?;
+    Ok((kmer, count))
+}
+
+// k-mer coverage as the peak of the abundance spectrum, ignoring the error peak at 1
+pub fn estimate_coverage(in_path: &str) -> Result<f64, String> {
+    let reader = ktio::seq::get_reader(in_path)?;
+    let mut spectrum: HashMap<u32, u64> = HashMap::new();
+    for line in reader.lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (_, count) = parse_line(&line)?;
+        *spectrum.entry(count).or_insert(0) += 1;
+    }
+    spectrum
+        .into_iter()
+        .filter(|&(count, _)| count > 1)
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map(|(count, _)| count as f64)
+        .ok_or("Unable to estimate coverage, no k-mers seen more than once".to_string())
+}
+
+pub fn rescale_counts(
+    in_path: &str,
+    out_path: &str,
+    factor: f64,
+    rounding: Rounding,
+    threads: usize,
+) -> Result<(), String> {
+    let mut threads = threads;
+    if threads == 0 {
+        threads = ktio::threads::default_threads();
+    }
+    if factor <= 0_f64 || !factor.is_finite() {
+        return Err(format!("Invalid scaling factor: {}", factor));
+    }
+    let reader = ktio::seq::get_reader(in_path)?;
+    let outf =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let mut lines = reader.lines().map_while(Result::ok);
+
+    loop {
+        let batch: Vec<String> = lines.by_ref().take(BATCH_LINES).collect();
+        if batch.is_empty() {
+            break;
+        }
+        let scaled = pool.install(|| {
+            batch
+                .par_iter()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| {
+                    let (kmer, count) = parse_line(line)?;
+                    // k-mers scaled down to zero are dropped
+                    Ok(match scale_count(kmer, count, factor, rounding) {
+                        0 => String::new(),
+                        count => format!("{}\t{}\n", kmer, count),
+                    })
+                })
+                .collect::<Result<Vec<String>, String>>()
+        })?;
+        buff.write_all(scaled.join("").as_bytes())
+            .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ktio::fops::load_lines_sorted;
+
+    #[test]
+    fn scale_count_test() {
+        assert_eq!(scale_count("1", 10, 0.25, Rounding::Deterministic), 3);
+        assert_eq!(scale_count("1", 10, 2.0, Rounding::Deterministic), 20);
+        // stochastic rounding stays within floor and ceil and is reproducible
+        for kmer in 0..100 {
+            let kmer = kmer.to_string();
+            let count = scale_count(&kmer, 10, 0.25, Rounding::Stochastic(42));
+            assert!(count == 2 || count == 3);
+            assert_eq!(
+                count,
+                scale_count(&kmer, 10, 0.25, Rounding::Stochastic(42))
+            );
+        }
+        // expected value is preserved
+        let total: u32 = (0..10000)
+            .map(|kmer| scale_count(&kmer.to_string(), 1, 0.5, Rounding::Stochastic(7)))
+            .sum();
+        assert!((4500..5500).contains(&total));
+    }
+
+    #[test]
+    fn rescale_counts_test() {
+        rescale_counts(
+            "../test_data/expected_counts_test.counts",
+            "../test_data/computed_counts_rescaled.counts",
+            0.5,
+            Rounding::Deterministic,
+            4,
+        )
+        .unwrap();
+        let res = load_lines_sorted("../test_data/computed_counts_rescaled.counts");
+        assert_eq!(res, vec!["1\t2", "11\t11", "2\t3", "22\t10", "23\t12"]);
+        assert!(rescale_counts(
+            "../test_data/expected_counts_test.counts",
+            "../test_data/computed_counts_rescaled.counts",
+            0.0,
+            Rounding::Deterministic,
+            4,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn estimate_coverage_test() {
+        let cov = estimate_coverage("../test_data/expected_counts_test.counts").unwrap();
+        assert_eq!(cov, 4.0);
+    }
+}
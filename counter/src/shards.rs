@@ -0,0 +1,269 @@
+use crate::{
+    counts::{CountsReader, CountsWriter, Partitioning},
+    width::{Count, CounterWidth},
+};
+use kmer::KmerInt;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{BufWriter, Write},
+    sync::Mutex,
+};
+
+// one sorted binary counts file per partition, <dir>/shard_<part>.bin, and <dir>/index.tsv
+// the index starts with #ksize=<k> parts=<n> signature=<m> then holds one
+// <shard>\t<file>\t<kmers>\t<first>\t<last> line per shard, first and last are - when empty
+// a k-mer can only be in the shard of its partition, so point queries open one small file
+pub struct ShardWriter {
+    dir: String,
+    ksize: usize,
+    n_parts: u64,
+    partitioning: Partitioning,
+    width: CounterWidth,
+    // index line of each shard written so far
+    index: Mutex<BTreeMap<u64, String>>,
+}
+
+impl ShardWriter {
+    pub fn new(
+        dir: &str,
+        ksize: usize,
+        n_parts: u64,
+        partitioning: Partitioning,
+        width: CounterWidth,
+    ) -> Result<Self, String> {
+        fs::create_dir_all(dir).map_err(|_| format!("Unable to create directory: {}", dir))?;
+        Ok(Self {
+            dir: dir.to_string(),
+            ksize,
+            n_parts,
+            partitioning,
+            width,
+            index: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    // next shard, as the partitions are written in order
+    pub fn write_partition<K: KmerInt, C: Count>(
+        &mut self,
+        entries: &mut [(K, C)],
+    ) -> Result<(), String> {
+        entries.sort_unstable();
+        let shard = self.index.lock().unwrap().len() as u64;
+        self.write_shard(shard, entries.iter().copied())
+    }
+
+    // shard of a partition, entries must come in ascending k-mer order,
+    // shards can be written concurrently and in any order
+    pub fn write_shard<K: KmerInt, C: Count>(
+        &self,
+        shard: u64,
+        entries: impl Iterator<Item = (K, C)>,
+    ) -> Result<(), String> {
+        let file = format!("shard_{}.bin", shard);
+        let mut writer = CountsWriter::with_layout(
+            &format!("{}/{}", self.dir, file),
+            self.ksize,
+            1,
+            Partitioning::Modulo,
+            self.width,
+        )?;
+        let (mut first, mut last, mut kmers) = (None, None, 0);
+        writer.write_sorted_partition(entries.inspect(|(kmer, _)| {
+            first.get_or_insert(*kmer);
+            last = Some(*kmer);
+            kmers += 1;
+        }))?;
+        writer.finish()?;
+        let (first, last) = match (first, last) {
+            (Some(first), Some(last)) => (first.to_string(), last.to_string()),
+            _ => ("-".to_string(), "-".to_string()),
+        };
+        self.index.lock().unwrap().insert(
+            shard,
+            format!("{}\t{}\t{}\t{}\t{}\n", shard, file, kmers, first, last),
+        );
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        let index = self.index.into_inner().unwrap();
+        if !index.keys().copied().eq(0..self.n_parts) {
+            return Err(format!(
+                "Expected {} shards, {} were written",
+                self.n_parts,
+                index.len()
+            ));
+        }
+        let path = format!("{}/index.tsv", self.dir);
+        let outf =
+            fs::File::create(&path).map_err(|_| format!("Unable to write to file: {}", path))?;
+        let mut buff = BufWriter::new(outf);
+        let signature = match self.partitioning {
+            Partitioning::Modulo => 0,
+            Partitioning::Signature(msize) => msize,
+        };
+        write!(
+            buff,
+            "#ksize={} parts={} signature={}\n{}",
+            self.ksize,
+            self.n_parts,
+            signature,
+            index.into_values().collect::<String>()
+        )
+        .map_err(|_| format!("Unable to write to file: {}", path))
+    }
+}
+
+// (file, first, last) of a shard, k-mers as numeric strings
+struct Shard {
+    file: String,
+    range: Option<(String, String)>,
+}
+
+// index of a sharded counts directory, shards are opened when queried
+pub struct ShardIndex {
+    dir: String,
+    ksize: usize,
+    partitioning: Partitioning,
+    shards: Vec<Shard>,
+}
+
+impl ShardIndex {
+    pub fn open(dir: &str) -> Result<Self, String> {
+        let path = format!("{}/index.tsv", dir);
+        let text =
+            fs::read_to_string(&path).map_err(|_| format!("Unable to read file: {}", path))?;
+        let mut lines = text.lines();
+        let invalid = || format!("Invalid shard index: {}", path);
+        let meta: Vec<usize> = lines
+            .next()
+            .and_then(|line| line.strip_prefix('#'))
+            .ok_or_else(invalid)?
+            .split(' ')
+            .map(|field| {
+                field
+                    .split_once('=')
+                    .and_then(|(_, value)| value.parse().ok())
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)?;
+        let [ksize, n_parts, signature] = meta[..] else {
+            return Err(invalid());
+        };
+        let shards: Vec<Shard> = lines
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                if fields.len() != 5 {
+                    return None;
+                }
+                Some(Shard {
+                    file: fields[1].to_string(),
+                    range: (fields[3] != "-")
+                        .then(|| (fields[3].to_string(), fields[4].to_string())),
+                })
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)?;
+        if shards.len() != n_parts || n_parts == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            dir: dir.to_string(),
+            ksize,
+            partitioning: match signature {
+                0 => Partitioning::Modulo,
+                msize => Partitioning::Signature(msize),
+            },
+            shards,
+        })
+    }
+
+    pub fn ksize(&self) -> usize {
+        self.ksize
+    }
+
+    // K must be u128 when ksize > 32 and u64 otherwise
+    pub fn get<K: KmerInt>(&self, kmer: K) -> Result<Option<u64>, String> {
+        let part = self
+            .partitioning
+            .part(kmer, self.ksize, self.shards.len() as u64);
+        let shard = &self.shards[part];
+        let in_range = shard.range.as_ref().is_some_and(|(first, last)| {
+            let parse = |value: &str| value.parse::<K>().ok();
+            parse(first).is_some_and(|first| first <= kmer)
+                && parse(last).is_some_and(|last| kmer <= last)
+        });
+        if !in_range {
+            return Ok(None);
+        }
+        let counts = CountsReader::open(&format!("{}/{}", self.dir, shard.file))?;
+        Ok(counts.get(kmer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shards_roundtrip_test() {
+        let dir = "../test_data/computed_shards";
+        let partitioning = Partitioning::Signature(9);
+        let kmers: Vec<u64> = (0..100).map(|i| i * 7919).collect();
+        let mut parts: Vec<Vec<(u64, u32)>> = vec![vec![]; 3];
+        for (idx, &kmer) in kmers.iter().enumerate() {
+            parts[partitioning.part(kmer, 15, 3)].push((kmer, idx as u32 + 1));
+        }
+        let mut writer = ShardWriter::new(dir, 15, 3, partitioning, CounterWidth::U32).unwrap();
+        for part in parts.iter_mut() {
+            writer.write_partition(part).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let index = ShardIndex::open(dir).unwrap();
+        assert_eq!(index.ksize(), 15);
+        for (idx, &kmer) in kmers.iter().enumerate() {
+            assert_eq!(index.get(kmer).unwrap(), Some(idx as u64 + 1));
+        }
+        assert_eq!(index.get(1_u64).unwrap(), None);
+    }
+
+    #[test]
+    fn shards_concurrent_test() {
+        let dir = "../test_data/computed_shards_concurrent";
+        let partitioning = Partitioning::Signature(9);
+        let kmers: Vec<u64> = (0..100).map(|i| i * 7919).collect();
+        let mut parts: Vec<Vec<(u64, u32)>> = vec![vec![]; 4];
+        for (idx, &kmer) in kmers.iter().enumerate() {
+            parts[partitioning.part(kmer, 15, 4)].push((kmer, idx as u32 + 1));
+        }
+        let writer = ShardWriter::new(dir, 15, 4, partitioning, CounterWidth::U32).unwrap();
+        // shards written from their own threads, last partition first
+        std::thread::scope(|scope| {
+            for (shard, part) in parts.iter_mut().enumerate().rev() {
+                let writer = &writer;
+                scope.spawn(move || {
+                    part.sort_unstable();
+                    writer
+                        .write_shard(shard as u64, part.iter().copied())
+                        .unwrap();
+                });
+            }
+        });
+        writer.finish().unwrap();
+
+        let index = ShardIndex::open(dir).unwrap();
+        for (idx, &kmer) in kmers.iter().enumerate() {
+            assert_eq!(index.get(kmer).unwrap(), Some(idx as u64 + 1));
+        }
+        let lines = fs::read_to_string(format!("{}/index.tsv", dir)).unwrap();
+        let shards: Vec<&str> = lines
+            .lines()
+            .filter_map(|line| line.split('\t').next())
+            .filter(|shard| shard.parse::<u64>().is_ok())
+            .collect();
+        assert_eq!(shards, vec!["0", "1", "2", "3"]);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::width::Count;
+use kmer::KmerInt;
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs,
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write},
+    marker::PhantomData,
+};
+
+// codec of the temporary chunk files written while counting
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SpillCompression {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+    Gzip,
+}
+
+impl SpillCompression {
+    pub fn writer(self, path: &str) -> Result<Box<dyn Write + Send>, String> {
+        let file =
+            fs::File::create(path).map_err(|_| format!("Unable to write to file: {}", path))?;
+        // encoders finish their frames when dropped, like the plain BufWriter flushes
+        Ok(match self {
+            SpillCompression::None => Box::new(BufWriter::new(file)),
+            SpillCompression::Lz4 => Box::new(BufWriter::new(
+                lz4_flex::frame::FrameEncoder::new(file).auto_finish(),
+            )),
+            SpillCompression::Zstd => Box::new(BufWriter::new(
+                // fastest level, spills are short lived
+                zstd::Encoder::new(file, 1)
+                    .map_err(|e| format!("Unable to compress {}: {}", path, e))?
+                    .auto_finish(),
+            )),
+            SpillCompression::Gzip => Box::new(BufWriter::new(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::fast(),
+            ))),
+        })
+    }
+
+    pub fn reader(self, path: &str) -> Result<Box<dyn BufRead + Send>, String> {
+        let file = fs::File::open(path).map_err(|_| format!("Unable to open: {}", path))?;
+        Ok(match self {
+            SpillCompression::None => Box::new(BufReader::new(file)),
+            SpillCompression::Lz4 => {
+                Box::new(BufReader::new(lz4_flex::frame::FrameDecoder::new(file)))
+            }
+            SpillCompression::Zstd => Box::new(BufReader::new(
+                zstd::Decoder::new(file)
+                    .map_err(|e| format!("Unable to decompress {}: {}", path, e))?,
+            )),
+            SpillCompression::Gzip => Box::new(BufReader::new(flate2::read::GzDecoder::new(file))),
+        })
+    }
+
+    // plain spills stay readable text, compressed ones hold fixed width little endian
+    // k-mers followed by counts of the counter width, which are smaller than decimal text
+    pub fn write_entry<K: KmerInt, C: Count>(
+        self,
+        writer: &mut dyn Write,
+        kmer: K,
+        count: C,
+    ) -> std::io::Result<()> {
+        if self == SpillCompression::None {
+            return writer.write_all(format!("{}\t{}\n", kmer, count).as_bytes());
+        }
+        let mut bytes = Vec::with_capacity(K::BYTES + C::BYTES);
+        kmer.write_le(&mut bytes);
+        count.write_le(&mut bytes);
+        writer.write_all(&bytes)
+    }
+
+    // entries must be read with the k-mer and counter types they were written with
+    pub fn entries<K: KmerInt, C: Count>(self, path: &str) -> Result<SpillEntries<K, C>, String> {
+        Ok(SpillEntries {
+            reader: self.reader(path)?,
+            codec: self,
+            path: path.to_string(),
+            entry: PhantomData,
+        })
+    }
+}
+
+// (k-mer, count) entries of a spill written with write_entry
+pub struct SpillEntries<K, C> {
+    reader: Box<dyn BufRead + Send>,
+    codec: SpillCompression,
+    path: String,
+    entry: PhantomData<(K, C)>,
+}
+
+impl<K: KmerInt, C: Count> Iterator for SpillEntries<K, C> {
+    type Item = (K, C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.codec == SpillCompression::None {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            let (kmer, count) = line.trim().split_once('\t')?;
+            let kmer = match kmer.parse() {
+                Ok(kmer) => kmer,
+                Err(_) => panic!("Invalid k-mer in {}", self.path),
+            };
+            let count = match count.parse() {
+                Ok(count) => count,
+                Err(_) => panic!("Invalid count in {}", self.path),
+            };
+            return Some((kmer, count));
+        }
+        let mut bytes = vec![0; K::BYTES + C::BYTES];
+        match self.reader.read_exact(&mut bytes) {
+            Ok(()) => Some((K::read_le(&bytes), C::read_le(&bytes[K::BYTES..]))),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => None,
+            Err(_) => panic!("Unable to read {}", self.path),
+        }
+    }
+}
+
+// k-way merge of spills sorted by k-mer into one ascending stream,
+// counts of a k-mer found in several spills are combined with add
+pub struct MergedRuns<K, C, F> {
+    runs: Vec<SpillEntries<K, C>>,
+    heap: BinaryHeap<Reverse<(K, C, usize)>>,
+    add: F,
+}
+
+impl<K: KmerInt, C: Count, F: FnMut(C, C) -> C> MergedRuns<K, C, F> {
+    pub fn new(runs: Vec<SpillEntries<K, C>>, add: F) -> Self {
+        let mut merged = Self {
+            heap: BinaryHeap::with_capacity(runs.len()),
+            runs,
+            add,
+        };
+        for idx in 0..merged.runs.len() {
+            merged.refill(idx);
+        }
+        merged
+    }
+
+    fn refill(&mut self, idx: usize) {
+        if let Some((kmer, count)) = self.runs[idx].next() {
+            self.heap.push(Reverse((kmer, count, idx)));
+        }
+    }
+}
+
+impl<K: KmerInt, C: Count, F: FnMut(C, C) -> C> Iterator for MergedRuns<K, C, F> {
+    type Item = (K, C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((kmer, mut count, idx)) = self.heap.pop()?;
+        self.refill(idx);
+        while let Some(Reverse((other_kmer, other, idx))) = self.heap.peek().copied() {
+            if other_kmer != kmer {
+                break;
+            }
+            self.heap.pop();
+            count = (self.add)(count, other);
+            self.refill(idx);
+        }
+        Some((kmer, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spill_roundtrip_test() {
+        let lines: Vec<String> = (0..10_000)
+            .map(|i| format!("{}\t{}", i * 7, i % 13))
+            .collect();
+        for codec in [
+            SpillCompression::None,
+            SpillCompression::Lz4,
+            SpillCompression::Zstd,
+            SpillCompression::Gzip,
+        ] {
+            let path = format!("../test_data/computed_spill_{:?}", codec);
+            {
+                let mut writer = codec.writer(&path).unwrap();
+                for line in &lines {
+                    writeln!(writer, "{}", line).unwrap();
+                }
+            }
+            let read: Vec<String> = codec
+                .reader(&path)
+                .unwrap()
+                .lines()
+                .map_while(Result::ok)
+                .collect();
+            assert_eq!(read, lines);
+            if codec != SpillCompression::None {
+                let plain = fs::metadata("../test_data/computed_spill_None")
+                    .unwrap()
+                    .len();
+                assert!(fs::metadata(&path).unwrap().len() < plain);
+            }
+        }
+    }
+
+    #[test]
+    fn spill_entries_test() {
+        let entries: Vec<(u128, u32)> = (0..10_000)
+            .map(|i| ((i as u128) << 70 | i as u128, i % 13))
+            .collect();
+        for codec in [
+            SpillCompression::None,
+            SpillCompression::Lz4,
+            SpillCompression::Zstd,
+            SpillCompression::Gzip,
+        ] {
+            let path = format!("../test_data/computed_spill_entries_{:?}", codec);
+            {
+                let mut writer = codec.writer(&path).unwrap();
+                for &(kmer, count) in &entries {
+                    codec.write_entry(&mut writer, kmer, count).unwrap();
+                }
+            }
+            let read: Vec<(u128, u32)> = codec.entries(&path).unwrap().collect();
+            assert_eq!(read, entries);
+        }
+    }
+
+    #[test]
+    fn merged_runs_test() {
+        let runs = [
+            vec![(1_u64, 2_u32), (4, 1)],
+            vec![(1, 3), (2, 1), (4, 4)],
+            vec![],
+        ];
+        for codec in [SpillCompression::None, SpillCompression::Lz4] {
+            let entries = runs
+                .iter()
+                .enumerate()
+                .map(|(idx, run)| {
+                    let path = format!("../test_data/computed_runs_{:?}_{}", codec, idx);
+                    {
+                        let mut writer = codec.writer(&path).unwrap();
+                        for (kmer, count) in run {
+                            codec.write_entry(&mut writer, *kmer, *count).unwrap();
+                        }
+                    }
+                    codec.entries::<u64, u32>(&path).unwrap()
+                })
+                .collect();
+            let merged: Vec<(u64, u32)> =
+                MergedRuns::new(entries, |count, other| count + other).collect();
+            assert_eq!(merged, vec![(1, 5), (2, 1), (4, 5)]);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::counts::{is_binary_counts, CountsReader};
+use kmer::{kmer::GenericKmerGenerator, strand::Strand, Kmer, KmerInt};
+use ktio::seq::{get_reader, SeqFormat, Sequences};
+use std::{collections::HashSet, fs, io::BufRead, path::Path, sync::Arc};
+
+// k-mers to count, all others are skipped, shared by every counter
+#[derive(Debug, Clone)]
+pub struct Whitelist {
+    kmers: Arc<HashSet<u128>>,
+}
+
+impl Whitelist {
+    // k-mers of the sequences of a FASTA/FASTQ file of targets, or the first column of
+    // a kmers.counts file of a previous run (numeric or ACGT) or a binary counts file,
+    // counts of another k-mer size are rejected, k-mers are picked again by strand
+    pub fn load(path: &str, ksize: usize, strand: Strand) -> Result<Self, String> {
+        let mut kmers = HashSet::new();
+        let pick = |(fmer, rmer): (u128, u128)| strand.pick(fmer, rmer);
+        let counted_ksize = if is_binary_counts(path) {
+            Some(CountsReader::open(path)?.ksize())
+        } else {
+            recorded_ksize(path)
+        };
+        if let Some(counted_ksize) = counted_ksize.filter(|&counted| counted != ksize) {
+            return Err(format!(
+                "Whitelist has {}-mers, expected {}-mers",
+                counted_ksize, ksize
+            ));
+        }
+        if is_binary_counts(path) {
+            let reader = CountsReader::open(path)?;
+            let numeric: Vec<u128> = if ksize > Kmer::MAX_KSIZE {
+                reader.iter::<u128>().map(|(kmer, _)| kmer).collect()
+            } else {
+                reader
+                    .iter::<Kmer>()
+                    .map(|(kmer, _)| kmer.as_u128())
+                    .collect()
+            };
+            kmers.extend(
+                numeric
+                    .into_iter()
+                    .map(|kmer| pick((kmer, rev_comp(kmer, ksize)))),
+            );
+        } else if let Some(format) = SeqFormat::get(path) {
+            for record in Sequences::new(format, get_reader(path)?)? {
+                kmers.extend(GenericKmerGenerator::<u128>::new(&record.seq, ksize).map(pick));
+            }
+        } else {
+            for line in get_reader(path)?.lines().map_while(Result::ok) {
+                let kmer = line.split('\t').next().unwrap().trim();
+                if kmer.is_empty() {
+                    continue;
+                }
+                let kmer = match kmer.parse::<u128>() {
+                    Ok(numeric) if numeric >> (2 * ksize) != 0 => {
+                        return Err(format!("Not a numeric {}-mer: {}", ksize, kmer))
+                    }
+                    Ok(numeric) => pick((numeric, rev_comp(numeric, ksize))),
+                    Err(_) if kmer.len() == ksize => {
+                        GenericKmerGenerator::<u128>::new(kmer.as_bytes(), ksize)
+                            .next()
+                            .map(pick)
+                            .ok_or(format!("Not an ACGT k-mer: {}", kmer))?
+                    }
+                    Err(_) => return Err(format!("Expected a {}-mer, got: {}", ksize, kmer)),
+                };
+                kmers.insert(kmer);
+            }
+        }
+
+        Ok(Self {
+            kmers: Arc::new(kmers),
+        })
+    }
+
+    #[inline]
+    pub fn contains<K: KmerInt>(&self, kmer: K) -> bool {
+        self.kmers.contains(&kmer.as_u128())
+    }
+
+    pub fn len(&self) -> usize {
+        self.kmers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kmers.is_empty()
+    }
+}
+
+// k-mer size in the kmers.stats written next to a kmers.counts file by ctr --stats
+fn recorded_ksize(path: &str) -> Option<usize> {
+    let stats = Path::new(path).with_file_name("kmers.stats");
+    fs::read_to_string(stats)
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("ksize\t")?.parse().ok())
+}
+
+fn rev_comp(kmer: u128, ksize: usize) -> u128 {
+    (0..ksize).fold(0, |rkmer, pos| {
+        (rkmer << 2) | (((kmer >> (2 * pos)) & 3) ^ 3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counts::CountsWriter;
+    use kmer::{kmer::KmerGenerator, kmer_to_numeric};
+    use std::fs;
+
+    #[test]
+    fn whitelist_load_test() {
+        let path = "../test_data/computed_whitelist.counts";
+        let acgt = kmer_to_numeric("AAAC").unwrap();
+        fs::write(path, format!("{}\t3\nGTTT\t1\nACGA\n", acgt)).unwrap();
+        let whitelist = Whitelist::load(path, 4, Strand::Canonical).unwrap();
+        // GTTT is counted as its reverse complement AAAC
+        assert_eq!(whitelist.len(), 2);
+        assert!(whitelist.contains(acgt));
+        assert!(whitelist.contains(kmer_to_numeric("ACGA").unwrap()));
+
+        let whitelist = Whitelist::load("../test_data/reads.fa", 15, Strand::Forward).unwrap();
+        let reads = fs::read_to_string("../test_data/reads.fa").unwrap();
+        let seq = reads.lines().nth(1).unwrap();
+        for (fmer, _) in KmerGenerator::new(seq.as_bytes(), 15) {
+            assert!(whitelist.contains(fmer));
+        }
+        fs::write(path, "ACG\n").unwrap();
+        assert!(Whitelist::load(path, 4, Strand::Canonical).is_err());
+    }
+    #[test]
+    fn whitelist_ksize_test() {
+        let out_dir = "../test_data/computed_whitelist_ksize";
+        fs::create_dir_all(out_dir).unwrap();
+        // counts of a k = 15 run are small enough to pass for 40-mers
+        let path = format!("{}/kmers.counts", out_dir);
+        fs::write(&path, "1\t4\n23\t24\n").unwrap();
+        fs::write(
+            format!("{}/kmers.stats", out_dir),
+            "kmers\t2\nskipped_kmers\t0\nstride\t1\nksize\t15\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Whitelist::load(&path, 40, Strand::Canonical).unwrap_err(),
+            "Whitelist has 15-mers, expected 40-mers"
+        );
+        assert_eq!(
+            Whitelist::load(&path, 15, Strand::Canonical).unwrap().len(),
+            2
+        );
+        fs::remove_file(format!("{}/kmers.stats", out_dir)).unwrap();
+
+        // numeric k-mers of more bases than k
+        fs::write(&path, "256\t1\n").unwrap();
+        assert!(Whitelist::load(&path, 4, Strand::Canonical).is_err());
+        // numeric k-mers are picked again, TTTT is counted as AAAA
+        fs::write(&path, "255\t1\n").unwrap();
+        let whitelist = Whitelist::load(&path, 4, Strand::Canonical).unwrap();
+        assert!(whitelist.contains(0_u64));
+        let whitelist = Whitelist::load(&path, 4, Strand::Forward).unwrap();
+        assert!(whitelist.contains(255_u64));
+
+        // binary counts record their k-mer size
+        let path = format!("{}/kmers.counts.bin", out_dir);
+        let mut writer = CountsWriter::new(&path, 4, 1).unwrap();
+        writer
+            .write_partition(&mut [(0_u64, 3_u32), (255, 1)])
+            .unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            Whitelist::load(&path, 5, Strand::Canonical).unwrap_err(),
+            "Whitelist has 4-mers, expected 5-mers"
+        );
+        assert_eq!(
+            Whitelist::load(&path, 4, Strand::Canonical).unwrap().len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    fmt::{Debug, Display},
+    hash::Hash,
+    str::FromStr,
+};
+
+// counter type of k-mer counts, in memory, in temporary chunks and in binary counts
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CounterWidth {
+    // 32-bit counters that wrap around past u32::MAX
+    #[default]
+    U32,
+    // 64-bit counters, twice the memory and disk space of 32-bit ones
+    U64,
+    // 32-bit counters held at u32::MAX, with a warning once counting is done
+    Saturating,
+}
+
+impl CounterWidth {
+    pub fn bytes(self) -> usize {
+        match self {
+            CounterWidth::U64 => <u64 as Count>::BYTES,
+            _ => <u32 as Count>::BYTES,
+        }
+    }
+
+    pub fn saturates(self) -> bool {
+        self == CounterWidth::Saturating
+    }
+}
+
+pub trait Count:
+    Copy + Ord + Hash + Default + Debug + Display + FromStr + Send + Sync + 'static
+{
+    const BYTES: usize;
+    const MAX: Self;
+
+    fn one() -> Self;
+
+    // wrapping sum, or held at MAX when saturating
+    fn add(self, other: Self, saturating: bool) -> Self;
+
+    fn as_u64(self) -> u64;
+
+    fn write_le(self, bytes: &mut Vec<u8>);
+
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_count {
+    ($t:ty) => {
+        impl Count for $t {
+            const BYTES: usize = std::mem::size_of::<$t>();
+            const MAX: Self = <$t>::MAX;
+
+            #[inline]
+            fn one() -> Self {
+                1
+            }
+
+            #[inline]
+            fn add(self, other: Self, saturating: bool) -> Self {
+                if saturating {
+                    self.saturating_add(other)
+                } else {
+                    self.wrapping_add(other)
+                }
+            }
+
+            #[inline]
+            fn as_u64(self) -> u64 {
+                self as u64
+            }
+
+            fn write_le(self, bytes: &mut Vec<u8>) {
+                bytes.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read_le(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes[..Self::BYTES].try_into().unwrap())
+            }
+        }
+    };
+}
+
+impl_count!(u32);
+impl_count!(u64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_add_test() {
+        assert_eq!(u32::MAX.add(2, false), 1);
+        assert_eq!(u32::MAX.add(2, true), u32::MAX);
+        assert_eq!((u32::MAX as u64).add(2, false), u32::MAX as u64 + 2);
+        let mut bytes = Vec::new();
+        (u32::MAX as u64 + 2).write_le(&mut bytes);
+        assert_eq!(bytes.len(), CounterWidth::U64.bytes());
+        assert_eq!(u64::read_le(&bytes), u32::MAX as u64 + 2);
+    }
+}
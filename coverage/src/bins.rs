@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+// how k-mer counts are split into the bins of coverage histograms
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinScale {
+    // bins of bin_size counts each
+    Linear,
+    // first bin of bin_size counts, each following bin twice as wide
+    Log,
+    // bins holding about as many k-mer occurrences each, taken from the counts table
+    Quantile,
+}
+
+pub fn linear_edges(bin_size: usize, bin_count: usize) -> Vec<u64> {
+    (0..bin_count).map(|bin| (bin * bin_size) as u64).collect()
+}
+
+pub fn log_edges(bin_size: usize, bin_count: usize) -> Vec<u64> {
+    let mut edges = vec![0_u64];
+    for bin in 1..bin_count {
+        let edge = 2_u64
+            .saturating_pow(bin as u32 - 1)
+            .saturating_mul(bin_size as u64);
+        edges.push(u64::max(edge, edges[bin - 1] + 1));
+    }
+    edges
+}
+
+// histogram maps a count to the number of distinct k-mers with it, k-mers are
+// weighted by their count as each is seen that many times in the reads
+pub fn quantile_edges(histogram: &BTreeMap<u64, u64>, bin_count: usize) -> Vec<u64> {
+    let total: u128 = histogram
+        .iter()
+        .map(|(&count, &kmers)| count as u128 * kmers as u128)
+        .sum();
+    let mut edges = vec![0_u64];
+    let mut seen = 0_u128;
+    let mut entries = histogram.iter();
+    for bin in 1..bin_count {
+        let target = total * bin as u128 / bin_count as u128;
+        // a bin ends with the count that reaches its share of the occurrences,
+        // bins are kept non-empty in range so that every bin has a name
+        let mut edge = edges[bin - 1].saturating_add(1);
+        while seen < target {
+            match entries.next() {
+                Some((&count, &kmers)) => {
+                    seen += count as u128 * kmers as u128;
+                    edge = u64::max(edge, count.saturating_add(1));
+                }
+                None => break,
+            }
+        }
+        edges.push(edge);
+    }
+    edges
+}
+
+// bin of a count, edges are the lowest count of each bin
+pub fn bin_of(edges: &[u64], count: u64) -> usize {
+    edges.partition_point(|&edge| edge <= count) - 1
+}
+
+// count range of each bin, the last bin takes all higher counts
+pub fn bin_names(edges: &[u64]) -> Vec<String> {
+    edges
+        .iter()
+        .enumerate()
+        .map(|(bin, edge)| match edges.get(bin + 1) {
+            Some(next) => format!("{}-{}", edge, next - 1),
+            None => format!("{}+", edge),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bin_edges_test() {
+        let edges = linear_edges(2, 3);
+        assert_eq!(edges, vec![0, 2, 4]);
+        assert_eq!(bin_names(&edges), vec!["0-1", "2-3", "4+"]);
+        assert_eq!(
+            (0..7)
+                .map(|count| bin_of(&edges, count))
+                .collect::<Vec<_>>(),
+            vec![0, 0, 1, 1, 2, 2, 2]
+        );
+
+        let edges = log_edges(2, 5);
+        assert_eq!(edges, vec![0, 2, 4, 8, 16]);
+        assert_eq!(bin_of(&edges, 15), 3);
+        assert_eq!(bin_of(&edges, 1000), 4);
+
+        // 10 occurrences each of count 1, 5 and 10
+        let histogram = BTreeMap::from([(1, 10), (5, 2), (10, 1)]);
+        assert_eq!(quantile_edges(&histogram, 3), vec![0, 2, 6]);
+        assert_eq!(quantile_edges(&histogram, 5), vec![0, 2, 6, 7, 11]);
+        assert_eq!(quantile_edges(&BTreeMap::new(), 3), vec![0, 1, 2]);
+    }
+}
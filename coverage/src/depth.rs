@@ -0,0 +1,83 @@
+use counter::counts::CountsReader;
+use kmer::{kmer::GenericKmerGenerator, strand::Strand, KmerInt};
+
+// text form of the per-position k-mer depths of a record
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DepthFormat {
+    // <id>\t<start>\t<end>\t<depth>, 0-based half-open, equal depths merged into one interval
+    BedGraph,
+    // <id>\t<start>\t<depth> for every position or window
+    Tsv,
+}
+
+impl DepthFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            DepthFormat::BedGraph => "bedgraph",
+            DepthFormat::Tsv => "tsv",
+        }
+    }
+}
+
+// count of the k-mer starting at each position, 0 for k-mers with ambiguous bases
+pub fn position_counts<K: KmerInt>(
+    seq: &[u8],
+    ksize: usize,
+    strand: Strand,
+    counts: &CountsReader,
+) -> Vec<u64> {
+    let mut depths = vec![0; (seq.len() + 1).saturating_sub(ksize)];
+    let mut start = 0;
+    // k-mers are only generated within runs of ACGT, so their positions are consecutive
+    for end in 0..=seq.len() {
+        if end < seq.len() && b"ACGTacgt".contains(&seq[end]) {
+            continue;
+        }
+        for (pos, (fmer, rmer)) in
+            GenericKmerGenerator::<K>::new(&seq[start..end], ksize).enumerate()
+        {
+            depths[start + pos] = counts.get(strand.pick(fmer, rmer)).unwrap_or(0);
+        }
+        start = end + 1;
+    }
+    depths
+}
+
+// mean depth of each window of k-mer positions, the last window may be shorter
+pub fn window_means(depths: &[u64], window: usize) -> Vec<f64> {
+    depths
+        .chunks(usize::max(1, window))
+        .map(|chunk| chunk.iter().sum::<u64>() as f64 / chunk.len() as f64)
+        .collect()
+}
+
+// intervals of equal depth as (start, end, depth) over the len positions of the windows
+pub fn depth_runs(depths: &[f64], window: usize, len: usize) -> Vec<(usize, usize, f64)> {
+    let mut runs: Vec<(usize, usize, f64)> = Vec::new();
+    for (idx, &depth) in depths.iter().enumerate() {
+        let end = usize::min(len, (idx + 1) * window);
+        match runs.last_mut() {
+            Some(run) if run.2 == depth => run.1 = end,
+            _ => runs.push((idx * window, end, depth)),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_runs_test() {
+        let depths = [2, 2, 3, 0, 0, 4, 4];
+        let means = window_means(&depths, 1);
+        assert_eq!(
+            depth_runs(&means, 1, depths.len()),
+            vec![(0, 2, 2.0), (2, 3, 3.0), (3, 5, 0.0), (5, 7, 4.0)]
+        );
+        let means = window_means(&depths, 3);
+        assert_eq!(means, vec![7.0 / 3.0, 4.0 / 3.0, 4.0]);
+        assert_eq!(depth_runs(&means, 3, depths.len())[2], (6, 7, 4.0));
+    }
+}
@@ -1,27 +1,93 @@
-use counter::CountComputer;
-use kmer::{kmer::KmerGenerator, Kmer};
-use ktio::seq::{SeqFormat, Sequences};
+pub mod bins;
+pub mod depth;
+pub mod solid;
+pub mod summary;
+use bins::{bin_names, bin_of, linear_edges, log_edges, quantile_edges, BinScale};
+use counter::{
+    counts::CountsReader, spill::SpillCompression, CountComputer, CountOptions, CountsOutput,
+    KmerOptions, OutputOptions, PartitionOptions,
+};
+use depth::{depth_runs, position_counts, window_means, DepthFormat};
+use kmer::{
+    kmer::{compress_homopolymers, GenericKmerGenerator},
+    segments::{segments, SEGMENT_SIZE},
+    stats::KmerStats,
+    strand::Strand,
+    Kmer, KmerInt,
+};
+use ktio::{
+    filter::RecordFilter,
+    format::{IdPolicy, OutputCompression, OutputFormat},
+    matrix::MatrixWriter,
+    mmap::MMWriter,
+    seq::{mate_name, PairedSequences, SeqFormat, Sequence, Sequences},
+};
 use rayon::prelude::*;
+use solid::{solid_mask, MaskEncoding};
 use std::{
-    cmp::min,
-    collections::HashMap,
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt::Write as _,
     fs::{self, File},
-    io::{BufRead, BufReader, BufWriter, Write},
+    io::{BufWriter, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
 };
+use summary::{kmer_counts, CountSummary, SUMMARY_NAMES};
 
 const NUMBER_SIZE: usize = 8;
 
+// scaling of raw histograms so that samples sequenced to different depths compare
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleScale {
+    // k-mers per million k-mers of the sample, as counted in the counts table
+    Cpm,
+    // bins multiplied by a factor given for the sample
+    Factor(f64),
+}
+
+type Fragments = Box<dyn Iterator<Item = Result<Vec<Sequence>, String>> + Send>;
+
+// pairs are named after their reads without the mate suffix
+fn fragment_id(mates: &[Sequence]) -> &str {
+    match mates {
+        [mate, _] => mate_name(&mate.id),
+        _ => &mates[0].id,
+    }
+}
+
 pub struct CovComputer {
     in_path: String,
+    // second file of paired reads, in step with in_path
+    mate_path: Option<String>,
     in_path_kmer: String,
     out_dir: String,
     ksize: usize,
     threads: usize,
     norm: bool,
-    delim: String,
+    sample_scale: Option<SampleScale>,
+    format: OutputFormat,
     bin_size: usize,
     bin_count: usize,
     memory_ceil_gb: f64,
+    filter: Option<RecordFilter>,
+    record_stats: bool,
+    stats: Mutex<KmerStats>,
+    strand: Strand,
+    counts_file: Option<String>,
+    // counted k-mers are kept as the binary table instead of text kmers.counts
+    binary_counts: bool,
+    // the binary table was counted for the coverages only and goes with the computer
+    temp_table: AtomicBool,
+    compress_tmp: SpillCompression,
+    segment_size: usize,
+    hpc: bool,
+    with_lengths: bool,
+    bin_scale: BinScale,
+    // lowest count of each bin, once known
+    edges: Mutex<Vec<u64>>,
 }
 
 impl CovComputer {
@@ -34,15 +100,30 @@ impl CovComputer {
     ) -> Self {
         Self {
             in_path: in_path.clone(),
+            mate_path: None,
             in_path_kmer: in_path,
             out_dir,
             ksize,
-            threads: rayon::current_num_threads(),
+            threads: ktio::threads::default_threads(),
             norm: true,
-            delim: " ".to_owned(),
+            sample_scale: None,
+            format: OutputFormat::default(),
             bin_size,
             bin_count,
             memory_ceil_gb: 6_f64,
+            filter: None,
+            record_stats: false,
+            stats: Mutex::new(KmerStats::default()),
+            strand: Strand::Canonical,
+            counts_file: None,
+            binary_counts: false,
+            temp_table: AtomicBool::new(false),
+            compress_tmp: SpillCompression::None,
+            segment_size: SEGMENT_SIZE,
+            hpc: false,
+            with_lengths: false,
+            bin_scale: BinScale::Linear,
+            edges: Mutex::new(Vec::new()),
         }
     }
 
@@ -54,8 +135,44 @@ impl CovComputer {
         self.norm = norm;
     }
 
-    pub fn set_delim(&mut self, delim: String) {
-        self.delim = delim;
+    // raw histograms scaled for comparison across samples, in place of normalising
+    // each record by its own k-mers
+    pub fn set_sample_scale(&mut self, sample_scale: Option<SampleScale>) {
+        self.sample_scale = sample_scale;
+        if sample_scale.is_some() {
+            self.norm = false;
+        }
+    }
+
+    // factor the raw histograms of this sample are multiplied by
+    fn sample_factor(&self, counts: &CountsReader) -> f64 {
+        match self.sample_scale {
+            Some(SampleScale::Cpm) => {
+                let total: u64 = if self.ksize > Kmer::MAX_KSIZE {
+                    counts.iter::<u128>().map(|(_, count)| count).sum()
+                } else {
+                    counts.iter::<Kmer>().map(|(_, count)| count).sum()
+                };
+                1e6 / f64::max(1_f64, total as f64)
+            }
+            Some(SampleScale::Factor(factor)) => factor,
+            None => 1_f64,
+        }
+    }
+
+    // delimiter, precision of frequencies, header and ID columns of the output
+    pub fn set_format(&mut self, format: OutputFormat) {
+        self.format = format;
+    }
+
+    // record lengths as the first column of the vectors, after the ID when written
+    pub fn set_with_lengths(&mut self, with_lengths: bool) {
+        self.with_lengths = with_lengths;
+    }
+
+    // mates of paired reads are counted together and share one histogram per pair
+    pub fn set_mate_path(&mut self, path: Option<String>) {
+        self.mate_path = path;
     }
 
     pub fn set_kmer_path(&mut self, path: String) {
@@ -66,128 +183,880 @@ impl CovComputer {
         self.memory_ceil_gb = memory_ceil_gb;
     }
 
+    // only applies to the vectorised records, counts are always from all records
+    pub fn set_filter(&mut self, filter: Option<RecordFilter>) {
+        self.filter = filter;
+    }
+
+    // write k-mers and skipped k-mers of each record to kmers.vectors.stats
+    pub fn set_record_stats(&mut self, record_stats: bool) {
+        self.record_stats = record_stats;
+    }
+
+    // counts and vectors use the same strand
+    pub fn set_strand(&mut self, strand: Strand) {
+        self.strand = strand;
+    }
+
+    // codec of the temporary chunk files of k-mer counting
+    pub fn set_compress_tmp(&mut self, compression: SpillCompression) {
+        self.compress_tmp = compression;
+    }
+
+    // counts used in place of counting the k-mers again, a kmertools binary table
+    // or a kmertools, jellyfish or kmc text dump
+    pub fn set_counts_file(&mut self, path: Option<String>) {
+        self.counts_file = path;
+    }
+
+    // counted k-mers are written as text kmers.counts unless kept as kmers.counts.bin
+    pub fn set_binary_counts(&mut self, binary: bool) {
+        self.binary_counts = binary;
+    }
+
+    // binary table the coverages are computed from, text counts are imported into the output
+    fn counts_path(&self) -> String {
+        match &self.counts_file {
+            Some(path) if counter::counts::is_binary_counts(path) => path.clone(),
+            _ => format!("{}/kmers.counts.bin", self.out_dir),
+        }
+    }
+
+    // quantile bins are taken from the counts table, so are known once it is built
+    pub fn set_bin_scale(&mut self, bin_scale: BinScale) {
+        self.bin_scale = bin_scale;
+        self.edges.lock().unwrap().clear();
+    }
+
+    // abundance range of each bin, the last bin takes all higher counts
+    pub fn feature_names(&self) -> Vec<String> {
+        bin_names(&self.bin_edges())
+    }
+
+    // lowest count of each bin, quantile bins need the counts table
+    pub fn bin_edges(&self) -> Vec<u64> {
+        let mut edges = self.edges.lock().unwrap();
+        if edges.is_empty() {
+            *edges = match self.bin_scale {
+                BinScale::Linear => linear_edges(self.bin_size, self.bin_count),
+                BinScale::Log => log_edges(self.bin_size, self.bin_count),
+                BinScale::Quantile => quantile_edges(
+                    &self.count_histogram().expect("Counts table must be built"),
+                    self.bin_count,
+                ),
+            };
+        }
+        edges.clone()
+    }
+
+    // counts table the coverages are computed from, once built
+    pub fn open_counts(&self) -> Result<CountsReader, String> {
+        CountsReader::open(&self.counts_path())
+    }
+
+    // coverage histogram of one record, for feature matrices combining it with others
+    pub fn record_vector(&self, seq: &Sequence, counts: &CountsReader, edges: &[u64]) -> Vec<f64> {
+        self.vectorise_one(std::slice::from_ref(seq), counts, edges)
+    }
+
+    // number of distinct k-mers of each count in the counts table
+    fn count_histogram(&self) -> Result<BTreeMap<u64, u64>, String> {
+        let counts = CountsReader::open(&self.counts_path())?;
+        let mut histogram = BTreeMap::new();
+        if self.ksize > Kmer::MAX_KSIZE {
+            for (_, count) in counts.iter::<u128>() {
+                *histogram.entry(count).or_insert(0) += 1;
+            }
+        } else {
+            for (_, count) in counts.iter::<Kmer>() {
+                *histogram.entry(count).or_insert(0) += 1;
+            }
+        }
+        Ok(histogram)
+    }
+
+    // records longer than this are processed in parallel segments and recombined
+    pub fn set_segment_size(&mut self, size: usize) {
+        self.segment_size = usize::max(1, size);
+    }
+
+    // k-mers of homopolymer compressed reads, both when counting and when vectorising
+    pub fn set_hpc(&mut self, hpc: bool) {
+        self.hpc = hpc;
+    }
+
+    fn sequence<'a>(&self, seq: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.hpc {
+            Cow::Owned(compress_homopolymers(seq))
+        } else {
+            Cow::Borrowed(seq)
+        }
+    }
+
+    pub fn kmer_stats(&self) -> KmerStats {
+        *self.stats.lock().unwrap()
+    }
+
+    fn stats_writer(&self) -> Option<BufWriter<File>> {
+        if !self.record_stats {
+            return None;
+        }
+        let file = File::create(format!("{}/kmers.vectors.stats", self.out_dir)).unwrap();
+        let mut buff = BufWriter::new(file);
+        buff.write_all(b"id\tkmers\tskipped_kmers\n").unwrap();
+        Some(buff)
+    }
+
+    fn update_stats(&self, buffer: &[Vec<Sequence>], stats_buffer: &mut Option<BufWriter<File>>) {
+        for mates in buffer {
+            let mut stats = KmerStats::default();
+            for mate in mates {
+                stats += KmerStats::from_seq(&self.sequence(&mate.seq), self.ksize);
+            }
+            *self.stats.lock().unwrap() += stats;
+            if let Some(stats_buffer) = stats_buffer.as_mut() {
+                writeln!(
+                    stats_buffer,
+                    "{}\t{}\t{}",
+                    fragment_id(mates),
+                    stats.kmers,
+                    stats.skipped
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    // records, or the pairs of paired reads, each vectorised as one fragment
+    fn fragments(&self) -> Result<Fragments, String> {
+        let records = |path: &str| {
+            let format = SeqFormat::from_path(path)?;
+            Sequences::new(format, ktio::seq::get_reader(path)?)
+        };
+        match &self.mate_path {
+            Some(mate_path) => {
+                let mut pairs = PairedSequences::new(records(&self.in_path)?, records(mate_path)?);
+                pairs.set_filter(self.filter.clone());
+                Ok(Box::new(pairs.map(|pair| {
+                    pair.map(|(mate_1, mate_2)| vec![mate_1, mate_2])
+                })))
+            }
+            None => {
+                let mut records = records(&self.in_path)?;
+                records.set_filter(self.filter.clone());
+                Ok(Box::new(records.map(|record| Ok(vec![record]))))
+            }
+        }
+    }
+
     pub fn build_table(&self) -> Result<(), String> {
-        let mut ctr =
-            CountComputer::new(self.in_path_kmer.clone(), self.out_dir.clone(), self.ksize);
+        self.edges.lock().unwrap().clear();
+        if let Some(mate_path) = &self.mate_path {
+            match (SeqFormat::get(&self.in_path), SeqFormat::get(mate_path)) {
+                (Some(SeqFormat::Bam), _) | (_, Some(SeqFormat::Bam)) => {
+                    return Err("Mates must be given as FASTA or FASTQ files".to_string());
+                }
+                (Some(format_1), Some(format_2)) if format_1 == format_2 => {}
+                _ => {
+                    return Err(format!(
+                        "Mates must be files of the same format: {} {}",
+                        self.in_path, mate_path
+                    ));
+                }
+            }
+        }
+        if let Some(path) = &self.counts_file {
+            let kmer_path = self.counts_path();
+            if kmer_path == *path {
+                let counts = CountsReader::open(path)?;
+                if counts.ksize() != self.ksize {
+                    return Err(format!(
+                        "Counts are of {}-mers, expected {}-mers: {}",
+                        counts.ksize(),
+                        self.ksize,
+                        path
+                    ));
+                }
+            } else {
+                counter::import::import_counts(path, &kmer_path, self.ksize, self.strand)?;
+            }
+            return Ok(());
+        }
+        let mut ctr = match &self.mate_path {
+            // both mates are counted unless counts are of another input
+            Some(mate_path) if self.in_path_kmer == self.in_path => CountComputer::from_mates(
+                self.in_path.clone(),
+                mate_path.clone(),
+                self.out_dir.clone(),
+                self.ksize,
+            ),
+            _ => CountComputer::new(self.in_path_kmer.clone(), self.out_dir.clone(), self.ksize),
+        };
         ctr.set_threads(self.threads);
         ctr.set_max_memory(self.memory_ceil_gb);
+        ctr.set_options(CountOptions {
+            kmers: KmerOptions {
+                strand: self.strand,
+                hpc: self.hpc,
+                ..KmerOptions::default()
+            },
+            output: OutputOptions {
+                counts: CountsOutput::Binary,
+                ..OutputOptions::default()
+            },
+            partitions: PartitionOptions {
+                compress_tmp: self.compress_tmp,
+                ..PartitionOptions::default()
+            },
+        });
         ctr.count();
         ctr.merge(true);
+        if !self.binary_counts {
+            self.temp_table.store(true, Ordering::Relaxed);
+            self.write_text_counts()?;
+        }
         Ok(())
     }
 
-    pub fn compute_coverages(&self) {
-        let kmer_path = format!("{}/kmers.counts", self.out_dir);
-        let vec_path = format!("{}/kmers.vectors", self.out_dir);
-        let file = fs::File::open(kmer_path).unwrap();
-        let buff = BufReader::new(file);
-        let mut counts = HashMap::new();
+    // numeric k-mers and their counts as ctr writes them to kmers.counts
+    fn write_text_counts(&self) -> Result<(), String> {
+        let counts = CountsReader::open(&self.counts_path())?;
+        let text_path = format!("{}/kmers.counts", self.out_dir);
+        let file = File::create(&text_path)
+            .map_err(|_| format!("Unable to write to file: {}", text_path))?;
+        let mut buff = BufWriter::new(file);
+        let result = if counts.ksize() > Kmer::MAX_KSIZE {
+            counts
+                .iter::<u128>()
+                .try_for_each(|(kmer, count)| writeln!(buff, "{}\t{}", kmer, count))
+        } else {
+            counts
+                .iter::<Kmer>()
+                .try_for_each(|(kmer, count)| writeln!(buff, "{}\t{}", kmer, count))
+        };
+        result
+            .and_then(|_| buff.flush())
+            .map_err(|_| format!("Unable to write to file: {}", text_path))
+    }
 
-        for line in buff.lines().map_while(Result::ok) {
-            let mut parts = line.trim().split('\t');
-            let kmer: Kmer = parts.next().unwrap().parse().unwrap();
-            let count: u32 = parts.next().unwrap().parse().unwrap();
-            counts.insert(kmer, count);
+    pub fn compute_coverages(&self) -> Result<(), String> {
+        let kmer_path = self.counts_path();
+        let vec_path = format!("{}/kmers.vectors", self.out_dir);
+        if self.format.sparse.is_some() && (self.format.header || self.format.matrix.is_some()) {
+            return Err("Sparse vectors are written as text rows without a header".to_string());
+        }
+        // counts are memory mapped and searched in place by all workers
+        let counts = CountsReader::open(&kmer_path)?;
+        let edges = self.bin_edges();
+        let factor = self.sample_factor(&counts);
+        *self.stats.lock().unwrap() = KmerStats::default();
+        if self.fixed_width_rows() {
+            self.compute_coverages_mmap(&vec_path, &counts, &edges);
+            return Ok(());
         }
 
-        let reader = ktio::seq::get_reader(&self.in_path).unwrap();
-        let format = SeqFormat::get(&self.in_path).unwrap();
-        let records = Sequences::new(format, reader).unwrap();
-        let file = File::create(vec_path).unwrap();
-        let mut out_buffer = BufWriter::new(file);
+        let fragments = self.fragments()?;
+        let mut names = bin_names(&edges);
+        if self.with_lengths {
+            names.insert(0, "length".to_string());
+        }
+        // binary matrices are written in place of kmers.vectors
+        let mut matrix = self
+            .format
+            .matrix
+            .map(|matrix| {
+                let path = format!("{}.{}", vec_path, matrix.extension());
+                MatrixWriter::new(&path, matrix, &names, self.format.ids == IdPolicy::First)
+            })
+            .transpose()?;
+        let mut out_buffer = match matrix {
+            Some(_) => None,
+            None => Some(self.format.writer(&vec_path).unwrap()),
+        };
+        if let (Some(out_buffer), Some(header)) =
+            (out_buffer.as_mut(), self.format.header_row(&names))
+        {
+            out_buffer.write_all(header.as_bytes()).unwrap();
+        }
+        let mut stats_buffer = self.stats_writer();
         let pool = rayon::ThreadPoolBuilder::new()
             .num_threads(self.threads)
             .build()
             .unwrap();
 
+        let mut write_buffer = |buffer: &[Vec<Sequence>]| {
+            if let Some(matrix) = matrix.as_mut() {
+                let rows = buffer
+                    .par_iter()
+                    .map(|mates| self.matrix_row(mates, &counts, &edges, factor))
+                    .collect::<Vec<Vec<f64>>>();
+                for (mates, row) in buffer.iter().zip(rows) {
+                    matrix.write_row(fragment_id(mates), &row).unwrap();
+                }
+            } else if let Some(out_buffer) = out_buffer.as_mut() {
+                let result = buffer
+                    .par_iter()
+                    .map(|mates| self.vector_row(mates, &counts, &edges, factor))
+                    .collect::<Vec<String>>()
+                    .join("");
+                out_buffer.write_all(result.as_bytes()).unwrap();
+            }
+            self.update_stats(buffer, &mut stats_buffer);
+        };
+
         pool.install(|| {
             rayon::scope(|_| {
                 let mut buffer = Vec::with_capacity(1000);
                 let mut total = 0_usize;
 
-                for record in records {
-                    total += record.seq.len();
-                    buffer.push(record);
+                for mates in fragments {
+                    let mates = mates?;
+                    total += mates.iter().map(|mate| mate.seq.len()).sum::<usize>();
+                    buffer.push(mates);
 
                     if total as u64 >= self.memory_ceil_gb as u64 * (1 << 30) {
-                        let result = buffer
-                            .par_iter()
-                            .map(|seq| {
-                                let kvec = self.vectorise_one(&seq.seq, &counts);
-                                // optimise this with pre-sized string
-                                let kvec_str: Vec<String> = kvec
-                                    .iter()
-                                    .map(|val| {
-                                        if self.norm {
-                                            format!("{:.*}", NUMBER_SIZE - 2, val)
-                                        } else {
-                                            format!("{}", val)
-                                        }
-                                    })
-                                    .collect();
-                                format!("{}\n", kvec_str.join(&self.delim))
-                            })
-                            .collect::<Vec<String>>()
-                            .join("");
-                        out_buffer.write_all(result.as_bytes()).unwrap();
+                        write_buffer(&buffer);
                         buffer.clear();
                         total = 0;
                     }
                 }
 
                 if total > 0 {
-                    // optimise this with pre-sized string
-                    let result = buffer
-                        .par_iter()
-                        .map(|seq| {
-                            let kvec = self.vectorise_one(&seq.seq, &counts);
-                            let kvec_str: Vec<String> = kvec
-                                .iter()
-                                .map(|val| {
-                                    if self.norm {
-                                        format!("{:.*}", NUMBER_SIZE - 2, val)
-                                    } else {
-                                        format!("{}", val)
-                                    }
-                                })
-                                .collect();
-                            format!("{}\n", kvec_str.join(&self.delim))
-                        })
-                        .collect::<Vec<String>>()
-                        .join("");
-                    out_buffer.write_all(result.as_bytes()).unwrap();
+                    write_buffer(&buffer);
                     buffer.clear();
                 }
-            });
+                Ok::<(), String>(())
+            })
+        })?;
+        if let Some(matrix) = matrix {
+            matrix.finish()?;
+        }
+        Ok(())
+    }
+
+    // normalised coverages are at most 1, so rows of plain text all have the same width
+    fn fixed_width_rows(&self) -> bool {
+        self.norm
+            && !self.with_lengths
+            && self.format.ids == IdPolicy::Omit
+            && self.format.matrix.is_none()
+            && self.format.compression == OutputCompression::None
+            && self.mate_path.is_none()
+            && self.format.sparse.is_none()
+    }
+
+    // rows are written by the workers straight to their offsets in the memory mapped output
+    fn compute_coverages_mmap(&self, vec_path: &str, counts: &CountsReader, edges: &[u64]) {
+        let precision = self.format.precision.unwrap_or(NUMBER_SIZE - 2);
+        let row_size =
+            edges.len() * (precision + 2) + (edges.len() - 1) * self.format.delim.len() + 1;
+        let header = self
+            .format
+            .header_row(&bin_names(edges))
+            .unwrap_or_default();
+        let seq_count = {
+            let format = SeqFormat::get(&self.in_path).unwrap();
+            let reader = ktio::seq::get_reader(&self.in_path).unwrap();
+            Sequences::seq_stats_filtered(format, reader, self.filter.as_ref()).seq_count
+        };
+        let mut mmap =
+            ktio::mmap::mmap_file_for_writing(vec_path, header.len() + seq_count * row_size)
+                .unwrap();
+        let format = SeqFormat::get(&self.in_path).unwrap();
+        let reader = ktio::seq::get_reader(&self.in_path).unwrap();
+        let mut records = Sequences::new(format, reader).unwrap();
+        records.set_filter(self.filter.clone());
+        let records = Mutex::new(records);
+        // records finish out of order, stats are sorted before writing
+        let record_stats: Mutex<Vec<(usize, String, KmerStats)>> = Mutex::new(Vec::new());
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+
+        pool.scope(|scope| {
+            let mm_slice: MMWriter<u8> = MMWriter::new(&mut mmap[..]);
+            unsafe {
+                mm_slice.write_at(header.as_bytes(), 0);
+            }
+            for _ in 0..self.threads {
+                let records = &records;
+                let record_stats = &record_stats;
+                let header_len = header.len();
+                scope.spawn(move |_| {
+                    // one row buffer per worker, reused for every record
+                    let mut row = String::with_capacity(row_size);
+                    loop {
+                        let Some(record) = records.lock().unwrap().next() else {
+                            break;
+                        };
+                        let kvec = self.vectorise_one(std::slice::from_ref(&record), counts, edges);
+                        let stats = KmerStats::from_seq(&self.sequence(&record.seq), self.ksize);
+                        *self.stats.lock().unwrap() += stats;
+                        if self.record_stats {
+                            record_stats
+                                .lock()
+                                .unwrap()
+                                .push((record.n, record.id, stats));
+                        }
+                        row.clear();
+                        for (idx, val) in kvec.iter().enumerate() {
+                            if idx > 0 {
+                                row.push_str(&self.format.delim);
+                            }
+                            write!(row, "{:.*}", precision, val).unwrap();
+                        }
+                        row.push('\n');
+                        debug_assert_eq!(row.len(), row_size);
+                        unsafe {
+                            mm_slice.write_at(row.as_bytes(), header_len + record.n * row_size);
+                        }
+                    }
+                });
+            }
         });
+
+        if let Some(mut stats_buffer) = self.stats_writer() {
+            let mut record_stats = record_stats.into_inner().unwrap();
+            record_stats.sort_by_key(|(n, _, _)| *n);
+            for (_, id, stats) in record_stats {
+                writeln!(stats_buffer, "{}\t{}\t{}", id, stats.kmers, stats.skipped).unwrap();
+            }
+        }
+    }
+
+    // <id>\t<mask> in kmers.solid, marking k-mer positions counted at least min_count times
+    pub fn compute_solid_masks(
+        &self,
+        min_count: u64,
+        encoding: MaskEncoding,
+    ) -> Result<(), String> {
+        let kmer_path = self.counts_path();
+        let mask_path = format!("{}/kmers.solid", self.out_dir);
+        if self.hpc {
+            return Err("Solid masks are of read positions, not of compressed reads".to_string());
+        }
+        let counts = CountsReader::open(&kmer_path)?;
+        let format = SeqFormat::from_path(&self.in_path)?;
+        let mut records = Sequences::new(format, ktio::seq::get_reader(&self.in_path)?)?;
+        records.set_filter(self.filter.clone());
+        let file = File::create(&mask_path)
+            .map_err(|_| format!("Unable to write to file: {}", mask_path))?;
+        let mut out_buffer = BufWriter::new(file);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let result: String = pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|seq| {
+                        let mask = self.solid_mask(&seq.seq, min_count, &counts);
+                        format!("{}\t{}\n", seq.id, encoding.encode(&mask))
+                    })
+                    .collect()
+            });
+            out_buffer
+                .write_all(result.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", mask_path))?;
+        }
+
+        Ok(())
+    }
+
+    // k-mer count at each position of every record, or its mean over windows of positions,
+    // in kmers.depth.bedgraph or kmers.depth.tsv; positions are k-mer starts
+    pub fn compute_depths(&self, window: usize, format: DepthFormat) -> Result<(), String> {
+        let kmer_path = self.counts_path();
+        let depth_path = format!("{}/kmers.depth.{}", self.out_dir, format.extension());
+        if self.hpc {
+            return Err("Depths are of read positions, not of compressed reads".to_string());
+        }
+        let window = usize::max(1, window);
+        let counts = CountsReader::open(&kmer_path)?;
+        let seq_format = SeqFormat::from_path(&self.in_path)?;
+        let mut records = Sequences::new(seq_format, ktio::seq::get_reader(&self.in_path)?)?;
+        records.set_filter(self.filter.clone());
+        let file = File::create(&depth_path)
+            .map_err(|_| format!("Unable to write to file: {}", depth_path))?;
+        let mut out_buffer = BufWriter::new(file);
+        if format == DepthFormat::Tsv {
+            out_buffer
+                .write_all(b"id\tstart\tdepth\n")
+                .map_err(|_| format!("Unable to write to file: {}", depth_path))?;
+        }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let result: String = pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|seq| {
+                        let depths = self.position_counts(&seq.seq, &counts);
+                        let means = window_means(&depths, window);
+                        let number = |val: f64| {
+                            if window == 1 {
+                                format!("{}", val)
+                            } else {
+                                self.format.number(val, Some(NUMBER_SIZE - 2))
+                            }
+                        };
+                        let mut rows = String::new();
+                        match format {
+                            DepthFormat::BedGraph => {
+                                for (start, end, depth) in depth_runs(&means, window, depths.len())
+                                {
+                                    writeln!(
+                                        rows,
+                                        "{}\t{}\t{}\t{}",
+                                        seq.id,
+                                        start,
+                                        end,
+                                        number(depth)
+                                    )
+                                    .unwrap();
+                                }
+                            }
+                            DepthFormat::Tsv => {
+                                for (idx, depth) in means.into_iter().enumerate() {
+                                    writeln!(
+                                        rows,
+                                        "{}\t{}\t{}",
+                                        seq.id,
+                                        idx * window,
+                                        number(depth)
+                                    )
+                                    .unwrap();
+                                }
+                            }
+                        }
+                        rows
+                    })
+                    .collect()
+            });
+            out_buffer
+                .write_all(result.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", depth_path))?;
+        }
+
+        Ok(())
+    }
+
+    // kmers.summary with the mean, median, min and max k-mer count of each record
+    // and the fraction of its k-mers counted at least min_count times
+    pub fn compute_summaries(&self, min_count: u64) -> Result<(), String> {
+        let kmer_path = self.counts_path();
+        let summary_path = format!("{}/kmers.summary", self.out_dir);
+        let counts = CountsReader::open(&kmer_path)?;
+        let format = SeqFormat::from_path(&self.in_path)?;
+        let mut records = Sequences::new(format, ktio::seq::get_reader(&self.in_path)?)?;
+        records.set_filter(self.filter.clone());
+        let file = File::create(&summary_path)
+            .map_err(|_| format!("Unable to write to file: {}", summary_path))?;
+        let mut out_buffer = BufWriter::new(file);
+        let names: Vec<String> = SUMMARY_NAMES.iter().map(|name| name.to_string()).collect();
+        if let Some(header) = self.format.header_row(&names) {
+            out_buffer
+                .write_all(header.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", summary_path))?;
+        }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let result: String = pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|seq| {
+                        let summary = CountSummary::from_counts(
+                            &mut self.record_counts(&seq.seq, &counts),
+                            min_count,
+                        );
+                        let number = |val| self.format.number(val, Some(NUMBER_SIZE - 2));
+                        let fields = vec![
+                            number(summary.mean),
+                            number(summary.median),
+                            summary.min.to_string(),
+                            summary.max.to_string(),
+                            number(summary.solid),
+                        ];
+                        self.format.row(&seq.id, &fields)
+                    })
+                    .collect()
+            });
+            out_buffer
+                .write_all(result.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", summary_path))?;
+        }
+
+        Ok(())
+    }
+
+    // reads whose median k-mer count is within min_median..=max_median are written to
+    // filtered.fq (filtered.fa for FASTA and BAM), returns the reads kept and seen
+    pub fn filter_reads(&self, min_median: f64, max_median: f64) -> Result<(u64, u64), String> {
+        let kmer_path = self.counts_path();
+        let counts = CountsReader::open(&kmer_path)?;
+        let format = SeqFormat::from_path(&self.in_path)?;
+        let mut records = Sequences::new(format, ktio::seq::get_reader(&self.in_path)?)?;
+        records.set_filter(self.filter.clone());
+        records.set_qualities(true);
+        let out_path = match format {
+            SeqFormat::Fastq => format!("{}/filtered.fq", self.out_dir),
+            _ => format!("{}/filtered.fa", self.out_dir),
+        };
+        let file = File::create(&out_path)
+            .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+        let mut out_buffer = BufWriter::new(file);
+        let (mut kept, mut seen) = (0, 0);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .unwrap();
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let result: Vec<String> = pool.install(|| {
+                batch
+                    .par_iter()
+                    .filter(|seq| {
+                        let median = CountSummary::from_counts(
+                            &mut self.record_counts(&seq.seq, &counts),
+                            1,
+                        )
+                        .median;
+                        (min_median..=max_median).contains(&median)
+                    })
+                    .map(Sequence::to_record)
+                    .collect()
+            });
+            seen += batch.len() as u64;
+            kept += result.len() as u64;
+            out_buffer
+                .write_all(result.concat().as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+        }
+
+        Ok((kept, seen))
+    }
+
+    // counts of the k-mers of a record, segments of long records are counted in parallel
+    fn record_counts(&self, seq: &[u8], counts: &CountsReader) -> Vec<u64> {
+        let seq = &self.sequence(seq);
+        let kmers = |seq: &[u8]| {
+            if self.ksize > Kmer::MAX_KSIZE {
+                kmer_counts::<u128>(seq, self.ksize, self.strand, counts)
+            } else {
+                kmer_counts::<Kmer>(seq, self.ksize, self.strand, counts)
+            }
+        };
+        if seq.len() <= self.segment_size {
+            return kmers(seq);
+        }
+        segments(seq.len(), self.ksize, self.segment_size)
+            .par_iter()
+            .flat_map_iter(|&(start, end)| kmers(&seq[start..end]))
+            .collect()
+    }
+
+    // depths of the segments of long records are stitched in order
+    fn position_counts(&self, seq: &[u8], counts: &CountsReader) -> Vec<u64> {
+        let depths = |seq: &[u8]| {
+            if self.ksize > Kmer::MAX_KSIZE {
+                position_counts::<u128>(seq, self.ksize, self.strand, counts)
+            } else {
+                position_counts::<Kmer>(seq, self.ksize, self.strand, counts)
+            }
+        };
+        if seq.len() <= self.segment_size {
+            return depths(seq);
+        }
+        segments(seq.len(), self.ksize, self.segment_size)
+            .par_iter()
+            .flat_map_iter(|&(start, end)| depths(&seq[start..end]))
+            .collect()
+    }
+
+    // masks of the segments of long records are stitched in order
+    fn solid_mask(&self, seq: &[u8], min_count: u64, counts: &CountsReader) -> Vec<bool> {
+        let mask = |seq: &[u8]| {
+            if self.ksize > Kmer::MAX_KSIZE {
+                solid_mask::<u128>(seq, self.ksize, self.strand, min_count, counts)
+            } else {
+                solid_mask::<Kmer>(seq, self.ksize, self.strand, min_count, counts)
+            }
+        };
+        if seq.len() <= self.segment_size {
+            return mask(seq);
+        }
+        segments(seq.len(), self.ksize, self.segment_size)
+            .par_iter()
+            .flat_map_iter(|&(start, end)| mask(&seq[start..end]))
+            .collect()
     }
 
-    fn vectorise_one(&self, seq: &[u8], counts: &HashMap<u64, u32>) -> Vec<f64> {
+    // factor scales raw histograms, it is 1 for normalised ones
+    fn matrix_row(
+        &self,
+        mates: &[Sequence],
+        counts: &CountsReader,
+        edges: &[u64],
+        factor: f64,
+    ) -> Vec<f64> {
+        let mut kvec = self.vectorise_one(mates, counts, edges);
+        kvec.iter_mut().for_each(|el| *el *= factor);
+        if self.with_lengths {
+            kvec.insert(
+                0,
+                mates.iter().map(|mate| mate.seq.len()).sum::<usize>() as f64,
+            );
+        }
+        kvec
+    }
+
+    fn vector_row(
+        &self,
+        mates: &[Sequence],
+        counts: &CountsReader,
+        edges: &[u64],
+        factor: f64,
+    ) -> String {
+        let mut kvec = self.vectorise_one(mates, counts, edges);
+        kvec.iter_mut().for_each(|el| *el *= factor);
+        // optimise this with pre-sized string
+        let mut kvec_str: Vec<String> = Vec::with_capacity(kvec.len() + 1);
+        if self.with_lengths {
+            kvec_str.push(
+                mates
+                    .iter()
+                    .map(|mate| mate.seq.len())
+                    .sum::<usize>()
+                    .to_string(),
+            );
+        }
+        kvec_str.extend(kvec.iter().map(|val| {
+            if self.norm || self.sample_scale.is_some() {
+                self.format.number(*val, Some(NUMBER_SIZE - 2))
+            } else {
+                format!("{}", val)
+            }
+        }));
+        if self.format.sparse.is_some() {
+            // the length column, when written, is never dropped
+            let offset = kvec_str.len() - kvec.len();
+            let pairs: Vec<(usize, String)> = kvec_str
+                .into_iter()
+                .enumerate()
+                .filter(|(col, _)| *col < offset || kvec[col - offset] != 0.0)
+                .collect();
+            return self.format.sparse_row(fragment_id(mates), &pairs);
+        }
+        self.format.row(fragment_id(mates), &kvec_str)
+    }
+
+    // k-mers of the mates of a pair add up to one histogram, normalised by their total
+    fn vectorise_one(&self, mates: &[Sequence], counts: &CountsReader, edges: &[u64]) -> Vec<f64> {
         let mut vec = vec![0_f64; self.bin_count];
         let mut total = 0_f64;
+        for mate in mates {
+            let seq = &self.sequence(&mate.seq);
+            // k-mers longer than 32 bases need 128 bits
+            let (mate_vec, mate_total) = if self.ksize > Kmer::MAX_KSIZE {
+                self.vectorise_kmers::<u128>(seq, counts, edges)
+            } else {
+                self.vectorise_kmers::<Kmer>(seq, counts, edges)
+            };
+            vec.iter_mut()
+                .zip(mate_vec)
+                .for_each(|(el, val)| *el += val);
+            total += mate_total;
+        }
+        if self.norm {
+            vec.iter_mut().for_each(|el| *el /= f64::max(1_f64, total));
+        }
+        vec
+    }
+
+    fn vectorise_kmers<K: KmerInt>(
+        &self,
+        seq: &[u8],
+        counts: &CountsReader,
+        edges: &[u64],
+    ) -> (Vec<f64>, f64) {
+        if seq.len() > self.segment_size {
+            segments(seq.len(), self.ksize, self.segment_size)
+                .par_iter()
+                .map(|&(start, end)| self.bin_kmers::<K>(&seq[start..end], counts, edges))
+                .reduce(
+                    || (vec![0_f64; self.bin_count], 0_f64),
+                    |(mut vec, total), (other, other_total)| {
+                        vec.iter_mut().zip(other).for_each(|(el, val)| *el += val);
+                        (vec, total + other_total)
+                    },
+                )
+        } else {
+            self.bin_kmers::<K>(seq, counts, edges)
+        }
+    }
 
-        for (fmer, rmer) in KmerGenerator::new(seq, self.ksize) {
-            let min_mer = u64::min(fmer, rmer);
-            let count = *counts.get(&min_mer).unwrap_or(&0);
-            let kmer_bin = (count as f64 / self.bin_size as f64).floor() as usize;
-            let vec_bin = min(kmer_bin, self.bin_count - 1);
+    // coverage histogram of the k-mers of a sequence and their total
+    fn bin_kmers<K: KmerInt>(
+        &self,
+        seq: &[u8],
+        counts: &CountsReader,
+        edges: &[u64],
+    ) -> (Vec<f64>, f64) {
+        let mut vec = vec![0_f64; self.bin_count];
+        let mut total = 0_f64;
+
+        for (fmer, rmer) in GenericKmerGenerator::<K>::new(seq, self.ksize) {
+            let min_mer = self.strand.pick(fmer, rmer);
+            let count = counts.get(min_mer).unwrap_or(0);
+            let vec_bin = bin_of(edges, count);
             unsafe {
                 // we already know the size of the vector and
                 *vec.get_unchecked_mut(vec_bin) += 1_f64;
                 total += 1_f64;
             }
         }
-        if self.norm {
-            vec.iter_mut().for_each(|el| *el /= f64::max(1_f64, total));
+        (vec, total)
+    }
+}
+
+impl Drop for CovComputer {
+    fn drop(&mut self) {
+        if *self.temp_table.get_mut() {
+            let _ = fs::remove_file(self.counts_path());
         }
-        vec
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ktio::fops::create_directory;
+    use ktio::{fops::create_directory, format::SparseFormat};
     use std::fs;
 
     const PATH_FQ: &str = "../test_data/reads.fq";
@@ -204,7 +1073,7 @@ mod tests {
         );
         cov.build_table().unwrap();
         cov.memory_ceil_gb = 0.1;
-        cov.compute_coverages();
+        cov.compute_coverages().unwrap();
 
         assert_eq!(
             fs::read("../test_data/expected_counts.vectors").unwrap(),
@@ -212,7 +1081,7 @@ mod tests {
         );
 
         cov.memory_ceil_gb = 1.0;
-        cov.compute_coverages();
+        cov.compute_coverages().unwrap();
 
         assert_eq!(
             fs::read("../test_data/expected_counts.vectors").unwrap(),
@@ -220,6 +1089,145 @@ mod tests {
         );
     }
 
+    #[test]
+    fn kmer_count_vecs_stats_test() {
+        let out_dir = "../test_data/computed_coverages_stats";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.build_table().unwrap();
+        cov.set_record_stats(true);
+        cov.compute_coverages().unwrap();
+        let stats = fs::read_to_string(format!("{}/kmers.vectors.stats", out_dir)).unwrap();
+        assert_eq!(stats.lines().count(), 3);
+        assert!(stats.starts_with("id\tkmers\tskipped_kmers\n"));
+        assert_eq!(cov.feature_names(), vec!["0-1", "2-3", "4+"]);
+        assert_eq!(
+            fs::read("../test_data/expected_counts.vectors").unwrap(),
+            fs::read(format!("{}/kmers.vectors", out_dir)).unwrap()
+        );
+
+        // fixed width rows follow the header in the memory mapped output
+        cov.set_format(OutputFormat {
+            header: true,
+            ..OutputFormat::default()
+        });
+        cov.compute_coverages().unwrap();
+        assert_eq!(
+            fs::read_to_string(format!("{}/kmers.vectors", out_dir)).unwrap(),
+            format!(
+                "0-1 2-3 4+\n{}",
+                fs::read_to_string("../test_data/expected_counts.vectors").unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn kmer_count_vecs_mmap_test() {
+        let out_dir = "../test_data/computed_coverage_mmap";
+        let in_path = "../test_data/computed_coverage_mmap.fa";
+        create_directory(out_dir).expect("Directory must be creatable");
+        // records of varying length, so rows differ and workers finish out of order
+        let seq = "GGGTGATGGCCGCTGCCGATGGCGTCAAATCCCACCAAGTTACCCTTAACAACTTAAGGGTTTTCAAATAGA";
+        let records: String = (0..40)
+            .map(|idx| format!(">Record_{}\n{}\n", idx, &seq[idx % 7..seq.len() - idx]))
+            .collect();
+        fs::write(in_path, records).unwrap();
+        let mut cov = CovComputer::new(in_path.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.set_threads(4);
+        cov.set_record_stats(true);
+        cov.build_table().unwrap();
+
+        // rows with IDs are written in order by the buffered writer
+        cov.set_format(OutputFormat {
+            ids: IdPolicy::First,
+            ..OutputFormat::default()
+        });
+        assert!(!cov.fixed_width_rows());
+        cov.compute_coverages().unwrap();
+        let buffered = fs::read_to_string(format!("{}/kmers.vectors", out_dir)).unwrap();
+        let buffered_stats =
+            fs::read_to_string(format!("{}/kmers.vectors.stats", out_dir)).unwrap();
+
+        cov.set_format(OutputFormat::default());
+        assert!(cov.fixed_width_rows());
+        cov.compute_coverages().unwrap();
+        let mmapped = fs::read_to_string(format!("{}/kmers.vectors", out_dir)).unwrap();
+        let expected: Vec<&str> = buffered
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .collect();
+        assert_eq!(mmapped.lines().collect::<Vec<_>>(), expected);
+        let width = mmapped.lines().next().unwrap().len();
+        assert!(mmapped.lines().all(|line| line.len() == width));
+        assert_eq!(
+            fs::read_to_string(format!("{}/kmers.vectors.stats", out_dir)).unwrap(),
+            buffered_stats
+        );
+
+        // unnormalised counts vary in width
+        cov.set_norm(false);
+        assert!(!cov.fixed_width_rows());
+    }
+
+    #[test]
+    fn kmer_count_vecs_wide_test() {
+        create_directory("../test_data/computed_coverage_wide")
+            .expect("Directory must be creatable");
+        let mut cov = CovComputer::new(
+            PATH_FQ.to_owned(),
+            "../test_data/computed_coverage_wide".to_owned(),
+            40,
+            1,
+            3,
+        );
+        cov.set_norm(false);
+        cov.build_table().unwrap();
+        cov.compute_coverages().unwrap();
+        // every 40-mer of the two reads is seen once or twice
+        let vectors =
+            fs::read_to_string("../test_data/computed_coverage_wide/kmers.vectors").unwrap();
+        let rows: Vec<Vec<f64>> = vectors
+            .lines()
+            .map(|line| line.split(' ').map(|v| v.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        for row in rows {
+            assert_eq!(row[0], 0.0);
+            assert_eq!(row.iter().sum::<f64>(), 33.0);
+        }
+    }
+
+    #[test]
+    fn solid_masks_test() {
+        let out_dir = "../test_data/computed_coverage_solid";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15, 1, 2);
+        cov.build_table().unwrap();
+        // every k-mer of the reads is counted at least once
+        cov.compute_solid_masks(1, MaskEncoding::Rle).unwrap();
+        let masks = fs::read_to_string(format!("{}/kmers.solid", out_dir)).unwrap();
+        assert_eq!(masks.lines().count(), 2);
+        for line in masks.lines() {
+            let (_, mask) = line.split_once('\t').unwrap();
+            assert!(mask.ends_with('S') && !mask.contains('W'));
+        }
+
+        cov.compute_solid_masks(u64::MAX, MaskEncoding::Hex)
+            .unwrap();
+        let masks = fs::read_to_string(format!("{}/kmers.solid", out_dir)).unwrap();
+        for line in masks.lines() {
+            let (_, mask) = line.split_once('\t').unwrap();
+            assert!(mask.chars().all(|c| c == '0'));
+        }
+
+        cov.compute_solid_masks(2, MaskEncoding::Rle).unwrap();
+        let whole = fs::read_to_string(format!("{}/kmers.solid", out_dir)).unwrap();
+        cov.set_segment_size(10);
+        cov.compute_solid_masks(2, MaskEncoding::Rle).unwrap();
+        let stitched = fs::read_to_string(format!("{}/kmers.solid", out_dir)).unwrap();
+        assert_eq!(whole, stitched);
+    }
+
     #[test]
     fn kmer_count_vecs_unnorm_test() {
         create_directory("../test_data/computed_coverage_unnorm")
@@ -233,11 +1241,343 @@ mod tests {
         );
         cov.set_norm(false);
         cov.build_table().unwrap();
-        cov.compute_coverages();
+        cov.compute_coverages().unwrap();
 
         assert_eq!(
             fs::read("../test_data/expected_counts_unnorm.vectors").unwrap(),
             fs::read("../test_data/computed_coverage_unnorm/kmers.vectors").unwrap()
         );
     }
+
+    #[test]
+    fn kmer_count_vecs_segments_test() {
+        let out_dir = "../test_data/computed_coverage_segments";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.set_norm(false);
+        cov.build_table().unwrap();
+        // reads split into segments give the same histograms
+        cov.set_segment_size(7);
+        cov.compute_coverages().unwrap();
+        assert_eq!(
+            fs::read("../test_data/expected_counts_unnorm.vectors").unwrap(),
+            fs::read(format!("{}/kmers.vectors", out_dir)).unwrap()
+        );
+
+        let mut format = OutputFormat::new("\t");
+        format.header = true;
+        format.ids = IdPolicy::First;
+        cov.set_format(format);
+        cov.set_with_lengths(true);
+        cov.compute_coverages().unwrap();
+        assert_eq!(
+            fs::read_to_string(format!("{}/kmers.vectors", out_dir)).unwrap(),
+            "id\tlength\t0-1\t2-3\t4+\nRead_1\t72\t22\t44\t3\nRead_2\t72\t13\t55\t1\n"
+        );
+    }
+
+    #[test]
+    fn kmer_count_vecs_ids_lengths_test() {
+        let out_dir = "../test_data/computed_coverage_ids_lengths";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.set_norm(false);
+        cov.build_table().unwrap();
+        let vectors = || fs::read_to_string(format!("{}/kmers.vectors", out_dir)).unwrap();
+
+        cov.set_with_lengths(true);
+        cov.compute_coverages().unwrap();
+        assert_eq!(vectors(), "72 22 44 3\n72 13 55 1\n");
+
+        let mut format = OutputFormat::new("\t");
+        format.ids = IdPolicy::First;
+        cov.set_format(format.clone());
+        cov.set_with_lengths(false);
+        cov.compute_coverages().unwrap();
+        assert_eq!(vectors(), "Read_1\t22\t44\t3\nRead_2\t13\t55\t1\n");
+
+        // the length column is kept with normalised vectors, after the ID
+        format.header = true;
+        cov.set_format(format);
+        cov.set_with_lengths(true);
+        cov.set_norm(true);
+        cov.compute_coverages().unwrap();
+        let rows = vectors();
+        let mut lines = rows.lines();
+        assert_eq!(lines.next(), Some("id\tlength\t0-1\t2-3\t4+"));
+        for (line, id) in lines.zip(["Read_1", "Read_2"]) {
+            let cols: Vec<&str> = line.split('\t').collect();
+            assert_eq!(cols.len(), 5);
+            assert_eq!(cols[..2], [id, "72"]);
+        }
+    }
+
+    #[test]
+    fn paired_vecs_test() {
+        let out_dir = "../test_data/computed_coverage_paired";
+        create_directory(out_dir).expect("Directory must be creatable");
+        // the two reads of reads.fq as mates of one pair
+        let records: Vec<Sequence> =
+            Sequences::new(SeqFormat::Fastq, ktio::seq::get_reader(PATH_FQ).unwrap())
+                .unwrap()
+                .collect();
+        let mates: Vec<String> = records
+            .iter()
+            .enumerate()
+            .map(|(n, record)| {
+                let path = format!("{}/mates_{}.fq", out_dir, n + 1);
+                let qual = "I".repeat(record.seq.len());
+                let seq = String::from_utf8_lossy(&record.seq);
+                fs::write(&path, format!("@Pair/{}\n{}\n+\n{}\n", n + 1, seq, qual)).unwrap();
+                path
+            })
+            .collect();
+        let mut cov = CovComputer::new(mates[0].clone(), out_dir.to_owned(), 4, 2, 3);
+        cov.set_mate_path(Some(mates[1].clone()));
+        cov.set_norm(false);
+        let mut format = OutputFormat::new("\t");
+        format.ids = IdPolicy::First;
+        cov.set_format(format);
+        cov.set_with_lengths(true);
+        cov.build_table().unwrap();
+        cov.compute_coverages().unwrap();
+        // both mates add to the histogram of the pair
+        assert_eq!(
+            fs::read_to_string(format!("{}/kmers.vectors", out_dir)).unwrap(),
+            "Pair\t144\t35\t99\t4\n"
+        );
+
+        cov.set_mate_path(Some(PATH_FQ.to_owned()));
+        cov.build_table().unwrap();
+        assert!(cov.compute_coverages().is_err());
+        cov.set_mate_path(Some("../test_data/reads.fa".to_owned()));
+        assert!(cov.build_table().is_err());
+    }
+
+    #[test]
+    fn filter_reads_test() {
+        let out_dir = "../test_data/computed_coverage_filter";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.build_table().unwrap();
+        // both reads have a median 4-mer count of 2
+        assert_eq!(cov.filter_reads(2.0, 2.0).unwrap(), (2, 2));
+        // the IDs repeated on + lines are dropped
+        assert_eq!(
+            fs::read_to_string(format!("{}/filtered.fq", out_dir)).unwrap(),
+            fs::read_to_string(PATH_FQ)
+                .unwrap()
+                .replace("+Read_1", "+")
+                .replace("+Read_2", "+")
+        );
+        assert_eq!(cov.filter_reads(3.0, f64::INFINITY).unwrap(), (0, 2));
+        assert!(fs::read_to_string(format!("{}/filtered.fq", out_dir))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn depths_test() {
+        let out_dir = "../test_data/computed_coverage_depth";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 15, 1, 2);
+        cov.build_table().unwrap();
+        cov.compute_depths(1, DepthFormat::Tsv).unwrap();
+        let tsv = fs::read_to_string(format!("{}/kmers.depth.tsv", out_dir)).unwrap();
+        // a line per 15-mer of the two 72 bp reads after the header
+        assert_eq!(tsv.lines().count(), 1 + 2 * 58);
+        assert!(tsv.starts_with("id\tstart\tdepth\nRead_1\t0\t"));
+
+        // runs of the same depth cover every position once
+        cov.compute_depths(1, DepthFormat::BedGraph).unwrap();
+        let bedgraph = fs::read_to_string(format!("{}/kmers.depth.bedgraph", out_dir)).unwrap();
+        let mut covered = 0;
+        for line in bedgraph.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            covered += fields[2].parse::<usize>().unwrap() - fields[1].parse::<usize>().unwrap();
+        }
+        assert_eq!(covered, 2 * 58);
+        let whole = bedgraph;
+        cov.set_segment_size(20);
+        cov.compute_depths(1, DepthFormat::BedGraph).unwrap();
+        assert_eq!(
+            whole,
+            fs::read_to_string(format!("{}/kmers.depth.bedgraph", out_dir)).unwrap()
+        );
+
+        cov.compute_depths(10, DepthFormat::Tsv).unwrap();
+        let tsv = fs::read_to_string(format!("{}/kmers.depth.tsv", out_dir)).unwrap();
+        assert_eq!(tsv.lines().count(), 1 + 2 * 6);
+        assert!(tsv.lines().last().unwrap().starts_with("Read_2\t50\t"));
+    }
+
+    #[test]
+    fn sample_scale_test() {
+        let out_dir = "../test_data/computed_coverage_sample_scale";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.set_sample_scale(Some(SampleScale::Factor(0.5)));
+        cov.build_table().unwrap();
+        cov.compute_coverages().unwrap();
+        assert_eq!(
+            fs::read_to_string(format!("{}/kmers.vectors", out_dir)).unwrap(),
+            "11.000000 22.000000 1.500000\n6.500000 27.500000 0.500000\n"
+        );
+
+        // the 138 4-mers of the reads are 10^6 / 138 per million
+        cov.set_sample_scale(Some(SampleScale::Cpm));
+        cov.compute_coverages().unwrap();
+        let vectors = fs::read_to_string(format!("{}/kmers.vectors", out_dir)).unwrap();
+        let first: f64 = vectors.split(' ').next().unwrap().parse().unwrap();
+        assert!((first - 22.0 * 1e6 / 138.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn sparse_vecs_test() {
+        let out_dir = "../test_data/computed_coverage_sparse";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.set_norm(false);
+        cov.set_with_lengths(true);
+        cov.set_format(OutputFormat {
+            sparse: Some(SparseFormat::Libsvm),
+            ids: IdPolicy::First,
+            ..OutputFormat::default()
+        });
+        cov.build_table().unwrap();
+        cov.compute_coverages().unwrap();
+        assert_eq!(
+            fs::read_to_string(format!("{}/kmers.vectors", out_dir)).unwrap(),
+            "0 1:72 2:22 3:44 4:3 # Read_1\n0 1:72 2:13 3:55 4:1 # Read_2\n"
+        );
+
+        cov.set_format(OutputFormat {
+            sparse: Some(SparseFormat::Pairs),
+            header: true,
+            ..OutputFormat::default()
+        });
+        assert!(cov.compute_coverages().is_err());
+    }
+
+    #[test]
+    fn counts_file_test() {
+        let table_dir = "../test_data/computed_coverage_table";
+        let out_dir = "../test_data/computed_coverage_counts_file";
+        create_directory(table_dir).expect("Directory must be creatable");
+        create_directory(out_dir).expect("Directory must be creatable");
+        let cov = CovComputer::new(PATH_FQ.to_owned(), table_dir.to_owned(), 4, 2, 3);
+        cov.build_table().unwrap();
+        let table_path = format!("{}/kmers.counts.bin", table_dir);
+        let text_path = format!("{}/kmers.counts", table_dir);
+        let text: String = CountsReader::open(&table_path)
+            .unwrap()
+            .iter::<u64>()
+            .map(|(kmer, count)| format!("{}\t{}\n", kmer, count))
+            .collect();
+        fs::write(&text_path, text).unwrap();
+
+        // binary tables are used in place, text counts are imported first
+        for path in [table_path, text_path] {
+            let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+            cov.set_counts_file(Some(path));
+            cov.build_table().unwrap();
+            cov.compute_coverages().unwrap();
+            assert_eq!(
+                fs::read("../test_data/expected_counts.vectors").unwrap(),
+                fs::read(format!("{}/kmers.vectors", out_dir)).unwrap()
+            );
+        }
+
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 5, 2, 3);
+        cov.set_counts_file(Some(format!("{}/kmers.counts.bin", table_dir)));
+        assert!(cov.build_table().is_err());
+    }
+
+    #[test]
+    fn counts_format_test() {
+        let out_dir = "../test_data/computed_coverage_counts_format";
+        let text_path = format!("{}/kmers.counts", out_dir);
+        let table_path = format!("{}/kmers.counts.bin", out_dir);
+        let _ = fs::remove_dir_all(out_dir);
+        create_directory(out_dir).expect("Directory must be creatable");
+        // text counts by default, the binary table goes with the computer
+        let cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.build_table().unwrap();
+        cov.compute_coverages().unwrap();
+        let expected: String = CountsReader::open(&table_path)
+            .unwrap()
+            .iter::<u64>()
+            .map(|(kmer, count)| format!("{}\t{}\n", kmer, count))
+            .collect();
+        assert_eq!(fs::read_to_string(&text_path).unwrap(), expected);
+        drop(cov);
+        assert!(fs::metadata(&table_path).is_err());
+        assert_eq!(
+            fs::read("../test_data/expected_counts.vectors").unwrap(),
+            fs::read(format!("{}/kmers.vectors", out_dir)).unwrap()
+        );
+
+        // binary table only when asked for
+        fs::remove_file(&text_path).unwrap();
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.set_binary_counts(true);
+        cov.build_table().unwrap();
+        drop(cov);
+        assert!(fs::metadata(&text_path).is_err());
+        assert_eq!(CountsReader::open(&table_path).unwrap().len(), 77);
+    }
+
+    #[test]
+    fn bin_scale_test() {
+        let out_dir = "../test_data/computed_coverage_bin_scale";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 4);
+        cov.set_norm(false);
+        cov.set_bin_scale(BinScale::Log);
+        assert_eq!(cov.feature_names(), vec!["0-1", "2-3", "4-7", "8+"]);
+        cov.build_table().unwrap();
+
+        // quantile bins split the k-mer occurrences of the table about evenly
+        cov.set_bin_scale(BinScale::Quantile);
+        cov.compute_coverages().unwrap();
+        let histogram = cov.count_histogram().unwrap();
+        let edges = quantile_edges(&histogram, 4);
+        assert_eq!(cov.feature_names(), bin_names(&edges));
+        let vectors = fs::read_to_string(format!("{}/kmers.vectors", out_dir)).unwrap();
+        let mut totals = [0_u64; 4];
+        for line in vectors.lines() {
+            for (total, val) in totals.iter_mut().zip(line.split(' ')) {
+                *total += val.parse::<u64>().unwrap();
+            }
+        }
+        assert_eq!(totals.iter().sum::<u64>(), 2 * (72 - 4 + 1));
+        assert!(totals[0] > 0);
+    }
+
+    #[test]
+    fn summaries_test() {
+        let out_dir = "../test_data/computed_coverage_summary";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.build_table().unwrap();
+        let mut format = OutputFormat::new("\t");
+        format.header = true;
+        format.ids = IdPolicy::First;
+        format.precision = Some(2);
+        cov.set_format(format);
+        cov.compute_summaries(2).unwrap();
+        let summary = fs::read_to_string(format!("{}/kmers.summary", out_dir)).unwrap();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines[0], "id\tmean\tmedian\tmin\tmax\tsolid_fraction");
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "Read_1\t2.09\t2.00\t1\t4\t0.68");
+
+        // segments of long records give the same statistics
+        cov.set_segment_size(7);
+        cov.compute_summaries(2).unwrap();
+        assert_eq!(
+            fs::read_to_string(format!("{}/kmers.summary", out_dir)).unwrap(),
+            summary
+        );
+    }
 }
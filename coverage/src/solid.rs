@@ -0,0 +1,80 @@
+use counter::counts::CountsReader;
+use kmer::{kmer::GenericKmerGenerator, strand::Strand, KmerInt};
+
+// text form of the per-record solid k-mer masks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaskEncoding {
+    // runs of solid (S) and weak (W) k-mer positions, as 12S3W40S
+    Rle,
+    // one bit per k-mer position, first position in the highest bit, zero padded to bytes
+    Hex,
+}
+
+impl MaskEncoding {
+    pub fn encode(self, mask: &[bool]) -> String {
+        match self {
+            MaskEncoding::Rle => {
+                let mut runs = String::new();
+                let mut start = 0;
+                for pos in 1..=mask.len() {
+                    if pos == mask.len() || mask[pos] != mask[start] {
+                        let kind = if mask[start] { 'S' } else { 'W' };
+                        runs.push_str(&format!("{}{}", pos - start, kind));
+                        start = pos;
+                    }
+                }
+                runs
+            }
+            MaskEncoding::Hex => mask
+                .chunks(8)
+                .map(|bits| {
+                    let byte = bits
+                        .iter()
+                        .enumerate()
+                        .fold(0_u8, |byte, (idx, &bit)| byte | ((bit as u8) << (7 - idx)));
+                    format!("{:02x}", byte)
+                })
+                .collect(),
+        }
+    }
+}
+
+// whether the k-mer starting at each position was counted at least min_count times,
+// positions of k-mers with ambiguous bases are never solid
+pub fn solid_mask<K: KmerInt>(
+    seq: &[u8],
+    ksize: usize,
+    strand: Strand,
+    min_count: u64,
+    counts: &CountsReader,
+) -> Vec<bool> {
+    let mut mask = vec![false; (seq.len() + 1).saturating_sub(ksize)];
+    let mut start = 0;
+    // k-mers are only generated within runs of ACGT, so their positions are consecutive
+    for end in 0..=seq.len() {
+        if end < seq.len() && b"ACGTacgt".contains(&seq[end]) {
+            continue;
+        }
+        for (pos, (fmer, rmer)) in
+            GenericKmerGenerator::<K>::new(&seq[start..end], ksize).enumerate()
+        {
+            let count = counts.get(strand.pick(fmer, rmer)).unwrap_or(0);
+            mask[start + pos] = count >= min_count;
+        }
+        start = end + 1;
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_encoding_test() {
+        let mask = [true, true, false, true, true, true, true, true, true, false];
+        assert_eq!(MaskEncoding::Rle.encode(&mask), "2S1W6S1W");
+        assert_eq!(MaskEncoding::Hex.encode(&mask), "df80");
+        assert_eq!(MaskEncoding::Rle.encode(&[]), "");
+    }
+}
@@ -0,0 +1,70 @@
+use counter::counts::CountsReader;
+use kmer::{kmer::GenericKmerGenerator, strand::Strand, KmerInt};
+
+// statistics of the counts of the k-mers of a record, all zero without k-mers
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CountSummary {
+    pub mean: f64,
+    pub median: f64,
+    pub min: u64,
+    pub max: u64,
+    // fraction of the k-mers counted at least min_count times
+    pub solid: f64,
+}
+
+pub const SUMMARY_NAMES: [&str; 5] = ["mean", "median", "min", "max", "solid_fraction"];
+
+impl CountSummary {
+    pub fn from_counts(counts: &mut [u64], min_count: u64) -> Self {
+        if counts.is_empty() {
+            return Self::default();
+        }
+        counts.sort_unstable();
+        let n = counts.len();
+        let mid = n / 2;
+        let median = if n.is_multiple_of(2) {
+            (counts[mid - 1] + counts[mid]) as f64 / 2_f64
+        } else {
+            counts[mid] as f64
+        };
+        let solid = n - counts.partition_point(|&count| count < min_count);
+        Self {
+            mean: counts.iter().map(|&count| count as f64).sum::<f64>() / n as f64,
+            median,
+            min: counts[0],
+            max: counts[n - 1],
+            solid: solid as f64 / n as f64,
+        }
+    }
+}
+
+// counts of the k-mers of a sequence in order, k-mers missing from the table count 0
+pub fn kmer_counts<K: KmerInt>(
+    seq: &[u8],
+    ksize: usize,
+    strand: Strand,
+    counts: &CountsReader,
+) -> Vec<u64> {
+    GenericKmerGenerator::<K>::new(seq, ksize)
+        .map(|(fmer, rmer)| counts.get(strand.pick(fmer, rmer)).unwrap_or(0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_summary_test() {
+        let summary = CountSummary::from_counts(&mut [4, 1, 9, 2], 3);
+        assert_eq!(summary.mean, 4.0);
+        assert_eq!(summary.median, 3.0);
+        assert_eq!((summary.min, summary.max), (1, 9));
+        assert_eq!(summary.solid, 0.5);
+        assert_eq!(CountSummary::from_counts(&mut [5, 1, 2], 3).median, 2.0);
+        assert_eq!(
+            CountSummary::from_counts(&mut [], 3),
+            CountSummary::default()
+        );
+    }
+}
@@ -0,0 +1,272 @@
+// https://github.com/lh3/minimap2/blob/0cc3cdca27f050fb80a19c90d25ecc6ab0b0907b/sketch.c#L9C1-L26C3
+const SEQ_NT4_TABLE: [u8; 256] = [
+    0, 1, 2, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+    4, 0, 4, 1, 4, 4, 4, 2, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+    4, 0, 4, 1, 4, 4, 4, 2, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+];
+
+// encoding of sequence symbols into k-mer bits, the k-mer and minimiser generators pack
+// bits() per symbol
+pub trait Encoder: Clone {
+    // symbols have complements and k-mers have reverse complements
+    const STRANDED: bool;
+
+    fn bits(&self) -> usize;
+
+    // code of a symbol, None for symbols that break k-mers
+    fn encode(&self, symbol: u8) -> Option<u64>;
+
+    // code of the complementary symbol, only used by stranded alphabets
+    fn complement(&self, code: u64) -> u64;
+
+    // reverse complement of a k-mer of len symbols, the k-mer itself for alphabets without
+    // a reverse strand
+    fn reverse_complement(&self, kmer: u64, len: usize) -> u64 {
+        if !Self::STRANDED {
+            return kmer;
+        }
+        let mask = u64::MAX >> (64 - self.bits());
+        (0..len).fold(0, |rkmer, pos| {
+            (rkmer << self.bits()) | self.complement((kmer >> (self.bits() * pos)) & mask)
+        })
+    }
+}
+
+// ACGT in 2 bits, U is read as T, any other symbol breaks k-mers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Dna;
+
+impl Encoder for Dna {
+    const STRANDED: bool = true;
+
+    #[inline]
+    fn bits(&self) -> usize {
+        2
+    }
+
+    #[inline]
+    fn encode(&self, symbol: u8) -> Option<u64> {
+        let code = SEQ_NT4_TABLE[symbol as usize];
+        (code < 4).then_some(code as u64)
+    }
+
+    #[inline]
+    fn complement(&self, code: u64) -> u64 {
+        code ^ 3
+    }
+}
+
+// ACGU in the 2-bit codes of Dna, so RNA k-mers equal those of the DNA they are
+// transcribed from, T is not an RNA base and breaks k-mers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rna;
+
+impl Encoder for Rna {
+    const STRANDED: bool = true;
+
+    #[inline]
+    fn bits(&self) -> usize {
+        2
+    }
+
+    #[inline]
+    fn encode(&self, symbol: u8) -> Option<u64> {
+        match symbol.to_ascii_uppercase() {
+            b'T' => None,
+            _ => Dna.encode(symbol),
+        }
+    }
+
+    #[inline]
+    fn complement(&self, code: u64) -> u64 {
+        code ^ 3
+    }
+}
+
+// IUPAC nucleotides in 4 bits, one bit per base they stand for (A 1, C 2, G 4, T 8),
+// ambiguity codes such as N are k-mer symbols of their own
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DnaIupac;
+
+impl Encoder for DnaIupac {
+    const STRANDED: bool = true;
+
+    #[inline]
+    fn bits(&self) -> usize {
+        4
+    }
+
+    #[inline]
+    fn encode(&self, symbol: u8) -> Option<u64> {
+        let code = match symbol.to_ascii_uppercase() {
+            b'A' => 1,
+            b'C' => 2,
+            b'G' => 4,
+            b'T' | b'U' => 8,
+            b'M' => 3,
+            b'R' => 5,
+            b'S' => 6,
+            b'V' => 7,
+            b'W' => 9,
+            b'Y' => 10,
+            b'H' => 11,
+            b'K' => 12,
+            b'D' => 13,
+            b'B' => 14,
+            b'N' => 15,
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    #[inline]
+    fn complement(&self, code: u64) -> u64 {
+        // A <-> T and C <-> G swap the bit order
+        ((code & 1) << 3) | ((code & 2) << 1) | ((code & 4) >> 1) | ((code & 8) >> 3)
+    }
+}
+
+const AMINO_ACIDS: &[u8; 20] = b"ACDEFGHIKLMNPQRSTVWY";
+
+// the 20 standard amino acids in 5 bits, k-mers have no reverse complement
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Protein;
+
+impl Encoder for Protein {
+    const STRANDED: bool = false;
+
+    #[inline]
+    fn bits(&self) -> usize {
+        5
+    }
+
+    #[inline]
+    fn encode(&self, symbol: u8) -> Option<u64> {
+        let symbol = symbol.to_ascii_uppercase();
+        AMINO_ACIDS
+            .iter()
+            .position(|&aa| aa == symbol)
+            .map(|code| code as u64)
+    }
+
+    #[inline]
+    fn complement(&self, code: u64) -> u64 {
+        code
+    }
+}
+
+// amino acids grouped into classes, symbols of a class share one code
+#[derive(Debug, Clone)]
+pub struct ReducedAlphabet {
+    table: [u8; 256],
+    bits: usize,
+}
+
+impl ReducedAlphabet {
+    // each group lists the symbols of one class, as ["AGPST", "C", ...]
+    pub fn new(groups: &[&str]) -> Result<Self, String> {
+        if groups.is_empty() || groups.len() > u8::MAX as usize {
+            return Err(format!("Invalid number of groups: {}", groups.len()));
+        }
+        let mut table = [u8::MAX; 256];
+        for (code, group) in groups.iter().enumerate() {
+            for symbol in group.bytes() {
+                for symbol in [symbol.to_ascii_uppercase(), symbol.to_ascii_lowercase()] {
+                    if table[symbol as usize] != u8::MAX && table[symbol as usize] != code as u8 {
+                        return Err(format!("Symbol in several groups: {}", symbol as char));
+                    }
+                    table[symbol as usize] = code as u8;
+                }
+            }
+        }
+        let bits = usize::max(
+            1,
+            (usize::BITS - (groups.len() - 1).leading_zeros()) as usize,
+        );
+        Ok(Self { table, bits })
+    }
+
+    // Dayhoff classes of amino acids
+    pub fn dayhoff() -> Self {
+        Self::new(&["AGPST", "C", "DENQ", "HKR", "ILMV", "FWY"]).unwrap()
+    }
+}
+
+impl Encoder for ReducedAlphabet {
+    const STRANDED: bool = false;
+
+    #[inline]
+    fn bits(&self) -> usize {
+        self.bits
+    }
+
+    #[inline]
+    fn encode(&self, symbol: u8) -> Option<u64> {
+        let code = self.table[symbol as usize];
+        (code != u8::MAX).then_some(code as u64)
+    }
+
+    #[inline]
+    fn complement(&self, code: u64) -> u64 {
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kmer::{GenericKmerGenerator, KmerGenerator};
+
+    #[test]
+    fn dna_encoder_test() {
+        let kmers: Vec<(u64, u64)> =
+            GenericKmerGenerator::<u64, Dna>::with_encoder(b"ACNGTT", 2, Dna).collect();
+        assert_eq!(kmers, KmerGenerator::new(b"ACNGTT", 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rna_encoder_test() {
+        let kmers: Vec<(u64, u64)> =
+            GenericKmerGenerator::<u64, Rna>::with_encoder(b"ACGUuTAC", 2, Rna).collect();
+        // the k-mers of ACGTT, T breaks them
+        assert_eq!(
+            kmers,
+            KmerGenerator::new(b"ACGTTNAC", 2).collect::<Vec<_>>()
+        );
+        assert_eq!(Rna.reverse_complement(0b00011011, 4), 0b00011011);
+        assert_eq!(DnaIupac.reverse_complement(0x1f, 2), 0xf8);
+        assert_eq!(Protein.reverse_complement(0x23, 2), 0x23);
+    }
+
+    #[test]
+    fn iupac_encoder_test() {
+        // AN and its reverse complement NT
+        let kmers: Vec<(u64, u64)> =
+            GenericKmerGenerator::<u64, DnaIupac>::with_encoder(b"AN", 2, DnaIupac).collect();
+        assert_eq!(kmers, vec![(0x1f, 0xf8)]);
+        assert_eq!(DnaIupac.complement(5), 10);
+        assert_eq!(DnaIupac.encode(b'x'), None);
+    }
+
+    #[test]
+    fn protein_encoder_test() {
+        let kmers: Vec<(u64, u64)> =
+            GenericKmerGenerator::<u64, Protein>::with_encoder(b"ACXDE", 2, Protein).collect();
+        // AC, then DE after the X
+        assert_eq!(kmers, vec![(1, 1), ((2 << 5) | 3, (2 << 5) | 3)]);
+
+        let dayhoff = ReducedAlphabet::dayhoff();
+        assert_eq!(dayhoff.bits(), 3);
+        let kmers: Vec<(u64, u64)> =
+            GenericKmerGenerator::<u64, ReducedAlphabet>::with_encoder(b"gsiL", 2, dayhoff)
+                .collect();
+        // G and S share a class, as do I and L
+        assert_eq!(kmers, vec![(0, 0), (4, 4), ((4 << 3) | 4, (4 << 3) | 4)]);
+        assert!(ReducedAlphabet::new(&["AC", "C"]).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{kmer::KmerGenerator, Kmer};
+use std::collections::HashSet;
+
+// k-mers within Hamming distance of a k-mer, by distance starting with the k-mer itself,
+// canonical neighbours are the smaller of each neighbour and its reverse complement
+// with repeats left out, for error tolerant lookups against count tables
+pub struct HammingNeighbours {
+    kmer: Kmer,
+    ksize: usize,
+    distance: usize,
+    canonical: bool,
+    seen: HashSet<Kmer>,
+    // positions substituted at the current distance and the xor applied to each base
+    positions: Vec<usize>,
+    subs: Vec<Kmer>,
+    done: bool,
+}
+
+impl HammingNeighbours {
+    pub fn new(kmer: Kmer, ksize: usize, distance: usize) -> Self {
+        Self {
+            kmer,
+            ksize,
+            distance: usize::min(distance, ksize),
+            canonical: false,
+            seen: HashSet::new(),
+            positions: Vec::new(),
+            subs: Vec::new(),
+            done: false,
+        }
+    }
+
+    pub fn with_canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    // next substitution, then the next positions, then the next distance
+    fn advance(&mut self) {
+        for sub in self.subs.iter_mut().rev() {
+            if *sub < 3 {
+                *sub += 1;
+                return;
+            }
+            *sub = 1;
+        }
+        let len = self.positions.len();
+        for i in (0..len).rev() {
+            if self.positions[i] < self.ksize - len + i {
+                self.positions[i] += 1;
+                for j in i + 1..len {
+                    self.positions[j] = self.positions[j - 1] + 1;
+                }
+                return;
+            }
+        }
+        if len == self.distance {
+            self.done = true;
+            return;
+        }
+        self.positions = (0..len + 1).collect();
+        self.subs = vec![1; len + 1];
+    }
+}
+
+impl Iterator for HammingNeighbours {
+    type Item = Kmer;
+
+    fn next(&mut self) -> Option<Kmer> {
+        while !self.done {
+            // xor with 1, 2 or 3 turns a base into each of the other three
+            let mut kmer = self.kmer;
+            for (&pos, &sub) in self.positions.iter().zip(&self.subs) {
+                kmer ^= sub << (2 * (self.ksize - 1 - pos));
+            }
+            self.advance();
+            if self.canonical {
+                kmer = Kmer::min(kmer, KmerGenerator::rev_comp(kmer, self.ksize));
+                if !self.seen.insert(kmer) {
+                    continue;
+                }
+            }
+            return Some(kmer);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{kmer_to_numeric, numeric_to_kmer};
+
+    fn hamming(a: &str, b: &str) -> usize {
+        a.bytes().zip(b.bytes()).filter(|(x, y)| x != y).count()
+    }
+
+    #[test]
+    fn hamming_neighbours_test() {
+        let kmer = kmer_to_numeric("ACGTA").unwrap();
+        let neighbours: Vec<Kmer> = HammingNeighbours::new(kmer, 5, 2).collect();
+        // 1 + 5 * 3 + 10 * 9 distinct k-mers, by distance
+        assert_eq!(neighbours.len(), 1 + 15 + 90);
+        assert_eq!(
+            neighbours.iter().collect::<HashSet<_>>().len(),
+            neighbours.len()
+        );
+        assert_eq!(neighbours[0], kmer);
+        let distances: Vec<usize> = neighbours
+            .iter()
+            .map(|&neighbour| hamming(&numeric_to_kmer(neighbour, 5), "ACGTA"))
+            .collect();
+        assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(*distances.last().unwrap(), 2);
+        // every k-mer within distance 2
+        let within = (0..4_u64.pow(5))
+            .filter(|&other| hamming(&numeric_to_kmer(other, 5), "ACGTA") <= 2)
+            .count();
+        assert_eq!(within, neighbours.len());
+
+        // distance past the k-mer size stops at every k-mer
+        assert_eq!(HammingNeighbours::new(0, 2, 5).count(), 16);
+        assert_eq!(
+            HammingNeighbours::new(kmer, 5, 0).collect::<Vec<_>>(),
+            vec![kmer]
+        );
+    }
+
+    #[test]
+    fn hamming_neighbours_canonical_test() {
+        let kmer = kmer_to_numeric("ACGTA").unwrap();
+        let canonical: Vec<Kmer> = HammingNeighbours::new(kmer, 5, 1)
+            .with_canonical(true)
+            .collect();
+        let mut expected: Vec<Kmer> = HammingNeighbours::new(kmer, 5, 1)
+            .map(|neighbour| Kmer::min(neighbour, KmerGenerator::rev_comp(neighbour, 5)))
+            .collect();
+        expected.sort();
+        expected.dedup();
+        let mut sorted = canonical.clone();
+        sorted.sort();
+        assert_eq!(sorted, expected);
+        assert_eq!(sorted.len(), canonical.len());
+        // the reverse complement has the same canonical neighbourhood
+        let rc = KmerGenerator::rev_comp(kmer, 5);
+        let mut rc_canonical: Vec<Kmer> = HammingNeighbours::new(rc, 5, 1)
+            .with_canonical(true)
+            .collect();
+        rc_canonical.sort();
+        assert_eq!(rc_canonical, sorted);
+    }
+}
@@ -1,42 +1,251 @@
-use super::Kmer;
+use super::{
+    encoder::{Dna, DnaIupac, Encoder},
+    packed::PackedSeq,
+    Kmer, KmerInt,
+};
 use std::collections::{HashMap, HashSet};
 use std::iter::Iterator;
 
-// https://github.com/lh3/minimap2/blob/0cc3cdca27f050fb80a19c90d25ecc6ab0b0907b/sketch.c#L9C1-L26C3
-const SEQ_NT4_TABLE: [u8; 256] = [
-    0, 1, 2, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 0, 4, 1, 4, 4, 4, 2, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 0, 4, 1, 4, 4, 4, 2, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-];
+pub use super::revcomp::reverse_complement;
+
 const REV_MASK: u64 = 3;
+// largest k for which the dense 4^k position map is worth allocating
+pub const MAX_DENSE_KSIZE: usize = 10;
 
-pub struct KmerGenerator<'a> {
+// k-mers of any KmerInt width, u128 is used when k > 32
+#[derive(Clone)]
+pub struct GenericKmerGenerator<'a, K: KmerInt, E: Encoder = Dna> {
     seq: &'a [u8],
-    fval: u64,
-    rval: u64,
+    // bases encoded up front, read instead of seq
+    packed: Option<&'a PackedSeq>,
+    fval: K,
+    rval: K,
     len: usize,
     pos: usize,
     ksize: usize,
-    mask: u64,
-    shift: u64,
+    mask: K,
+    bits: usize,
+    shift: usize,
+    stride: usize,
+    // runs of a symbol count once (homopolymer compression), last is the previous symbol
+    hpc: bool,
+    last: Option<u64>,
+    // k-mers with IUPAC ambiguity codes are expanded into at most iupac concrete k-mers,
+    // ambiguous is the position of the last ambiguity code and expanded the k-mers to yield
+    iupac: usize,
+    ambiguous: Option<usize>,
+    expanded: Vec<(K, K)>,
+    encoder: E,
 }
 
-impl<'a> KmerGenerator<'a> {
+pub type KmerGenerator<'a> = GenericKmerGenerator<'a, Kmer>;
+
+impl<'a, K: KmerInt> GenericKmerGenerator<'a, K> {
     pub fn new(seq: &'a [u8], ksize: usize) -> Self {
-        KmerGenerator {
+        Self::with_encoder(seq, ksize, Dna)
+    }
+
+    // k-mers of a sequence packed once, so that passes over it do not encode it again,
+    // packed sequences have no ambiguity codes left to expand
+    pub fn from_packed(packed: &'a PackedSeq, ksize: usize) -> Self {
+        let mut kg = Self::with_encoder(&[], ksize, Dna);
+        kg.packed = Some(packed);
+        kg
+    }
+
+    // k-mers over IUPAC ambiguity codes (R, Y, N, ...) yield every concrete k-mer they stand
+    // for, in order, unless there are more than limit of them, 0 breaks k-mers at the codes,
+    // codes still break k-mers of homopolymer compressed sequences
+    pub fn with_iupac(mut self, limit: usize) -> Self {
+        self.iupac = limit;
+        self
+    }
+}
+
+impl<'a, K: KmerInt, E: Encoder> GenericKmerGenerator<'a, K, E> {
+    // k-mers of another alphabet, ksize symbols of encoder.bits() must fit in K
+    pub fn with_encoder(seq: &'a [u8], ksize: usize, encoder: E) -> Self {
+        let bits = encoder.bits();
+        assert!(
+            bits * ksize <= 2 * K::MAX_KSIZE,
+            "{} symbols of {} bits do not fit in a k-mer",
+            ksize,
+            bits
+        );
+        GenericKmerGenerator {
             seq,
-            fval: 0,
-            rval: 0,
+            packed: None,
+            fval: K::default(),
+            rval: K::default(),
             len: 0,
             pos: 0,
             ksize,
-            mask: (1_u64 << (2 * ksize)) - 1,
-            shift: 2 * (ksize - 1) as u64,
+            mask: if bits * ksize == 2 * K::MAX_KSIZE {
+                !K::default()
+            } else {
+                !(!K::default() << (bits * ksize))
+            },
+            bits,
+            shift: bits * (ksize - 1),
+            stride: 1,
+            hpc: false,
+            last: None,
+            iupac: 0,
+            ambiguous: None,
+            expanded: Vec::new(),
+            encoder,
+        }
+    }
+
+    // only yield k-mers starting at every stride-th position of the sequence
+    pub fn with_stride(mut self, stride: usize) -> Self {
+        self.stride = usize::max(1, stride);
+        self
+    }
+
+    // k-mers of the homopolymer compressed sequence, as minimap2 -H for long reads,
+    // stride is still of positions in the uncompressed sequence
+    pub fn with_hpc(mut self, hpc: bool) -> Self {
+        self.hpc = hpc;
+        self
+    }
+
+    // smaller of each k-mer and its reverse complement, with whether it was the forward one
+    pub fn canonical(self) -> CanonicalKmers<'a, K, E> {
+        CanonicalKmers { kmers: self }
+    }
+
+    #[inline]
+    fn encode(&mut self, symbol: u8) -> Option<u64> {
+        match self.encoder.encode(symbol) {
+            None if self.iupac > 0 && !self.hpc => {
+                // ambiguity codes roll in as their first base, k-mers over them are expanded
+                DnaIupac.encode(symbol).map(|bases| {
+                    self.ambiguous = Some(self.pos);
+                    bases.trailing_zeros() as u64
+                })
+            }
+            code => code,
+        }
+    }
+
+    // concrete k-mers of the window starting at start, in reverse order to be popped,
+    // none when there are more than the limit
+    fn expand(&self, start: usize) -> Vec<(K, K)> {
+        let window = &self.seq[start..self.pos];
+        let bases: Vec<u64> = window
+            .iter()
+            .map(|&base| DnaIupac.encode(base).unwrap_or(0))
+            .collect();
+        let combinations = bases.iter().fold(1_usize, |combinations, code| {
+            combinations.saturating_mul(code.count_ones() as usize)
+        });
+        if combinations > self.iupac {
+            return Vec::new();
+        }
+        let mut kmers = vec![(K::default(), K::default())];
+        for (i, code) in bases.into_iter().enumerate() {
+            kmers = kmers
+                .into_iter()
+                .flat_map(|(fmer, rmer)| {
+                    (0..4_u64)
+                        .filter(move |base| (code >> base) & 1 == 1)
+                        .map(move |base| {
+                            (
+                                (fmer << self.bits) | K::from_u64(base),
+                                rmer | (K::from_u64(base ^ REV_MASK) << (self.bits * i)),
+                            )
+                        })
+                })
+                .collect();
+        }
+        kmers.reverse();
+        kmers
+    }
+}
+
+#[derive(Clone)]
+pub struct CanonicalKmers<'a, K: KmerInt, E: Encoder = Dna> {
+    kmers: GenericKmerGenerator<'a, K, E>,
+}
+
+impl<K: KmerInt, E: Encoder> Iterator for CanonicalKmers<'_, K, E> {
+    type Item = (K, bool);
+
+    #[inline]
+    fn next(&mut self) -> Option<(K, bool)> {
+        let (fmer, rmer) = self.kmers.next()?;
+        // palindromes are reported on the forward strand
+        Some(if fmer <= rmer {
+            (fmer, true)
+        } else {
+            (rmer, false)
+        })
+    }
+}
+
+// column of the canonical k-mer of each k-mer, looked up by the forward k-mer in
+// canonical_columns(ksize), ranked with canonical_rank when there are no columns
+pub struct CanonicalIndices<'a, 'm> {
+    kmers: KmerGenerator<'a>,
+    columns: &'m [usize],
+}
+
+impl Iterator for CanonicalIndices<'_, '_> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        let (fmer, rmer) = self.kmers.next()?;
+        if self.columns.is_empty() {
+            let min_mer = u64::min(fmer, rmer);
+            return Some(KmerGenerator::canonical_rank(min_mer, self.kmers.ksize));
+        }
+        // the columns have an entry for each of the 4^k k-mers
+        Some(unsafe { *self.columns.get_unchecked(fmer as usize) })
+    }
+}
+
+// sequence with runs of a base collapsed to one base, k-mers of it are those of with_hpc
+pub fn compress_homopolymers(seq: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::with_capacity(seq.len());
+    let mut last = None;
+    for &base in seq {
+        let code = Dna.encode(base);
+        if code.is_some() && code == last {
+            continue;
+        }
+        last = code;
+        compressed.push(base);
+    }
+    compressed
+}
+
+// sequence with soft-masked (lowercase) bases replaced by N, so no k-mer overlaps them
+pub fn hard_mask(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .map(|&base| {
+            if base.is_ascii_lowercase() {
+                b'N'
+            } else {
+                base
+            }
+        })
+        .collect()
+}
+
+impl<'a> KmerGenerator<'a> {
+    // dense columns of the canonical k-mers, columns are canonical_columns(ksize) or empty
+    // for k-mer sizes without them
+    pub fn canonical_indices<'m>(self, columns: &'m [usize]) -> CanonicalIndices<'a, 'm> {
+        assert!(
+            columns.is_empty() || columns.len() == 1 << (2 * self.ksize),
+            "columns are not of {}-mers",
+            self.ksize
+        );
+        CanonicalIndices {
+            kmers: self,
+            columns,
         }
     }
 
@@ -71,36 +280,142 @@ impl<'a> KmerGenerator<'a> {
         }
         (min_mer_pos_map, pos_min_mer_map, count)
     }
+
+    // kmer_pos_maps(ksize).0 with the column of its canonical k-mer for every k-mer as well,
+    // so that columns are looked up without taking the smaller of the two strands
+    pub fn canonical_columns(ksize: usize) -> Vec<usize> {
+        let (mut columns, _, _) = KmerGenerator::kmer_pos_maps(ksize);
+        for kmer in 0..columns.len() {
+            let min_mer = u64::min(kmer as u64, KmerGenerator::rev_comp(kmer as u64, ksize));
+            columns[kmer] = columns[min_mer as usize];
+        }
+        columns
+    }
+
+    pub fn canonical_count(ksize: usize) -> usize {
+        // odd k has no reverse complement palindromes
+        let total = 4_usize.pow(ksize as u32);
+        if ksize.is_multiple_of(2) {
+            (total + 4_usize.pow(ksize as u32 / 2)) / 2
+        } else {
+            total / 2
+        }
+    }
+
+    pub fn canonical_rank(kmer: Kmer, ksize: usize) -> usize {
+        // closed-form equivalent of kmer_pos_maps(ksize).0[kmer] for a canonical kmer
+        // number of canonical kmers smaller than kmer, counted prefix by prefix
+        let mut digits = [0_u8; 32];
+        let mut rank = 0;
+        for (pos, digit) in digits.iter_mut().enumerate().take(ksize) {
+            *digit = ((kmer >> (2 * (ksize - 1 - pos))) & 3) as u8;
+        }
+        let mut prefix = [0_u8; 32];
+        for pos in 0..ksize {
+            for smaller in 0..digits[pos] {
+                prefix[pos] = smaller;
+                rank += KmerGenerator::canonical_count_with_prefix(&prefix[..=pos], ksize);
+            }
+            prefix[pos] = digits[pos];
+        }
+        rank
+    }
+
+    fn canonical_count_with_prefix(prefix: &[u8], ksize: usize) -> usize {
+        // canonical kmers starting with prefix; a kmer is canonical when kmer <= rev_comp(kmer)
+        // compared base by base as kmer[i] against complement of kmer[k - 1 - i]
+        let mut free = (ksize - prefix.len()) as u32;
+        let mut count = 0;
+        for i in 0..ksize / 2 {
+            match (prefix.get(i), prefix.get(ksize - 1 - i)) {
+                (Some(&left), Some(&right)) => {
+                    let right = 3 - right;
+                    if left < right {
+                        return count + 4_usize.pow(free);
+                    } else if left > right {
+                        return count;
+                    }
+                }
+                (Some(&left), None) => {
+                    // mirror base is free, 3 - left choices make the kmer smaller
+                    free -= 1;
+                    count += (3 - left as usize) * 4_usize.pow(free);
+                }
+                _ => {
+                    // rest is free, half of it is canonical plus the palindromes
+                    return if ksize.is_multiple_of(2) {
+                        count + (4_usize.pow(free) + 4_usize.pow(free / 2)) / 2
+                    } else {
+                        count + 4_usize.pow(free) / 2
+                    };
+                }
+            }
+        }
+        if ksize.is_multiple_of(2) {
+            // palindrome
+            return count + 4_usize.pow(free);
+        }
+        // middle base of an odd kmer, A and C are smaller than their complements
+        match prefix.get(ksize / 2) {
+            Some(&middle) if middle < 2 => count + 4_usize.pow(free),
+            Some(_) => count,
+            None => count + 2 * 4_usize.pow(free - 1),
+        }
+    }
 }
 
 // technique adopted from https://github.com/lh3/minimap2/blob/0cc3cdca27f050fb80a19c90d25ecc6ab0b0907b/sketch.c#L77
-impl Iterator for KmerGenerator<'_> {
-    type Item = (Kmer, Kmer);
+// alphabets without a reverse strand yield the forward k-mer twice
+impl<K: KmerInt, E: Encoder> Iterator for GenericKmerGenerator<'_, K, E> {
+    type Item = (K, K);
 
-    fn next(&mut self) -> Option<(Kmer, Kmer)> {
+    fn next(&mut self) -> Option<(K, K)> {
         // valid base
         loop {
-            if self.pos == self.seq.len() {
+            if let Some(kmer) = self.expanded.pop() {
+                return Some(kmer);
+            }
+            if self.pos == self.packed.map_or(self.seq.len(), PackedSeq::len) {
                 return None;
             }
-            let pos_char = self.seq[self.pos];
-            let pos_f_val = SEQ_NT4_TABLE[pos_char as usize] as u64;
-            let pos_r_val = pos_f_val ^ REV_MASK;
+            let code = match self.packed {
+                Some(packed) => packed.get(self.pos),
+                None => self.encode(self.seq[self.pos]),
+            };
             self.pos += 1;
 
-            if pos_f_val < 4 {
+            if let Some(pos_f_val) = code {
+                if self.hpc && self.last == Some(pos_f_val) {
+                    continue;
+                }
+                self.last = Some(pos_f_val);
                 // non ambiguous
-                self.fval = ((self.fval << 2) | pos_f_val) & self.mask;
-                self.rval = (self.rval >> 2) | (pos_r_val << self.shift);
+                self.fval = ((self.fval << self.bits) | K::from_u64(pos_f_val)) & self.mask;
+                if E::STRANDED {
+                    let pos_r_val = self.encoder.complement(pos_f_val);
+                    self.rval = (self.rval >> self.bits) | (K::from_u64(pos_r_val) << self.shift);
+                }
                 self.len += 1;
             } else {
                 // ambiguous
                 self.len = 0;
+                self.last = None;
             }
 
             if self.len == self.ksize {
                 self.len -= 1;
-                return Some((self.fval, self.rval));
+                if (self.pos - self.ksize).is_multiple_of(self.stride) {
+                    let start = self.pos - self.ksize;
+                    if self.ambiguous.is_some_and(|at| at >= start) {
+                        self.expanded = self.expand(start);
+                        continue;
+                    }
+                    return Some(if E::STRANDED {
+                        (self.fval, self.rval)
+                    } else {
+                        (self.fval, self.fval)
+                    });
+                }
             }
         }
     }
@@ -109,6 +424,7 @@ impl Iterator for KmerGenerator<'_> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::numeric_to_kmer;
 
     #[test]
     fn kmers_generated_test() {
@@ -144,6 +460,108 @@ mod tests {
         assert_eq!(kmer4, None);
     }
 
+    #[test]
+    fn kmers_canonical_test() {
+        // AC/GT, CG palindrome, GT/AC
+        let kmers: Vec<(u64, bool)> = KmerGenerator::new(b"ACGT", 2).canonical().collect();
+        assert_eq!(kmers, vec![(1, true), (6, true), (1, false)]);
+        let kmers: Vec<(u64, bool)> = KmerGenerator::new(b"ACNGTT", 2)
+            .with_stride(3)
+            .canonical()
+            .collect();
+        assert_eq!(kmers, vec![(1, true), (1, false)]);
+    }
+
+    #[test]
+    fn kmers_iupac_test() {
+        let names = |seq: &[u8], limit: usize| -> Vec<String> {
+            KmerGenerator::new(seq, 3)
+                .with_iupac(limit)
+                .map(|(fmer, rmer)| {
+                    assert_eq!(rmer, KmerGenerator::rev_comp(fmer, 3));
+                    numeric_to_kmer(fmer, 3)
+                })
+                .collect()
+        };
+        // R is A or G
+        assert_eq!(names(b"ACRT", 4), vec!["ACA", "ACG", "CAT", "CGT"]);
+        // ambiguity codes break k-mers without expansion
+        assert_eq!(names(b"ACRT", 0), Vec::<String>::new());
+        // N stands for 4 k-mers, RN for 8
+        assert_eq!(names(b"ACNTT", 4).len(), 4 + 4 + 4);
+        assert!(names(b"ACNTT", 3).is_empty());
+        assert_eq!(names(b"ARNTT", 8).len(), 8 + 8 + 4);
+        assert_eq!(names(b"ARNTT", 4).len(), 4);
+        // other symbols still break k-mers
+        assert_eq!(names(b"AC-GTA", 4), vec!["GTA"]);
+        // concrete k-mers after the codes leave the window
+        assert_eq!(names(b"NACGT", 1), vec!["ACG", "CGT"]);
+    }
+
+    #[test]
+    fn kmers_generated_stride_test() {
+        // positions 0, 2, 4 of ACGTAC
+        let kmers: Vec<(u64, u64)> = KmerGenerator::new(b"ACGTAC", 2).with_stride(2).collect();
+        assert_eq!(kmers, vec![(1, 11), (11, 1), (1, 11)]);
+        // position 1 (CN) is ambiguous, position 3 (GT) is sampled
+        let kmers: Vec<(u64, u64)> = KmerGenerator::new(b"ACNGTT", 2).with_stride(3).collect();
+        assert_eq!(kmers, vec![(1, 11), (11, 1)]);
+    }
+
+    #[test]
+    fn kmers_generated_hpc_test() {
+        // AACCGT -> ACGT, the N breaks the run of As
+        let kmers: Vec<(u64, u64)> = KmerGenerator::new(b"AACcGGGT", 2).with_hpc(true).collect();
+        assert_eq!(kmers, KmerGenerator::new(b"ACGT", 2).collect::<Vec<_>>());
+        let seq = b"AAANAACGTTTTGCA";
+        assert_eq!(compress_homopolymers(seq), b"ANACGTGCA");
+        let kmers: Vec<(u64, u64)> = KmerGenerator::new(seq, 3).with_hpc(true).collect();
+        assert_eq!(
+            kmers,
+            KmerGenerator::new(&compress_homopolymers(seq), 3).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn hard_mask_test() {
+        assert_eq!(hard_mask(b"ACgtNA"), b"ACNNNA");
+        let kmers: Vec<(u64, u64)> = KmerGenerator::new(&hard_mask(b"ACGtACG"), 3).collect();
+        assert_eq!(kmers, KmerGenerator::new(b"ACGNACG", 3).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn kmers_generated_wide_test() {
+        let seq = b"ACGTTGCATGCATTAGCTAGCATCGATCGATTAGCGCGATCGATTTAGCGCAGTCGA";
+        // agrees with u64 k-mers where both fit
+        let narrow: Vec<(u64, u64)> = KmerGenerator::new(seq, 31).collect();
+        let wide: Vec<(u128, u128)> = GenericKmerGenerator::new(seq, 31).collect();
+        assert_eq!(narrow.len(), wide.len());
+        for ((f, r), (wf, wr)) in narrow.iter().zip(wide.iter()) {
+            assert_eq!((*f as u128, *r as u128), (*wf, *wr));
+        }
+        // 50-mers read back as ACGT on both strands
+        let wide: Vec<(u128, u128)> = GenericKmerGenerator::new(seq, 50).collect();
+        assert_eq!(wide.len(), seq.len() - 49);
+        assert_eq!(numeric_to_kmer(wide[0].0, 50).as_bytes(), &seq[..50]);
+        let rc: String = numeric_to_kmer(wide[0].1, 50)
+            .chars()
+            .rev()
+            .map(|c| match c {
+                'A' => 'T',
+                'C' => 'G',
+                'G' => 'C',
+                _ => 'A',
+            })
+            .collect();
+        assert_eq!(rc.as_bytes(), &seq[..50]);
+    }
+
+    #[test]
+    fn reverse_complement_test() {
+        assert_eq!(reverse_complement(b"ACGTNacgu"), b"acgtNACGT");
+        assert_eq!(reverse_complement(b""), b"");
+    }
+
     #[test]
     fn rev_comp_test() {
         // ACGT 00 01 10 11 -> ACGT 00 01 10 11
@@ -174,4 +592,47 @@ mod tests {
         // AAAT -> 11
         assert_eq!(min_mer_pos_map[0b11], 0b11);
     }
+
+    #[test]
+    fn canonical_count_test() {
+        for ksize in 1..=8 {
+            let (_, _, count) = KmerGenerator::kmer_pos_maps(ksize);
+            assert_eq!(KmerGenerator::canonical_count(ksize), count);
+        }
+    }
+
+    #[test]
+    fn canonical_rank_test() {
+        for ksize in 1..=8 {
+            let (_, pos_min_mer_map, _) = KmerGenerator::kmer_pos_maps(ksize);
+            for (&pos, &kmer) in pos_min_mer_map.iter() {
+                assert_eq!(KmerGenerator::canonical_rank(kmer, ksize), pos);
+            }
+        }
+        // AAAT -> 11
+        assert_eq!(KmerGenerator::canonical_rank(0b11, 4), 0b11);
+    }
+
+    #[test]
+    fn canonical_indices_test() {
+        let seq = b"ATGCGATATCGNTAGGCGTCGATGGAGAGCTAGATCGATCGATCTAAATCCCGATCGATT";
+        let (pos_map, _, _) = KmerGenerator::kmer_pos_maps(5);
+        let expected: Vec<usize> = KmerGenerator::new(seq, 5)
+            .canonical()
+            .map(|(kmer, _)| pos_map[kmer as usize])
+            .collect();
+        let columns = KmerGenerator::canonical_columns(5);
+        let indices: Vec<usize> = KmerGenerator::new(seq, 5)
+            .canonical_indices(&columns)
+            .collect();
+        assert_eq!(indices, expected);
+        // ranks without a map are the same columns
+        let ranked: Vec<usize> = KmerGenerator::new(seq, 5).canonical_indices(&[]).collect();
+        assert_eq!(ranked, expected);
+        // a k-mer and its reverse complement share the column of the canonical k-mer
+        for kmer in 0..1 << 10 {
+            let min_mer = u64::min(kmer, KmerGenerator::rev_comp(kmer, 5));
+            assert_eq!(columns[kmer as usize], pos_map[min_mer as usize]);
+        }
+    }
 }
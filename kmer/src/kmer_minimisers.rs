@@ -1,23 +1,13 @@
-use super::Kmer;
+use super::{
+    encoder::{Dna, Encoder},
+    Kmer,
+};
 use std::cmp::min;
 use std::collections::VecDeque;
 use std::iter::Iterator;
 
 // TODO I cannot remember why I made this, probably a test script, but well.
-// https://github.com/lh3/minimap2/blob/0cc3cdca27f050fb80a19c90d25ecc6ab0b0907b/sketch.c#L9C1-L26C3
-const SEQ_NT4_TABLE: [u8; 256] = [
-    0, 1, 2, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 0, 4, 1, 4, 4, 4, 2, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 0, 4, 1, 4, 4, 4, 2, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-];
-const REV_MASK: u64 = 3;
-
-pub struct KmerMinimiserGenerator<'a> {
+pub struct KmerMinimiserGenerator<'a, E: Encoder = Dna> {
     seq: &'a [u8],
     pos: usize,
     wsize: usize,
@@ -29,18 +19,36 @@ pub struct KmerMinimiserGenerator<'a> {
     m_val_r: u64,
     m_val_l: usize,
     m_active: u64,
+    // whether the active minimiser came from the forward strand
+    m_forward: bool,
     m_shift: u64,
     k_mask: u64,
     k_val_f: u64,
     k_val_r: u64,
     k_val_l: usize,
     k_shift: u64,
-    buff: VecDeque<u64>,
+    buff: VecDeque<(u64, bool)>,
     buff_pos: usize,
+    bits: usize,
+    encoder: E,
 }
 
 impl<'a> KmerMinimiserGenerator<'a> {
     pub fn new(seq: &'a [u8], wsize: usize, msize: usize) -> Self {
+        Self::with_encoder(seq, wsize, msize, Dna)
+    }
+}
+
+impl<'a, E: Encoder> KmerMinimiserGenerator<'a, E> {
+    // k-mers and minimisers of another alphabet, wsize symbols must fit in 64 bits
+    pub fn with_encoder(seq: &'a [u8], wsize: usize, msize: usize, encoder: E) -> Self {
+        let bits = encoder.bits();
+        assert!(
+            bits * wsize <= 64,
+            "{} symbols of {} bits do not fit in a k-mer",
+            wsize,
+            bits
+        );
         KmerMinimiserGenerator {
             seq,
             wsize,
@@ -48,32 +56,37 @@ impl<'a> KmerMinimiserGenerator<'a> {
             pos: 0,
             buff_pos: 0,
             m_active: u64::MAX,
-            m_mask: (1_u64 << (2 * msize)) - 1,
+            m_forward: true,
+            m_mask: u64::MAX >> (64 - bits * msize),
             m_val_f: 0,
             m_val_r: 0,
             m_val_l: 0,
-            k_mask: (1_u64 << (2 * wsize)) - 1,
+            k_mask: u64::MAX >> (64 - bits * wsize),
             k_val_f: 0,
             k_val_r: 0,
             k_val_l: 0,
             m_window_end: 0,
             m_window_start: 0,
             buff: VecDeque::with_capacity(wsize - msize + 1),
-            m_shift: 2 * (msize - 1) as u64,
-            k_shift: 2 * (wsize - 1) as u64,
+            m_shift: (bits * (msize - 1)) as u64,
+            k_shift: (bits * (wsize - 1)) as u64,
+            bits,
+            encoder,
         }
     }
 }
 
 // technique adopted from https://github.com/lh3/minimap2/blob/0cc3cdca27f050fb80a19c90d25ecc6ab0b0907b/sketch.c#L77
-impl Iterator for KmerMinimiserGenerator<'_> {
-    type Item = (Kmer, usize, usize, Vec<Kmer>);
+// items are (minimiser, start, end, forward, k-mers) with the strand as in MinimiserGenerator
+impl<E: Encoder> Iterator for KmerMinimiserGenerator<'_, E> {
+    type Item = (Kmer, usize, usize, bool, Vec<Kmer>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut min_m_val: u64;
+        let mut min_m_val: (u64, bool);
         let mut k_buff = Vec::new();
         let mut prev_k_buff = Vec::new();
         let mut prev_m_val: u64;
+        let mut prev_forward: bool;
         let mut prev_w_start: usize;
         let mut prev_w_end: usize;
 
@@ -81,25 +94,28 @@ impl Iterator for KmerMinimiserGenerator<'_> {
             if self.pos == self.seq.len() {
                 return None;
             }
-            let pos_char = self.seq[self.pos];
-            let pos_f_val = SEQ_NT4_TABLE[pos_char as usize] as u64;
-            let pos_r_val = pos_f_val ^ REV_MASK;
-
-            if pos_f_val < 4 {
+            if let Some(pos_f_val) = self.encoder.encode(self.seq[self.pos]) {
                 // non ambiguous
                 // kmer
-                self.k_val_f = ((self.k_val_f << 2) | pos_f_val) & self.k_mask;
-                self.k_val_r = (self.k_val_r >> 2) | (pos_r_val << self.k_shift);
-                self.k_val_l += 1;
+                self.k_val_f = ((self.k_val_f << self.bits) | pos_f_val) & self.k_mask;
                 // minimiser
-                self.m_val_f = ((self.m_val_f << 2) | pos_f_val) & self.m_mask;
-                self.m_val_r = (self.m_val_r >> 2) | (pos_r_val << self.m_shift);
+                self.m_val_f = ((self.m_val_f << self.bits) | pos_f_val) & self.m_mask;
+                if E::STRANDED {
+                    let pos_r_val = self.encoder.complement(pos_f_val);
+                    self.k_val_r = (self.k_val_r >> self.bits) | (pos_r_val << self.k_shift);
+                    self.m_val_r = (self.m_val_r >> self.bits) | (pos_r_val << self.m_shift);
+                } else {
+                    self.k_val_r = self.k_val_f;
+                    self.m_val_r = self.m_val_f;
+                }
+                self.k_val_l += 1;
                 self.m_val_l += 1;
             } else {
                 // ambiguous
                 // check if we have passed a good complete window
                 let should_return = self.buff.len() == self.wsize - self.msize + 1;
                 prev_m_val = self.m_active;
+                prev_forward = self.m_forward;
                 prev_w_start = self.m_window_start;
                 prev_w_end = self.pos;
                 if should_return {
@@ -120,7 +136,13 @@ impl Iterator for KmerMinimiserGenerator<'_> {
                 self.pos += 1;
 
                 if should_return {
-                    return Some((prev_m_val, prev_w_start, prev_w_end, prev_k_buff));
+                    return Some((
+                        prev_m_val,
+                        prev_w_start,
+                        prev_w_end,
+                        prev_forward,
+                        prev_k_buff,
+                    ));
                 }
                 continue;
             }
@@ -132,7 +154,10 @@ impl Iterator for KmerMinimiserGenerator<'_> {
 
             self.m_val_l -= 1;
 
-            min_m_val = min(self.m_val_f, self.m_val_r);
+            min_m_val = (
+                min(self.m_val_f, self.m_val_r),
+                self.m_val_f <= self.m_val_r,
+            );
 
             if self.k_val_l == self.wsize {
                 k_buff.push(min(self.k_val_f, self.k_val_r));
@@ -147,35 +172,37 @@ impl Iterator for KmerMinimiserGenerator<'_> {
 
                 // we have removed the minimum
                 if self.buff_pos == 0 {
-                    let mut new_min = u64::MAX;
+                    let mut new_min = (u64::MAX, true);
                     for j in 0..self.buff.len() {
-                        if *self.buff.get(j).unwrap() < new_min {
+                        if self.buff.get(j).unwrap().0 < new_min.0 {
                             self.buff_pos = j;
                             new_min = *self.buff.get(j).unwrap();
                         }
                     }
                     // minimiser changed
-                    if new_min != self.m_active {
+                    if new_min.0 != self.m_active {
                         self.m_window_end = self.pos;
                         prev_m_val = self.m_active;
+                        prev_forward = self.m_forward;
                         prev_w_start = self.m_window_start;
                         prev_w_end = self.m_window_end;
-                        self.m_active = new_min;
+                        (self.m_active, self.m_forward) = new_min;
                         self.m_window_start = self.pos - self.wsize + 1;
                         self.pos += 1;
-                        return Some((prev_m_val, prev_w_start, prev_w_end, k_buff));
+                        return Some((prev_m_val, prev_w_start, prev_w_end, prev_forward, k_buff));
                     }
-                } else if min_m_val < self.m_active {
+                } else if min_m_val.0 < self.m_active {
                     // break the window
                     self.m_window_end = self.pos;
                     prev_m_val = self.m_active;
+                    prev_forward = self.m_forward;
                     prev_w_start = self.m_window_start;
                     prev_w_end = self.m_window_end;
-                    self.m_active = min_m_val;
+                    (self.m_active, self.m_forward) = min_m_val;
                     self.buff_pos = self.buff.len() - 1;
                     self.m_window_start = self.pos - self.wsize + 1;
                     self.pos += 1;
-                    return Some((prev_m_val, prev_w_start, prev_w_end, k_buff));
+                    return Some((prev_m_val, prev_w_start, prev_w_end, prev_forward, k_buff));
                 } else {
                     self.buff_pos -= 1;
                 }
@@ -187,17 +214,22 @@ impl Iterator for KmerMinimiserGenerator<'_> {
             // first time we are experiencing all minimizers
             if self.m_active == u64::MAX && self.buff.len() == self.wsize - self.msize + 1 {
                 for j in 0..self.buff.len() {
-                    if *self.buff.get(j).unwrap() < self.m_active {
+                    if self.buff.get(j).unwrap().0 < self.m_active {
                         self.buff_pos = j;
-                        self.m_active = *self.buff.get(j).unwrap();
+                        (self.m_active, self.m_forward) = *self.buff.get(j).unwrap();
                     }
                 }
             }
 
             if self.pos == self.seq.len() - 1 {
                 self.pos += 1;
-                // TODO return strand (implement only when needed)
-                return Some((self.m_active, self.m_window_start, self.seq.len(), k_buff));
+                return Some((
+                    self.m_active,
+                    self.m_window_start,
+                    self.seq.len(),
+                    self.m_forward,
+                    k_buff,
+                ));
             }
 
             self.pos += 1;
@@ -287,7 +319,7 @@ mod tests {
             ("TCAAAGCGCGATAGGCTAGCTAAAGCTAGCA", "AAAGCGC"),
         ];
         let mut i = 0;
-        for (m, _, _, ks) in mg {
+        for (m, _, _, _, ks) in mg {
             for k in ks {
                 println!("{}, {}", numeric_to_kmer(k, 31), numeric_to_kmer(m, 7));
                 assert_eq!(numeric_to_kmer(k, 31), expected[i].0);
@@ -315,7 +347,7 @@ mod tests {
             ("TCCATCGA", "ATCGA"),
         ];
         let mut i = 0;
-        for (m, _, _, ks) in mg {
+        for (m, _, _, _, ks) in mg {
             for k in ks {
                 println!(
                     "Kmer: {}, Minimiser: {}",
@@ -328,4 +360,19 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn minimisers_protein_test() {
+        use crate::{encoder::Protein, kmer::GenericKmerGenerator};
+
+        let seq = b"MKVLAAGIVGLLLAQWERTYXPASDFGHKLCVNMMKVLWWYYRPQ";
+        let mg = KmerMinimiserGenerator::with_encoder(seq, 9, 4, Protein);
+        let kmers: Vec<Kmer> = mg.flat_map(|(_, _, _, _, ks)| ks).collect();
+        // every 9-mer of the sequence, X breaks them as N does for DNA
+        let expected: Vec<Kmer> =
+            GenericKmerGenerator::<u64, Protein>::with_encoder(seq, 9, Protein)
+                .map(|(fmer, _)| fmer)
+                .collect();
+        assert_eq!(kmers, expected);
+    }
 }
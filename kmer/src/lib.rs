@@ -1,21 +1,192 @@
+pub mod encoder;
+pub mod hamming;
 pub mod kmer;
-pub mod minimiser;
 pub mod kmer_minimisers;
+pub mod minimiser;
+pub mod packed;
+pub mod revcomp;
+pub mod segments;
+pub mod sketch;
+pub mod spaced;
+pub mod stats;
+pub mod strand;
+pub mod superkmer;
+pub mod syncmer;
+use std::{
+    fmt::{Debug, Display},
+    hash::Hash,
+    ops::{BitAnd, BitOr, BitXor, Not, Rem, Shl, Shr},
+    str::FromStr,
+};
+
 pub type Kmer = u64;
 
-pub fn numeric_to_kmer(kmer: u64, k: usize) -> String {
+// unsigned integers holding 2-bit encoded k-mers, u128 allows k up to 64
+pub trait KmerInt:
+    Copy
+    + Ord
+    + Hash
+    + Default
+    + Debug
+    + Display
+    + FromStr
+    + Send
+    + Sync
+    + 'static
+    + Shl<usize, Output = Self>
+    + Shr<usize, Output = Self>
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Rem<Output = Self>
+    + Not<Output = Self>
+{
+    const MAX_KSIZE: usize;
+    const BYTES: usize;
+
+    fn from_u64(value: u64) -> Self;
+
+    // lowest 64 bits
+    fn as_u64(self) -> u64;
+
+    fn as_u128(self) -> u128;
+
+    fn mask(ksize: usize) -> Self {
+        if ksize >= Self::MAX_KSIZE {
+            !Self::default()
+        } else {
+            !(!Self::default() << (2 * ksize))
+        }
+    }
+
+    fn write_le(self, bytes: &mut Vec<u8>);
+
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_kmer_int {
+    ($t:ty) => {
+        impl KmerInt for $t {
+            const MAX_KSIZE: usize = <$t>::BITS as usize / 2;
+            const BYTES: usize = <$t>::BITS as usize / 8;
+
+            fn from_u64(value: u64) -> Self {
+                value as $t
+            }
+
+            fn as_u64(self) -> u64 {
+                self as u64
+            }
+
+            fn as_u128(self) -> u128 {
+                self as u128
+            }
+
+            fn write_le(self, bytes: &mut Vec<u8>) {
+                bytes.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read_le(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes[..Self::BYTES].try_into().unwrap())
+            }
+        }
+    };
+}
+
+impl_kmer_int!(u64);
+impl_kmer_int!(u128);
+
+// nucleic acid k-mers are written in, U takes the place of T in RNA, both are read as
+// the same 2-bit base
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Molecule {
+    #[default]
+    Dna,
+    Rna,
+}
+
+impl Molecule {
+    // letters of the 2-bit bases 00, 01, 10 and 11
+    pub fn bases(self) -> [char; 4] {
+        match self {
+            Molecule::Dna => ['A', 'C', 'G', 'T'],
+            Molecule::Rna => ['A', 'C', 'G', 'U'],
+        }
+    }
+
+    // a k-mer written in ACGT in the letters of this molecule
+    pub fn spell(self, kmer: &str) -> String {
+        match self {
+            Molecule::Dna => kmer.to_string(),
+            Molecule::Rna => kmer.replace('T', "U"),
+        }
+    }
+}
+
+pub fn numeric_to_kmer<K: KmerInt>(kmer: K, k: usize) -> String {
+    numeric_to_kmer_in(kmer, k, Molecule::Dna)
+}
+
+pub fn numeric_to_kmer_in<K: KmerInt>(kmer: K, k: usize, molecule: Molecule) -> String {
+    let bases = molecule.bases();
     let mut s = String::new();
     let mut kmer = kmer;
     for _ in 0..k {
-        let c = match kmer & 0b11 {
-            0b00 => 'A',
-            0b01 => 'C',
-            0b10 => 'G',
-            0b11 => 'T',
-            _ => panic!("Impossible!"),
-        };
-        s.push(c);
-        kmer >>= 2;
+        s.push(bases[(kmer & K::from_u64(0b11)).as_u64() as usize]);
+        kmer = kmer >> 2;
     }
     s.chars().rev().collect()
 }
+
+pub fn kmer_to_numeric(kmer: &str) -> Option<u64> {
+    if kmer.len() > 32 {
+        return None;
+    }
+    let mut value = 0;
+    for c in kmer.chars() {
+        let bits = match c {
+            'A' | 'a' => 0b00,
+            'C' | 'c' => 0b01,
+            'G' | 'g' => 0b10,
+            'T' | 't' | 'U' | 'u' => 0b11,
+            _ => return None,
+        };
+        value = (value << 2) | bits;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kmer_to_numeric_test() {
+        assert_eq!(kmer_to_numeric("ACGT"), Some(0b00011011));
+        assert_eq!(kmer_to_numeric("acgt"), Some(0b00011011));
+        assert_eq!(kmer_to_numeric("ACGU"), Some(0b00011011));
+        assert_eq!(kmer_to_numeric("ANGT"), None);
+        assert_eq!(
+            numeric_to_kmer(kmer_to_numeric("GATTACA").unwrap(), 7),
+            "GATTACA"
+        );
+        assert_eq!(numeric_to_kmer_in(0b00011011_u64, 4, Molecule::Rna), "ACGU");
+        assert_eq!(Molecule::Rna.spell("GATTACA"), "GAUUACA");
+    }
+
+    #[test]
+    fn kmer_int_test() {
+        assert_eq!(<u64 as KmerInt>::MAX_KSIZE, 32);
+        assert_eq!(<u128 as KmerInt>::MAX_KSIZE, 64);
+        assert_eq!(u64::mask(3), 0b111111);
+        assert_eq!(u64::mask(32), u64::MAX);
+        assert_eq!(u128::mask(40), (1_u128 << 80) - 1);
+        let kmer = (1_u128 << 100) | 0b1110;
+        let mut bytes = Vec::new();
+        kmer.write_le(&mut bytes);
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(u128::read_le(&bytes), kmer);
+        // 50-mer of A followed by TG
+        assert_eq!(numeric_to_kmer(0b1110_u128, 52), "A".repeat(50) + "TG");
+    }
+}
@@ -1,23 +1,49 @@
 use super::Kmer;
+use crate::{
+    encoder::{Dna, Encoder},
+    kmer::KmerGenerator,
+    packed::PackedSeq,
+    sketch::{hash64, hash64_inverse},
+    spaced::SpacedSeed,
+};
 use std::cmp::min;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::iter::Iterator;
 
-// https://github.com/lh3/minimap2/blob/0cc3cdca27f050fb80a19c90d25ecc6ab0b0907b/sketch.c#L9C1-L26C3
-const SEQ_NT4_TABLE: [u8; 256] = [
-    0, 1, 2, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 0, 4, 1, 4, 4, 4, 2, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 0, 4, 1, 4, 4, 4, 2, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-    4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
-];
-const REV_MASK: u64 = 3;
-
-pub struct MinimiserGenerator<'a> {
+// order key of the empty window
+const NO_KEY: (u64, u64) = (u64::MAX, u64::MAX);
+
+// abundances of canonical m-mers, minimisers are picked by (abundance, m-mer)
+// so rare m-mers win over repeat-derived ones, m-mers not listed have abundance 0
+#[derive(Debug, Clone, Default)]
+pub struct MinimiserWeights {
+    counts: HashMap<Kmer, u64>,
+}
+
+impl MinimiserWeights {
+    pub fn new(counts: HashMap<Kmer, u64>) -> Self {
+        Self { counts }
+    }
+
+    #[inline]
+    pub fn weight(&self, mmer: Kmer) -> u64 {
+        self.counts.get(&mmer).copied().unwrap_or(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+}
+
+// minimisers of any alphabet, m-mers of alphabets without a reverse strand are forward
+pub struct MinimiserGenerator<'a, E: Encoder = Dna> {
     seq: &'a [u8],
+    // bases encoded up front, read instead of seq
+    packed: Option<&'a PackedSeq>,
     pos: usize,
     wsize: usize,
     msize: usize,
@@ -27,66 +53,196 @@ pub struct MinimiserGenerator<'a> {
     m_val_f: u64,
     m_val_r: u64,
     m_val_l: usize,
-    m_active: u64,
+    m_active: (u64, u64),
+    // whether the active minimiser came from the forward strand
+    m_forward: bool,
     m_shift: u64,
-    buff: VecDeque<u64>,
+    buff: VecDeque<((u64, u64), bool)>,
     buff_pos: usize,
+    weights: Option<&'a MinimiserWeights>,
+    hashed: bool,
+    // m-mers span the seed and minimisers are the k-mers of its care positions
+    seed: Option<&'a SpacedSeed>,
+    // bits of the minimisers, hashes are invertible within them
+    key_mask: u64,
+    bits: usize,
+    encoder: E,
 }
 
 impl<'a> MinimiserGenerator<'a> {
     pub fn new(seq: &'a [u8], wsize: usize, msize: usize) -> Self {
+        Self::with_encoder(seq, wsize, msize, Dna)
+    }
+
+    // minimisers ordered by abundance first, ties go to the smaller m-mer
+    pub fn weighted(
+        seq: &'a [u8],
+        wsize: usize,
+        msize: usize,
+        weights: &'a MinimiserWeights,
+    ) -> Self {
+        let mut mg = Self::new(seq, wsize, msize);
+        mg.weights = Some(weights);
+        mg
+    }
+
+    // minimisers of a sequence packed once, so that passes over it do not encode it again
+    pub fn from_packed(packed: &'a PackedSeq, wsize: usize, msize: usize) -> Self {
+        let mut mg = Self::new(&[], wsize, msize);
+        mg.packed = Some(packed);
+        mg
+    }
+
+    // minimisers over a spaced seed such as 110101 instead of contiguous m-mers, so that a
+    // SNP at a 0 position keeps the minimiser, m-mers span the seed and minimisers are the
+    // k-mers of its weight, reverse complemented as spaced k-mers are
+    pub fn with_seed(mut self, seed: &'a SpacedSeed) -> Self {
+        let span = seed.span();
+        assert!(
+            span <= self.wsize,
+            "seed of span {} does not fit in a window of {}",
+            span,
+            self.wsize
+        );
+        self.msize = span;
+        self.m_mask = u64::MAX >> (64 - 2 * span);
+        self.m_shift = 2 * (span - 1) as u64;
+        self.key_mask = u64::MAX >> (64 - 2 * seed.weight());
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl<'a, E: Encoder> MinimiserGenerator<'a, E> {
+    // minimisers of another alphabet, msize symbols of encoder.bits() must fit in 64 bits
+    pub fn with_encoder(seq: &'a [u8], wsize: usize, msize: usize, encoder: E) -> Self {
+        let bits = encoder.bits();
+        assert!(
+            bits * msize <= 64,
+            "{} symbols of {} bits do not fit in a minimiser",
+            msize,
+            bits
+        );
         MinimiserGenerator {
             seq,
+            packed: None,
             wsize,
             msize,
             pos: 0,
             buff_pos: 0,
-            m_active: u64::MAX,
-            m_mask: (1_u64 << (2 * msize)) - 1,
+            m_active: NO_KEY,
+            m_forward: true,
+            m_mask: u64::MAX >> (64 - bits * msize),
             m_val_f: 0,
             m_val_r: 0,
             m_val_l: 0,
             m_window_end: 0,
             m_window_start: 0,
             buff: VecDeque::with_capacity(wsize - msize + 1),
-            m_shift: 2 * (msize - 1) as u64,
+            m_shift: (bits * (msize - 1)) as u64,
+            weights: None,
+            hashed: false,
+            seed: None,
+            key_mask: u64::MAX >> (64 - bits * msize),
+            bits,
+            encoder,
+        }
+    }
+
+    // m-mers are ordered by an invertible hash of their value instead of the value, so
+    // low complexity m-mers such as poly-A are no more likely to be minimisers than others
+    pub fn with_hash(mut self, hashed: bool) -> Self {
+        self.hashed = hashed;
+        self
+    }
+
+    #[inline]
+    fn key(&self, mmer: u64) -> (u64, u64) {
+        let order = if self.hashed {
+            hash64(mmer, self.key_mask)
+        } else {
+            mmer
+        };
+        match self.weights {
+            Some(weights) => (weights.weight(mmer), order),
+            None => (0, order),
+        }
+    }
+
+    #[inline]
+    fn seq_len(&self) -> usize {
+        self.packed.map_or(self.seq.len(), PackedSeq::len)
+    }
+
+    // code of the symbol at pos, None for symbols that break m-mers
+    #[inline]
+    fn code(&self, pos: usize) -> Option<u64> {
+        match self.packed {
+            Some(packed) => packed.get(pos),
+            None => self.encoder.encode(self.seq[pos]),
+        }
+    }
+
+    // forward and reverse minimiser candidates of the current m-mer
+    #[inline]
+    fn candidates(&self) -> (u64, u64) {
+        match self.seed {
+            Some(seed) => {
+                let fmer = seed.extract(self.m_val_f);
+                (fmer, KmerGenerator::rev_comp(fmer, seed.weight()))
+            }
+            None => (self.m_val_f, self.m_val_r),
+        }
+    }
+
+    #[inline]
+    fn mmer(&self, key: (u64, u64)) -> Kmer {
+        if self.hashed {
+            hash64_inverse(key.1, self.key_mask)
+        } else {
+            key.1
         }
     }
 }
 
 // technique adopted from https://github.com/lh3/minimap2/blob/0cc3cdca27f050fb80a19c90d25ecc6ab0b0907b/sketch.c#L77
-impl Iterator for MinimiserGenerator<'_> {
-    type Item = (Kmer, usize, usize);
+// items are (minimiser, start, end, forward), forward tells whether the first occurrence of
+// the canonical minimiser in the window came from the forward strand, palindromes are forward
+impl<E: Encoder> Iterator for MinimiserGenerator<'_, E> {
+    type Item = (Kmer, usize, usize, bool);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut min_m_val: u64;
-        let mut prev_m_val: u64;
+        let mut min_m_val: ((u64, u64), bool);
+        let mut prev_m_val: (u64, u64);
+        let mut prev_forward: bool;
         let mut prev_w_start: usize;
         let mut prev_w_end: usize;
 
         loop {
-            if self.pos == self.seq.len() {
+            if self.pos == self.seq_len() {
                 return None;
             }
-            let pos_char = self.seq[self.pos];
-            let pos_f_val = SEQ_NT4_TABLE[pos_char as usize] as u64;
-            let pos_r_val = pos_f_val ^ REV_MASK;
-
-            if pos_f_val < 4 {
+            if let Some(pos_f_val) = self.code(self.pos) {
                 // non ambiguous
                 // minimiser
-                self.m_val_f = ((self.m_val_f << 2) | pos_f_val) & self.m_mask;
-                self.m_val_r = (self.m_val_r >> 2) | (pos_r_val << self.m_shift);
+                self.m_val_f = ((self.m_val_f << self.bits) | pos_f_val) & self.m_mask;
+                if E::STRANDED {
+                    let pos_r_val = self.encoder.complement(pos_f_val);
+                    self.m_val_r = (self.m_val_r >> self.bits) | (pos_r_val << self.m_shift);
+                } else {
+                    self.m_val_r = self.m_val_f;
+                }
                 self.m_val_l += 1;
             } else {
                 // ambiguous
                 // check if we have passed a good complete window
                 let should_return = self.buff.len() == self.wsize - self.msize + 1;
                 prev_m_val = self.m_active;
+                prev_forward = self.m_forward;
                 prev_w_start = self.m_window_start;
                 prev_w_end = self.pos;
                 self.buff_pos = 0;
-                self.m_active = u64::MAX;
+                self.m_active = NO_KEY;
                 self.m_val_f = 0;
                 self.m_val_r = 0;
                 self.m_val_l = 0;
@@ -96,7 +252,12 @@ impl Iterator for MinimiserGenerator<'_> {
                 self.buff.clear();
                 self.pos += 1;
                 if should_return {
-                    return Some((prev_m_val, prev_w_start, prev_w_end));
+                    return Some((
+                        self.mmer(prev_m_val),
+                        prev_w_start,
+                        prev_w_end,
+                        prev_forward,
+                    ));
                 }
                 continue;
             }
@@ -108,7 +269,8 @@ impl Iterator for MinimiserGenerator<'_> {
 
             self.m_val_l -= 1;
             // self.w_val_l -= 1;
-            min_m_val = min(self.m_val_f, self.m_val_r);
+            let (fmer, rmer) = self.candidates();
+            min_m_val = (self.key(min(fmer, rmer)), fmer <= rmer);
 
             // minimiser buffer is full
             if self.buff.len() == self.wsize - self.msize + 1 {
@@ -118,35 +280,47 @@ impl Iterator for MinimiserGenerator<'_> {
 
                 // we have removed the minimum
                 if self.buff_pos == 0 {
-                    let mut new_min = u64::MAX;
+                    let mut new_min = (NO_KEY, true);
                     for j in 0..self.buff.len() {
-                        if *self.buff.get(j).unwrap() < new_min {
+                        if self.buff.get(j).unwrap().0 < new_min.0 {
                             self.buff_pos = j;
                             new_min = *self.buff.get(j).unwrap();
                         }
                     }
                     // minimiser changed
-                    if new_min != self.m_active {
+                    if new_min.0 != self.m_active {
                         self.m_window_end = self.pos;
                         prev_m_val = self.m_active;
+                        prev_forward = self.m_forward;
                         prev_w_start = self.m_window_start;
                         prev_w_end = self.m_window_end;
-                        self.m_active = new_min;
+                        (self.m_active, self.m_forward) = new_min;
                         self.m_window_start = self.pos - self.wsize + 1;
                         self.pos += 1;
-                        return Some((prev_m_val, prev_w_start, prev_w_end));
+                        return Some((
+                            self.mmer(prev_m_val),
+                            prev_w_start,
+                            prev_w_end,
+                            prev_forward,
+                        ));
                     }
-                } else if min_m_val < self.m_active {
+                } else if min_m_val.0 < self.m_active {
                     // break the window
                     self.m_window_end = self.pos;
                     prev_m_val = self.m_active;
+                    prev_forward = self.m_forward;
                     prev_w_start = self.m_window_start;
                     prev_w_end = self.m_window_end;
-                    self.m_active = min_m_val;
+                    (self.m_active, self.m_forward) = min_m_val;
                     self.buff_pos = self.buff.len() - 1;
                     self.m_window_start = self.pos - self.wsize + 1;
                     self.pos += 1;
-                    return Some((prev_m_val, prev_w_start, prev_w_end));
+                    return Some((
+                        self.mmer(prev_m_val),
+                        prev_w_start,
+                        prev_w_end,
+                        prev_forward,
+                    ));
                 } else {
                     self.buff_pos -= 1;
                 }
@@ -156,18 +330,23 @@ impl Iterator for MinimiserGenerator<'_> {
             }
 
             // first time we are experiencing all minimizers
-            if self.m_active == u64::MAX && self.buff.len() == self.wsize - self.msize + 1 {
+            if self.m_active == NO_KEY && self.buff.len() == self.wsize - self.msize + 1 {
                 for j in 0..self.buff.len() {
-                    if *self.buff.get(j).unwrap() < self.m_active {
+                    if self.buff.get(j).unwrap().0 < self.m_active {
                         self.buff_pos = j;
-                        self.m_active = *self.buff.get(j).unwrap();
+                        (self.m_active, self.m_forward) = *self.buff.get(j).unwrap();
                     }
                 }
             }
 
-            if self.pos == self.seq.len() - 1 {
+            if self.pos == self.seq_len() - 1 {
                 self.pos += 1;
-                return Some((self.m_active, self.m_window_start, self.seq.len()));
+                return Some((
+                    self.mmer(self.m_active),
+                    self.m_window_start,
+                    self.seq_len(),
+                    self.m_forward,
+                ));
             }
 
             self.pos += 1;
@@ -184,7 +363,7 @@ mod tests {
     fn minimisers_generated_test() {
         // Acquired from https://homolog.us/blogs/bioinfo/2017/10/25/intro-minimizer/
         let mut mg = MinimiserGenerator::new(b"ATGCGATATCGTAGGCGTCGATGGAGAGCTAGATCGATCGATCTAAATCCCGATCGATTCCGAGCGCGATCAAAGCGCGATAGGCTAGCTAAAGCTAGCA", 31, 7);
-        let (kmer, start, end) = mg.next().unwrap();
+        let (kmer, start, end, _) = mg.next().unwrap();
         assert_eq!(numeric_to_kmer(kmer, 7), "ACGATAT");
         assert_eq!(
             &mg.seq[start..end],
@@ -197,7 +376,7 @@ mod tests {
             String::from_utf8(mg.seq[start..end].to_vec()).unwrap(),
             numeric_to_kmer(kmer, 7)
         );
-        let (kmer, start, end) = mg.next().unwrap();
+        let (kmer, start, end, _) = mg.next().unwrap();
         assert_eq!(numeric_to_kmer(kmer, 7), "ACGCCTA");
         assert_eq!(
             &mg.seq[start..end],
@@ -210,7 +389,7 @@ mod tests {
             String::from_utf8(mg.seq[start..end].to_vec()).unwrap(),
             numeric_to_kmer(kmer, 7)
         );
-        let (kmer, start, end) = mg.next().unwrap();
+        let (kmer, start, end, _) = mg.next().unwrap();
         assert_eq!(numeric_to_kmer(kmer, 7), "AGAGCTA");
         assert_eq!(
             &mg.seq[start..end],
@@ -223,7 +402,7 @@ mod tests {
             String::from_utf8(mg.seq[start..end].to_vec()).unwrap(),
             numeric_to_kmer(kmer, 7)
         );
-        let (kmer, start, end) = mg.next().unwrap();
+        let (kmer, start, end, _) = mg.next().unwrap();
         assert_eq!(numeric_to_kmer(kmer, 7), "AAATCCC");
         assert_eq!(
             &mg.seq[start..end],
@@ -236,7 +415,7 @@ mod tests {
             String::from_utf8(mg.seq[start..end].to_vec()).unwrap(),
             numeric_to_kmer(kmer, 7)
         );
-        let (kmer, start, end) = mg.next().unwrap();
+        let (kmer, start, end, _) = mg.next().unwrap();
         assert_eq!(numeric_to_kmer(kmer, 7), "AATCCCG");
         assert_eq!(
             &mg.seq[start..end],
@@ -249,7 +428,7 @@ mod tests {
             String::from_utf8(mg.seq[start..end].to_vec()).unwrap(),
             numeric_to_kmer(kmer, 7)
         );
-        let (kmer, start, end) = mg.next().unwrap();
+        let (kmer, start, end, _) = mg.next().unwrap();
         assert_eq!(numeric_to_kmer(kmer, 7), "AATCGAT");
         assert_eq!(
             &mg.seq[start..end],
@@ -262,7 +441,7 @@ mod tests {
             String::from_utf8(mg.seq[start..end].to_vec()).unwrap(),
             numeric_to_kmer(kmer, 7)
         );
-        let (kmer, start, end) = mg.next().unwrap();
+        let (kmer, start, end, _) = mg.next().unwrap();
         assert_eq!(numeric_to_kmer(kmer, 7), "AAAGCGC");
         assert_eq!(
             &mg.seq[start..end],
@@ -291,7 +470,7 @@ mod tests {
             ("GCGTCGATGGA", "ATCGA"),
         ];
 
-        for (i, (kmer, start, end)) in mg.enumerate() {
+        for (i, (kmer, start, end, _)) in mg.enumerate() {
             assert_eq!(&seq[start..end], expected[i].0.as_bytes());
             assert_eq!(numeric_to_kmer(kmer, 5), expected[i].1);
             println!(
@@ -303,4 +482,126 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn minimisers_weighted_test() {
+        let seq = b"ATGCGATATCGNTAGGCGTCGATGGA";
+        let plain: Vec<_> = MinimiserGenerator::new(seq, 8, 5).collect();
+        let weights = MinimiserWeights::default();
+        let unweighted: Vec<_> = MinimiserGenerator::weighted(seq, 8, 5, &weights).collect();
+        assert_eq!(plain, unweighted);
+
+        // an abundant ATCGC gives way to the next rarest m-mer of the first window
+        let atcgc = crate::kmer_to_numeric("ATCGC").unwrap();
+        let weights = MinimiserWeights::new(HashMap::from([(atcgc, 100)]));
+        let (kmer, _, _, _) = MinimiserGenerator::weighted(seq, 8, 5, &weights)
+            .next()
+            .unwrap();
+        assert_ne!(kmer, atcgc);
+        assert_eq!(numeric_to_kmer(kmer, 5), "ATGCG");
+    }
+
+    #[test]
+    fn minimisers_strand_test() {
+        let seq = b"ATGCGATATCGTAGGCGTCGATGGAGAGCTAGATCGATCGATCTAAATCCCGATCGATTCCGAGCGCGATCAAAGCGCGATAGGCTAGCTAAAGCTAGCA";
+        let mins: Vec<_> = MinimiserGenerator::new(seq, 31, 7).collect();
+        // strand of the first occurrence of the minimiser in its window
+        for &(mmer, start, end, forward) in &mins {
+            let (_, strand) = crate::kmer::KmerGenerator::new(&seq[start..end], 7)
+                .canonical()
+                .find(|(kmer, _)| *kmer == mmer)
+                .unwrap();
+            assert_eq!(strand, forward);
+        }
+        assert!(mins.iter().any(|min| min.3) && mins.iter().any(|min| !min.3));
+
+        // poly-T is reported as poly-A of the reverse strand
+        let (mmer, _, _, forward) = MinimiserGenerator::new(b"TTTTTTTTTT", 8, 7).next().unwrap();
+        assert_eq!(
+            (numeric_to_kmer(mmer, 7).as_str(), forward),
+            ("AAAAAAA", false)
+        );
+        let (_, _, _, forward) = MinimiserGenerator::new(b"AAAAAAAAAA", 8, 7).next().unwrap();
+        assert!(forward);
+    }
+
+    #[test]
+    fn minimisers_hashed_test() {
+        let seq = b"AAAAAAAAAACGTGCATGCATCGATGCTAGCTAGNATCGATCGGGCATCAGCA";
+        let plain: Vec<_> = MinimiserGenerator::new(seq, 12, 5).collect();
+        let hashed: Vec<_> = MinimiserGenerator::new(seq, 12, 5)
+            .with_hash(true)
+            .collect();
+        // poly-A is the lexicographic minimiser of the first window but not the hashed one
+        assert_eq!(numeric_to_kmer(plain[0].0, 5), "AAAAA");
+        assert_ne!(hashed[0].0, plain[0].0);
+        // minimisers are m-mers of their windows, with the smallest hash
+        for (mmer, start, end, _) in hashed {
+            let hashes: Vec<(u64, u64)> = crate::kmer::KmerGenerator::new(&seq[start..end], 5)
+                .map(|(fmer, rmer)| {
+                    let mmer = u64::min(fmer, rmer);
+                    (hash64(mmer, (1 << 10) - 1), mmer)
+                })
+                .collect();
+            assert_eq!(hashes.iter().min().unwrap().1, mmer);
+        }
+    }
+
+    #[test]
+    fn minimisers_spaced_test() {
+        use crate::spaced::{SpacedKmerGenerator, SpacedSeed};
+
+        let seq = b"ATGCGATATCGNTAGGCGTCGATGGAGAGCTAGATCGATCGATCTAAATCCCGATCGATTCCGAGCGCGATCAAAGCG";
+        let seed = SpacedSeed::parse("1101011").unwrap();
+        let mins: Vec<_> = MinimiserGenerator::new(seq, 15, 5)
+            .with_seed(&seed)
+            .collect();
+        assert!(!mins.is_empty());
+        // smallest canonical spaced k-mer of each window
+        for (mmer, start, end, forward) in mins {
+            let (fmer, rmer) = SpacedKmerGenerator::new(&seq[start..end], &seed)
+                .min_by_key(|&(fmer, rmer)| u64::min(fmer, rmer))
+                .unwrap();
+            assert_eq!(mmer, u64::min(fmer, rmer));
+            assert_eq!(forward, fmer <= rmer);
+        }
+
+        // a SNP at a 0 position of the seed keeps the minimiser
+        let seed = SpacedSeed::parse("11011").unwrap();
+        let (snp, _, _, _) = MinimiserGenerator::new(b"ACTGA", 5, 5)
+            .with_seed(&seed)
+            .next()
+            .unwrap();
+        let (reference, _, _, _) = MinimiserGenerator::new(b"ACGGA", 5, 5)
+            .with_seed(&seed)
+            .next()
+            .unwrap();
+        assert_eq!(snp, reference);
+
+        // hashed spaced minimisers are k-mers of the seed weight
+        let hashed: Vec<_> = MinimiserGenerator::new(seq, 15, 5)
+            .with_seed(&seed)
+            .with_hash(true)
+            .collect();
+        assert!(hashed.iter().all(|&(mmer, _, _, _)| mmer < 1 << 8));
+    }
+
+    #[test]
+    fn minimisers_protein_test() {
+        use crate::{encoder::Protein, kmer::GenericKmerGenerator};
+
+        let seq = b"MKVLAAGIVGLLLAQWERTYXPASDFGHKLCVNMMKVLWWYYRPQ";
+        let mins: Vec<_> = MinimiserGenerator::with_encoder(seq, 9, 4, Protein).collect();
+        assert!(!mins.is_empty());
+        // smallest 4-mer of each window, proteins have a single strand
+        for (mmer, start, end, forward) in mins {
+            let smallest =
+                GenericKmerGenerator::<u64, Protein>::with_encoder(&seq[start..end], 4, Protein)
+                    .map(|(fmer, _)| fmer)
+                    .min()
+                    .unwrap();
+            assert_eq!(mmer, smallest);
+            assert!(forward);
+        }
+    }
 }
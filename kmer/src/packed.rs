@@ -0,0 +1,178 @@
+use crate::encoder::{Dna, Encoder};
+
+const BASES: &[u8; 4] = b"ACGT";
+// bases per word
+const WORD_BASES: usize = 32;
+
+// sequence encoded once into 2 bits per base, 32 bases per word from the low bits,
+// non ACGTU symbols are kept as ambiguous bases that break k-mers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackedSeq {
+    words: Vec<u64>,
+    // one bit per base
+    ambiguous: Vec<u64>,
+    len: usize,
+}
+
+impl PackedSeq {
+    pub fn new(seq: &[u8]) -> Self {
+        let words = seq.len().div_ceil(WORD_BASES);
+        let mut packed = Self {
+            words: vec![0; words],
+            ambiguous: vec![0; words],
+            len: seq.len(),
+        };
+        for (word, chunk) in seq.chunks(WORD_BASES).enumerate() {
+            let (bits, ambiguous) = encode_word(chunk);
+            packed.words[word] = bits;
+            packed.ambiguous[word] = ambiguous;
+        }
+        packed
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // 2-bit code of the base at pos, None for ambiguous bases
+    #[inline]
+    pub fn get(&self, pos: usize) -> Option<u64> {
+        let (word, offset) = (pos / WORD_BASES, pos % WORD_BASES);
+        if (self.ambiguous[word] >> offset) & 1 == 1 {
+            None
+        } else {
+            Some((self.words[word] >> (2 * offset)) & 3)
+        }
+    }
+
+    // upper case ACGT with N for ambiguous bases
+    pub fn unpack(&self) -> Vec<u8> {
+        (0..self.len)
+            .map(|pos| match self.get(pos) {
+                Some(code) => BASES[code as usize],
+                None => b'N',
+            })
+            .collect()
+    }
+}
+
+// 2-bit codes and ambiguous bits of up to 32 bases
+#[inline]
+fn encode_word(chunk: &[u8]) -> (u64, u64) {
+    let (mut bits, mut ambiguous) = (0, 0);
+    let start = encode_simd(chunk, &mut bits, &mut ambiguous);
+    for (pos, &base) in chunk.iter().enumerate().skip(start) {
+        match Dna.encode(base) {
+            Some(code) => bits |= code << (2 * pos),
+            None => ambiguous |= 1 << pos,
+        }
+    }
+    (bits, ambiguous)
+}
+
+// encodes blocks of 16 bases with SSE2, which every x86_64 target has, returns the number
+// of bases encoded
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn encode_simd(chunk: &[u8], bits: &mut u64, ambiguous: &mut u64) -> usize {
+    let blocks = chunk.len() / 16;
+    for block in 0..blocks {
+        let (block_bits, block_ambiguous) = unsafe { x86::encode16(&chunk[16 * block..]) };
+        *bits |= (block_bits as u64) << (32 * block);
+        *ambiguous |= (block_ambiguous as u64) << (16 * block);
+    }
+    16 * blocks
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+#[inline]
+fn encode_simd(_chunk: &[u8], _bits: &mut u64, _ambiguous: &mut u64) -> usize {
+    0
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    // codes of the first 16 bases and their ambiguous bits, ((base >> 1) ^ (base >> 2)) & 3
+    // is 0, 1, 2 and 3 for A, C, G and T or U of either case, bytes 0 to 3 are codes
+    // already, as Dna encodes them
+    #[inline]
+    pub(super) unsafe fn encode16(bases: &[u8]) -> (u32, u16) {
+        assert!(bases.len() >= 16);
+        let block = _mm_loadu_si128(bases.as_ptr() as *const __m128i);
+        let mut letters = _mm_setzero_si128();
+        for base in *b"ACGTUacgtu" {
+            letters = _mm_or_si128(letters, _mm_cmpeq_epi8(block, _mm_set1_epi8(base as i8)));
+        }
+        let raw = _mm_cmpeq_epi8(_mm_and_si128(block, _mm_set1_epi8(!3)), _mm_setzero_si128());
+        // 16 bit shifts only move bits of the next base into the high bits of each byte
+        let codes = _mm_xor_si128(_mm_srli_epi16(block, 1), _mm_srli_epi16(block, 2));
+        let codes = _mm_or_si128(
+            _mm_and_si128(_mm_and_si128(codes, _mm_set1_epi8(3)), letters),
+            _mm_and_si128(block, raw),
+        );
+        let valid = _mm_or_si128(letters, raw);
+        let mut lanes = [0_u8; 16];
+        _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, codes);
+        let bits = lanes
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (pos, &code)| bits | (code as u32) << (2 * pos));
+        (bits, !(_mm_movemask_epi8(valid) as u16))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        kmer::{GenericKmerGenerator, KmerGenerator},
+        minimiser::MinimiserGenerator,
+    };
+
+    #[test]
+    fn packed_seq_test() {
+        // long enough for SIMD blocks, a partial word and a scalar tail
+        let seq = b"ACGTacgtUuNnRY-.ACGTTGCAACGTTGCAAAAAACCCCGGGGTTTTxACGTAC";
+        let packed = PackedSeq::new(seq);
+        assert_eq!(packed.len(), seq.len());
+        for (pos, &base) in seq.iter().enumerate() {
+            assert_eq!(packed.get(pos), Dna.encode(base));
+        }
+        let unpacked: Vec<u8> = seq
+            .iter()
+            .map(|&base| match Dna.encode(base) {
+                Some(code) => BASES[code as usize],
+                None => b'N',
+            })
+            .collect();
+        assert_eq!(packed.unpack(), unpacked);
+        // every byte is encoded as Dna encodes it
+        let bytes: Vec<u8> = (0..=255).collect();
+        let packed = PackedSeq::new(&bytes);
+        for (pos, &base) in bytes.iter().enumerate() {
+            assert_eq!(packed.get(pos), Dna.encode(base));
+        }
+        assert!(PackedSeq::new(b"").is_empty());
+    }
+
+    #[test]
+    fn packed_generators_test() {
+        let seq = b"ATGCGATATCGNTAGGCGTCGATGGAGAGCTAGATCGATCGATCTAAATCCCGATCGATTCCGAGCGCGATCAAAGCGCGATAGG";
+        let packed = PackedSeq::new(seq);
+        let kmers: Vec<_> = KmerGenerator::new(seq, 7).collect();
+        let packed_kmers: Vec<_> = KmerGenerator::from_packed(&packed, 7).collect();
+        assert_eq!(kmers, packed_kmers);
+        let wide: Vec<_> = GenericKmerGenerator::<u128>::new(seq, 40).collect();
+        let packed_wide: Vec<_> = GenericKmerGenerator::<u128>::from_packed(&packed, 40).collect();
+        assert_eq!(wide, packed_wide);
+        let mins: Vec<_> = MinimiserGenerator::new(seq, 15, 7).collect();
+        let packed_mins: Vec<_> = MinimiserGenerator::from_packed(&packed, 15, 7).collect();
+        assert_eq!(mins, packed_mins);
+    }
+}
@@ -0,0 +1,128 @@
+// reverse complement of a sequence, keeping the case of bases, U pairs with A and
+// other symbols such as N are kept as they are
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    let mut rc = seq.to_vec();
+    reverse_complement_in_place(&mut rc);
+    rc
+}
+
+// reverse complement without allocating, blocks of 16 bases from both ends are swapped
+// with SIMD and the middle base by base
+pub fn reverse_complement_in_place(seq: &mut [u8]) {
+    let (mut left, mut right) = reverse_complement_simd(seq);
+    while left + 1 < right {
+        let base = complement(seq[left]);
+        seq[left] = complement(seq[right - 1]);
+        seq[right - 1] = base;
+        left += 1;
+        right -= 1;
+    }
+    if left + 1 == right {
+        seq[left] = complement(seq[left]);
+    }
+}
+
+#[inline]
+fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' | b'U' => b'A',
+        b'a' => b't',
+        b'c' => b'g',
+        b'g' => b'c',
+        b't' | b'u' => b'a',
+        base => base,
+    }
+}
+
+// swaps blocks from both ends while they do not overlap, returns the bounds of the rest
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn reverse_complement_simd(seq: &mut [u8]) -> (usize, usize) {
+    let (mut left, mut right) = (0, seq.len());
+    while right - left >= 32 {
+        unsafe { x86::swap16(seq, left, right - 16) };
+        left += 16;
+        right -= 16;
+    }
+    (left, right)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+#[inline]
+fn reverse_complement_simd(seq: &mut [u8]) -> (usize, usize) {
+    (0, seq.len())
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    // SSE2 only, which every x86_64 target has, so bytes are reversed by shuffling 32 and
+    // 16 bit lanes and then swapping the bytes of each 16 bit lane
+    #[inline]
+    unsafe fn reverse_complement16(block: __m128i) -> __m128i {
+        let block = _mm_shuffle_epi32(block, 0b00_01_10_11);
+        let block = _mm_shufflelo_epi16(block, 0b10_11_00_01);
+        let block = _mm_shufflehi_epi16(block, 0b10_11_00_01);
+        let block = _mm_or_si128(_mm_slli_epi16(block, 8), _mm_srli_epi16(block, 8));
+        let mut rc = block;
+        for (base, comp) in [
+            (b'A', b'T'),
+            (b'C', b'G'),
+            (b'G', b'C'),
+            (b'T', b'A'),
+            (b'U', b'A'),
+            (b'a', b't'),
+            (b'c', b'g'),
+            (b'g', b'c'),
+            (b't', b'a'),
+            (b'u', b'a'),
+        ] {
+            let hit = _mm_cmpeq_epi8(block, _mm_set1_epi8(base as i8));
+            rc = _mm_or_si128(
+                _mm_andnot_si128(hit, rc),
+                _mm_and_si128(hit, _mm_set1_epi8(comp as i8)),
+            );
+        }
+        rc
+    }
+
+    // reverse complements of the blocks at left and right stored in place of each other
+    #[inline]
+    pub(super) unsafe fn swap16(seq: &mut [u8], left: usize, right: usize) {
+        assert!(left + 16 <= right && right + 16 <= seq.len());
+        let ptr = seq.as_mut_ptr();
+        let lblock = _mm_loadu_si128(ptr.add(left) as *const __m128i);
+        let rblock = _mm_loadu_si128(ptr.add(right) as *const __m128i);
+        _mm_storeu_si128(ptr.add(left) as *mut __m128i, reverse_complement16(rblock));
+        _mm_storeu_si128(ptr.add(right) as *mut __m128i, reverse_complement16(lblock));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverse_complement_in_place_test() {
+        // every length around the block sizes, against the base by base complement
+        let bases = b"ACGTUacgtuNnRY-.";
+        for len in 0..100 {
+            let seq: Vec<u8> = (0..len).map(|pos| bases[(pos * 7) % bases.len()]).collect();
+            let expected: Vec<u8> = seq.iter().rev().map(|&base| complement(base)).collect();
+            let mut rc = seq.clone();
+            reverse_complement_in_place(&mut rc);
+            assert_eq!(rc, expected);
+            assert_eq!(reverse_complement(&seq), expected);
+        }
+        // every byte is complemented as it is base by base
+        let mut bytes: Vec<u8> = (0..=255).collect();
+        reverse_complement_in_place(&mut bytes);
+        for (pos, &base) in bytes.iter().rev().enumerate() {
+            assert_eq!(base, complement(pos as u8));
+        }
+    }
+}
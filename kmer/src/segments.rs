@@ -0,0 +1,50 @@
+// records longer than this are split into segments that are processed in parallel
+pub const SEGMENT_SIZE: usize = 1 << 20;
+
+// overlapping (start, end) ranges of a sequence of len bases, each holding the k-mers
+// starting at size consecutive positions (fewer in the last), so every k-mer is in
+// exactly one segment and segment starts are multiples of size
+pub fn segments(len: usize, ksize: usize, size: usize) -> Vec<(usize, usize)> {
+    (0..(len + 1).saturating_sub(ksize))
+        .step_by(usize::max(1, size))
+        .map(|start| (start, usize::min(len, start + size + ksize - 1)))
+        .collect()
+}
+
+// (start, end) ranges of the windows of size bases every step bases along a sequence of
+// len bases, a partial window at the end is left out unless the sequence is shorter
+// than one window, which is then a window of its own
+pub fn windows(len: usize, size: usize, step: usize) -> Vec<(usize, usize)> {
+    if len <= size {
+        return vec![(0, len)];
+    }
+    (0..=len - size)
+        .step_by(usize::max(1, step))
+        .map(|start| (start, start + size))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kmer::KmerGenerator;
+
+    #[test]
+    fn segments_test() {
+        assert_eq!(segments(10, 3, 4), vec![(0, 6), (4, 10)]);
+        assert_eq!(segments(2, 3, 4), vec![]);
+        let seq = b"ACGTTGCANNACGTAGCTAGCTAGGATCGATCGANACG";
+        let kmers: Vec<(u64, u64)> = segments(seq.len(), 5, 7)
+            .into_iter()
+            .flat_map(|(start, end)| KmerGenerator::new(&seq[start..end], 5))
+            .collect();
+        assert_eq!(kmers, KmerGenerator::new(seq, 5).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn windows_test() {
+        assert_eq!(windows(10, 4, 3), vec![(0, 4), (3, 7), (6, 10)]);
+        assert_eq!(windows(10, 4, 4), vec![(0, 4), (4, 8)]);
+        assert_eq!(windows(3, 4, 2), vec![(0, 3)]);
+    }
+}
@@ -0,0 +1,401 @@
+use super::{kmer::KmerGenerator, Kmer};
+use std::collections::BTreeSet;
+
+// invertible 64-bit mixer so hashes of canonical k-mers are uniformly spread
+// https://github.com/lh3/minimap2/blob/0cc3cdca27f050fb80a19c90d25ecc6ab0b0907b/sketch.c#L28
+pub fn hash64(key: u64, mask: u64) -> u64 {
+    let mut key = (!key).wrapping_add(key << 21) & mask;
+    key ^= key >> 24;
+    key = (key.wrapping_add(key << 3)).wrapping_add(key << 8) & mask;
+    key ^= key >> 14;
+    key = (key.wrapping_add(key << 2)).wrapping_add(key << 4) & mask;
+    key ^= key >> 28;
+    key = key.wrapping_add(key << 31) & mask;
+    key
+}
+
+// inverse of hash64 for keys within the same mask
+pub fn hash64_inverse(key: u64, mask: u64) -> u64 {
+    // key + (key << 31)
+    let tmp = key.wrapping_sub(key << 31);
+    let mut key = key.wrapping_sub(tmp << 31) & mask;
+    // key ^ (key >> 28)
+    let tmp = key ^ (key >> 28);
+    key ^= tmp >> 28;
+    // key * 21
+    key = key.wrapping_mul(14933078535860113213) & mask;
+    // key ^ (key >> 14)
+    let tmp = key ^ (key >> 14);
+    let tmp = key ^ (tmp >> 14);
+    let tmp = key ^ (tmp >> 14);
+    key ^= tmp >> 14;
+    // key * 265
+    key = key.wrapping_mul(15244667743933553977) & mask;
+    // key ^ (key >> 24)
+    let tmp = key ^ (key >> 24);
+    key ^= tmp >> 24;
+    // !key + (key << 21)
+    let tmp = !key;
+    let tmp = !(key.wrapping_sub(tmp << 21));
+    let tmp = !(key.wrapping_sub(tmp << 21));
+    !(key.wrapping_sub(tmp << 21)) & mask
+}
+
+// same value for a k-mer and its reverse complement, unrelated to their order
+pub fn strand_neutral_hash(fmer: Kmer, rmer: Kmer) -> u64 {
+    hash64(fmer, u64::MAX).wrapping_add(hash64(rmer, u64::MAX))
+}
+
+// bottom-k MinHash sketch of canonical k-mers, or a scaled (FracMinHash) sketch keeping
+// every hash in the lowest 1/scale of the hash space
+#[derive(Debug, Clone)]
+pub struct Sketch {
+    ksize: usize,
+    size: usize,
+    mask: u64,
+    // largest hash kept, the mask for bottom-k sketches
+    max_hash: u64,
+    hashes: BTreeSet<u64>,
+}
+
+impl Sketch {
+    pub fn new(ksize: usize, size: usize) -> Self {
+        let mask = if ksize == 32 {
+            u64::MAX
+        } else {
+            (1_u64 << (2 * ksize)) - 1
+        };
+        Self {
+            ksize,
+            size,
+            mask,
+            max_hash: mask,
+            hashes: BTreeSet::new(),
+        }
+    }
+
+    // FracMinHash as in sourmash, grows with the number of distinct k-mers
+    pub fn scaled(ksize: usize, scale: u64) -> Self {
+        let mut sketch = Self::new(ksize, usize::MAX);
+        sketch.max_hash = sketch.mask / u64::max(1, scale);
+        sketch
+    }
+
+    pub fn is_scaled(&self) -> bool {
+        self.size == usize::MAX
+    }
+
+    pub fn ksize(&self) -> usize {
+        self.ksize
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    pub fn hashes(&self) -> impl Iterator<Item = &u64> {
+        self.hashes.iter()
+    }
+
+    pub fn add_hash(&mut self, hash: u64) {
+        if hash > self.max_hash {
+            return;
+        }
+        if self.hashes.len() == self.size && hash >= *self.hashes.last().unwrap() {
+            return;
+        }
+        self.hashes.insert(hash);
+        if self.hashes.len() > self.size {
+            self.hashes.pop_last();
+        }
+    }
+
+    pub fn add_seq(&mut self, seq: &[u8]) {
+        self.add_kmers(
+            KmerGenerator::new(seq, self.ksize)
+                .canonical()
+                .map(|(kmer, _)| kmer),
+        );
+    }
+
+    // canonical k-mers of ksize from any source, such as a count table
+    pub fn add_kmers(&mut self, kmers: impl IntoIterator<Item = Kmer>) {
+        for kmer in kmers {
+            self.add_hash(hash64(kmer, self.mask));
+        }
+    }
+
+    pub fn merge(&mut self, other: &Sketch) {
+        other.hashes.iter().for_each(|&hash| self.add_hash(hash));
+    }
+
+    // hashes up to this value are all in the sketch
+    fn covered(&self) -> u64 {
+        if self.hashes.len() < self.size {
+            self.max_hash
+        } else {
+            *self.hashes.last().unwrap()
+        }
+    }
+
+    // hashes of self both sketches cover, and how many of them are in other
+    fn shared(&self, other: &Sketch) -> (usize, usize) {
+        let covered = u64::min(self.covered(), other.covered());
+        let hashes = self.hashes.range(..=covered);
+        let total = hashes.clone().count();
+        let shared = hashes.filter(|hash| other.hashes.contains(hash)).count();
+        (total, shared)
+    }
+
+    // estimated from the bottom-k of the union, or from the hashes both scaled sketches keep
+    pub fn jaccard(&self, other: &Sketch) -> f64 {
+        if self.is_scaled() || other.is_scaled() {
+            let (total, shared) = self.shared(other);
+            let (other_total, _) = other.shared(self);
+            let union = total + other_total - shared;
+            return if union == 0 {
+                0_f64
+            } else {
+                shared as f64 / union as f64
+            };
+        }
+        let size = usize::min(self.size, other.size);
+        let mut union = self.hashes.union(&other.hashes).take(size).peekable();
+        if union.peek().is_none() {
+            return 0_f64;
+        }
+        let mut total = 0;
+        let mut shared = 0;
+        for hash in union {
+            total += 1;
+            if self.hashes.contains(hash) && other.hashes.contains(hash) {
+                shared += 1;
+            }
+        }
+        shared as f64 / total as f64
+    }
+
+    // share of the k-mers of self found in other
+    pub fn containment(&self, other: &Sketch) -> f64 {
+        let (total, shared) = self.shared(other);
+        if total == 0 {
+            0_f64
+        } else {
+            shared as f64 / total as f64
+        }
+    }
+
+    // mash distance https://doi.org/10.1186/s13059-016-0997-x
+    pub fn distance(&self, other: &Sketch) -> f64 {
+        let jaccard = self.jaccard(other);
+        if jaccard == 0_f64 {
+            return 1_f64;
+        }
+        if jaccard == 1_f64 {
+            return 0_f64;
+        }
+        let distance = -(2_f64 * jaccard / (1_f64 + jaccard)).ln() / self.ksize as f64;
+        f64::min(1_f64, distance)
+    }
+}
+
+// HyperLogLog cardinality sketch of 2^precision 6-bit registers held as bytes
+// https://doi.org/10.46298/dmtcs.3545 with linear counting for small cardinalities
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    precision: u32,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    // precision between 4 and 18, the relative error is about 1.04 / sqrt(2^precision)
+    pub fn new(precision: u32) -> Self {
+        let precision = precision.clamp(4, 18);
+        Self {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    // hashes must be uniformly spread over 64 bits, as hash64 of a full mask
+    #[inline]
+    pub fn add_hash(&mut self, hash: u64) {
+        let idx = (hash >> (64 - self.precision)) as usize;
+        // leading zeros of the remaining bits, with a stop bit so it is at most 64 - precision
+        let rank = ((hash << self.precision) | (1 << (self.precision - 1))).leading_zeros() + 1;
+        self.registers[idx] = u8::max(self.registers[idx], rank as u8);
+    }
+
+    // union of the sketched sets, precisions must match
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        assert_eq!(self.precision, other.precision);
+        self.registers
+            .iter_mut()
+            .zip(other.registers.iter())
+            .for_each(|(reg, &other)| *reg = u8::max(*reg, other));
+    }
+
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1_f64 + 1.079 / m),
+        };
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&reg| 2_f64.powi(-(reg as i32)))
+            .sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&reg| reg == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash64_inverse_test() {
+        for mask in [(1_u64 << 20) - 1, (1_u64 << 62) - 1, u64::MAX] {
+            for key in [0, 1, 12345, 0xabcdef, mask] {
+                let key = key & mask;
+                assert_eq!(hash64_inverse(hash64(key, mask), mask), key);
+            }
+        }
+    }
+
+    #[test]
+    fn hyperloglog_test() {
+        let mut hll = HyperLogLog::new(12);
+        assert_eq!(hll.estimate(), 0.0);
+        for key in 0..10_000_u64 {
+            hll.add_hash(hash64(key, u64::MAX));
+        }
+        // duplicates do not change the estimate
+        let estimate = hll.estimate();
+        for key in 0..5_000_u64 {
+            hll.add_hash(hash64(key, u64::MAX));
+        }
+        assert_eq!(hll.estimate(), estimate);
+        assert!((estimate - 10_000.0).abs() < 500.0);
+
+        let mut other = HyperLogLog::new(12);
+        for key in 5_000..20_000_u64 {
+            other.add_hash(hash64(key, u64::MAX));
+        }
+        hll.merge(&other);
+        assert!((hll.estimate() - 20_000.0).abs() < 1_000.0);
+    }
+
+    #[test]
+    fn sketch_test() {
+        let seq = b"ACGTTGCATGCATTAGCTAGCATCGATCGATTAGCGCGATCGATTTAGCGCAGTCGATGCATGC";
+        let mut a = Sketch::new(5, 10);
+        a.add_seq(seq);
+        assert_eq!(a.len(), 10);
+        // bottom hashes are kept in order
+        let hashes: Vec<u64> = a.hashes().cloned().collect();
+        assert!(hashes.windows(2).all(|w| w[0] < w[1]));
+
+        let mut b = Sketch::new(5, 10);
+        b.add_seq(seq);
+        assert_eq!(a.jaccard(&b), 1.0);
+        assert_eq!(a.distance(&b), 0.0);
+
+        // reverse complement gives the same canonical sketch
+        let rc: Vec<u8> = seq
+            .iter()
+            .rev()
+            .map(|&c| match c {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                _ => b'A',
+            })
+            .collect();
+        let mut c = Sketch::new(5, 10);
+        c.add_seq(&rc);
+        assert_eq!(a.jaccard(&c), 1.0);
+
+        let mut d = Sketch::new(5, 10);
+        d.add_seq(b"TTTTTTTTTTTT");
+        assert_eq!(a.jaccard(&d), 0.0);
+        assert_eq!(a.distance(&d), 1.0);
+
+        let mut e = Sketch::new(5, 10);
+        e.merge(&a);
+        assert_eq!(e.jaccard(&a), 1.0);
+        assert!(Sketch::new(5, 10).is_empty());
+    }
+
+    #[test]
+    fn sketch_scaled_test() {
+        // pseudo-random bases from a linear congruential generator
+        let mut state = 42_u64;
+        let seq: Vec<u8> = (0..20_000)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                b"ACGT"[(state >> 62) as usize]
+            })
+            .collect();
+        let (whole, half) = (&seq[..], &seq[..10_000]);
+        let mut a = Sketch::scaled(21, 10);
+        a.add_seq(whole);
+        let mut b = Sketch::scaled(21, 10);
+        b.add_seq(half);
+        assert!(a.is_scaled() && !Sketch::new(21, 10).is_scaled());
+        // about a tenth of the distinct k-mers are kept
+        assert!((1_500..2_500).contains(&a.len()));
+        assert!(a.hashes().all(|&hash| hash <= a.mask / 10));
+
+        // half is contained in whole, whole is about half in half
+        assert_eq!(b.containment(&a), 1.0);
+        assert!((a.containment(&b) - 0.5).abs() < 0.05);
+        assert!((a.jaccard(&b) - 0.5).abs() < 0.05);
+        assert_eq!(a.jaccard(&a), 1.0);
+
+        // k-mers from a stream give the same sketch
+        let mut c = Sketch::scaled(21, 10);
+        c.add_kmers(
+            KmerGenerator::new(half, 21)
+                .canonical()
+                .map(|(kmer, _)| kmer),
+        );
+        assert_eq!(
+            c.hashes().collect::<Vec<_>>(),
+            b.hashes().collect::<Vec<_>>()
+        );
+
+        // bottom-k sketches estimate containment over the hashes both cover
+        let mut d = Sketch::new(21, 500);
+        d.add_seq(half);
+        let mut e = Sketch::new(21, 500);
+        e.add_seq(whole);
+        assert!(d.containment(&e) > 0.9);
+        assert!((e.containment(&d) - 0.5).abs() < 0.15);
+        assert_eq!(Sketch::scaled(21, 10).containment(&a), 0.0);
+    }
+
+    #[test]
+    fn strand_neutral_hash_test() {
+        for (fmer, rmer) in KmerGenerator::new(b"ACGTTGCATGCATTAGCTAGCATC", 5) {
+            assert_eq!(
+                strand_neutral_hash(fmer, rmer),
+                strand_neutral_hash(rmer, fmer)
+            );
+        }
+        assert_ne!(strand_neutral_hash(0, 1023), strand_neutral_hash(1, 1022));
+    }
+}
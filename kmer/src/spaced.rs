@@ -0,0 +1,208 @@
+use crate::{
+    encoder::{Dna, Encoder},
+    Kmer,
+};
+
+// spaced seed such as 110101, k-mers are read at the care (1) positions of each window of
+// the span of the seed, so that bases at the other positions, mismatches or Ns, do not
+// change them
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpacedSeed {
+    care: Vec<usize>,
+    span: usize,
+}
+
+impl SpacedSeed {
+    // a pattern of 0s and 1s starting and ending with 1, of at most 32 positions
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        if !pattern.starts_with('1')
+            || !pattern.ends_with('1')
+            || pattern.chars().any(|c| c != '0' && c != '1')
+        {
+            return Err(format!(
+                "Seed must be 0s and 1s starting and ending with 1, got {}",
+                pattern
+            ));
+        }
+        if pattern.len() > 32 {
+            return Err(format!(
+                "Seed spans at most 32 bases, got {}",
+                pattern.len()
+            ));
+        }
+        Ok(Self {
+            care: pattern
+                .chars()
+                .enumerate()
+                .filter(|(_, c)| *c == '1')
+                .map(|(pos, _)| pos)
+                .collect(),
+            span: pattern.len(),
+        })
+    }
+
+    // bases covered by a k-mer
+    pub fn span(&self) -> usize {
+        self.span
+    }
+
+    // care positions, the size of the k-mers
+    pub fn weight(&self) -> usize {
+        self.care.len()
+    }
+
+    pub fn pattern(&self) -> String {
+        let mut pattern = vec!['0'; self.span];
+        self.care.iter().for_each(|&pos| pattern[pos] = '1');
+        pattern.into_iter().collect()
+    }
+
+    // k-mer of the care positions of a window of span 2-bit bases, first base highest
+    #[inline]
+    pub fn extract(&self, window: u64) -> Kmer {
+        self.extract_symbols(window, 2)
+    }
+
+    // k-mer of the care positions of a window of span symbols of bits each
+    #[inline]
+    fn extract_symbols(&self, window: u64, bits: usize) -> Kmer {
+        let mask = u64::MAX >> (64 - bits);
+        self.care.iter().fold(0, |kmer, &pos| {
+            (kmer << bits) | ((window >> (bits * (self.span - 1 - pos))) & mask)
+        })
+    }
+}
+
+// k-mers of the care positions of every window of a sequence, with their reverse
+// complements, windows with an N at a care position are skipped
+pub struct SpacedKmerGenerator<'a, E: Encoder = Dna> {
+    seq: &'a [u8],
+    seed: &'a SpacedSeed,
+    pos: usize,
+    // symbols and a bit per ambiguous symbol of the last span symbols, first symbol highest
+    bases: u64,
+    ambiguous: u64,
+    care_mask: u64,
+    stride: usize,
+    bits: usize,
+    encoder: E,
+}
+
+impl<'a> SpacedKmerGenerator<'a> {
+    pub fn new(seq: &'a [u8], seed: &'a SpacedSeed) -> Self {
+        Self::with_encoder(seq, seed, Dna)
+    }
+}
+
+impl<'a, E: Encoder> SpacedKmerGenerator<'a, E> {
+    // spaced k-mers of another alphabet, the span of the seed in symbols of encoder.bits()
+    // must fit in 64 bits
+    pub fn with_encoder(seq: &'a [u8], seed: &'a SpacedSeed, encoder: E) -> Self {
+        let bits = encoder.bits();
+        assert!(
+            bits * seed.span <= 64,
+            "{} symbols of {} bits do not fit in a spaced seed",
+            seed.span,
+            bits
+        );
+        Self {
+            seq,
+            seed,
+            pos: 0,
+            bases: 0,
+            // positions before the start of the sequence are never valid
+            ambiguous: u64::MAX,
+            care_mask: seed
+                .care
+                .iter()
+                .fold(0, |mask, &pos| mask | (1 << (seed.span - 1 - pos))),
+            stride: 1,
+            bits,
+            encoder,
+        }
+    }
+
+    // only yield k-mers of windows starting at every stride-th position of the sequence
+    pub fn with_stride(mut self, stride: usize) -> Self {
+        self.stride = usize::max(1, stride);
+        self
+    }
+}
+
+impl<E: Encoder> Iterator for SpacedKmerGenerator<'_, E> {
+    type Item = (Kmer, Kmer);
+
+    fn next(&mut self) -> Option<(Kmer, Kmer)> {
+        let span = self.seed.span;
+        loop {
+            if self.pos == self.seq.len() {
+                return None;
+            }
+            let code = self.encoder.encode(self.seq[self.pos]);
+            self.pos += 1;
+            self.bases = (self.bases << self.bits) | code.unwrap_or(0);
+            self.ambiguous = (self.ambiguous << 1) | code.is_none() as u64;
+
+            if self.pos >= span
+                && self.ambiguous & self.care_mask == 0
+                && (self.pos - span).is_multiple_of(self.stride)
+            {
+                let fmer = self.seed.extract_symbols(self.bases, self.bits);
+                let rmer = self.encoder.reverse_complement(fmer, self.seed.weight());
+                return Some((fmer, rmer));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{kmer::KmerGenerator, kmer_to_numeric, numeric_to_kmer};
+
+    #[test]
+    fn spaced_seed_test() {
+        let seed = SpacedSeed::parse("1101").unwrap();
+        assert_eq!((seed.span(), seed.weight()), (4, 3));
+        assert_eq!(seed.pattern(), "1101");
+        assert!(SpacedSeed::parse("0110").is_err());
+        assert!(SpacedSeed::parse("1201").is_err());
+        assert!(SpacedSeed::parse(&"1".repeat(33)).is_err());
+    }
+
+    #[test]
+    fn spaced_kmers_test() {
+        let seed = SpacedSeed::parse("1101").unwrap();
+        // ACGT -> ACT, CGTA -> CGA, GTAN is skipped and the N of TANC is not cared for
+        let kmers: Vec<String> = SpacedKmerGenerator::new(b"ACGTANC", &seed)
+            .map(|(fmer, _)| numeric_to_kmer(fmer, 3))
+            .collect();
+        assert_eq!(kmers, vec!["ACT", "CGA", "TAC"]);
+        let (fmer, rmer) = SpacedKmerGenerator::new(b"ACGT", &seed).next().unwrap();
+        assert_eq!(fmer, kmer_to_numeric("ACT").unwrap());
+        assert_eq!(rmer, kmer_to_numeric("AGT").unwrap());
+        // a contiguous seed gives the k-mers of KmerGenerator
+        let seed = SpacedSeed::parse("111").unwrap();
+        let seq = b"ACGTTNGCATGCA";
+        let spaced: Vec<(u64, u64)> = SpacedKmerGenerator::new(seq, &seed)
+            .with_stride(2)
+            .collect();
+        let contiguous: Vec<(u64, u64)> = KmerGenerator::new(seq, 3).with_stride(2).collect();
+        assert_eq!(spaced, contiguous);
+    }
+
+    #[test]
+    fn spaced_kmers_protein_test() {
+        use crate::encoder::Protein;
+
+        let seed = SpacedSeed::parse("101").unwrap();
+        // MKX and XLA are skipped for the X at a care position, the X of KXL is not cared for
+        let kmers: Vec<(u64, u64)> =
+            SpacedKmerGenerator::with_encoder(b"MKXLA", &seed, Protein).collect();
+        let (m, k, l) = (10, 8, 9);
+        assert_eq!(kmers, vec![((k << 5) | l, (k << 5) | l)]);
+        let kmers: Vec<(u64, u64)> =
+            SpacedKmerGenerator::with_encoder(b"MKVLA", &seed, Protein).collect();
+        assert_eq!(kmers[0].0, (m << 5) | 17);
+    }
+}
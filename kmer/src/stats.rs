@@ -0,0 +1,95 @@
+use super::encoder::{Dna, Encoder};
+use std::{fmt, ops::AddAssign};
+
+// k-mer positions seen and those skipped due to Ns/ambiguous bases
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KmerStats {
+    pub kmers: u64,
+    pub skipped: u64,
+}
+
+impl KmerStats {
+    // from the number of k-mers a generator produced for a sequence
+    pub fn new(seq_len: usize, ksize: usize, kmers: u64) -> Self {
+        let positions = seq_len.saturating_sub(ksize - 1) as u64;
+        Self {
+            kmers,
+            skipped: positions.saturating_sub(kmers),
+        }
+    }
+
+    pub fn from_seq(seq: &[u8], ksize: usize) -> Self {
+        Self::from_seq_with_encoder(seq, ksize, &Dna)
+    }
+
+    // k-mers of another alphabet, symbols the encoder does not encode are skipped
+    pub fn from_seq_with_encoder<E: Encoder>(seq: &[u8], ksize: usize, encoder: &E) -> Self {
+        let mut run = 0;
+        let mut kmers = 0;
+        for &c in seq {
+            if encoder.encode(c).is_some() {
+                run += 1;
+                if run >= ksize {
+                    kmers += 1;
+                }
+            } else {
+                run = 0;
+            }
+        }
+        Self::new(seq.len(), ksize, kmers)
+    }
+}
+
+impl AddAssign for KmerStats {
+    fn add_assign(&mut self, other: Self) {
+        self.kmers += other.kmers;
+        self.skipped += other.skipped;
+    }
+}
+
+impl fmt::Display for KmerStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "kmers\t{}\nskipped_kmers\t{}\n",
+            self.kmers, self.skipped
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encoder::Protein, kmer::KmerGenerator};
+
+    #[test]
+    fn kmer_stats_test() {
+        let stats = KmerStats::from_seq(b"AAAANGAGA", 4);
+        assert_eq!(
+            stats,
+            KmerStats {
+                kmers: 2,
+                skipped: 4
+            }
+        );
+        let seq = b"ACGTNNACGTACNGTA";
+        assert_eq!(
+            KmerStats::from_seq(seq, 3),
+            KmerStats::new(seq.len(), 3, KmerGenerator::new(seq, 3).count() as u64)
+        );
+        assert_eq!(KmerStats::from_seq(b"AC", 3), KmerStats::default());
+        // X is not an amino acid
+        assert_eq!(
+            KmerStats::from_seq_with_encoder(b"MKXVLA", 2, &Protein),
+            KmerStats {
+                kmers: 3,
+                skipped: 2
+            }
+        );
+
+        let mut total = KmerStats::default();
+        total += KmerStats::from_seq(b"AAAANGAGA", 4);
+        total += KmerStats::from_seq(b"ACGTA", 4);
+        assert_eq!(total.to_string(), "kmers\t4\nskipped_kmers\t4\n");
+    }
+}
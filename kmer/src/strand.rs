@@ -0,0 +1,47 @@
+use super::KmerInt;
+
+// strand a k-mer is counted on
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Strand {
+    // smaller of the k-mer and its reverse complement, for unstranded data
+    #[default]
+    Canonical,
+    // k-mer as observed in the read
+    Forward,
+    // reverse complement of the observed k-mer
+    Reverse,
+}
+
+impl Strand {
+    // k-mer counted for a forward/reverse complement pair from a generator
+    #[inline]
+    pub fn pick<K: KmerInt>(self, fmer: K, rmer: K) -> K {
+        match self {
+            Strand::Canonical => K::min(fmer, rmer),
+            Strand::Forward => fmer,
+            Strand::Reverse => rmer,
+        }
+    }
+
+    pub fn is_canonical(self) -> bool {
+        self == Strand::Canonical
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kmer::KmerGenerator;
+
+    #[test]
+    fn strand_pick_test() {
+        // ACG and its reverse complement CGT
+        let (fmer, rmer) = KmerGenerator::new(b"ACG", 3).next().unwrap();
+        assert_eq!(rmer, KmerGenerator::rev_comp(fmer, 3));
+        assert_eq!(Strand::Canonical.pick(fmer, rmer), fmer);
+        assert_eq!(Strand::Canonical.pick(rmer, fmer), fmer);
+        assert_eq!(Strand::Forward.pick(rmer, fmer), rmer);
+        assert_eq!(Strand::Reverse.pick(fmer, rmer), rmer);
+        assert!(Strand::default().is_canonical());
+    }
+}
@@ -0,0 +1,113 @@
+use super::{
+    encoder::{Dna, Encoder},
+    kmer::KmerGenerator,
+    sketch::hash64,
+    KmerInt,
+};
+use std::collections::VecDeque;
+
+// largest m-mer used as the signature of a k-mer
+const MAX_SIGNATURE_SIZE: usize = 9;
+
+pub fn signature_size(ksize: usize) -> usize {
+    usize::min(MAX_SIGNATURE_SIZE, ksize - 1).max(1)
+}
+
+// smallest hashed canonical m-mer of a k-mer, same for the k-mer and its reverse complement
+pub fn kmer_signature<K: KmerInt>(kmer: K, ksize: usize, msize: usize) -> u64 {
+    let m_mask = (1_u64 << (2 * msize)) - 1;
+    (0..=ksize - msize)
+        .map(|shift| {
+            let mmer = (kmer >> (2 * shift)).as_u64() & m_mask;
+            let mmer = u64::min(mmer, KmerGenerator::rev_comp(mmer, msize));
+            hash64(mmer, m_mask)
+        })
+        .min()
+        .unwrap()
+}
+
+// runs of consecutive k-mers of a sequence sharing their signature, as (signature, start, end)
+// where seq[start..end] holds the k-mers of the run (KMC style super-k-mers), signatures are
+// of 2-bit m-mers so bases are encoded as Dna
+pub fn super_kmers(seq: &[u8], ksize: usize, msize: usize) -> Vec<(u64, usize, usize)> {
+    let m_mask = (1_u64 << (2 * msize)) - 1;
+    let shift = 2 * (msize - 1);
+    let mut runs: Vec<(u64, usize, usize)> = Vec::new();
+    // (m-mer start, hash) with increasing hashes, front is the signature of the window
+    let mut window: VecDeque<(usize, u64)> = VecDeque::new();
+    let (mut fval, mut rval, mut valid) = (0_u64, 0_u64, 0_usize);
+
+    for (pos, &base) in seq.iter().enumerate() {
+        let Some(fbase) = Dna.encode(base) else {
+            valid = 0;
+            window.clear();
+            continue;
+        };
+        fval = ((fval << 2) | fbase) & m_mask;
+        rval = (rval >> 2) | (Dna.complement(fbase) << shift);
+        valid += 1;
+        if valid < msize {
+            continue;
+        }
+        let hash = hash64(u64::min(fval, rval), m_mask);
+        while window.back().is_some_and(|&(_, back)| back >= hash) {
+            window.pop_back();
+        }
+        window.push_back((pos + 1 - msize, hash));
+        if valid < ksize {
+            continue;
+        }
+        let start = pos + 1 - ksize;
+        while window.front().is_some_and(|&(mstart, _)| mstart < start) {
+            window.pop_front();
+        }
+        let signature = window.front().unwrap().1;
+        match runs.last_mut() {
+            Some((last, _, end)) if *last == signature && *end == pos => *end = pos + 1,
+            _ => runs.push((signature, start, pos + 1)),
+        }
+    }
+
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{kmer::GenericKmerGenerator, Kmer};
+
+    const SEQ: &[u8] = b"ATGCGATATCGTAGGCGTCGATGGAGAGCTAGATCGATCGATCTAAATCCCGATCGATNTCCGAGCGCGATCAAAGCGCGATAGGCTAGCTAAAGCTAGCA";
+
+    #[test]
+    fn signature_test() {
+        let (fmer, rmer) = KmerGenerator::new(b"ACGTTGCATGCAT", 13).next().unwrap();
+        assert_eq!(kmer_signature(fmer, 13, 9), kmer_signature(rmer, 13, 9));
+        assert_eq!(signature_size(7), 6);
+        assert_eq!(signature_size(31), 9);
+    }
+
+    #[test]
+    fn super_kmers_test() {
+        let ksize = 15;
+        let msize = signature_size(ksize);
+        let runs = super_kmers(SEQ, ksize, msize);
+        assert!(runs.len() > 1);
+        let mut kmers: Vec<(Kmer, Kmer)> = Vec::new();
+        for &(signature, start, end) in runs.iter() {
+            for (fmer, rmer) in GenericKmerGenerator::<Kmer>::new(&SEQ[start..end], ksize) {
+                assert_eq!(kmer_signature(fmer, ksize, msize), signature);
+                kmers.push((fmer, rmer));
+            }
+        }
+        // every k-mer belongs to exactly one super-k-mer
+        let all: Vec<(Kmer, Kmer)> = KmerGenerator::new(SEQ, ksize).collect();
+        assert_eq!(kmers, all);
+        // wide k-mers share the signatures
+        let (fmer, _) = GenericKmerGenerator::<u128>::new(&SEQ[..40], 40)
+            .next()
+            .unwrap();
+        let runs = super_kmers(&SEQ[..40], 40, 9);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(kmer_signature(fmer, 40, 9), runs[0].0);
+    }
+}
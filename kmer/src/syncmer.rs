@@ -0,0 +1,202 @@
+use crate::{
+    encoder::{Dna, Encoder},
+    Kmer,
+};
+use std::collections::VecDeque;
+
+// canonical k-mers whose smallest canonical s-mer starts at the offset (open syncmers) or
+// at either end (closed syncmers), as (syncmer, start, end, forward), k-mers with ambiguous
+// bases are skipped, k-mers of alphabets without a reverse strand are forward
+pub struct SyncmerGenerator<'a, E: Encoder = Dna> {
+    seq: &'a [u8],
+    pos: usize,
+    ksize: usize,
+    ssize: usize,
+    offset: Option<usize>,
+    k_mask: u64,
+    s_mask: u64,
+    k_val_f: u64,
+    k_val_r: u64,
+    s_val_f: u64,
+    s_val_r: u64,
+    // bases since the last ambiguous base
+    len: usize,
+    // canonical s-mers of the current k-mer
+    smers: VecDeque<u64>,
+    bits: usize,
+    encoder: E,
+}
+
+impl<'a> SyncmerGenerator<'a> {
+    // k-mers of ksize whose smallest s-mer of ssize starts at offset, offset <= k - s
+    pub fn open(seq: &'a [u8], ksize: usize, ssize: usize, offset: usize) -> Self {
+        Self::with_encoder(seq, ksize, ssize, Some(offset), Dna)
+    }
+
+    // k-mers of ksize whose smallest s-mer of ssize is the first or the last
+    pub fn closed(seq: &'a [u8], ksize: usize, ssize: usize) -> Self {
+        Self::with_encoder(seq, ksize, ssize, None, Dna)
+    }
+}
+
+impl<'a, E: Encoder> SyncmerGenerator<'a, E> {
+    // syncmers of another alphabet, open at offset or closed without one, ksize symbols of
+    // encoder.bits() must fit in 64 bits
+    pub fn with_encoder(
+        seq: &'a [u8],
+        ksize: usize,
+        ssize: usize,
+        offset: Option<usize>,
+        encoder: E,
+    ) -> Self {
+        let bits = encoder.bits();
+        assert!(
+            bits * ksize <= 64,
+            "{} symbols of {} bits do not fit in a syncmer",
+            ksize,
+            bits
+        );
+        Self {
+            seq,
+            pos: 0,
+            ksize,
+            ssize,
+            offset,
+            k_mask: u64::MAX >> (64 - bits * ksize),
+            s_mask: u64::MAX >> (64 - bits * ssize),
+            k_val_f: 0,
+            k_val_r: 0,
+            s_val_f: 0,
+            s_val_r: 0,
+            len: 0,
+            smers: VecDeque::with_capacity(ksize - ssize + 1),
+            bits,
+            encoder,
+        }
+    }
+
+    fn is_syncmer(&self) -> bool {
+        let min = *self.smers.iter().min().unwrap();
+        match self.offset {
+            Some(offset) => self.smers[offset] == min,
+            None => self.smers[0] == min || self.smers[self.smers.len() - 1] == min,
+        }
+    }
+}
+
+impl<E: Encoder> Iterator for SyncmerGenerator<'_, E> {
+    type Item = (Kmer, usize, usize, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bits = self.bits;
+        let (k_shift, s_shift) = (bits * (self.ksize - 1), bits * (self.ssize - 1));
+        while self.pos < self.seq.len() {
+            let symbol = self.encoder.encode(self.seq[self.pos]);
+            self.pos += 1;
+            let Some(f_val) = symbol else {
+                self.len = 0;
+                self.smers.clear();
+                continue;
+            };
+            self.k_val_f = ((self.k_val_f << bits) | f_val) & self.k_mask;
+            self.s_val_f = ((self.s_val_f << bits) | f_val) & self.s_mask;
+            if E::STRANDED {
+                let r_val = self.encoder.complement(f_val);
+                self.k_val_r = (self.k_val_r >> bits) | (r_val << k_shift);
+                self.s_val_r = (self.s_val_r >> bits) | (r_val << s_shift);
+            } else {
+                self.k_val_r = self.k_val_f;
+                self.s_val_r = self.s_val_f;
+            }
+            self.len += 1;
+
+            if self.len >= self.ssize {
+                if self.smers.len() == self.ksize - self.ssize + 1 {
+                    self.smers.pop_front();
+                }
+                self.smers.push_back(u64::min(self.s_val_f, self.s_val_r));
+            }
+            if self.len >= self.ksize && self.is_syncmer() {
+                let kmer = u64::min(self.k_val_f, self.k_val_r);
+                let forward = self.k_val_f <= self.k_val_r;
+                return Some((kmer, self.pos - self.ksize, self.pos, forward));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kmer::{reverse_complement, KmerGenerator};
+
+    #[test]
+    fn syncmers_test() {
+        let seq = b"ATGCGATATCGTAGGCGTCGATGGAGAGCTAGATCG";
+        // brute force over the canonical s-mers of each k-mer
+        let expected = |offset: Option<usize>| -> Vec<(u64, usize, usize, bool)> {
+            KmerGenerator::new(seq, 8)
+                .canonical()
+                .enumerate()
+                .filter(|(start, _)| {
+                    let smers: Vec<u64> = KmerGenerator::new(&seq[*start..start + 8], 3)
+                        .canonical()
+                        .map(|(smer, _)| smer)
+                        .collect();
+                    let min = *smers.iter().min().unwrap();
+                    match offset {
+                        Some(offset) => smers[offset] == min,
+                        None => smers[0] == min || smers[5] == min,
+                    }
+                })
+                .map(|(start, (kmer, forward))| (kmer, start, start + 8, forward))
+                .collect()
+        };
+        let open: Vec<_> = SyncmerGenerator::open(seq, 8, 3, 2).collect();
+        assert_eq!(open, expected(Some(2)));
+        let closed: Vec<_> = SyncmerGenerator::closed(seq, 8, 3).collect();
+        assert_eq!(closed, expected(None));
+        assert!(!closed.is_empty());
+
+        // closed syncmers are chosen the same on both strands
+        let rc = reverse_complement(seq);
+        let mut fwd: Vec<u64> = closed.iter().map(|(kmer, _, _, _)| *kmer).collect();
+        let mut rev: Vec<u64> = SyncmerGenerator::closed(&rc, 8, 3)
+            .map(|(kmer, _, _, _)| kmer)
+            .collect();
+        fwd.sort();
+        rev.sort();
+        assert_eq!(fwd, rev);
+
+        // Ns break k-mers
+        let syncmers: Vec<_> = SyncmerGenerator::closed(b"ACGTANCGTAC", 4, 2).collect();
+        assert!(syncmers
+            .iter()
+            .all(|(_, start, end, _)| *end <= 5 || *start >= 6));
+    }
+
+    #[test]
+    fn syncmers_protein_test() {
+        use crate::{encoder::Protein, kmer::GenericKmerGenerator};
+
+        let seq = b"MKVLAAGIVGLLLAQWERTYXPASDFGHKLCVNMMKVLWWYYRPQ";
+        let syncmers: Vec<_> = SyncmerGenerator::with_encoder(seq, 6, 2, None, Protein).collect();
+        assert!(!syncmers.is_empty());
+        // smallest 2-mer of each syncmer at either end, proteins have a single strand
+        for (kmer, start, end, forward) in syncmers {
+            let smers: Vec<u64> =
+                GenericKmerGenerator::<u64, Protein>::with_encoder(&seq[start..end], 2, Protein)
+                    .map(|(smer, _)| smer)
+                    .collect();
+            let min = *smers.iter().min().unwrap();
+            assert!(smers[0] == min || smers[4] == min);
+            let (fmer, _) =
+                GenericKmerGenerator::<u64, Protein>::with_encoder(&seq[start..end], 6, Protein)
+                    .next()
+                    .unwrap();
+            assert_eq!(kmer, fmer);
+            assert!(forward);
+        }
+    }
+}
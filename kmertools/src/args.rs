@@ -1,8 +1,54 @@
-use clap::{Args, Parser, Subcommand, ValueEnum};
-use composition::{cgr::CgrComputer, oligo::OligoComputer, oligocgr::OligoCgrComputer};
-use coverage::CovComputer;
-use ktio::fops::create_directory;
-use misc::minimisers;
+use crate::{
+    daemon,
+    scaffold::{self, Workflow},
+    selftest,
+    vectorise::Vectoriser,
+};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use composition::{
+    cgr::CgrComputer,
+    codon::{CodonComputer, Frame},
+    markov::Enrichment,
+    oligo::{
+        Normalisation, OligoComputer, OligoKmers, OligoOptions, OligoOutput, Orientation, Rescale,
+        Rows,
+    },
+    oligocgr::OligoCgrComputer,
+    stats::{ColumnScaler, Scaling},
+};
+use counter::{
+    matrix,
+    memory::MemoryPolicy,
+    ops::{self, SetOp},
+    rescale::{self, Rounding},
+    spill::SpillCompression,
+    whitelist::Whitelist,
+    width::CounterWidth,
+    CountOptions, CountsOutput, KmerOptions, KmerText, OutputOptions, PartitionOptions,
+};
+use coverage::{bins::BinScale, depth::DepthFormat, solid::MaskEncoding, CovComputer, SampleScale};
+use kmer::{
+    kmer::MAX_DENSE_KSIZE, sketch::HyperLogLog, spaced::SpacedSeed, stats::KmerStats,
+    strand::Strand, Molecule,
+};
+use ktio::{
+    bundle::{record_ids, Bundle},
+    filter::RecordFilter,
+    fops::create_directory,
+    format::{IdPolicy, OutputCompression, OutputFormat, SparseFormat},
+    matrix::MatrixFormat,
+    profile::Profiler,
+    seq::{get_reader, SeqFormat},
+};
+use misc::{
+    convert::{self, KmerFormat},
+    dedupe,
+    labels::KmerLabels,
+    locate,
+    minimisers::{self, MinimiserOptions, Scheme},
+    pairs, recruit, regions, shuffle,
+};
+use std::{collections::HashSet, process};
 
 const ABOUT: &str = "kmertools: DNA vectorisation
 
@@ -21,6 +67,13 @@ pub struct Cli {
 }
 
 // COMMON
+// Thread count shared by the subcommands
+#[derive(Debug, Args)]
+pub struct ThreadArgs {
+    /// Thread count for computations 0=auto (KMERTOOLS_THREADS or CPUs allowed by cgroups/affinity)
+    #[arg(short, long, default_value_t = 0)]
+    pub threads: usize,
+}
 
 // Presets for vector outputs
 #[derive(Debug, ValueEnum, Clone)]
@@ -31,15 +84,344 @@ pub enum VecFmtPreset {
     Tsv,
     /// Space separated format
     Spc,
+    /// NumPy float64 matrix (record IDs are not stored)
+    Npy,
+    /// Apache Parquet with a float64 column per feature
+    Parquet,
+    /// HDF5 chunked float64 dataset with feature names and record IDs (needs the hdf5 feature)
+    Hdf5,
+}
+
+impl VecFmtPreset {
+    fn output_format(
+        &self,
+        header: bool,
+        precision: Option<usize>,
+        compress: Option<CompressPreset>,
+    ) -> OutputFormat {
+        let mut format = OutputFormat::new(match self {
+            VecFmtPreset::Csv => ",",
+            VecFmtPreset::Tsv => "\t",
+            _ => " ",
+        });
+        format.header = header;
+        format.precision = precision;
+        format.matrix = self.matrix();
+        format.compression = CompressPreset::compression(compress);
+        format
+    }
+
+    fn matrix(&self) -> Option<MatrixFormat> {
+        match self {
+            VecFmtPreset::Npy => Some(MatrixFormat::Npy),
+            VecFmtPreset::Parquet => Some(MatrixFormat::Parquet),
+            VecFmtPreset::Hdf5 => Some(MatrixFormat::Hdf5),
+            _ => None,
+        }
+    }
 }
 
 // Presets for minimiser outputs
-#[derive(Debug, ValueEnum, Clone)]
+#[derive(Debug, ValueEnum, Clone, PartialEq)]
 pub enum MinFmtPreset {
     /// Conver sequences into minimiser representation
     S2m,
     /// Group sequences by minimiser
     M2s,
+    /// Assign reads to the reference sharing most minimisers
+    Map,
+    /// Group sequences binned together under every (w_size, m_size) setting
+    Consensus,
+}
+
+// Ways of picking the m-mers of a sequence
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq)]
+pub enum SchemePreset {
+    /// Smallest m-mer of each window
+    Minimiser,
+    /// m-mers whose smallest s-mer is at --offset, or at either end without it
+    Syncmer,
+}
+
+// Presets for canonical k-mer features
+#[derive(Debug, ValueEnum, Clone)]
+pub enum CanonicalPreset {
+    /// Lexicographically smaller of k-mer and reverse complement
+    Min,
+    /// Strand-neutral hash buckets, avoids lexicographic collapse
+    Hash,
+}
+
+// Codecs for temporary files
+#[derive(Debug, ValueEnum, Clone)]
+pub enum TmpCodecPreset {
+    /// Fastest, moderate compression
+    Lz4,
+    /// Better compression at a little more CPU
+    Zstd,
+    /// Widely readable, slowest
+    Gzip,
+}
+
+// Codecs of compressed vector outputs
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum CompressPreset {
+    /// Widely readable
+    Gzip,
+    /// Faster with better compression
+    Zstd,
+}
+
+// Counter types of k-mer counts
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum CounterWidthPreset {
+    /// 32-bit counters, wrap around past 4294967295
+    U32,
+    /// 64-bit counters for deep data, twice the memory
+    U64,
+    /// 32-bit counters held at 4294967295 with a warning
+    Saturating,
+}
+
+// Formats of the k-mer counts kept with the coverages
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum CountsFmtPreset {
+    /// Text <output>/kmers.counts of numeric k-mers and counts, as ctr writes
+    Text,
+    /// Sorted binary <output>/kmers.counts.bin the coverages are computed from
+    Bin,
+}
+
+// Measures of memory use against --memory
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum MemoryPolicyPreset {
+    /// Slots allocated by the counting tables times the entry size
+    Occupancy,
+    /// 8 bytes per base read, the estimate of earlier versions
+    SequenceLength,
+}
+
+// Formats of reads given on stdin
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum SeqFormatPreset {
+    Fasta,
+    Fastq,
+    Bam,
+}
+
+// Encodings of solid k-mer masks
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum MaskPreset {
+    /// Runs of solid and weak positions, as 12S3W40S
+    Rle,
+    /// One bit per position, first position in the highest bit
+    Hex,
+}
+
+// Formats of per-position k-mer depths
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum DepthPreset {
+    /// Intervals of equal depth, as <id> <start> <end> <depth>
+    Bedgraph,
+    /// A line per position or window, as <id> <start> <depth>
+    Tsv,
+}
+
+// Extra rows written for each record
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum AugmentPreset {
+    /// The reverse complement, with _rc after the ID of the record
+    Rc,
+}
+
+// Layouts of sparse vector rows
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum SparsePreset {
+    /// Non-zero columns as <column>:<value> pairs, columns from 0
+    Pairs,
+    /// libsvm/svmlight rows, a 0 label then <column>:<value> pairs from 1, IDs as # comments
+    Libsvm,
+}
+
+// Scales of coverage histogram bins
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum BinScalePreset {
+    /// Bins of bin-size counts each
+    Linear,
+    /// First bin of bin-size counts, each following bin twice as wide
+    Log,
+    /// Bins holding about as many k-mer occurrences each (bin-size is not used)
+    Quantile,
+}
+
+// Normalisations of oligo counts
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum NormPreset {
+    /// Frequencies, counts over the k-mers of each record
+    Freq,
+    /// Frequencies standardised per k-mer across all records (reads the input twice)
+    Zscore,
+    /// Relative abundance, counts over those expected from the base composition
+    Oddsratio,
+    /// 1 for k-mers present in the record and 0 otherwise
+    Presence,
+}
+
+impl NormPreset {
+    // packed presence vectors are written as bitsets
+    fn normalisation(self, packed: bool) -> Result<Normalisation, String> {
+        match self {
+            NormPreset::Presence => Ok(Normalisation::Presence { packed }),
+            _ if packed => Err("--packed needs --norm presence".to_string()),
+            NormPreset::Freq => Ok(Normalisation::Freq),
+            NormPreset::Zscore => Ok(Normalisation::ZScore),
+            NormPreset::Oddsratio => Ok(Normalisation::OddsRatio),
+        }
+    }
+}
+
+// Rescaling of the columns of vectors across the records of the input
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum ScalePreset {
+    /// Zero mean and unit variance
+    Standard,
+    /// Range of each column to 0..1
+    Minmax,
+}
+
+impl ScalePreset {
+    fn scaling(self) -> Scaling {
+        match self {
+            ScalePreset::Standard => Scaling::Standard,
+            ScalePreset::Minmax => Scaling::MinMax,
+        }
+    }
+}
+
+// Presets for Markov model enrichment scores
+#[derive(Debug, ValueEnum, Clone)]
+pub enum ScorePreset {
+    /// Observed / expected
+    Ratio,
+    /// ln((observed + 1) / (expected + 1))
+    LogOdds,
+}
+
+// RNA-seq library protocols, single-end reads are treated as read 1
+#[derive(Debug, ValueEnum, Clone, Copy)]
+pub enum LibraryPreset {
+    /// Strand is unknown, k-mers and reverse complements are merged
+    Unstranded,
+    /// Read 1 is antisense (dUTP), reverse complements are counted
+    FrFirststrand,
+    /// Read 1 is sense, k-mers are counted as observed
+    FrSecondstrand,
+}
+
+impl TmpCodecPreset {
+    fn codec(preset: Option<Self>) -> SpillCompression {
+        match preset {
+            Some(TmpCodecPreset::Lz4) => SpillCompression::Lz4,
+            Some(TmpCodecPreset::Zstd) => SpillCompression::Zstd,
+            Some(TmpCodecPreset::Gzip) => SpillCompression::Gzip,
+            None => SpillCompression::None,
+        }
+    }
+}
+
+impl CompressPreset {
+    fn compression(preset: Option<Self>) -> OutputCompression {
+        match preset {
+            Some(CompressPreset::Gzip) => OutputCompression::Gzip,
+            Some(CompressPreset::Zstd) => OutputCompression::Zstd,
+            None => OutputCompression::None,
+        }
+    }
+}
+
+impl CounterWidthPreset {
+    fn width(self) -> CounterWidth {
+        match self {
+            CounterWidthPreset::U32 => CounterWidth::U32,
+            CounterWidthPreset::U64 => CounterWidth::U64,
+            CounterWidthPreset::Saturating => CounterWidth::Saturating,
+        }
+    }
+}
+
+impl MemoryPolicyPreset {
+    fn policy(self) -> MemoryPolicy {
+        match self {
+            MemoryPolicyPreset::Occupancy => MemoryPolicy::Occupancy,
+            MemoryPolicyPreset::SequenceLength => MemoryPolicy::SequenceLength,
+        }
+    }
+}
+
+impl SeqFormatPreset {
+    fn format(self) -> SeqFormat {
+        match self {
+            SeqFormatPreset::Fasta => SeqFormat::Fasta,
+            SeqFormatPreset::Fastq => SeqFormat::Fastq,
+            SeqFormatPreset::Bam => SeqFormat::Bam,
+        }
+    }
+}
+
+impl MaskPreset {
+    fn encoding(self) -> MaskEncoding {
+        match self {
+            MaskPreset::Rle => MaskEncoding::Rle,
+            MaskPreset::Hex => MaskEncoding::Hex,
+        }
+    }
+}
+
+impl DepthPreset {
+    fn format(self) -> DepthFormat {
+        match self {
+            DepthPreset::Bedgraph => DepthFormat::BedGraph,
+            DepthPreset::Tsv => DepthFormat::Tsv,
+        }
+    }
+}
+
+impl SparsePreset {
+    fn format(self) -> SparseFormat {
+        match self {
+            SparsePreset::Pairs => SparseFormat::Pairs,
+            SparsePreset::Libsvm => SparseFormat::Libsvm,
+        }
+    }
+}
+
+impl BinScalePreset {
+    fn scale(self) -> BinScale {
+        match self {
+            BinScalePreset::Linear => BinScale::Linear,
+            BinScalePreset::Log => BinScale::Log,
+            BinScalePreset::Quantile => BinScale::Quantile,
+        }
+    }
+}
+
+impl LibraryPreset {
+    // --no-canonical and --stranded are a forward stranded library
+    fn or_forward(self, no_canonical: bool) -> Self {
+        if no_canonical {
+            LibraryPreset::FrSecondstrand
+        } else {
+            self
+        }
+    }
+
+    fn strand(self) -> Strand {
+        match self {
+            LibraryPreset::Unstranded => Strand::Canonical,
+            LibraryPreset::FrFirststrand => Strand::Reverse,
+            LibraryPreset::FrSecondstrand => Strand::Forward,
+        }
+    }
 }
 
 /// Subcommands available
@@ -52,10 +434,33 @@ pub enum Commands {
     },
     /// Generates coverage histogram based on the reads
     Cov(CoverageCommand),
+    /// Keep reads by the median count of their k-mers
+    Filter(FilterCommand),
+    /// Oligo frequencies and coverage histograms of each record as one matrix
+    Vectorise(VectoriseCommand),
     /// Bin reads using minimisers
     Min(MinimiserCommand),
     /// Count k-mers
     Ctr(CounterCommand),
+    /// Convert k-mer tables between numeric and ACGT
+    Convert(ConvertCommand),
+    /// Rescale k-mer counts to a target coverage
+    Rescale(RescaleCommand),
+    /// Count k-mers of annotated regions (GFF/BED) and background
+    Regions(RegionsCommand),
+    /// Shuffle sequences preserving base or dinucleotide composition
+    Shuffle(ShuffleCommand),
+    /// Check this build end-to-end on a small embedded dataset
+    Selftest(SelftestCommand),
+    /// Write a workflow module wrapping ctr, cov and comp
+    Scaffold(ScaffoldCommand),
+    /// Serve count indexes and jobs over a local socket
+    Daemon(DaemonCommand),
+    /// MinHash sketch based sample comparisons
+    Sketch {
+        #[clap(subcommand)]
+        command: SketchCommands,
+    },
 }
 
 // COMPOSITION
@@ -65,6 +470,10 @@ pub enum CompositionCommands {
     Oligo(OligoCommand),
     /// Generates Chaos Game Representations
     Cgr(CGRCommand),
+    /// Report contigs whose composition is an outlier of the contig set
+    Outliers(OutliersCommand),
+    /// Generate codon usage vectors of one reading frame of each record
+    Codon(CodonCommand),
 }
 
 #[derive(Debug, Args)]
@@ -81,21 +490,232 @@ pub struct OligoCommand {
     #[arg(short, long)]
     pub counts: bool,
 
-    /// Set k-mer size
-    #[arg(short, long, value_parser = clap::value_parser!(u64).range(3..=7), default_value_t = 3)]
+    /// Set k-mer size (k > 10 writes sparse <column>:<value> rows of the non-zero k-mers)
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(3..=31), default_value_t = 3)]
     pub k_size: u64,
 
+    /// Spaced seed such as 110101, k-mers of its 1 positions are counted (k is its weight)
+    #[arg(long, value_parser = SpacedSeed::parse, conflicts_with = "k_size")]
+    pub seed: Option<SpacedSeed>,
+
     /// Output type to write
     #[clap(value_enum, short, long, default_value_t = VecFmtPreset::Spc)]
     pub preset: VecFmtPreset,
 
+    /// Compress the output vectors (adds .gz or .zst to the output path)
+    #[clap(value_enum, long)]
+    pub compress: Option<CompressPreset>,
+
     /// Include header (with k-mer in ACGT format)
     #[clap(value_enum, short = 'H', long)]
     pub header: bool,
 
-    /// Thread count for computations 0=auto
-    #[arg(short, long, default_value_t = 0)]
-    pub threads: usize,
+    /// Write k-mers of the header in RNA letters (U in place of T)
+    #[arg(long)]
+    pub rna: bool,
+
+    /// Start each row with the ID of its record
+    #[arg(long, conflicts_with = "sklearn_bundle")]
+    pub with_ids: bool,
+
+    /// Add the length of each record after its ID
+    #[arg(long, requires = "with_ids")]
+    pub with_lengths: bool,
+
+    /// Write a vector per window of this many bases, as <id> <start> <end> <vector>
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["with_lengths", "sparse", "sklearn_bundle"])]
+    pub window: Option<u64>,
+
+    /// Bases between the starts of windows [default: the window size]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), requires = "window")]
+    pub step: Option<u64>,
+
+    /// Write only the non-zero k-mers of each record (the default for k > 10)
+    #[clap(value_enum, long, conflicts_with_all = ["header", "sklearn_bundle", "markov"])]
+    pub sparse: Option<SparsePreset>,
+
+    /// Decimal places of normalised frequencies
+    #[arg(long, default_value_t = 6)]
+    pub precision: usize,
+
+    /// Write k-mers and skipped k-mers (Ns/ambiguous bases) of each record
+    #[arg(long)]
+    pub record_stats: bool,
+
+    /// Report skipped k-mers (Ns/ambiguous bases) of all records
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Write <output>.run.json with the time and peak memory of each stage, and report them
+    #[arg(long)]
+    pub provenance: bool,
+
+    /// Use only every S-th k-mer position for approximate profiles
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 1)]
+    pub stride: u64,
+
+    /// Take k-mers of homopolymer compressed sequences (runs of a base count once)
+    #[arg(long)]
+    pub hpc: bool,
+
+    /// Leave out k-mers overlapping soft-masked (lowercase) bases, such as repeats
+    #[arg(long)]
+    pub skip_masked: bool,
+
+    /// Append gc, gc_skew, at_skew, length and entropy (of the bases in bits) columns
+    #[arg(long)]
+    pub extra_features: bool,
+
+    /// How k-mers and their reverse complements are combined
+    #[clap(value_enum, long, default_value_t = CanonicalPreset::Min)]
+    pub canonical: CanonicalPreset,
+
+    /// Library strandedness, stranded libraries count k-mers in transcript orientation
+    #[clap(value_enum, long, default_value_t = LibraryPreset::Unstranded)]
+    pub library: LibraryPreset,
+
+    /// Count forward-strand k-mers over all 4^k k-mers (as --library fr-secondstrand)
+    #[arg(long, conflicts_with = "library")]
+    pub stranded: bool,
+
+    /// Also write a row for each record of this augmentation, after the row of the record
+    #[clap(value_enum, long, conflicts_with_all = ["window", "sklearn_bundle"])]
+    pub augment: Option<AugmentPreset>,
+
+    /// Normalisation of the k-mer counts of each record
+    #[clap(value_enum, long, conflicts_with_all = ["counts", "markov"], default_value_t = NormPreset::Freq)]
+    pub norm: NormPreset,
+
+    /// Write presence vectors as hex bitsets, column i is bit i % 8 of byte i / 8
+    #[arg(long, conflicts_with = "sklearn_bundle")]
+    pub packed: bool,
+
+    /// Rescale each column across all records (reads the input twice), the parameters are
+    /// written to <output>.scaling.json
+    #[clap(value_enum, long, conflicts_with_all = ["packed", "window", "sparse"])]
+    pub scale: Option<ScalePreset>,
+
+    /// Rescale columns with the parameters of <output>.scaling.json of another dataset
+    #[arg(long, conflicts_with_all = ["scale", "packed", "window", "sparse"])]
+    pub scale_params: Option<String>,
+
+    /// Output observed vs expected scores under a Markov model of this order
+    #[arg(long, value_parser = clap::value_parser!(u64).range(0..=2), conflicts_with = "counts")]
+    pub markov: Option<u64>,
+
+    /// Score used with --markov
+    #[clap(value_enum, long, requires = "markov", default_value_t = ScorePreset::Ratio)]
+    pub score: ScorePreset,
+
+    /// Write <output>.json with feature names, record IDs, settings, dtype and shape
+    #[arg(long)]
+    pub sklearn_bundle: bool,
+
+    /// Also write <output>.npz with X and ids for numpy.load
+    #[arg(long, requires = "sklearn_bundle")]
+    pub npz: bool,
+
+    /// Only process records whose IDs are listed in this file
+    #[arg(long, conflicts_with = "exclude_ids")]
+    pub include_ids: Option<String>,
+
+    /// Skip records whose IDs are listed in this file
+    #[arg(long)]
+    pub exclude_ids: Option<String>,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct OutliersCommand {
+    /// Input file path
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Output path
+    ///
+    /// Writes <id>\t<distance>\t<score>\t<outlier> per record, with the robust
+    /// Mahalanobis distance of its frequencies and the distance over the median distance
+    #[arg(short, long, verbatim_doc_comment)]
+    pub output: String,
+
+    /// Set k-mer size
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(3..=5), default_value_t = 4)]
+    pub k_size: u64,
+
+    /// Records scoring above this are outliers (candidate contamination)
+    #[arg(long, default_value_t = 3.0)]
+    pub threshold: f64,
+
+    /// Only process records whose IDs are listed in this file
+    #[arg(long, conflicts_with = "exclude_ids")]
+    pub include_ids: Option<String>,
+
+    /// Skip records whose IDs are listed in this file
+    #[arg(long)]
+    pub exclude_ids: Option<String>,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct CodonCommand {
+    /// Input file path
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Output vectors path
+    #[arg(short, long)]
+    pub output: String,
+
+    /// Disable normalisation and output raw counts
+    #[arg(short, long)]
+    pub counts: bool,
+
+    /// Offset of the reading frame on the forward strand
+    #[arg(long, value_parser = clap::value_parser!(u64).range(0..=2), default_value_t = 0)]
+    pub frame: u64,
+
+    /// Use the frame of either strand with the longest stretch of codons without stops
+    #[arg(long, conflicts_with = "frame")]
+    pub detect_frame: bool,
+
+    /// Output type to write
+    #[clap(value_enum, short, long, default_value_t = VecFmtPreset::Spc)]
+    pub preset: VecFmtPreset,
+
+    /// Compress the output vectors (adds .gz or .zst to the output path)
+    #[clap(value_enum, long)]
+    pub compress: Option<CompressPreset>,
+
+    /// Include header (with codons in ACGT format)
+    #[arg(short = 'H', long)]
+    pub header: bool,
+
+    /// Write codons of the header in RNA letters (U in place of T)
+    #[arg(long)]
+    pub rna: bool,
+
+    /// Start each row with the ID of its record
+    #[arg(long)]
+    pub with_ids: bool,
+
+    /// Decimal places of normalised frequencies
+    #[arg(long, default_value_t = 6)]
+    pub precision: usize,
+
+    /// Only process records whose IDs are listed in this file
+    #[arg(long, conflicts_with = "exclude_ids")]
+    pub include_ids: Option<String>,
+
+    /// Skip records whose IDs are listed in this file
+    #[arg(long)]
+    pub exclude_ids: Option<String>,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
 }
 
 #[derive(Debug, Args)]
@@ -120,34 +740,132 @@ pub struct CGRCommand {
     #[arg(short, long)]
     pub vec_size: Option<u64>,
 
-    /// Thread count for computations 0=auto
-    #[arg(short, long, default_value_t = 0)]
-    pub threads: usize,
+    /// Write the 2^k x 2^k frequency CGR matrix of each sequence, flattened row by row
+    #[arg(long, requires = "k_size", conflicts_with = "vec_size")]
+    pub fcgr: bool,
+
+    /// Leave out soft-masked (lowercase) bases, whole sequence walks restart after them
+    #[arg(long)]
+    pub skip_masked: bool,
+
+    /// Include header (k-mers of points or FCGR cells, p<n> for the n-th point of whole sequences)
+    #[arg(short = 'H', long)]
+    pub header: bool,
+
+    /// Write k-mers of the header in RNA letters (U in place of T)
+    #[arg(long, requires = "k_size")]
+    pub rna: bool,
+
+    /// Start each row with the ID of its record
+    #[arg(long)]
+    pub with_ids: bool,
+
+    /// Also write a grayscale PNG of each record to this directory (builds with --features png)
+    #[arg(long)]
+    pub png: Option<String>,
+
+    /// Width and height of PNGs of CGR points in pixels (FCGR images are 2^k wide)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=8192), default_value_t = 256, requires = "png")]
+    pub png_size: u64,
+
+    /// Output type to write, points become separate columns with csv and tsv
+    #[clap(value_enum, short, long, default_value_t = VecFmtPreset::Spc)]
+    pub preset: VecFmtPreset,
+
+    /// Compress the output vectors (adds .gz or .zst to the output path)
+    #[clap(value_enum, long)]
+    pub compress: Option<CompressPreset>,
+
+    /// Decimal places of coordinates and frequencies (default: shortest exact value)
+    #[arg(long)]
+    pub precision: Option<usize>,
+
+    /// Only process records whose IDs are listed in this file
+    #[arg(long, conflicts_with = "exclude_ids")]
+    pub include_ids: Option<String>,
+
+    /// Skip records whose IDs are listed in this file
+    #[arg(long)]
+    pub exclude_ids: Option<String>,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
 }
 
 // COVERAGE
 #[derive(Debug, Args)]
 pub struct CoverageCommand {
     /// Input file path
-    #[arg(short, long)]
-    pub input: String,
+    #[arg(short, long, required_unless_present = "mate_1")]
+    pub input: Option<String>,
+
+    /// First mates of paired reads, k-mers of both mates of a pair make one histogram
+    #[arg(
+        short = '1',
+        long,
+        requires = "mate_2",
+        conflicts_with_all = ["input", "solid", "summary", "depth", "sklearn_bundle"]
+    )]
+    pub mate_1: Option<String>,
+
+    /// Second mates of paired reads, in the same order as the first mates
+    #[arg(short = '2', long, requires = "mate_1")]
+    pub mate_2: Option<String>,
 
     /// Input file path, for k-mer counting
     #[arg(short, long)]
     pub alt_input: Option<String>,
 
+    /// Compress temporary chunk files (lz4 when no codec is given)
+    #[clap(value_enum, long, num_args = 0..=1, default_missing_value = "lz4")]
+    pub compress_tmp: Option<TmpCodecPreset>,
+
+    /// Use existing k-mer counts instead of counting: a kmertools counts table (binary or text)
+    /// or a Jellyfish (dump, dump -c) or KMC (kmc_dump) text dump, .jf and KMC databases are
+    /// not read
+    #[arg(long, visible_alias = "import-counts", conflicts_with = "alt_input")]
+    pub counts_input: Option<String>,
+
+    /// Format of the counted k-mers written to the output
+    #[clap(value_enum, long, default_value_t = CountsFmtPreset::Text)]
+    pub counts_format: CountsFmtPreset,
+
     /// Output directory path
     #[arg(short, long)]
     pub output: String,
 
-    /// K size for the coverage histogram
-    #[arg(short, long, value_parser = clap::value_parser!(u64).range(7..=31), default_value_t = 15)]
+    /// K size for the coverage histogram (k > 32 uses 128-bit k-mers)
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(7..=63), default_value_t = 15)]
     pub k_size: u64,
 
     /// Output type to write
     #[clap(value_enum, short, long, default_value_t = VecFmtPreset::Spc)]
     pub preset: VecFmtPreset,
 
+    /// Compress the output vectors (adds .gz or .zst to the output path)
+    #[clap(value_enum, long)]
+    pub compress: Option<CompressPreset>,
+
+    /// Include header (with the abundance range of each bin)
+    #[arg(short = 'H', long)]
+    pub header: bool,
+
+    /// Write only the non-zero bins of each record
+    #[clap(value_enum, long, conflicts_with_all = ["header", "sklearn_bundle"])]
+    pub sparse: Option<SparsePreset>,
+
+    /// Start each row with the ID of its record
+    #[arg(long, conflicts_with = "sklearn_bundle")]
+    pub with_ids: bool,
+
+    /// Add the length of each record after its ID
+    #[arg(long, requires = "with_ids")]
+    pub with_lengths: bool,
+
+    /// Decimal places of normalised coverages
+    #[arg(long, default_value_t = 6)]
+    pub precision: usize,
+
     /// Bin size for the coverage histogram
     #[arg(short = 's', long = "bin-size", value_parser = clap::value_parser!(u64).range(5..), default_value_t = 16)]
     pub bin_size: u64,
@@ -156,6 +874,10 @@ pub struct CoverageCommand {
     #[arg(short = 'c', long = "bin-count", value_parser = clap::value_parser!(u64).range(5..), default_value_t = 16)]
     pub bin_count: u64,
 
+    /// Scale of the coverage histogram bins
+    #[clap(value_enum, long, default_value_t = BinScalePreset::Linear)]
+    pub bin_scale: BinScalePreset,
+
     /// Max memory in GB
     #[arg(short, long, value_parser = clap::value_parser!(u64).range(6..=128), default_value_t = 6)]
     pub memory: u64,
@@ -164,71 +886,837 @@ pub struct CoverageCommand {
     #[arg(long)]
     pub counts: bool,
 
-    /// Thread count for computations 0=auto
-    #[arg(short, long, default_value_t = 0)]
-    pub threads: usize,
-}
+    /// Scale raw counts to k-mers per million k-mers of the sample, for comparing samples
+    #[arg(long, conflicts_with_all = ["counts", "scale_factor"])]
+    pub cpm: bool,
 
-// MINIMISERS
-#[derive(Debug, Args)]
-pub struct MinimiserCommand {
-    /// Input file path
-    #[arg(short, long)]
-    pub input: String,
+    /// Multiply raw counts by this factor of the sample, for comparing samples
+    #[arg(long, conflicts_with = "counts")]
+    pub scale_factor: Option<f64>,
 
-    /// Output vectors path
-    #[arg(short, long)]
-    pub output: String,
+    /// Library strandedness, stranded libraries count k-mers in transcript orientation
+    #[clap(value_enum, long, default_value_t = LibraryPreset::Unstranded)]
+    pub library: LibraryPreset,
 
-    /// Minimiser size
-    #[arg(short, long, value_parser = clap::value_parser!(u64).range(7..=28), default_value_t = 10)]
-    pub m_size: u64,
+    /// Count forward-strand k-mers as observed (as --library fr-secondstrand)
+    #[arg(long, conflicts_with = "library")]
+    pub no_canonical: bool,
 
-    /// Window size
-    ///
-    /// 0 - emits one minimiser per sequence (useful for sequencing reads)
-    /// w_size must be longer than m_size
-    #[arg(short, long, value_parser = clap::value_parser!(u64).range(0..), verbatim_doc_comment, default_value_t = 0)]
-    pub w_size: u64,
+    /// Take k-mers of homopolymer compressed reads (runs of a base count once)
+    #[arg(long, conflicts_with_all = ["solid", "depth"])]
+    pub hpc: bool,
 
-    /// Output type to write
-    #[clap(value_enum, short, long, default_value_t = MinFmtPreset::S2m)]
-    pub preset: MinFmtPreset,
+    /// Write k-mers and skipped k-mers (Ns/ambiguous bases) of each record
+    #[arg(long)]
+    pub record_stats: bool,
 
-    /// Thread count for computations 0=auto
-    #[arg(short, long, default_value_t = 0)]
-    pub threads: usize,
+    /// Report skipped k-mers (Ns/ambiguous bases) of all records
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Write <output>/run.json with the time and peak memory of each stage, and report them
+    #[arg(long)]
+    pub provenance: bool,
+
+    /// Also write <output>/kmers.solid marking k-mer positions counted at least this many times
+    ///
+    /// Lines are <id>\t<mask>, one mask position per k-mer start
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), verbatim_doc_comment)]
+    pub solid: Option<u64>,
+
+    /// Encoding of the solid k-mer masks
+    #[clap(value_enum, long, default_value_t = MaskPreset::Rle, requires = "solid")]
+    pub solid_format: MaskPreset,
+
+    /// Also write the k-mer count at each position of every record to <output>/kmers.depth.*,
+    /// averaged over windows of this many positions (1 for every position)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), num_args = 0..=1, default_missing_value = "1")]
+    pub depth: Option<u64>,
+
+    /// Format of the per-position depths
+    #[clap(value_enum, long, default_value_t = DepthPreset::Bedgraph, requires = "depth")]
+    pub depth_format: DepthPreset,
+
+    /// Also write <output>/kmers.summary with the mean, median, min and max k-mer count of each
+    /// record and the fraction of its k-mers counted at least this many times
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub summary: Option<u64>,
+
+    /// Write <output>/kmers.vectors.json with feature names, record IDs, settings, dtype and shape
+    #[arg(long)]
+    pub sklearn_bundle: bool,
+
+    /// Also write <output>/kmers.vectors.npz with X and ids for numpy.load
+    #[arg(long, requires = "sklearn_bundle")]
+    pub npz: bool,
+
+    /// Only process records whose IDs are listed in this file
+    #[arg(long, conflicts_with = "exclude_ids")]
+    pub include_ids: Option<String>,
+
+    /// Skip records whose IDs are listed in this file
+    #[arg(long)]
+    pub exclude_ids: Option<String>,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+// FILTER
+#[derive(Debug, Args)]
+pub struct FilterCommand {
+    /// Input file path
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Output directory path (filtered.fq, or filtered.fa for FASTA and BAM input)
+    #[arg(short, long)]
+    pub output: String,
+
+    /// K size for counting (k > 32 uses 128-bit k-mers)
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(7..=63), default_value_t = 15)]
+    pub k_size: u64,
+
+    /// Drop reads with a lower median k-mer count (likely sequencing errors)
+    #[arg(long, default_value_t = 0.0)]
+    pub min_median: f64,
+
+    /// Drop reads with a higher median k-mer count (likely repeats or contaminants)
+    #[arg(long)]
+    pub max_median: Option<f64>,
+
+    /// Use existing k-mer counts instead of counting: a kmertools counts table (binary or text)
+    /// or a Jellyfish (dump, dump -c) or KMC (kmc_dump) text dump, .jf and KMC databases are
+    /// not read
+    #[arg(long)]
+    pub counts_input: Option<String>,
+
+    /// Library strandedness, stranded libraries count k-mers in transcript orientation
+    #[clap(value_enum, long, default_value_t = LibraryPreset::Unstranded)]
+    pub library: LibraryPreset,
+
+    /// Max memory in GB
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(6..=128), default_value_t = 6)]
+    pub memory: u64,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+// VECTORISE
+#[derive(Debug, Args)]
+pub struct VectoriseCommand {
+    /// Input file path
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Output directory path (features.vectors with the counts table of the coverages)
+    #[arg(short, long)]
+    pub output: String,
+
+    /// K size for the oligo frequencies
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(3..=7), default_value_t = 4)]
+    pub k_size: u64,
+
+    /// K size for the coverage histogram (k > 32 uses 128-bit k-mers)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(7..=63), default_value_t = 15)]
+    pub cov_k_size: u64,
+
+    /// Bin size for the coverage histogram
+    #[arg(short = 's', long = "bin-size", value_parser = clap::value_parser!(u64).range(5..), default_value_t = 16)]
+    pub bin_size: u64,
+
+    /// Number of bins for the coverage histogram
+    #[arg(short = 'c', long = "bin-count", value_parser = clap::value_parser!(u64).range(5..), default_value_t = 16)]
+    pub bin_count: u64,
+
+    /// Scale of the coverage histogram bins
+    #[clap(value_enum, long, default_value_t = BinScalePreset::Linear)]
+    pub bin_scale: BinScalePreset,
+
+    /// Use existing k-mer counts for the coverages instead of counting: a kmertools counts table
+    /// (binary or text) or a Jellyfish (dump, dump -c) or KMC (kmc_dump) text dump, .jf and KMC
+    /// databases are not read
+    #[arg(long)]
+    pub counts_input: Option<String>,
+
+    /// Format of the counted k-mers written to the output
+    #[clap(value_enum, long, default_value_t = CountsFmtPreset::Text)]
+    pub counts_format: CountsFmtPreset,
+
+    /// Disable normalisation and output raw counts
+    #[arg(long)]
+    pub counts: bool,
+
+    /// Write <output>/run.json with the time and peak memory of each stage, and report them
+    #[arg(long)]
+    pub provenance: bool,
+
+    /// Output type to write
+    #[clap(value_enum, short, long, default_value_t = VecFmtPreset::Spc)]
+    pub preset: VecFmtPreset,
+
+    /// Compress the output vectors (adds .gz or .zst to the output path)
+    #[clap(value_enum, long)]
+    pub compress: Option<CompressPreset>,
+
+    /// Include header (k-mers, then coverage bins prefixed with cov_)
+    #[arg(short = 'H', long)]
+    pub header: bool,
+
+    /// Start each row with the ID of its record
+    #[arg(long)]
+    pub with_ids: bool,
+
+    /// Decimal places of normalised values
+    #[arg(long, default_value_t = 6)]
+    pub precision: usize,
+
+    /// Max memory in GB
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(6..=128), default_value_t = 6)]
+    pub memory: u64,
+
+    /// Only process records whose IDs are listed in this file
+    #[arg(long, conflicts_with = "exclude_ids")]
+    pub include_ids: Option<String>,
+
+    /// Skip records whose IDs are listed in this file
+    #[arg(long)]
+    pub exclude_ids: Option<String>,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+// MINIMISERS
+#[derive(Debug, Args)]
+pub struct MinimiserCommand {
+    /// Input file path
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Output vectors path
+    #[arg(short, long)]
+    pub output: String,
+
+    /// Minimiser size
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(7..=28), default_value_t = 10)]
+    pub m_size: u64,
+
+    /// Window size
+    ///
+    /// 0 - emits one minimiser per sequence (useful for sequencing reads)
+    /// w_size must be longer than m_size
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(0..), verbatim_doc_comment, default_value_t = 0)]
+    pub w_size: u64,
+
+    /// How m-mers are picked, syncmers are m-mers picked by their s-mers without windows
+    #[clap(value_enum, long, default_value_t = SchemePreset::Minimiser)]
+    pub scheme: SchemePreset,
+
+    /// Pick minimisers by an invertible hash of m-mers instead of their value, so that
+    /// low complexity m-mers such as poly-A do not gather sequences into large bins
+    #[arg(long)]
+    pub hash: bool,
+
+    /// Append the strand the minimiser was picked from (:+ or :-) to s2m output
+    #[arg(long)]
+    pub strand: bool,
+
+    /// Spaced seed such as 1101011, minimisers are k-mers of its 1 positions (m is its span)
+    ///
+    /// SNPs at 0 positions no longer change the minimisers of a window
+    #[arg(long, value_parser = SpacedSeed::parse, conflicts_with = "m_size", verbatim_doc_comment)]
+    pub seed: Option<SpacedSeed>,
+
+    /// s-mer size of syncmers
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 5)]
+    pub s_size: u64,
+
+    /// Position of the smallest s-mer in open syncmers, closed syncmers have it at either end
+    #[arg(long)]
+    pub offset: Option<u64>,
+
+    /// Output type to write
+    #[clap(value_enum, short, long, default_value_t = MinFmtPreset::S2m)]
+    pub preset: MinFmtPreset,
+
+    /// K-mer to label mapping (<kmer>\t<label> per line) to label m2s bins
+    ///
+    /// Writes majority label and confidence of each bin to <output>.labels
+    #[arg(short, long, verbatim_doc_comment)]
+    pub labels: Option<String>,
+
+    /// Reference sequences (e.g. MAG contigs) to assign reads with the map preset
+    ///
+    /// Writes <read>\t<reference>\t<shared minimisers>\t<read minimisers> per read
+    /// Reads sharing no minimisers are assigned to *
+    #[arg(short, long, verbatim_doc_comment)]
+    pub reference: Option<String>,
+
+    /// Additional <w_size>:<m_size> setting for the consensus preset (repeatable)
+    ///
+    /// Sequences share a bin only if they share the minimiser covering most
+    /// of the sequence under -w/-m and every additional setting
+    #[arg(long, value_parser = parse_min_setting, verbatim_doc_comment)]
+    pub setting: Vec<(usize, usize)>,
+
+    /// m-mer counts (kmers.counts of ctr -k <m_size>) to prefer rare minimisers in m2s and s2m
+    ///
+    /// Minimisers are picked by abundance then value, so repeat-derived
+    /// m-mers no longer gather sequences into a few huge bins
+    #[arg(long, verbatim_doc_comment)]
+    pub weights: Option<String>,
+
+    /// Input holds interleaved mate pairs, report how often mates land in different bins
+    ///
+    /// Writes <minimiser>\t<pairs>\t<discordant pairs>\t<rate> per bin to <output>.pairs
+    #[arg(long, verbatim_doc_comment)]
+    pub pairs: bool,
+
+    /// Write <output>.run.json with the time and peak memory of each stage, and report them
+    #[arg(long)]
+    pub provenance: bool,
+
+    /// Only process records whose IDs are listed in this file
+    #[arg(long, conflicts_with = "exclude_ids")]
+    pub include_ids: Option<String>,
+
+    /// Skip records whose IDs are listed in this file
+    #[arg(long)]
+    pub exclude_ids: Option<String>,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
 }
 
 // COUNTER
 #[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CounterCommand {
+    #[command(subcommand)]
+    pub command: Option<CounterCommands>,
+
+    #[command(flatten)]
+    pub count: Option<CountArgs>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CounterCommands {
+    /// Report reads containing given k-mers or their reverse complements
+    Locate(LocateCommand),
+    /// Set operations on two kmers.counts files
+    Ops(OpsCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct CountArgs {
+    /// Input file path
+    ///
+    /// Several inputs write a k-mer x sample table to <output>/kmers.matrix
+    #[arg(short, long, num_args = 1.., required_unless_present = "samples", verbatim_doc_comment)]
+    pub input: Vec<String>,
+
+    /// File of input files, one <path>[\t<name>] per line, counted into kmers.matrix
+    #[arg(long, conflicts_with = "input")]
+    pub samples: Option<String>,
+
+    /// Format of reads given on stdin with --input -
+    ///
+    /// Stdin is counted in a single pass, partitions grow as reads arrive
+    #[clap(value_enum, long, verbatim_doc_comment)]
+    pub format: Option<SeqFormatPreset>,
+
+    /// Output directory path
+    #[arg(short, long)]
+    pub output: String,
+
+    /// k size for counting (k > 32 uses 128-bit k-mers)
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(counter::KSIZES))]
+    pub k_size: u64,
+
+    /// Max memory in GB
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(6..=128), default_value_t = 6)]
+    pub memory: u64,
+
+    /// Fraction of the memory at which the largest partition is spilled early
+    ///
+    /// Reads are held while spilling, 1 or more only cuts chunks at the memory limit
+    #[arg(long, default_value_t = 0.75, verbatim_doc_comment)]
+    pub soft_limit: f64,
+
+    /// How memory use is measured against the memory limit
+    #[clap(value_enum, long, default_value_t = MemoryPolicyPreset::Occupancy)]
+    pub memory_policy: MemoryPolicyPreset,
+
+    /// Output ACGT instead of numeric values
+    ///
+    /// This requires a larger space for the final result
+    /// compared to the compact numeric representation
+    #[arg(short, long, verbatim_doc_comment)]
+    pub acgt: bool,
+
+    /// Write numeric k-mers with an ACGT column in one pass
+    ///
+    /// Lines are <numeric>\t<ACGT>\t<count>
+    #[arg(long, conflicts_with = "acgt", verbatim_doc_comment)]
+    pub acgt_column: bool,
+
+    /// Write ACGT k-mers in RNA letters (U in place of T)
+    #[arg(long)]
+    pub rna: bool,
+
+    /// Write k-mers in ascending order (numeric, which is also ACGT lexicographic order)
+    #[arg(long)]
+    pub sorted: bool,
+
+    /// Use only every S-th k-mer position for approximate profiles
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 1)]
+    pub stride: u64,
+
+    /// Count k-mers of homopolymer compressed reads (runs of a base count once)
+    ///
+    /// As minimap2 -H, for long reads with homopolymer length errors
+    #[arg(long, verbatim_doc_comment)]
+    pub hpc: bool,
+
+    /// Extract k-mers on a CUDA GPU (k up to 32, builds with --features gpu)
+    ///
+    /// Counting falls back to the CPU when no GPU can be used
+    #[arg(long, verbatim_doc_comment)]
+    pub gpu: bool,
+
+    /// Library strandedness, stranded libraries count k-mers in transcript orientation
+    #[clap(value_enum, long, default_value_t = LibraryPreset::Unstranded)]
+    pub library: LibraryPreset,
+
+    /// Count forward-strand k-mers as observed (as --library fr-secondstrand)
+    #[arg(long, conflicts_with = "library")]
+    pub no_canonical: bool,
+
+    /// Compress temporary chunk files (lz4 when no codec is given)
+    #[clap(value_enum, long, num_args = 0..=1, default_missing_value = "lz4")]
+    pub compress_tmp: Option<TmpCodecPreset>,
+
+    /// Write the k-mer abundance spectrum to <output>/kmers.histo
+    ///
+    /// Lines are <count>\t<number of distinct k-mers> (as jellyfish histo)
+    /// for genome size and heterozygosity estimation
+    #[arg(long, verbatim_doc_comment)]
+    pub histo: bool,
+
+    /// Drop k-mers counted fewer times from kmers.counts
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 1)]
+    pub min_count: u64,
+
+    /// Drop k-mers counted more times from kmers.counts
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = u64::MAX)]
+    pub max_count: u64,
+
+    /// Counter type, 32-bit counts overflow past 4294967295
+    #[clap(value_enum, long, default_value_t = CounterWidthPreset::U32)]
+    pub counter_width: CounterWidthPreset,
+
+    /// Write sorted binary shards to <output>/kmers.shards instead of kmers.counts
+    ///
+    /// One shard per partition with index.tsv of their k-mer ranges,
+    /// a point query reads a single shard
+    #[arg(long, conflicts_with_all = ["acgt", "acgt_column", "sorted"], verbatim_doc_comment)]
+    pub shards: bool,
+
+    /// Only estimate distinct and total k-mers with a HyperLogLog sketch, nothing is written
+    ///
+    /// Distinct k-mers are within about 1% without counting tables or temporary files,
+    /// several inputs are estimated together
+    #[arg(long, verbatim_doc_comment)]
+    pub estimate: bool,
+
+    /// Only count k-mers listed in this file
+    ///
+    /// kmers.counts of a previous run (numeric or ACGT k-mers in the first column)
+    /// or FASTA/FASTQ of target sequences whose k-mers are taken
+    #[arg(long, verbatim_doc_comment)]
+    pub whitelist: Option<String>,
+
+    /// Write <output>/kmers.stats and the <output>/kmers.chunks log, and report skipped k-mers
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Write <output>/run.json with the time and peak memory of each stage, and report them
+    #[arg(long)]
+    pub provenance: bool,
+
+    /// Only process records whose IDs are listed in this file
+    #[arg(long, conflicts_with = "exclude_ids")]
+    pub include_ids: Option<String>,
+
+    /// Skip records whose IDs are listed in this file
+    #[arg(long)]
+    pub exclude_ids: Option<String>,
+
+    // kept inline, a flattened group inside the optional CountArgs makes clap parse it as None
+    /// Thread count for computations 0=auto (KMERTOOLS_THREADS or CPUs allowed by cgroups/affinity)
+    #[arg(short, long, default_value_t = 0)]
+    pub threads: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct LocateCommand {
+    /// Input file path
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Output path
+    ///
+    /// Writes <read>\t<kmer>\t<position>\t<strand> per occurrence
+    #[arg(short, long, verbatim_doc_comment)]
+    pub output: String,
+
+    /// Query k-mer (repeat for several k-mers)
+    #[arg(short, long, required_unless_present = "kmers_file")]
+    pub kmer: Vec<String>,
+
+    /// File with query k-mers, one per line
+    #[arg(long)]
+    pub kmers_file: Option<String>,
+
+    /// Only process records whose IDs are listed in this file
+    #[arg(long, conflicts_with = "exclude_ids")]
+    pub include_ids: Option<String>,
+
+    /// Skip records whose IDs are listed in this file
+    #[arg(long)]
+    pub exclude_ids: Option<String>,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+// Set operations on k-mer counts
+#[derive(Debug, ValueEnum, Clone)]
+pub enum SetOpPreset {
+    /// K-mers of both inputs with the smaller count
+    Intersect,
+    /// K-mers of either input with summed counts
+    Union,
+    /// K-mers of the first input missing from the second
+    Subtract,
+    /// K-mers of the first input found in the second, with the fraction contained
+    Containment,
+}
+
+impl SetOpPreset {
+    fn op(&self) -> SetOp {
+        match self {
+            SetOpPreset::Intersect => SetOp::Intersect,
+            SetOpPreset::Union => SetOp::Union,
+            SetOpPreset::Subtract => SetOp::Subtract,
+            SetOpPreset::Containment => SetOp::Containment,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct OpsCommand {
+    /// Two kmers.counts files, both numeric or both ACGT k-mers of the same size
+    #[arg(short, long, num_args = 2, required = true)]
+    pub input: Vec<String>,
+
+    /// Output counts path
+    #[arg(short, long)]
+    pub output: String,
+
+    /// Operation, the first input is streamed and the second is held in memory
+    #[clap(value_enum, long, default_value_t = SetOpPreset::Intersect)]
+    pub op: SetOpPreset,
+}
+
+fn parse_min_setting(value: &str) -> Result<(usize, usize), String> {
+    let (wsize, msize) = value
+        .split_once(':')
+        .ok_or(format!("Expected <w_size>:<m_size>, got {}", value))?;
+    let wsize: usize = wsize
+        .parse()
+        .map_err(|_| format!("Invalid window size: {}", wsize))?;
+    let msize: usize = msize
+        .parse()
+        .map_err(|_| format!("Invalid minimiser size: {}", msize))?;
+    if !(7..=28).contains(&msize) {
+        return Err(format!("Minimiser size must be within 7-28, got {}", msize));
+    }
+    if wsize > 0 && wsize <= msize {
+        return Err("Window size must be longer than minimiser size!".to_string());
+    }
+    Ok((wsize, msize))
+}
+
+#[derive(Debug, Args)]
+pub struct SelftestCommand {
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+// SCAFFOLD
+// Workflow languages
+#[derive(Debug, ValueEnum, Clone)]
+pub enum WorkflowPreset {
+    /// Nextflow DSL2 module
+    Nextflow,
+    /// Snakemake rules
+    Snakemake,
+}
+
+impl WorkflowPreset {
+    fn workflow(&self) -> Workflow {
+        match self {
+            WorkflowPreset::Nextflow => Workflow::Nextflow,
+            WorkflowPreset::Snakemake => Workflow::Snakemake,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ScaffoldCommand {
+    /// Workflow language of the module
+    ///
+    /// Processes take their options from the arguments of this build,
+    /// with the run JSON of each step as a provenance output
+    #[clap(value_enum, short, long, verbatim_doc_comment)]
+    pub workflow: WorkflowPreset,
+
+    /// Output module path
+    #[arg(short, long)]
+    pub output: String,
+}
+
+// DAEMON
+#[derive(Debug, Args)]
+pub struct DaemonCommand {
+    /// Unix socket path to listen on
+    ///
+    /// Requests are JSON objects, one per line, answered by one JSON line:
+    ///   {"op": "load", "name": "ref", "path": "ref/kmers.counts.bin"}
+    ///   {"op": "query", "index": "ref", "kmers": ["ACGTACGTACGTACG"]}
+    ///   {"op": "classify", "index": "ref", "input": "reads.fq", "output": "hits.tsv"}
+    ///   {"op": "count", "input": "reads.fq", "output": "counts", "k": 15}
+    ///   {"op": "job", "id": 0}, {"op": "cancel", "id": 0}, {"op": "status"}
+    ///   {"op": "unload", "name": "ref"}, {"op": "shutdown"}
+    /// count and classify are queued and run one at a time, they answer with a job id
+    /// indexes are kmertools counts tables, only queued jobs can be cancelled and jobs report
+    /// their state without progress
+    /// the socket is only usable by its owner and serves 16 connections at once
+    #[arg(short, long, verbatim_doc_comment)]
+    pub socket: String,
+
+    /// Thread count for counting jobs 0=auto (KMERTOOLS_THREADS or CPUs allowed by cgroups/affinity)
+    #[arg(short, long, default_value_t = 0)]
+    pub threads: usize,
+}
+
+// rows are the records of the input, in order
+fn sklearn_bundle(
+    in_path: &str,
+    filter: Option<RecordFilter>,
+    feature_names: Vec<String>,
+) -> Result<Bundle, String> {
+    if in_path == "-" {
+        return Err(String::from("Bundles need a file input to list record IDs"));
+    }
+    Ok(Bundle::new(feature_names, record_ids(in_path, filter)?))
+}
+
+// k-mers are written in RNA letters with --rna
+fn molecule(rna: bool) -> Molecule {
+    if rna {
+        Molecule::Rna
+    } else {
+        Molecule::Dna
+    }
+}
+
+fn record_filter(
+    include_ids: &Option<String>,
+    exclude_ids: &Option<String>,
+) -> Result<Option<RecordFilter>, String> {
+    match (include_ids, exclude_ids) {
+        (Some(path), _) => Ok(Some(RecordFilter::include_from(path)?)),
+        (None, Some(path)) => Ok(Some(RecordFilter::exclude_from(path)?)),
+        (None, None) => Ok(None),
+    }
+}
+
+fn print_kmer_stats(stats: KmerStats) {
+    eprintln!(
+        "Skipped k-mers (Ns/ambiguous bases): {} of {}",
+        stats.skipped,
+        stats.kmers + stats.skipped
+    );
+}
+
+// wall time and peak memory of each stage, also written as run JSON
+fn finish_profile(profiler: &Profiler, run_path: &str) {
+    eprint!("{}", profiler.report());
+    if let Err(e) = profiler.write_json(run_path) {
+        eprintln!("Error: {}", e);
+    }
+}
+
+// CONVERT
+// Target k-mer representation
+#[derive(Debug, ValueEnum, Clone)]
+pub enum KmerFmtPreset {
+    /// ACGT k-mers
+    Acgt,
+    /// Compact numeric k-mers
+    Numeric,
+    /// Jellyfish column dump text (as jellyfish dump -c), counts only, not a .jf database
+    Jellyfish,
+    /// Jellyfish FASTA dump text (as jellyfish dump), counts only, not a .jf database
+    JellyfishFasta,
+    /// KMC dump text (as kmc_dump), counts only, not a KMC database
+    Kmc,
+}
+
+#[derive(Debug, Args)]
+pub struct ConvertCommand {
+    /// Input file path (kmers.counts or m2s minimiser table)
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Output file path
+    #[arg(short, long)]
+    pub output: String,
+
+    /// Representation to convert the k-mers to
+    #[clap(value_enum, long)]
+    pub to: KmerFmtPreset,
+
+    /// k size of the numeric k-mers (required with --to acgt and when exporting numeric k-mers)
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..=32))]
+    pub k_size: Option<u64>,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+// RESCALE
+#[derive(Debug, Args)]
+pub struct RescaleCommand {
+    /// Input file path (kmers.counts)
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Output file path
+    #[arg(short, long)]
+    pub output: String,
+
+    /// Multiply counts by this factor
+    #[arg(short, long, conflicts_with = "target")]
+    pub factor: Option<f64>,
+
+    /// Target k-mer coverage
+    #[arg(long, required_unless_present = "factor")]
+    pub target: Option<f64>,
+
+    /// Current k-mer coverage (estimated from the count spectrum if not given)
+    #[arg(short, long, requires = "target")]
+    pub coverage: Option<f64>,
+
+    /// Round scaled counts stochastically using this seed
+    ///
+    /// Counts are rounded to the nearest integer otherwise
+    #[arg(short, long, verbatim_doc_comment)]
+    pub seed: Option<u64>,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+// REGIONS
+#[derive(Debug, Args)]
+pub struct RegionsCommand {
+    /// Input FASTA file path
+    #[arg(short, long)]
+    pub input: String,
+
+    /// Annotation file path (BED labelled by name, GFF/GTF labelled by feature type)
+    #[arg(short, long)]
+    pub regions: String,
+
+    /// Output file path (<kmer>\t<label>\t<count> per line)
+    #[arg(short, long)]
+    pub output: String,
+
+    /// k size for counting
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..=32))]
+    pub k_size: u64,
+
+    /// Output ACGT instead of numeric values
+    #[arg(short, long)]
+    pub acgt: bool,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+// SHUFFLE
+#[derive(Debug, Args)]
+pub struct ShuffleCommand {
     /// Input file path
     #[arg(short, long)]
     pub input: String,
 
-    /// Output directory path
+    /// Output FASTA path
+    #[arg(short, long)]
+    pub output: String,
+
+    /// Preserve counts of k-lets (1=bases, 2=dinucleotides)
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..=2), default_value_t = 2)]
+    pub klet: u64,
+
+    /// Number of shuffled copies per sequence
+    #[arg(short, long, default_value_t = 1)]
+    pub count: usize,
+
+    /// Random seed for reproducible shuffles
+    #[arg(short, long, default_value_t = 0)]
+    pub seed: u64,
+
+    #[command(flatten)]
+    pub pool: ThreadArgs,
+}
+
+// SKETCH
+#[derive(Debug, Subcommand)]
+pub enum SketchCommands {
+    /// Flag near-identical samples (possible sample swaps/duplicates)
+    DedupeSamples(DedupeSamplesCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct DedupeSamplesCommand {
+    /// Input file paths, one per sample
+    #[arg(short, long, num_args = 2.., required = true)]
+    pub input: Vec<String>,
+
+    /// Output distance table path
     #[arg(short, long)]
     pub output: String,
 
-    /// k size for counting
-    #[arg(short, long, value_parser = clap::value_parser!(u64).range(10..32))]
+    /// k size for sketching
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(5..=32), default_value_t = 21)]
     pub k_size: u64,
 
-    /// Max memory in GB
-    #[arg(short, long, value_parser = clap::value_parser!(u64).range(6..=128), default_value_t = 6)]
-    pub memory: u64,
+    /// Number of hashes kept per sketch
+    #[arg(short, long, default_value_t = 1000)]
+    pub sketch_size: usize,
 
-    /// Output ACGT instead of numeric values
-    ///
-    /// This requires a larger space for the final result
-    /// compared to the compact numeric representation
-    #[arg(short, long, verbatim_doc_comment)]
-    pub acgt: bool,
+    /// Flag sample pairs within this mash distance
+    #[arg(short, long, default_value_t = 0.001)]
+    pub max_distance: f64,
 
-    /// Thread count for computations 0=auto
-    #[arg(short, long, default_value_t = 0)]
-    pub threads: usize,
+    #[command(flatten)]
+    pub pool: ThreadArgs,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -236,24 +1724,275 @@ pub fn cli(cli: Cli) {
     match cli.command {
         Commands::Comp { command } => match command {
             CompositionCommands::Oligo(command) => {
+                let filter = match record_filter(&command.include_ids, &command.exclude_ids) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                };
+                if command.sklearn_bundle && command.preset.matrix().is_some() {
+                    eprintln!("Error: --sklearn-bundle needs a text preset");
+                    return;
+                }
+                if command.compress.is_some() && command.preset.matrix().is_some() {
+                    eprintln!("Error: --compress needs a text preset");
+                    return;
+                }
+                if command.sparse.is_some() && command.preset.matrix().is_some() {
+                    eprintln!("Error: --sparse needs a text preset");
+                    return;
+                }
+                let k_size = command
+                    .seed
+                    .as_ref()
+                    .map_or(command.k_size as usize, SpacedSeed::weight);
+                if !(3..=31).contains(&k_size) {
+                    eprintln!("Error: --seed needs a weight between 3 and 31");
+                    return;
+                }
+                if command.sklearn_bundle && k_size > MAX_DENSE_KSIZE {
+                    eprintln!("Error: --sklearn-bundle needs k up to {}", MAX_DENSE_KSIZE);
+                    return;
+                }
+                let run_path = format!("{}.run.json", command.output);
+                let bundle_filter = filter.clone();
+                let mut com =
+                    OligoComputer::new(command.input.clone(), command.output.clone(), k_size);
+                if command.pool.threads > 0 {
+                    com.set_threads(command.pool.threads);
+                }
+                let mut format = command.preset.output_format(
+                    command.header,
+                    Some(command.precision),
+                    command.compress,
+                );
+                if command.with_ids {
+                    format.ids = IdPolicy::First;
+                }
+                format.sparse = command.sparse.map(SparsePreset::format);
+                let delim = format.delim.clone();
+                let matrix_path = format.path(&command.output);
+                let library = command.library.or_forward(command.stranded);
+                // hash buckets are of canonical k-mers only
+                let orientation = match (&command.canonical, library.strand()) {
+                    (CanonicalPreset::Min, Strand::Canonical) => Orientation::Min,
+                    (CanonicalPreset::Hash, Strand::Canonical) => Orientation::Hash,
+                    (CanonicalPreset::Min, Strand::Forward) => Orientation::Forward,
+                    (CanonicalPreset::Min, Strand::Reverse) => Orientation::Reverse,
+                    (CanonicalPreset::Hash, _) => {
+                        eprintln!("Error: --canonical hash needs an unstranded library");
+                        return;
+                    }
+                };
+                let normalisation = match command.markov {
+                    Some(order) => Normalisation::Markov {
+                        order: order as usize,
+                        enrichment: match command.score {
+                            ScorePreset::Ratio => Enrichment::Ratio,
+                            ScorePreset::LogOdds => Enrichment::LogOdds,
+                        },
+                    },
+                    None if command.counts => Normalisation::Counts,
+                    None => match command.norm.normalisation(command.packed) {
+                        Ok(normalisation) => normalisation,
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            return;
+                        }
+                    },
+                };
+                let scale = match (command.scale, command.scale_params.as_deref()) {
+                    (Some(scale), _) => Some(Rescale::Fit(scale.scaling())),
+                    (None, Some(path)) => match ColumnScaler::load(path) {
+                        Ok(scaler) => Some(Rescale::Saved(scaler)),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            return;
+                        }
+                    },
+                    (None, None) => None,
+                };
+                let rows = match (command.window, &command.augment) {
+                    (Some(size), _) => Rows::Windows {
+                        size: size as usize,
+                        step: command.step.unwrap_or(size) as usize,
+                    },
+                    (None, Some(AugmentPreset::Rc)) => Rows::AugmentRc,
+                    (None, None) => Rows::Records,
+                };
+                let options = OligoOptions {
+                    kmers: OligoKmers {
+                        orientation,
+                        seed: command.seed.clone(),
+                        stride: command.stride as usize,
+                        hpc: command.hpc,
+                        skip_masked: command.skip_masked,
+                        molecule: molecule(command.rna),
+                    },
+                    normalisation,
+                    scale,
+                    output: OligoOutput {
+                        format,
+                        rows,
+                        with_lengths: command.with_lengths,
+                        extra_features: command.extra_features,
+                        record_stats: command.record_stats,
+                    },
+                    filter,
+                };
+                if let Err(e) = com.set_options(options) {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+                let mut profiler = Profiler::new("comp oligo");
+                if let Err(e) = profiler.stage("vectorise", || com.vectorise()) {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+                if command.stats {
+                    print_kmer_stats(com.kmer_stats());
+                }
+                if command.sklearn_bundle {
+                    let result = sklearn_bundle(&command.input, bundle_filter, com.feature_names())
+                        .and_then(|mut bundle| {
+                            bundle.set_setting_str("command", "comp oligo");
+                            bundle.set_setting("ksize", k_size);
+                            if let Some(seed) = command.seed.as_ref() {
+                                bundle.set_setting_str("seed", &seed.pattern());
+                            }
+                            bundle.set_setting("normalised", !command.counts);
+                            if !command.counts {
+                                bundle.set_setting_str(
+                                    "norm",
+                                    command.norm.to_possible_value().unwrap().get_name(),
+                                );
+                            }
+                            if let Some(scale) = command.scale {
+                                bundle.set_setting_str(
+                                    "scale",
+                                    scale.to_possible_value().unwrap().get_name(),
+                                );
+                            }
+                            bundle.set_setting("stride", command.stride);
+                            bundle.set_setting("extra_features", command.extra_features);
+                            bundle.set_setting_str(
+                                "library",
+                                library.to_possible_value().unwrap().get_name(),
+                            );
+                            bundle.set_setting_str(
+                                "canonical",
+                                match command.canonical {
+                                    CanonicalPreset::Min => "min",
+                                    CanonicalPreset::Hash => "hash",
+                                },
+                            );
+                            if let Some(order) = command.markov {
+                                bundle.set_setting("markov_order", order);
+                                bundle.set_setting_str(
+                                    "score",
+                                    match command.score {
+                                        ScorePreset::Ratio => "ratio",
+                                        ScorePreset::LogOdds => "log-odds",
+                                    },
+                                );
+                            }
+                            bundle.write(&matrix_path, &delim, command.header, command.npz)
+                        });
+                    if let Err(e) = result {
+                        eprintln!("Error: {}", e);
+                    }
+                }
+                if command.provenance {
+                    finish_profile(&profiler, &run_path);
+                }
+            }
+            CompositionCommands::Outliers(command) => {
+                let filter = match record_filter(&command.include_ids, &command.exclude_ids) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                };
                 let mut com =
                     OligoComputer::new(command.input, command.output, command.k_size as usize);
-                if command.threads > 0 {
-                    com.set_threads(command.threads);
+                if command.pool.threads > 0 {
+                    com.set_threads(command.pool.threads);
+                }
+                if let Err(e) = com.set_options(OligoOptions {
+                    filter,
+                    ..Default::default()
+                }) {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+                match com.compute_outliers(command.threshold) {
+                    Ok((outliers, total)) => eprintln!("Outliers: {}/{}", outliers, total),
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+            CompositionCommands::Codon(command) => {
+                let filter = match record_filter(&command.include_ids, &command.exclude_ids) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                };
+                if command.compress.is_some() && command.preset.matrix().is_some() {
+                    eprintln!("Error: --compress needs a text preset");
+                    return;
+                }
+                let mut com = CodonComputer::new(command.input, command.output);
+                if command.pool.threads > 0 {
+                    com.set_threads(command.pool.threads);
                 }
                 com.set_norm(!command.counts);
-                com.set_header(command.header);
-
-                match command.preset {
-                    VecFmtPreset::Csv => com.set_delim(",".to_owned()),
-                    VecFmtPreset::Spc => com.set_delim(" ".to_owned()),
-                    VecFmtPreset::Tsv => com.set_delim("\t".to_owned()),
+                let frame = if command.detect_frame {
+                    Frame::LongestOrf
+                } else {
+                    Frame::Offset(command.frame as usize)
+                };
+                if let Err(e) = com.set_frame(frame) {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+                let mut format = command.preset.output_format(
+                    command.header,
+                    Some(command.precision),
+                    command.compress,
+                );
+                if command.with_ids {
+                    format.ids = IdPolicy::First;
                 }
+                com.set_format(format);
+                com.set_filter(filter);
+                com.set_molecule(molecule(command.rna));
                 if let Err(e) = com.vectorise() {
                     eprintln!("Error: {}", e);
                 }
             }
             CompositionCommands::Cgr(command) => {
+                let filter = match record_filter(&command.include_ids, &command.exclude_ids) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                };
+                if command.preset.matrix().is_some() && !command.fcgr {
+                    eprintln!("Error: CGR vectors are only written as text, or --fcgr matrices");
+                    return;
+                }
+                let mut cgr_format = command.preset.output_format(
+                    command.header,
+                    command.precision,
+                    command.compress,
+                );
+                if command.with_ids {
+                    cgr_format.ids = IdPolicy::First;
+                }
                 if let Some(ksize) = command.k_size {
                     let vecsize = command
                         .vec_size
@@ -265,10 +2004,21 @@ pub fn cli(cli: Cli) {
                         ksize as usize,
                         vecsize,
                     );
-                    if command.threads > 0 {
-                        cgr.set_threads(command.threads);
+                    if command.pool.threads > 0 {
+                        cgr.set_threads(command.pool.threads);
                     }
                     cgr.set_norm(!command.counts);
+                    cgr.set_fcgr(command.fcgr);
+                    cgr.set_molecule(molecule(command.rna));
+                    if let Err(e) =
+                        cgr.set_png(command.png.map(|dir| (dir, command.png_size as usize)))
+                    {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                    cgr.set_filter(filter);
+                    cgr.set_format(cgr_format);
+                    cgr.set_skip_masked(command.skip_masked);
                     if let Err(e) = cgr.vectorise() {
                         eprintln!("Error: {}", e);
                     }
@@ -279,9 +2029,18 @@ pub fn cli(cli: Cli) {
                     }
                     let vecsize = command.vec_size.unwrap_or(1) as usize;
                     let mut cgr = CgrComputer::new(command.input, command.output, vecsize);
-                    if command.threads > 0 {
-                        cgr.set_threads(command.threads);
+                    if command.pool.threads > 0 {
+                        cgr.set_threads(command.pool.threads);
                     }
+                    if let Err(e) =
+                        cgr.set_png(command.png.map(|dir| (dir, command.png_size as usize)))
+                    {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                    cgr.set_filter(filter);
+                    cgr.set_format(cgr_format);
+                    cgr.set_skip_masked(command.skip_masked);
                     if let Err(e) = cgr.vectorise() {
                         eprintln!("Error: {}", e);
                     }
@@ -289,34 +2048,153 @@ pub fn cli(cli: Cli) {
             }
         },
         Commands::Cov(command) => {
+            let filter = match record_filter(&command.include_ids, &command.exclude_ids) {
+                Ok(filter) => filter,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            };
+            if command.sklearn_bundle && command.preset.matrix().is_some() {
+                eprintln!("Error: --sklearn-bundle needs a text preset");
+                return;
+            }
+            if command.compress.is_some() && command.preset.matrix().is_some() {
+                eprintln!("Error: --compress needs a text preset");
+                return;
+            }
+            if command.sparse.is_some() && command.preset.matrix().is_some() {
+                eprintln!("Error: --sparse needs a text preset");
+                return;
+            }
+            if let Some(Err(e)) = command.preset.matrix().map(MatrixFormat::available) {
+                eprintln!("Error: {}", e);
+                return;
+            }
             create_directory(&command.output).unwrap();
+            let run_path = format!("{}/run.json", command.output);
+            let bundle_filter = filter.clone();
+            // pairs are vectorised from the first mates, in step with the second
+            let input = command.input.or(command.mate_1).unwrap();
             let mut cov = CovComputer::new(
-                command.input,
-                command.output,
+                input.clone(),
+                command.output.clone(),
                 command.k_size as usize,
                 command.bin_size as usize,
                 command.bin_count as usize,
             );
-            if command.threads > 0 {
-                cov.set_threads(command.threads);
+            if command.pool.threads > 0 {
+                cov.set_threads(command.pool.threads);
             }
             if let Some(path) = command.alt_input {
                 cov.set_kmer_path(path);
             }
+            cov.set_mate_path(command.mate_2);
             if command.counts {
                 cov.set_norm(false);
             }
+            let sample_scale = match (command.cpm, command.scale_factor) {
+                (true, _) => Some(SampleScale::Cpm),
+                (_, Some(factor)) => Some(SampleScale::Factor(factor)),
+                _ => None,
+            };
+            cov.set_sample_scale(sample_scale);
             cov.set_max_memory(command.memory as f64);
-            match command.preset {
-                VecFmtPreset::Csv => cov.set_delim(",".to_owned()),
-                VecFmtPreset::Spc => cov.set_delim(" ".to_owned()),
-                VecFmtPreset::Tsv => cov.set_delim("\t".to_owned()),
+            cov.set_filter(filter);
+            cov.set_record_stats(command.record_stats);
+            let library = command.library.or_forward(command.no_canonical);
+            cov.set_strand(library.strand());
+            cov.set_hpc(command.hpc);
+            cov.set_counts_file(command.counts_input);
+            cov.set_binary_counts(matches!(command.counts_format, CountsFmtPreset::Bin));
+            cov.set_compress_tmp(TmpCodecPreset::codec(command.compress_tmp));
+            let mut format = command.preset.output_format(
+                command.header,
+                Some(command.precision),
+                command.compress,
+            );
+            if command.with_ids {
+                format.ids = IdPolicy::First;
+            }
+            format.sparse = command.sparse.map(SparsePreset::format);
+            let delim = format.delim.clone();
+            let matrix_path = format.path(&format!("{}/kmers.vectors", command.output));
+            cov.set_format(format);
+            cov.set_with_lengths(command.with_lengths);
+            cov.set_bin_scale(command.bin_scale.scale());
+            let mut profiler = Profiler::new("cov");
+            if let Err(e) = profiler.stage("count", || cov.build_table()) {
+                eprintln!("Error: {}", e);
+                return;
+            }
+            if let Err(e) = profiler.stage("vectorise", || cov.compute_coverages()) {
+                eprintln!("Error: {}", e);
+                return;
+            }
+            if let Some(min_count) = command.solid {
+                if let Err(e) = profiler.stage("solid", || {
+                    cov.compute_solid_masks(min_count, command.solid_format.encoding())
+                }) {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            }
+            if let Some(window) = command.depth {
+                if let Err(e) = profiler.stage("depth", || {
+                    cov.compute_depths(window as usize, command.depth_format.format())
+                }) {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            }
+            if let Some(min_count) = command.summary {
+                if let Err(e) = profiler.stage("summary", || cov.compute_summaries(min_count)) {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            }
+            if command.stats {
+                print_kmer_stats(cov.kmer_stats());
+            }
+            if command.sklearn_bundle {
+                let result = sklearn_bundle(&input, bundle_filter, cov.feature_names()).and_then(
+                    |mut bundle| {
+                        bundle.set_setting_str("command", "cov");
+                        bundle.set_setting("ksize", command.k_size);
+                        bundle.set_setting("normalised", !command.counts && sample_scale.is_none());
+                        if command.cpm {
+                            bundle.set_setting_str("sample_scale", "cpm");
+                        }
+                        if let Some(factor) = command.scale_factor {
+                            bundle.set_setting("sample_scale", factor);
+                        }
+                        bundle.set_setting("bin_size", command.bin_size);
+                        bundle.set_setting("bin_count", command.bin_count);
+                        bundle.set_setting_str(
+                            "bin_scale",
+                            command.bin_scale.to_possible_value().unwrap().get_name(),
+                        );
+                        bundle.set_setting_str(
+                            "library",
+                            library.to_possible_value().unwrap().get_name(),
+                        );
+                        bundle.write(&matrix_path, &delim, command.header, command.npz)
+                    },
+                );
+                if let Err(e) = result {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            if command.provenance {
+                finish_profile(&profiler, &run_path);
             }
-            cov.build_table().unwrap();
-            cov.compute_coverages();
         }
         Commands::Min(command) => {
-            if command.w_size <= command.m_size && command.w_size > 0 {
+            let m_size = command
+                .seed
+                .as_ref()
+                .map_or(command.m_size, |seed| seed.span() as u64);
+            if command.w_size <= m_size && command.w_size > 0 {
                 eprintln!("Window size must be longer than minimiser size!");
                 return;
             }
@@ -324,37 +2202,836 @@ pub fn cli(cli: Cli) {
                 eprintln!("Minimisers longer than 30 bases not allowed!");
                 return;
             }
+            let filter = match record_filter(&command.include_ids, &command.exclude_ids) {
+                Ok(filter) => filter,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            };
+
+            let labels = match command.labels.as_deref().map(KmerLabels::load) {
+                Some(Ok(labels)) => Some(labels),
+                Some(Err(e)) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+                None => None,
+            };
+            if command.preset == MinFmtPreset::Map && command.reference.is_none() {
+                eprintln!("Map preset requires --reference!");
+                return;
+            }
+            if command.preset == MinFmtPreset::Consensus && command.setting.is_empty() {
+                eprintln!("Consensus preset requires at least one --setting!");
+                return;
+            }
+            if command.weights.is_some()
+                && !matches!(command.preset, MinFmtPreset::M2s | MinFmtPreset::S2m)
+            {
+                eprintln!("Weights are only used by the m2s and s2m presets!");
+                return;
+            }
+            if command.hash && !matches!(command.preset, MinFmtPreset::M2s | MinFmtPreset::S2m) {
+                eprintln!("Hashed minimisers are only used by the m2s and s2m presets!");
+                return;
+            }
+            if command.strand && command.preset != MinFmtPreset::S2m {
+                eprintln!("Strands are only written by the s2m preset!");
+                return;
+            }
+            if command.hash && command.scheme == SchemePreset::Syncmer {
+                eprintln!("Hashing only applies to the minimiser scheme!");
+                return;
+            }
+            if command.seed.is_some()
+                && (command.scheme == SchemePreset::Syncmer
+                    || !matches!(command.preset, MinFmtPreset::M2s | MinFmtPreset::S2m))
+            {
+                eprintln!("Spaced seeds are only used by minimisers of the m2s and s2m presets!");
+                return;
+            }
+            let scheme = match command.scheme {
+                SchemePreset::Minimiser => Scheme::Minimiser {
+                    hashed: command.hash,
+                    seed: command.seed.as_ref(),
+                },
+                SchemePreset::Syncmer => {
+                    if !matches!(command.preset, MinFmtPreset::M2s | MinFmtPreset::S2m)
+                        || command.weights.is_some()
+                        || command.pairs
+                    {
+                        eprintln!("Syncmers are only used by the m2s and s2m presets without weights or pairs!");
+                        return;
+                    }
+                    if command.s_size >= command.m_size {
+                        eprintln!("s-mer size must be smaller than minimiser size!");
+                        return;
+                    }
+                    if command
+                        .offset
+                        .is_some_and(|offset| offset > command.m_size - command.s_size)
+                    {
+                        eprintln!("Offset must leave the s-mer within the syncmer!");
+                        return;
+                    }
+                    Scheme::Syncmer {
+                        ssize: command.s_size as usize,
+                        offset: command.offset.map(|offset| offset as usize),
+                    }
+                }
+            };
+            let weights = match command.weights.as_deref().map(|path| {
+                minimisers::load_weights(path, scheme.mmer_size(command.m_size as usize))
+            }) {
+                Some(Ok(weights)) => Some(weights),
+                Some(Err(e)) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+                None => None,
+            };
 
-            match command.preset {
+            let pairs_filter = filter.clone();
+            let mut profiler = Profiler::new("min");
+            profiler.stage("bin", || match command.preset {
                 MinFmtPreset::M2s => minimisers::bin_sequences(
                     command.w_size as usize,
                     command.m_size as usize,
                     &command.input,
                     &command.output,
-                    command.threads,
+                    command.pool.threads,
+                    labels.as_ref(),
+                    &MinimiserOptions {
+                        scheme,
+                        weights: weights.as_ref(),
+                        filter,
+                        ..Default::default()
+                    },
                 ),
                 MinFmtPreset::S2m => minimisers::seq_to_min(
                     command.w_size as usize,
                     command.m_size as usize,
                     &command.input,
                     &command.output,
-                    command.threads,
+                    command.pool.threads,
+                    &MinimiserOptions {
+                        scheme,
+                        weights: weights.as_ref(),
+                        filter,
+                        strand: command.strand,
+                    },
+                ),
+                MinFmtPreset::Map => {
+                    if let Err(e) = recruit::map_reads(
+                        command.reference.as_deref().unwrap(),
+                        &command.input,
+                        &command.output,
+                        command.w_size as usize,
+                        command.m_size as usize,
+                        command.pool.threads,
+                        filter,
+                    ) {
+                        eprintln!("Error: {}", e);
+                    }
+                }
+                MinFmtPreset::Consensus => {
+                    let mut settings = vec![(command.w_size as usize, command.m_size as usize)];
+                    settings.extend(command.setting.iter().copied());
+                    match minimisers::consensus_bins(
+                        &settings,
+                        &command.input,
+                        &command.output,
+                        command.pool.threads,
+                        filter,
+                    ) {
+                        Ok(bins) => eprintln!("Consensus bins: {}", bins),
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+            });
+            if command.pairs {
+                let result = profiler.stage("pairs", || {
+                    pairs::pair_concordance(
+                        command.w_size as usize,
+                        command.m_size as usize,
+                        &command.input,
+                        &format!("{}.pairs", command.output),
+                        command.pool.threads,
+                        pairs_filter,
+                    )
+                });
+                match result {
+                    Ok(global) => eprintln!(
+                        "Discordant pairs: {}/{} ({:.4})",
+                        global.discordant,
+                        global.pairs,
+                        global.rate()
+                    ),
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+            if command.provenance {
+                finish_profile(&profiler, &format!("{}.run.json", command.output));
+            }
+        }
+        Commands::Ctr(CounterCommand {
+            command: Some(CounterCommands::Locate(command)),
+            ..
+        }) => {
+            let filter = match record_filter(&command.include_ids, &command.exclude_ids) {
+                Ok(filter) => filter,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            };
+            let mut kmers = command.kmer;
+            if let Some(path) = &command.kmers_file {
+                match locate::load_kmers(path) {
+                    Ok(loaded) => kmers.extend(loaded),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                }
+            }
+            match locate::locate_kmers(
+                &command.input,
+                &kmers,
+                &command.output,
+                command.pool.threads,
+                filter,
+            ) {
+                Ok(found) => eprintln!("Reads containing query k-mers: {}", found),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        }
+        Commands::Ctr(CounterCommand {
+            command: Some(CounterCommands::Ops(command)),
+            ..
+        }) => {
+            match ops::set_operation(
+                &command.input[0],
+                &command.input[1],
+                &command.output,
+                command.op.op(),
+            ) {
+                Ok(stats) => {
+                    eprintln!("K-mers in inputs: {}, {}", stats.a, stats.b);
+                    eprintln!("Shared k-mers: {}", stats.shared);
+                    eprintln!("Containment: {:.6}", stats.containment());
+                    eprintln!("Jaccard: {:.6}", stats.jaccard());
+                    eprintln!("K-mers written: {}", stats.written);
+                }
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        }
+        Commands::Ctr(CounterCommand {
+            count: Some(command),
+            ..
+        }) => {
+            let filter = match record_filter(&command.include_ids, &command.exclude_ids) {
+                Ok(filter) => filter,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            };
+            if command.min_count > command.max_count {
+                eprintln!("Minimum count must not exceed maximum count!");
+                return;
+            }
+            let strand = command.library.or_forward(command.no_canonical).strand();
+            let whitelist = match &command.whitelist {
+                Some(path) => match Whitelist::load(path, command.k_size as usize, strand) {
+                    Ok(whitelist) => Some(whitelist),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                },
+                None => None,
+            };
+            let samples = match &command.samples {
+                Some(path) => match matrix::load_samples(path) {
+                    Ok(samples) => samples,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                },
+                None => {
+                    let names: HashSet<String> = command
+                        .input
+                        .iter()
+                        .map(|path| matrix::sample_name(path))
+                        .collect();
+                    // paths name the columns when file names collide
+                    command
+                        .input
+                        .iter()
+                        .map(|path| {
+                            if names.len() == command.input.len() {
+                                (matrix::sample_name(path), path.clone())
+                            } else {
+                                (path.clone(), path.clone())
+                            }
+                        })
+                        .collect()
+                }
+            };
+            if samples.is_empty() {
+                eprintln!("No input files given!");
+                return;
+            }
+            if samples.iter().any(|(_, path)| path == "-") {
+                if samples.len() > 1 {
+                    eprintln!("Stdin can only be counted on its own!");
+                    return;
+                }
+                if command.format.is_none() {
+                    eprintln!("Counting stdin requires --format!");
+                    return;
+                }
+            }
+            // stdin is streamed in the given format, files are read twice to plan partitions
+            let new_ctr = |path: &String| match (path.as_str(), command.format) {
+                ("-", Some(format)) => counter::CountComputer::from_stream(
+                    format.format(),
+                    get_reader("-").unwrap(),
+                    command.output.clone(),
+                    command.k_size as usize,
+                ),
+                _ => counter::CountComputer::new(
+                    path.clone(),
+                    command.output.clone(),
+                    command.k_size as usize,
                 ),
+            };
+            let kmers = if command.acgt {
+                KmerText::Acgt(molecule(command.rna))
+            } else if command.acgt_column {
+                KmerText::NumericAcgt(molecule(command.rna))
+            } else {
+                KmerText::Numeric
+            };
+            let options = CountOptions {
+                kmers: KmerOptions {
+                    strand,
+                    stride: command.stride as usize,
+                    hpc: command.hpc,
+                    whitelist,
+                    filter,
+                },
+                output: OutputOptions {
+                    counts: if command.shards {
+                        CountsOutput::Shards
+                    } else {
+                        CountsOutput::Text {
+                            kmers,
+                            sorted: command.sorted,
+                        }
+                    },
+                    width: command.counter_width.width(),
+                    histogram: command.histo,
+                    min_count: command.min_count,
+                    max_count: command.max_count,
+                    stats: command.stats,
+                },
+                partitions: PartitionOptions {
+                    soft_limit: command.soft_limit,
+                    memory_policy: command.memory_policy.policy(),
+                    compress_tmp: TmpCodecPreset::codec(command.compress_tmp),
+                    ..PartitionOptions::default()
+                },
+            };
+            let configure = |ctr: &mut counter::CountComputer| {
+                if command.threads > 0 {
+                    ctr.set_threads(command.threads);
+                }
+                ctr.set_max_memory(command.memory as f64);
+                ctr.set_options(options.clone());
+                if command.gpu {
+                    if let Err(e) = ctr.set_gpu(true) {
+                        eprintln!("Warning: {}, counting on the CPU", e);
+                    }
+                }
+            };
+            if command.estimate {
+                let mut estimate: Option<(HyperLogLog, u64)> = None;
+                for (_, path) in samples.iter() {
+                    let mut ctr = new_ctr(path);
+                    configure(&mut ctr);
+                    match (ctr.estimate(), estimate.as_mut()) {
+                        (Ok((hll, kmers)), Some((total_hll, total))) => {
+                            total_hll.merge(&hll);
+                            *total += kmers;
+                        }
+                        (Ok(sample), None) => estimate = Some(sample),
+                        (Err(e), _) => {
+                            eprintln!("Error: {}", e);
+                            return;
+                        }
+                    }
+                }
+                let (hll, total) = estimate.unwrap();
+                eprintln!("Distinct k-mers (estimated): {:.0}", hll.estimate());
+                eprintln!("Total k-mers: {}", total);
+                return;
+            }
+            create_directory(&command.output).unwrap();
+            let run_path = format!("{}/run.json", command.output);
+            if samples.len() > 1 {
+                let mut profiler = Profiler::new("ctr");
+                match profiler.stage("count", || {
+                    matrix::count_matrix(
+                        &samples,
+                        &command.output,
+                        command.k_size as usize,
+                        configure,
+                    )
+                }) {
+                    Ok(stats) if command.stats => print_kmer_stats(stats),
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                }
+                if command.provenance {
+                    finish_profile(&profiler, &run_path);
+                }
+                return;
+            }
+            let mut ctr = new_ctr(&samples[0].1);
+            configure(&mut ctr);
+            let mut profiler = Profiler::new("ctr");
+            profiler.stage("count", || ctr.count());
+            profiler.stage("merge", || ctr.merge(true));
+            if command.stats {
+                print_kmer_stats(ctr.kmer_stats());
+            }
+            if command.provenance {
+                finish_profile(&profiler, &run_path);
+            }
+        }
+        Commands::Selftest(command) => {
+            let results = match selftest::run(command.pool.threads) {
+                Ok(results) => results,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            let mut failed = false;
+            for (name, result) in results {
+                match result {
+                    Ok(()) => eprintln!("{:<12}ok", name),
+                    Err(e) => {
+                        eprintln!("{:<12}FAILED ({})", name, e);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                process::exit(1);
+            }
+        }
+        Commands::Scaffold(command) => {
+            let result =
+                scaffold::render(command.workflow.workflow(), &Cli::command()).and_then(|module| {
+                    std::fs::write(&command.output, module)
+                        .map_err(|_| format!("Unable to write to file: {}", command.output))
+                });
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+            }
+        }
+        Commands::Daemon(command) => {
+            if let Err(e) = daemon::serve(&command.socket, command.threads) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
             }
         }
-        Commands::Ctr(command) => {
+        Commands::Ctr(_) => unreachable!("clap requires counting arguments or a subcommand"),
+        Commands::Convert(command) => {
+            let to = match command.to {
+                KmerFmtPreset::Acgt => KmerFormat::Acgt,
+                KmerFmtPreset::Numeric => KmerFormat::Numeric,
+                KmerFmtPreset::Jellyfish => KmerFormat::Jellyfish,
+                KmerFmtPreset::JellyfishFasta => KmerFormat::JellyfishFasta,
+                KmerFmtPreset::Kmc => KmerFormat::Kmc,
+            };
+            if let Err(e) = convert::convert_kmers(
+                &command.input,
+                &command.output,
+                to,
+                command.k_size.unwrap_or(0) as usize,
+                command.pool.threads,
+            ) {
+                eprintln!("Error: {}", e);
+            }
+        }
+        Commands::Filter(command) => {
+            create_directory(&command.output).unwrap();
+            // bins are not used, reads are kept by their k-mer counts
+            let mut cov =
+                CovComputer::new(command.input, command.output, command.k_size as usize, 1, 1);
+            if command.pool.threads > 0 {
+                cov.set_threads(command.pool.threads);
+            }
+            cov.set_max_memory(command.memory as f64);
+            cov.set_strand(command.library.strand());
+            cov.set_counts_file(command.counts_input);
+            // reads are kept by searching the binary table, which stays in the output
+            cov.set_binary_counts(true);
+            let max_median = command.max_median.unwrap_or(f64::INFINITY);
+            match cov
+                .build_table()
+                .and_then(|_| cov.filter_reads(command.min_median, max_median))
+            {
+                Ok((kept, seen)) => eprintln!("Kept reads: {} of {}", kept, seen),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+        }
+        Commands::Vectorise(command) => {
+            let filter = match record_filter(&command.include_ids, &command.exclude_ids) {
+                Ok(filter) => filter,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            };
+            if command.compress.is_some() && command.preset.matrix().is_some() {
+                eprintln!("Error: --compress needs a text preset");
+                return;
+            }
+            if let Some(Err(e)) = command.preset.matrix().map(MatrixFormat::available) {
+                eprintln!("Error: {}", e);
+                return;
+            }
             create_directory(&command.output).unwrap();
-            let mut ctr =
-                counter::CountComputer::new(command.input, command.output, command.k_size as usize);
-            if command.threads > 0 {
-                ctr.set_threads(command.threads);
+            let vec_path = format!("{}/features.vectors", command.output);
+            let mut com = OligoComputer::new(
+                command.input.clone(),
+                vec_path.clone(),
+                command.k_size as usize,
+            );
+            if command.counts {
+                if let Err(e) = com.set_options(OligoOptions {
+                    normalisation: Normalisation::Counts,
+                    ..Default::default()
+                }) {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            }
+            let mut cov = CovComputer::new(
+                command.input.clone(),
+                command.output.clone(),
+                command.cov_k_size as usize,
+                command.bin_size as usize,
+                command.bin_count as usize,
+            );
+            cov.set_norm(!command.counts);
+            cov.set_max_memory(command.memory as f64);
+            cov.set_counts_file(command.counts_input);
+            cov.set_binary_counts(matches!(command.counts_format, CountsFmtPreset::Bin));
+            cov.set_bin_scale(command.bin_scale.scale());
+            if command.pool.threads > 0 {
+                com.set_threads(command.pool.threads);
+                cov.set_threads(command.pool.threads);
+            }
+            let mut format = command.preset.output_format(
+                command.header,
+                Some(command.precision),
+                command.compress,
+            );
+            if command.with_ids {
+                format.ids = IdPolicy::First;
+            }
+            let mut profiler = Profiler::new("vectorise");
+            if let Err(e) = profiler.stage("count", || cov.build_table()) {
+                eprintln!("Error: {}", e);
+                return;
+            }
+            let mut vectoriser = Vectoriser::new(command.input, vec_path, &com, &cov);
+            vectoriser.set_format(format);
+            vectoriser.set_norm(!command.counts);
+            vectoriser.set_filter(filter);
+            if command.pool.threads > 0 {
+                vectoriser.set_threads(command.pool.threads);
+            }
+            match profiler.stage("vectorise", || vectoriser.vectorise()) {
+                Ok(records) => eprintln!("Records vectorised: {}", records),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
             }
-            if command.acgt {
-                ctr.set_acgt_output(true);
+            if command.provenance {
+                finish_profile(&profiler, &format!("{}/run.json", command.output));
             }
-            ctr.set_max_memory(command.memory as f64);
-            ctr.count();
-            ctr.merge(true);
+        }
+        Commands::Rescale(command) => {
+            let factor = match (command.factor, command.target, command.coverage) {
+                (Some(factor), _, _) => factor,
+                (None, Some(target), Some(coverage)) => target / coverage,
+                (None, Some(target), None) => match rescale::estimate_coverage(&command.input) {
+                    Ok(coverage) => {
+                        eprintln!("Estimated k-mer coverage: {}", coverage);
+                        target / coverage
+                    }
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                },
+                (None, None, _) => unreachable!(),
+            };
+            let rounding = match command.seed {
+                Some(seed) => Rounding::Stochastic(seed),
+                None => Rounding::Deterministic,
+            };
+            if let Err(e) = rescale::rescale_counts(
+                &command.input,
+                &command.output,
+                factor,
+                rounding,
+                command.pool.threads,
+            ) {
+                eprintln!("Error: {}", e);
+            }
+        }
+        Commands::Regions(command) => {
+            if let Err(e) = regions::count_regions(
+                &command.input,
+                &command.regions,
+                &command.output,
+                command.k_size as usize,
+                command.acgt,
+                command.pool.threads,
+            ) {
+                eprintln!("Error: {}", e);
+            }
+        }
+        Commands::Shuffle(command) => {
+            if let Err(e) = shuffle::shuffle_sequences(
+                &command.input,
+                &command.output,
+                command.klet as usize,
+                command.count,
+                command.seed,
+                command.pool.threads,
+            ) {
+                eprintln!("Error: {}", e);
+            }
+        }
+        Commands::Sketch { command } => match command {
+            SketchCommands::DedupeSamples(command) => {
+                match dedupe::dedupe_samples(
+                    &command.input,
+                    &command.output,
+                    command.k_size as usize,
+                    command.sketch_size,
+                    command.max_distance,
+                    command.pool.threads,
+                ) {
+                    Ok(flagged) => eprintln!("Flagged sample pairs: {}", flagged),
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ctr_parse_test() {
+        let cli =
+            Cli::try_parse_from(["kmertools", "ctr", "-i", "x.fq", "-o", "o", "-k", "15"]).unwrap();
+        match cli.command {
+            Commands::Ctr(command) => {
+                assert!(command.command.is_none());
+                let count = command.count.unwrap();
+                assert_eq!(count.input, vec!["x.fq"]);
+                assert_eq!(count.threads, 0);
+            }
+            _ => panic!("expected ctr"),
+        }
+        let cli = Cli::try_parse_from([
+            "kmertools",
+            "ctr",
+            "-i",
+            "x.fq",
+            "-o",
+            "o",
+            "-k",
+            "15",
+            "-t",
+            "3",
+        ])
+        .unwrap();
+        match cli.command {
+            Commands::Ctr(command) => assert_eq!(command.count.unwrap().threads, 3),
+            _ => panic!("expected ctr"),
+        }
+        // shards are binary, text output options do not apply to them
+        for flag in ["--sorted", "--acgt", "--acgt-column"] {
+            assert!(Cli::try_parse_from([
+                "kmertools",
+                "ctr",
+                "-i",
+                "x.fq",
+                "-o",
+                "o",
+                "-k",
+                "15",
+                "--shards",
+                flag
+            ])
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn no_canonical_test() {
+        let count = |extra: &[&str]| {
+            let out = format!("../test_data/computed_no_canonical{}", extra.join(""));
+            let mut args = vec![
+                "kmertools",
+                "ctr",
+                "-i",
+                "../test_data/reads.fq",
+                "-o",
+                &out,
+            ];
+            args.extend(["-k", "15"]);
+            args.extend(extra);
+            std::fs::create_dir_all(&out).unwrap();
+            cli(Cli::try_parse_from(&args).unwrap());
+            std::fs::read_to_string(format!("{}/kmers.counts", out)).unwrap()
+        };
+        // forward-strand counts are those of a fr-secondstrand library
+        let mut forward: Vec<String> = count(&["--no-canonical"])
+            .lines()
+            .map(String::from)
+            .collect();
+        let mut library: Vec<String> = count(&["--library", "fr-secondstrand"])
+            .lines()
+            .map(String::from)
+            .collect();
+        forward.sort();
+        library.sort();
+        assert_eq!(forward, library);
+        let mut canonical: Vec<String> = count(&[]).lines().map(String::from).collect();
+        canonical.sort();
+        assert_ne!(forward, canonical);
+        // cov takes the same strand, and a library cannot be given with it
+        let cov = Cli::try_parse_from([
+            "kmertools",
+            "cov",
+            "-i",
+            "x.fq",
+            "-o",
+            "o",
+            "--no-canonical",
+        ])
+        .unwrap();
+        match cov.command {
+            Commands::Cov(command) => assert_eq!(
+                command.library.or_forward(command.no_canonical).strand(),
+                Strand::Forward
+            ),
+            _ => panic!("expected cov"),
+        }
+        let args = ["--no-canonical", "--library", "unstranded"];
+        assert!(Cli::try_parse_from(
+            ["kmertools", "cov", "-i", "x.fq", "-o", "o"]
+                .iter()
+                .chain(&args)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn stranded_oligo_test() {
+        let columns = |extra: &[&str]| {
+            let out = format!("../test_data/computed_stranded{}.txt", extra.join(""));
+            let mut args = vec!["kmertools", "comp", "oligo", "-i", "../test_data/reads.fq"];
+            args.extend(["-o", &out, "-k", "3"]);
+            args.extend(extra);
+            cli(Cli::try_parse_from(&args).unwrap());
+            let vectors = std::fs::read_to_string(&out).unwrap();
+            let counts: Vec<usize> = vectors
+                .lines()
+                .map(|line| line.split(' ').count())
+                .collect();
+            assert_eq!(counts.len(), 2);
+            counts[0]
+        };
+        // all 4^k forward k-mers in place of the canonical ones
+        assert_eq!(columns(&[]), 32);
+        assert_eq!(columns(&["--stranded"]), 64);
+        assert!(Cli::try_parse_from([
+            "kmertools",
+            "comp",
+            "oligo",
+            "-i",
+            "x.fq",
+            "-o",
+            "o",
+            "--stranded",
+            "--library",
+            "unstranded"
+        ])
+        .is_err());
+        // Markov scores are not counts
+        assert!(Cli::try_parse_from([
+            "kmertools",
+            "comp",
+            "oligo",
+            "-i",
+            "x.fq",
+            "-o",
+            "o",
+            "--counts",
+            "--markov",
+            "1"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn provenance_opt_in_test() {
+        let out = "../test_data/computed_provenance";
+        let runs = [
+            (vec!["ctr", "-k", "15"], format!("{}/ctr/run.json", out)),
+            (vec!["cov", "-k", "15"], format!("{}/cov/run.json", out)),
+            (vec!["vectorise"], format!("{}/vectorise/run.json", out)),
+            (vec!["min"], format!("{}/min.run.json", out)),
+            (vec!["comp", "oligo"], format!("{}/oligo.run.json", out)),
+        ];
+        for (command, run_path) in runs {
+            let _ = std::fs::remove_dir_all(out);
+            std::fs::create_dir_all(out).unwrap();
+            let output = run_path
+                .trim_end_matches("/run.json")
+                .trim_end_matches(".run.json");
+            let mut args = vec!["kmertools"];
+            args.extend(command);
+            args.extend(["-i", "../test_data/reads.fq", "-o", output]);
+            // no run JSON unless asked for
+            cli(Cli::try_parse_from(&args).unwrap());
+            assert!(std::path::Path::new(output).exists(), "{}", output);
+            assert!(!std::path::Path::new(&run_path).exists(), "{}", run_path);
+            args.push("--provenance");
+            cli(Cli::try_parse_from(&args).unwrap());
+            assert!(std::path::Path::new(&run_path).exists(), "{}", run_path);
         }
     }
 }
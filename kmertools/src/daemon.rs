@@ -0,0 +1,809 @@
+use counter::{counts::CountsReader, CountComputer, CountOptions, CountsOutput, OutputOptions};
+use kmer::{kmer::GenericKmerGenerator, strand::Strand, KmerInt};
+use ktio::{
+    bundle::json_string,
+    fops::create_directory,
+    json::{parse_object, Value},
+    seq::{get_reader, SeqFormat, Sequences},
+};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader, BufWriter, Write},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex, RwLock,
+    },
+    thread,
+};
+
+// connections served at once, further ones are answered with an error and closed
+const MAX_CONNECTIONS: usize = 16;
+
+// fields of a request line such as {"op": "query", "index": "ref", "kmers": ["ACGT"]}
+struct Request {
+    fields: HashMap<String, Value>,
+}
+
+impl Request {
+    fn parse(line: &str) -> Result<Self, String> {
+        let fields = parse_object(line).map_err(|e| format!("Invalid request: {}", e))?;
+        Ok(Self { fields })
+    }
+
+    fn str(&self, key: &str) -> Result<&str, String> {
+        match self.fields.get(key) {
+            Some(Value::Str(value)) => Ok(value),
+            Some(_) => Err(format!("Field must be a string: {}", key)),
+            None => Err(format!("Missing field: {}", key)),
+        }
+    }
+
+    fn usize(&self, key: &str) -> Result<usize, String> {
+        match self.fields.get(key) {
+            Some(Value::Num(value)) if *value >= 0_f64 && value.fract() == 0_f64 => {
+                Ok(*value as usize)
+            }
+            Some(_) => Err(format!("Field must be a non-negative integer: {}", key)),
+            None => Err(format!("Missing field: {}", key)),
+        }
+    }
+
+    fn flag(&self, key: &str) -> Result<bool, String> {
+        match self.fields.get(key) {
+            Some(Value::Bool(value)) => Ok(*value),
+            None | Some(Value::Null) => Ok(false),
+            Some(_) => Err(format!("Field must be a boolean: {}", key)),
+        }
+    }
+
+    fn strings(&self, key: &str) -> Result<Vec<&str>, String> {
+        match self.fields.get(key) {
+            Some(Value::List(values)) => values
+                .iter()
+                .map(|value| match value {
+                    Value::Str(value) => Ok(value.as_str()),
+                    _ => Err(format!("Field must be a list of strings: {}", key)),
+                })
+                .collect(),
+            Some(_) => Err(format!("Field must be a list of strings: {}", key)),
+            None => Err(format!("Missing field: {}", key)),
+        }
+    }
+}
+
+fn error_response(e: &str) -> String {
+    format!("{{\"ok\": false, \"error\": {}}}", json_string(e))
+}
+
+#[derive(Debug, Clone)]
+enum JobState {
+    Queued,
+    Running,
+    Done(String),
+    Failed(String),
+    Cancelled,
+}
+
+enum Job {
+    // k-mer counts of a file into an output directory, as ctr
+    Count {
+        input: String,
+        output: String,
+        ksize: usize,
+        binary: bool,
+    },
+    // fraction of the k-mers of each read found in a loaded index
+    Classify {
+        index: Arc<CountsReader>,
+        strand: Strand,
+        input: String,
+        output: String,
+    },
+    // stops the daemon once the jobs queued before it have run
+    Shutdown,
+}
+
+// loaded indexes and job states shared by the connection threads and the worker
+struct State {
+    threads: usize,
+    indexes: RwLock<HashMap<String, Arc<CountsReader>>>,
+    jobs: Mutex<Vec<JobState>>,
+    queue: Mutex<mpsc::Sender<(usize, Job)>>,
+}
+
+impl State {
+    fn submit(&self, job: Job) -> Result<usize, String> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = jobs.len();
+        self.queue
+            .lock()
+            .unwrap()
+            .send((id, job))
+            .map_err(|_| "Job queue is closed".to_string())?;
+        jobs.push(JobState::Queued);
+        Ok(id)
+    }
+
+    fn set_job(&self, id: usize, state: JobState) {
+        self.jobs.lock().unwrap()[id] = state;
+    }
+
+    // queued jobs only, a running job runs to its end
+    fn cancel(&self, id: usize) -> Result<(), String> {
+        let mut jobs = self.jobs.lock().unwrap();
+        match jobs.get(id) {
+            Some(JobState::Queued) => {
+                jobs[id] = JobState::Cancelled;
+                Ok(())
+            }
+            Some(_) => Err(format!("Job is not queued: {}", id)),
+            None => Err(format!("Unknown job: {}", id)),
+        }
+    }
+
+    // marks a job running unless it was cancelled while queued
+    fn start(&self, id: usize) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        if matches!(jobs[id], JobState::Cancelled) {
+            return false;
+        }
+        jobs[id] = JobState::Running;
+        true
+    }
+
+    fn index(&self, name: &str) -> Result<Arc<CountsReader>, String> {
+        self.indexes
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or(format!("Index not loaded: {}", name))
+    }
+}
+
+fn strand_of(request: &Request) -> Result<Strand, String> {
+    Ok(if request.flag("forward")? {
+        Strand::Forward
+    } else {
+        Strand::Canonical
+    })
+}
+
+fn query_kmers<K: KmerInt>(
+    index: &CountsReader,
+    strand: Strand,
+    kmers: &[&str],
+) -> Result<Vec<u64>, String> {
+    kmers
+        .iter()
+        .map(|kmer| {
+            let mut generator = GenericKmerGenerator::<K>::new(kmer.as_bytes(), index.ksize());
+            match (kmer.len() == index.ksize(), generator.next()) {
+                (true, Some((fmer, rmer))) => Ok(index.get(strand.pick(fmer, rmer)).unwrap_or(0)),
+                _ => Err(format!(
+                    "Not an ACGT k-mer of size {}: {}",
+                    index.ksize(),
+                    kmer
+                )),
+            }
+        })
+        .collect()
+}
+
+fn classify_reads<K: KmerInt>(
+    index: &CountsReader,
+    strand: Strand,
+    in_path: &str,
+    out_path: &str,
+) -> Result<usize, String> {
+    let format = SeqFormat::from_path(in_path)?;
+    let records = Sequences::new(format, get_reader(in_path)?)?;
+    let outf =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    let mut reads = 0;
+    for record in records {
+        let (mut kmers, mut found) = (0_u64, 0_u64);
+        for (fmer, rmer) in GenericKmerGenerator::<K>::new(&record.seq, index.ksize()) {
+            kmers += 1;
+            if index.get(strand.pick(fmer, rmer)).is_some() {
+                found += 1;
+            }
+        }
+        writeln!(
+            buff,
+            "{}\t{}\t{}\t{:.6}",
+            record.id,
+            kmers,
+            found,
+            found as f64 / u64::max(1, kmers) as f64
+        )
+        .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+        reads += 1;
+    }
+    buff.flush()
+        .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    Ok(reads)
+}
+
+fn run_job(state: &State, job: Job) -> Result<String, String> {
+    match job {
+        Job::Count {
+            input,
+            output,
+            ksize,
+            binary,
+        } => {
+            create_directory(&output)
+                .map_err(|_| format!("Unable to create directory: {}", output))?;
+            let mut ctr = CountComputer::new(input, output, ksize);
+            if state.threads > 0 {
+                ctr.set_threads(state.threads);
+            }
+            if binary {
+                ctr.set_options(CountOptions {
+                    output: OutputOptions {
+                        counts: CountsOutput::Binary,
+                        ..OutputOptions::default()
+                    },
+                    ..CountOptions::default()
+                });
+            }
+            ctr.count();
+            ctr.merge(true);
+            Ok(format!("{} k-mers counted", ctr.kmer_stats().kmers))
+        }
+        Job::Classify {
+            index,
+            strand,
+            input,
+            output,
+        } => {
+            let reads = if index.ksize() > 32 {
+                classify_reads::<u128>(&index, strand, &input, &output)?
+            } else {
+                classify_reads::<u64>(&index, strand, &input, &output)?
+            };
+            Ok(format!("{} reads classified", reads))
+        }
+        Job::Shutdown => Ok("Shutting down".to_string()),
+    }
+}
+
+// jobs run one at a time in submission order, a job that panics is marked failed and
+// cancelled jobs are skipped, returns once a shutdown job has run
+fn worker(state: &State, jobs: mpsc::Receiver<(usize, Job)>) {
+    for (id, job) in jobs {
+        if !state.start(id) {
+            continue;
+        }
+        let shutdown = matches!(job, Job::Shutdown);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run_job(state, job)))
+            .unwrap_or_else(|_| Err("Job panicked".to_string()));
+        state.set_job(
+            id,
+            match result {
+                Ok(message) => JobState::Done(message),
+                Err(e) => JobState::Failed(e),
+            },
+        );
+        if shutdown {
+            return;
+        }
+    }
+}
+
+fn handle(state: &State, line: &str) -> Result<String, String> {
+    let request = Request::parse(line)?;
+    match request.str("op")? {
+        "load" => {
+            let name = request.str("name")?;
+            let index = CountsReader::open(request.str("path")?)?;
+            let response = format!(
+                "{{\"ok\": true, \"name\": {}, \"ksize\": {}, \"kmers\": {}}}",
+                json_string(name),
+                index.ksize(),
+                index.len()
+            );
+            state
+                .indexes
+                .write()
+                .unwrap()
+                .insert(name.to_string(), Arc::new(index));
+            Ok(response)
+        }
+        "unload" => {
+            let name = request.str("name")?;
+            match state.indexes.write().unwrap().remove(name) {
+                Some(_) => Ok("{\"ok\": true}".to_string()),
+                None => Err(format!("Index not loaded: {}", name)),
+            }
+        }
+        "query" => {
+            let index = state.index(request.str("index")?)?;
+            let strand = strand_of(&request)?;
+            let kmers = request.strings("kmers")?;
+            let counts = if index.ksize() > 32 {
+                query_kmers::<u128>(&index, strand, &kmers)?
+            } else {
+                query_kmers::<u64>(&index, strand, &kmers)?
+            };
+            let counts: Vec<String> = counts.iter().map(|count| count.to_string()).collect();
+            Ok(format!(
+                "{{\"ok\": true, \"counts\": [{}]}}",
+                counts.join(", ")
+            ))
+        }
+        "count" => {
+            let ksize = request.usize("k")?;
+            if !counter::KSIZES.contains(&(ksize as u64)) {
+                return Err(format!("Unsupported k-mer size: {}", ksize));
+            }
+            let id = state.submit(Job::Count {
+                input: request.str("input")?.to_string(),
+                output: request.str("output")?.to_string(),
+                ksize,
+                binary: request.flag("binary")?,
+            })?;
+            Ok(format!("{{\"ok\": true, \"job\": {}}}", id))
+        }
+        "classify" => {
+            let id = state.submit(Job::Classify {
+                index: state.index(request.str("index")?)?,
+                strand: strand_of(&request)?,
+                input: request.str("input")?.to_string(),
+                output: request.str("output")?.to_string(),
+            })?;
+            Ok(format!("{{\"ok\": true, \"job\": {}}}", id))
+        }
+        "cancel" => {
+            state.cancel(request.usize("id")?)?;
+            Ok("{\"ok\": true}".to_string())
+        }
+        "job" => {
+            let id = request.usize("id")?;
+            let job = state
+                .jobs
+                .lock()
+                .unwrap()
+                .get(id)
+                .cloned()
+                .ok_or(format!("Unknown job: {}", id))?;
+            let (name, message) = match job {
+                JobState::Queued => ("queued", None),
+                JobState::Running => ("running", None),
+                JobState::Done(message) => ("done", Some(message)),
+                JobState::Failed(e) => ("failed", Some(e)),
+                JobState::Cancelled => ("cancelled", None),
+            };
+            Ok(match message {
+                Some(message) => format!(
+                    "{{\"ok\": true, \"job\": {}, \"state\": \"{}\", \"message\": {}}}",
+                    id,
+                    name,
+                    json_string(&message)
+                ),
+                None => format!("{{\"ok\": true, \"job\": {}, \"state\": \"{}\"}}", id, name),
+            })
+        }
+        "status" => {
+            let mut indexes: Vec<String> = state
+                .indexes
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(name, index)| {
+                    format!(
+                        "{{\"name\": {}, \"ksize\": {}, \"kmers\": {}}}",
+                        json_string(name),
+                        index.ksize(),
+                        index.len()
+                    )
+                })
+                .collect();
+            indexes.sort();
+            let jobs = state.jobs.lock().unwrap();
+            let pending = jobs
+                .iter()
+                .filter(|job| matches!(job, JobState::Queued | JobState::Running))
+                .count();
+            Ok(format!(
+                "{{\"ok\": true, \"indexes\": [{}], \"jobs\": {}, \"pending\": {}}}",
+                indexes.join(", "),
+                jobs.len(),
+                pending
+            ))
+        }
+        "shutdown" => {
+            state.submit(Job::Shutdown)?;
+            Ok("{\"ok\": true}".to_string())
+        }
+        op => Err(format!("Unknown op: {}", op)),
+    }
+}
+
+#[cfg(unix)]
+fn connection(state: &State, stream: std::os::unix::net::UnixStream) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle(state, &line).unwrap_or_else(|e| error_response(&e));
+        if writeln!(writer, "{}", response).is_err() {
+            return;
+        }
+    }
+}
+
+// frees the connection slot it holds when the connection ends
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// serves newline delimited JSON requests on a Unix socket only its owner can use, one
+// response line per request, indexes stay loaded between requests and count/classify jobs
+// are queued for one worker
+// indexes are kmertools counts tables (no Bloom filters or sketches), queued jobs can be
+// cancelled and jobs report their state but not their progress
+#[cfg(unix)]
+pub fn serve(socket: &str, threads: usize) -> Result<(), String> {
+    use std::os::unix::{fs::PermissionsExt, net::UnixListener};
+
+    if fs::metadata(socket).is_ok() {
+        return Err(format!("Socket already exists: {}", socket));
+    }
+    let listener =
+        UnixListener::bind(socket).map_err(|_| format!("Unable to bind socket: {}", socket))?;
+    if fs::set_permissions(socket, fs::Permissions::from_mode(0o600)).is_err() {
+        let _ = fs::remove_file(socket);
+        return Err(format!("Unable to restrict socket: {}", socket));
+    }
+    let (sender, receiver) = mpsc::channel();
+    let state = Arc::new(State {
+        threads,
+        indexes: RwLock::new(HashMap::new()),
+        jobs: Mutex::new(Vec::new()),
+        queue: Mutex::new(sender),
+    });
+    let listener_state = state.clone();
+    let open = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        for mut stream in listener.incoming().map_while(Result::ok) {
+            if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                open.fetch_sub(1, Ordering::SeqCst);
+                let _ = writeln!(stream, "{}", error_response("Too many connections"));
+                continue;
+            }
+            let slot = Slot(open.clone());
+            let state = listener_state.clone();
+            thread::spawn(move || {
+                let _slot = slot;
+                connection(&state, stream)
+            });
+        }
+    });
+    eprintln!("Listening on {}", socket);
+    // the worker returns on shutdown, connections still open end with the process
+    worker(&state, receiver);
+    let _ = fs::remove_file(socket);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_socket: &str, _threads: usize) -> Result<(), String> {
+    Err("The daemon needs Unix domain sockets".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use counter::counts::CountsWriter;
+
+    const PATH_FQ: &str = "../test_data/reads.fq";
+
+    fn state() -> (State, mpsc::Receiver<(usize, Job)>) {
+        let (sender, receiver) = mpsc::channel();
+        let state = State {
+            threads: 2,
+            indexes: RwLock::new(HashMap::new()),
+            jobs: Mutex::new(Vec::new()),
+            queue: Mutex::new(sender),
+        };
+        (state, receiver)
+    }
+
+    // AAAA (and TTTT) 3 times, ACGA (and TCGT) 5 times
+    fn write_index(path: &str) {
+        let mut writer = CountsWriter::new(path, 4, 1).unwrap();
+        let acga = kmer::kmer_to_numeric("ACGA").unwrap();
+        writer
+            .write_partition(&mut [(0_u64, 3_u32), (acga, 5)])
+            .unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn request_parse_test() {
+        let request = Request::parse(r#"{"op": "query", "k": 15, "kmers": ["ACGT"]}"#).unwrap();
+        assert_eq!(request.str("op").unwrap(), "query");
+        assert_eq!(request.usize("k").unwrap(), 15);
+        assert_eq!(request.strings("kmers").unwrap(), vec!["ACGT"]);
+        assert!(!request.flag("binary").unwrap());
+        // wrong types and missing fields
+        assert!(request.str("k").is_err());
+        assert!(request.usize("op").is_err());
+        assert!(request.flag("op").is_err());
+        assert!(request.strings("op").is_err());
+        assert!(request.str("index").is_err());
+        let request = Request::parse(r#"{"k": -1, "id": 1.5, "kmers": ["A", 1]}"#).unwrap();
+        assert!(request.usize("k").is_err());
+        assert!(request.usize("id").is_err());
+        assert!(request.strings("kmers").is_err());
+        // malformed lines
+        for line in [
+            "",
+            "op",
+            "{",
+            r#"{"op": "query""#,
+            r#"{"op" "query"}"#,
+            r#"{"op": "query",}"#,
+            r#"["op", "query"]"#,
+            r#"{"op": "\q"}"#,
+        ] {
+            let e = Request::parse(line).err().unwrap();
+            assert!(e.starts_with("Invalid request: "), "{}", line);
+        }
+    }
+
+    #[test]
+    fn handle_index_test() {
+        let path = "../test_data/computed_daemon_index.bin";
+        write_index(path);
+        let (state, _receiver) = state();
+        let load = format!(r#"{{"op": "load", "name": "ref", "path": "{}"}}"#, path);
+        assert_eq!(
+            handle(&state, &load).unwrap(),
+            r#"{"ok": true, "name": "ref", "ksize": 4, "kmers": 2}"#
+        );
+        assert_eq!(
+            handle(&state, r#"{"op": "status"}"#).unwrap(),
+            r#"{"ok": true, "indexes": [{"name": "ref", "ksize": 4, "kmers": 2}], "jobs": 0, "pending": 0}"#
+        );
+        let query = r#"{"op": "query", "index": "ref", "kmers": ["AAAA", "TTTT", "TCGT", "CCCC"]}"#;
+        assert_eq!(
+            handle(&state, query).unwrap(),
+            r#"{"ok": true, "counts": [3, 3, 5, 0]}"#
+        );
+        let forward =
+            r#"{"op": "query", "index": "ref", "kmers": ["AAAA", "TTTT"], "forward": true}"#;
+        assert_eq!(
+            handle(&state, forward).unwrap(),
+            r#"{"ok": true, "counts": [3, 0]}"#
+        );
+        assert_eq!(
+            handle(&state, r#"{"op": "unload", "name": "ref"}"#).unwrap(),
+            r#"{"ok": true}"#
+        );
+        assert_eq!(
+            handle(&state, r#"{"op": "status"}"#).unwrap(),
+            r#"{"ok": true, "indexes": [], "jobs": 0, "pending": 0}"#
+        );
+    }
+
+    #[test]
+    fn handle_jobs_test() {
+        let (state, receiver) = state();
+        let count = r#"{"op": "count", "input": "in.fq", "output": "out", "k": 15}"#;
+        assert_eq!(handle(&state, count).unwrap(), r#"{"ok": true, "job": 0}"#);
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok((
+                0,
+                Job::Count {
+                    ksize: 15,
+                    binary: false,
+                    ..
+                }
+            ))
+        ));
+        assert_eq!(
+            handle(&state, r#"{"op": "job", "id": 0}"#).unwrap(),
+            r#"{"ok": true, "job": 0, "state": "queued"}"#
+        );
+        state.set_job(0, JobState::Failed("Bad \"input\"".to_string()));
+        assert_eq!(
+            handle(&state, r#"{"op": "job", "id": 0}"#).unwrap(),
+            r#"{"ok": true, "job": 0, "state": "failed", "message": "Bad \"input\""}"#
+        );
+        assert_eq!(
+            handle(&state, r#"{"op": "status"}"#).unwrap(),
+            r#"{"ok": true, "indexes": [], "jobs": 1, "pending": 0}"#
+        );
+        assert_eq!(
+            handle(&state, r#"{"op": "shutdown"}"#).unwrap(),
+            r#"{"ok": true}"#
+        );
+        assert!(matches!(receiver.try_recv(), Ok((1, Job::Shutdown))));
+    }
+
+    #[test]
+    fn handle_errors_test() {
+        let (state, _receiver) = state();
+        for (line, error) in [
+            ("{", "Invalid request: "),
+            (r#"{"name": "ref"}"#, "Missing field: op"),
+            (r#"{"op": 1}"#, "Field must be a string: op"),
+            (r#"{"op": "merge"}"#, "Unknown op: merge"),
+            (
+                r#"{"op": "load", "name": "ref", "path": "../test_data/reads.fa"}"#,
+                "",
+            ),
+            (
+                r#"{"op": "unload", "name": "ref"}"#,
+                "Index not loaded: ref",
+            ),
+            (
+                r#"{"op": "query", "index": "ref", "kmers": []}"#,
+                "Index not loaded: ref",
+            ),
+            (
+                r#"{"op": "classify", "index": "ref", "input": "a", "output": "b"}"#,
+                "Index not loaded: ref",
+            ),
+            (r#"{"op": "job", "id": 3}"#, "Unknown job: 3"),
+        ] {
+            let e = handle(&state, line).err().unwrap();
+            assert!(e.starts_with(error), "{}: {}", line, e);
+        }
+        // k-mer sizes ctr does not count
+        for ksize in [0, 1, 5, 64] {
+            let line = format!(
+                r#"{{"op": "count", "input": "a", "output": "b", "k": {}}}"#,
+                ksize
+            );
+            assert_eq!(
+                handle(&state, &line).err().unwrap(),
+                format!("Unsupported k-mer size: {}", ksize)
+            );
+        }
+        // nothing was queued
+        assert!(state.jobs.lock().unwrap().is_empty());
+        // k-mers of another size than the index
+        let path = "../test_data/computed_daemon_errors.bin";
+        write_index(path);
+        let load = format!(r#"{{"op": "load", "name": "ref", "path": "{}"}}"#, path);
+        handle(&state, &load).unwrap();
+        let query = r#"{"op": "query", "index": "ref", "kmers": ["AAAAA"]}"#;
+        assert_eq!(
+            handle(&state, query).err().unwrap(),
+            "Not an ACGT k-mer of size 4: AAAAA"
+        );
+        assert_eq!(
+            error_response("Bad \"op\""),
+            r#"{"ok": false, "error": "Bad \"op\""}"#
+        );
+    }
+
+    #[test]
+    fn worker_test() {
+        let out_dir = "../test_data/computed_daemon_counts";
+        let (state, receiver) = state();
+        let count = format!(
+            r#"{{"op": "count", "input": "{}", "output": "{}", "k": 15}}"#,
+            PATH_FQ, out_dir
+        );
+        handle(&state, &count).unwrap();
+        // a job that fails does not stop the worker
+        let count = r#"{"op": "count", "input": "../test_data/doesnotexist.fq", "output": "../test_data/computed_daemon_missing", "k": 15}"#;
+        handle(&state, count).unwrap();
+        handle(&state, r#"{"op": "shutdown"}"#).unwrap();
+        // returns after the shutdown job instead of waiting for more jobs
+        worker(&state, receiver);
+        let jobs = state.jobs.lock().unwrap().clone();
+        assert!(matches!(&jobs[0], JobState::Done(message) if message.ends_with("k-mers counted")));
+        assert!(matches!(&jobs[1], JobState::Failed(_)));
+        assert!(matches!(&jobs[2], JobState::Done(message) if message == "Shutting down"));
+        assert!(fs::metadata(format!("{}/kmers.counts", out_dir)).is_ok());
+    }
+
+    #[test]
+    fn cancel_test() {
+        let (state, receiver) = state();
+        let count = r#"{"op": "count", "input": "in.fq", "output": "out", "k": 15}"#;
+        handle(&state, count).unwrap();
+        assert_eq!(
+            handle(&state, r#"{"op": "cancel", "id": 0}"#).unwrap(),
+            r#"{"ok": true}"#
+        );
+        assert_eq!(
+            handle(&state, r#"{"op": "job", "id": 0}"#).unwrap(),
+            r#"{"ok": true, "job": 0, "state": "cancelled"}"#
+        );
+        assert_eq!(
+            handle(&state, r#"{"op": "status"}"#).unwrap(),
+            r#"{"ok": true, "indexes": [], "jobs": 1, "pending": 0}"#
+        );
+        // only queued jobs are cancelled
+        assert_eq!(
+            handle(&state, r#"{"op": "cancel", "id": 0}"#)
+                .err()
+                .unwrap(),
+            "Job is not queued: 0"
+        );
+        assert_eq!(
+            handle(&state, r#"{"op": "cancel", "id": 1}"#)
+                .err()
+                .unwrap(),
+            "Unknown job: 1"
+        );
+        // the worker skips the cancelled job
+        handle(&state, r#"{"op": "shutdown"}"#).unwrap();
+        worker(&state, receiver);
+        let jobs = state.jobs.lock().unwrap().clone();
+        assert!(matches!(jobs[0], JobState::Cancelled));
+        assert!(matches!(&jobs[1], JobState::Done(message) if message == "Shutting down"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serve_test() {
+        use std::os::unix::{fs::PermissionsExt, net::UnixStream};
+
+        let socket = "../test_data/computed_daemon.sock";
+        let _ = fs::remove_file(socket);
+        let server = thread::spawn(move || serve(socket, 1));
+        let connect = || loop {
+            if let Ok(stream) = UnixStream::connect(socket) {
+                return stream;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        };
+        let request = |stream: &UnixStream, line: &str| {
+            writeln!(&*stream, "{}", line).unwrap();
+            let mut response = String::new();
+            BufReader::new(stream).read_line(&mut response).unwrap();
+            response
+        };
+        let first = connect();
+        assert_eq!(
+            request(&first, r#"{"op": "status"}"#).trim(),
+            r#"{"ok": true, "indexes": [], "jobs": 0, "pending": 0}"#
+        );
+        let mode = fs::metadata(socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // connections past the limit are refused while the others stay open
+        let open: Vec<UnixStream> = (1..MAX_CONNECTIONS).map(|_| connect()).collect();
+        let mut response = String::new();
+        BufReader::new(connect()).read_line(&mut response).unwrap();
+        assert_eq!(
+            response.trim(),
+            r#"{"ok": false, "error": "Too many connections"}"#
+        );
+        // a closed connection frees its slot
+        drop(open);
+        let mut last = connect();
+        loop {
+            let response = request(&last, r#"{"op": "status"}"#);
+            if response.contains("\"ok\": true") {
+                break;
+            }
+            last = connect();
+        }
+        assert_eq!(
+            request(&first, r#"{"op": "shutdown"}"#).trim(),
+            r#"{"ok": true}"#
+        );
+        assert_eq!(server.join().unwrap(), Ok(()));
+        assert!(fs::metadata(socket).is_err());
+    }
+}
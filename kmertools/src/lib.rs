@@ -1 +1,5 @@
 pub mod args;
+pub mod daemon;
+pub mod scaffold;
+pub mod selftest;
+pub mod vectorise;
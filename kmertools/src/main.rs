@@ -1,6 +1,10 @@
 use args::Cli;
 use clap::Parser;
 mod args;
+mod daemon;
+mod scaffold;
+mod selftest;
+mod vectorise;
 
 #[cfg(not(tarpaulin_include))]
 fn main() {
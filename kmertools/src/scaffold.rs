@@ -0,0 +1,340 @@
+use clap::{Arg, Command};
+
+// workflow languages a module can be written for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Workflow {
+    Nextflow,
+    Snakemake,
+}
+
+// what a subcommand writes to --output
+enum Output {
+    // a directory, with the run JSON inside
+    Dir(&'static str),
+    // a file, with the run JSON next to it
+    File(&'static str, Option<&'static str>),
+}
+
+// a workflow process wrapping one subcommand
+struct Process {
+    name: &'static str,
+    path: &'static [&'static str],
+    output: Output,
+}
+
+const PROCESSES: [Process; 4] = [
+    Process {
+        name: "ctr",
+        path: &["ctr"],
+        output: Output::Dir("run.json"),
+    },
+    Process {
+        name: "cov",
+        path: &["cov"],
+        output: Output::Dir("run.json"),
+    },
+    Process {
+        name: "oligo",
+        path: &["comp", "oligo"],
+        output: Output::File("txt", Some(".run.json")),
+    },
+    Process {
+        name: "cgr",
+        path: &["comp", "cgr"],
+        output: Output::File("txt", None),
+    },
+];
+
+// arguments every process fills in itself, provenance when the subcommand has it
+const WIRED: [&str; 4] = ["input", "output", "threads", "provenance"];
+
+fn subcommand<'a>(cli: &'a Command, path: &[&str]) -> Result<&'a Command, String> {
+    path.iter().try_fold(cli, |cmd, name| {
+        cmd.find_subcommand(name)
+            .ok_or(format!("Unknown subcommand: {}", path.join(" ")))
+    })
+}
+
+// flag asking the subcommand for the run JSON of the provenance outputs
+fn provenance_flag(cmd: &Command) -> &'static str {
+    if cmd
+        .get_arguments()
+        .any(|arg| arg.get_long() == Some("provenance"))
+    {
+        " --provenance"
+    } else {
+        ""
+    }
+}
+
+fn long(arg: &Arg) -> Option<&str> {
+    arg.get_long()
+        .filter(|long| !["help", "version"].contains(long))
+}
+
+// (long name, argument) of the arguments with a long name, required arguments become
+// parameters, the others are passed through <process>_args
+type Args<'a> = Vec<(&'a str, &'a Arg)>;
+
+fn split_args(cmd: &Command) -> (Args<'_>, Args<'_>) {
+    cmd.get_arguments()
+        .filter_map(|arg| long(arg).map(|long| (long, arg)))
+        .filter(|(long, _)| !WIRED.contains(&long.replace('-', "_").as_str()))
+        .partition(|(_, arg)| arg.is_required_set())
+}
+
+fn param_name(process: &Process, arg: &Arg) -> String {
+    format!("{}_{}", process.name, arg.get_id().as_str())
+}
+
+fn help(arg: &Arg) -> String {
+    arg.get_help()
+        .map(|help| help.to_string())
+        .unwrap_or_default()
+}
+
+// name of the output of a process for an input named by `stem`
+fn output_name(process: &Process, stem: &str) -> String {
+    match process.output {
+        Output::Dir(_) => format!("{}_{}", process.name, stem),
+        Output::File(ext, _) => format!("{}_{}.{}", process.name, stem, ext),
+    }
+}
+
+fn provenance(process: &Process, output: &str) -> Option<String> {
+    match process.output {
+        Output::Dir(run) => Some(format!("{}/{}", output, run)),
+        Output::File(_, run) => run.map(|run| format!("{}{}", output, run)),
+    }
+}
+
+fn nextflow_process(process: &Process, cmd: &Command) -> String {
+    let (required, optional) = split_args(cmd);
+    let output = output_name(process, "${reads.simpleName}");
+    let mut text = String::new();
+    text.push_str(&format!("// kmertools {}\n", process.path.join(" ")));
+    if let Some(about) = cmd.get_about() {
+        text.push_str(&format!("// {}\n", about));
+    }
+    text.push_str(&format!("// options for params.{}_args:\n", process.name));
+    for (long, arg) in optional.iter() {
+        text.push_str(&format!("//   --{:<20} {}\n", long, help(arg)));
+    }
+    text.push_str(&format!(
+        "process KMERTOOLS_{} {{\n    tag \"${{reads.simpleName}}\"\n\n    input:\n    path reads\n\n    output:\n    path \"{}\", emit: out\n",
+        process.name.to_uppercase(),
+        output
+    ));
+    if let Some(run) = provenance(process, &output) {
+        text.push_str(&format!("    path \"{}\", emit: provenance\n", run));
+    }
+    let mut command = format!(
+        "kmertools {} --input ${{reads}} --output {}",
+        process.path.join(" "),
+        output
+    );
+    for (long, arg) in required.iter() {
+        command.push_str(&format!(
+            " --{} ${{params.{}}}",
+            long,
+            param_name(process, arg)
+        ));
+    }
+    command.push_str(&format!(
+        "{} --threads ${{task.cpus}} ${{params.{}_args}}",
+        provenance_flag(cmd),
+        process.name
+    ));
+    text.push_str(&format!(
+        "\n    script:\n    \"\"\"\n    {}\n    \"\"\"\n}}\n",
+        command
+    ));
+    text
+}
+
+fn snakemake_rule(process: &Process, cmd: &Command) -> String {
+    let (required, optional) = split_args(cmd);
+    let output = output_name(process, "{sample}");
+    let mut text = String::new();
+    text.push_str(&format!("# kmertools {}\n", process.path.join(" ")));
+    if let Some(about) = cmd.get_about() {
+        text.push_str(&format!("# {}\n", about));
+    }
+    text.push_str(&format!(
+        "# options for config[\"{}_args\"]:\n",
+        process.name
+    ));
+    for (long, arg) in optional.iter() {
+        text.push_str(&format!("#   --{:<20} {}\n", long, help(arg)));
+    }
+    text.push_str(&format!(
+        "rule kmertools_{}:\n    input:\n        reads=config[\"reads\"],\n    output:\n",
+        process.name
+    ));
+    let output = format!("config[\"outdir\"] + \"/{}\"", output);
+    match process.output {
+        // files inside a directory output are not allowed, the run JSON stands for it
+        Output::Dir(run) => text.push_str(&format!(
+            "        provenance={}/{}\",\n",
+            output.trim_end_matches('"'),
+            run
+        )),
+        Output::File(_, run) => {
+            text.push_str(&format!("        out={},\n", output));
+            if let Some(run) = run {
+                text.push_str(&format!(
+                    "        provenance={}{}\",\n",
+                    output.trim_end_matches('"'),
+                    run
+                ));
+            }
+        }
+    }
+    text.push_str(&format!(
+        "    params:\n        args=config.get(\"{}_args\", \"\"),\n",
+        process.name
+    ));
+    for (_, arg) in required.iter() {
+        text.push_str(&format!(
+            "        {}=config[\"{}\"],\n",
+            arg.get_id().as_str(),
+            param_name(process, arg)
+        ));
+    }
+    let out = match process.output {
+        Output::Dir(_) => "$(dirname {output.provenance})",
+        Output::File(..) => "{output.out}",
+    };
+    let mut command = format!(
+        "kmertools {} --input {{input.reads}} --output {}",
+        process.path.join(" "),
+        out
+    );
+    for (long, arg) in required.iter() {
+        command.push_str(&format!(" --{} {{params.{}}}", long, arg.get_id().as_str()));
+    }
+    command.push_str(provenance_flag(cmd));
+    command.push_str(" --threads {threads} {params.args}");
+    text.push_str(&format!(
+        "    threads: config.get(\"threads\", 8)\n    shell:\n        \"{}\"\n",
+        command
+    ));
+    text
+}
+
+// workflow module with a process per counting and composition subcommand of the CLI,
+// required arguments become parameters and run JSONs are outputs for provenance
+pub fn render(workflow: Workflow, cli: &Command) -> Result<String, String> {
+    let version = cli.get_version().unwrap_or("unknown");
+    let mut text = match workflow {
+        Workflow::Nextflow => format!(
+            "// kmertools {} module, written by kmertools scaffold --workflow nextflow\n// include {{ KMERTOOLS_CTR }} from './kmertools.nf'\n\n",
+            version
+        ),
+        Workflow::Snakemake => format!(
+            "# kmertools {} rules, written by kmertools scaffold --workflow snakemake\n# config: reads (input path pattern with {{sample}}), outdir, threads\n# and the <process>_<argument> values of required arguments\n\n",
+            version
+        ),
+    };
+    let mut params = Vec::new();
+    let mut blocks = Vec::new();
+    for process in PROCESSES.iter() {
+        let cmd = subcommand(cli, process.path)?;
+        let (required, _) = split_args(cmd);
+        match workflow {
+            Workflow::Nextflow => {
+                for (_, arg) in required.iter() {
+                    params.push(format!(
+                        "params.{} = null // {}\n",
+                        param_name(process, arg),
+                        help(arg)
+                    ));
+                }
+                params.push(format!("params.{}_args = ''\n", process.name));
+                blocks.push(nextflow_process(process, cmd));
+            }
+            Workflow::Snakemake => blocks.push(snakemake_rule(process, cmd)),
+        }
+    }
+    if !params.is_empty() {
+        text.push_str(&params.concat());
+        text.push('\n');
+    }
+    text.push_str(&blocks.join("\n"));
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Cli;
+    use clap::CommandFactory;
+
+    #[test]
+    fn render_test() {
+        let cli = Cli::command();
+        for workflow in [Workflow::Nextflow, Workflow::Snakemake] {
+            let text = render(workflow, &cli).unwrap();
+            for process in PROCESSES.iter() {
+                let header = match workflow {
+                    Workflow::Nextflow => {
+                        format!("process KMERTOOLS_{} {{", process.name.to_uppercase())
+                    }
+                    Workflow::Snakemake => format!("rule kmertools_{}:", process.name),
+                };
+                assert!(text.contains(&header), "{}", header);
+                let cmd = subcommand(&cli, process.path).unwrap();
+                let command = text
+                    .lines()
+                    .find(|line| {
+                        line.trim_start_matches([' ', '"'])
+                            .starts_with(&format!("kmertools {} ", process.path.join(" ")))
+                    })
+                    .unwrap();
+                // required arguments are wired, the others are listed for <process>_args
+                let (required, optional) = split_args(cmd);
+                for (long, _) in required.iter() {
+                    assert!(command.contains(&format!(" --{} ", long)), "{}", long);
+                }
+                // run JSONs of the provenance outputs are asked for
+                assert_eq!(
+                    command.contains(" --provenance "),
+                    !provenance_flag(cmd).is_empty()
+                );
+                for (long, _) in optional.iter() {
+                    assert!(text.contains(&format!("   --{:<20} ", long)), "{}", long);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn render_positional_test() {
+        // arguments without a long name are left out rather than failing the module
+        let positional = |name: &'static str| {
+            Command::new(name)
+                .arg(Arg::new("input").long("input").required(true))
+                .arg(Arg::new("reads").required(true))
+                .arg(Arg::new("extra"))
+        };
+        let cli = Command::new("kmertools")
+            .version("0.0.0")
+            .subcommand(positional("ctr"))
+            .subcommand(positional("cov"))
+            .subcommand(
+                Command::new("comp")
+                    .subcommand(positional("oligo"))
+                    .subcommand(positional("cgr")),
+            );
+        for workflow in [Workflow::Nextflow, Workflow::Snakemake] {
+            let text = render(workflow, &cli).unwrap();
+            assert!(text.contains("kmertools comp cgr --input"));
+            assert!(!text.contains("--reads") && !text.contains("extra"));
+        }
+        let cli = Command::new("kmertools").subcommand(positional("ctr"));
+        assert_eq!(
+            render(Workflow::Nextflow, &cli).unwrap_err(),
+            "Unknown subcommand: cov"
+        );
+    }
+}
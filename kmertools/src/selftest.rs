@@ -0,0 +1,127 @@
+use composition::oligo::OligoComputer;
+use counter::CountComputer;
+use ktio::fops::create_directory;
+use misc::minimisers::{self, MinimiserOptions};
+use std::{env, fs, process};
+
+// tiny dataset with an ambiguous base and outputs of a known good build
+const READS: &str = include_str!("selftest/reads.fa");
+const EXPECTED_COUNTS: &str = include_str!("selftest/expected_counts.txt");
+const EXPECTED_OLIGO: &str = include_str!("selftest/expected_oligo.txt");
+const EXPECTED_MINIMISERS: &str = include_str!("selftest/expected_minimisers.txt");
+
+// name of a check and its outcome
+pub type CheckResult = (&'static str, Result<(), String>);
+
+fn sorted_lines(text: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = text.lines().collect();
+    lines.sort();
+    lines
+}
+
+fn compare(name: &str, path: &str, expected: &str, sort: bool) -> Result<(), String> {
+    let result =
+        fs::read_to_string(path).map_err(|_| format!("{}: no output at {}", name, path))?;
+    let matches = if sort {
+        sorted_lines(&result) == sorted_lines(expected)
+    } else {
+        result == expected
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(format!("{}: output differs from the expected output", name))
+    }
+}
+
+fn check_counts(reads: &str, dir: &str, threads: usize) -> Result<(), String> {
+    let out_dir = format!("{}/ctr", dir);
+    create_directory(&out_dir).map_err(|_| format!("Unable to create directory: {}", out_dir))?;
+    let mut ctr = CountComputer::new(reads.to_string(), out_dir.clone(), 11);
+    ctr.set_threads(threads);
+    ctr.count();
+    ctr.merge(true);
+    compare(
+        "ctr",
+        &format!("{}/kmers.counts", out_dir),
+        EXPECTED_COUNTS,
+        true,
+    )
+}
+
+fn check_oligo(reads: &str, dir: &str, threads: usize) -> Result<(), String> {
+    let out_path = format!("{}/oligo.txt", dir);
+    let mut com = OligoComputer::new(reads.to_string(), out_path.clone(), 3);
+    com.set_threads(threads);
+    com.vectorise()?;
+    compare("comp oligo", &out_path, EXPECTED_OLIGO, false)
+}
+
+fn check_minimisers(reads: &str, dir: &str, threads: usize) -> Result<(), String> {
+    let out_path = format!("{}/minimisers.txt", dir);
+    minimisers::seq_to_min(
+        15,
+        7,
+        reads,
+        &out_path,
+        threads,
+        &MinimiserOptions::default(),
+    );
+    compare("min", &out_path, EXPECTED_MINIMISERS, true)
+}
+
+// runs each check in a temporary directory
+pub fn run(threads: usize) -> Result<Vec<CheckResult>, String> {
+    let threads = if threads == 0 {
+        ktio::threads::default_threads()
+    } else {
+        threads
+    };
+    let dir = env::temp_dir()
+        .join(format!("kmertools-selftest-{}", process::id()))
+        .to_string_lossy()
+        .to_string();
+    let results = run_in(&dir, threads)?;
+    fs::remove_dir_all(&dir).map_err(|_| format!("Unable to remove directory: {}", dir))?;
+
+    Ok(results)
+}
+
+// runs each check with its outputs in dir
+fn run_in(dir: &str, threads: usize) -> Result<Vec<CheckResult>, String> {
+    create_directory(dir).map_err(|_| format!("Unable to create directory: {}", dir))?;
+    let reads = format!("{}/reads.fa", dir);
+    fs::write(&reads, READS).map_err(|_| format!("Unable to write to file: {}", reads))?;
+
+    Ok(vec![
+        ("ctr", check_counts(&reads, dir, threads)),
+        ("comp oligo", check_oligo(&reads, dir, threads)),
+        ("min", check_minimisers(&reads, dir, threads)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selftest_test() {
+        let dir = "../test_data/computed_selftest";
+        for threads in [1, 4] {
+            let results = run_in(dir, threads).unwrap();
+            let names: Vec<&str> = results.iter().map(|(name, _)| *name).collect();
+            assert_eq!(names, vec!["ctr", "comp oligo", "min"]);
+            for (name, result) in results {
+                assert_eq!(result, Ok(()), "{}", name);
+            }
+        }
+        // outputs are compared with the expected outputs
+        let out_path = format!("{}/oligo.txt", dir);
+        assert!(compare("comp oligo", &out_path, EXPECTED_OLIGO, false).is_ok());
+        assert_eq!(
+            compare("comp oligo", &out_path, EXPECTED_COUNTS, false),
+            Err("comp oligo: output differs from the expected output".to_string())
+        );
+        assert!(compare("min", &format!("{}/none", dir), "", true).is_err());
+    }
+}
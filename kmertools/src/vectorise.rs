@@ -0,0 +1,243 @@
+use composition::oligo::OligoComputer;
+use coverage::CovComputer;
+use ktio::{
+    filter::RecordFilter,
+    format::{IdPolicy, OutputFormat},
+    matrix::MatrixWriter,
+    seq::{get_reader, SeqFormat, Sequence, Sequences},
+};
+use rayon::prelude::*;
+use std::io::Write;
+
+const BATCH_RECORDS: usize = 10_000;
+const NUMBER_SIZE: usize = 8;
+
+// oligo frequencies followed by coverage histogram bins of every record, from one pass
+// over the records once the counts table of the coverages is built; binary matrices
+// are written to <out_path>.npy, .parquet or .h5
+pub struct Vectoriser<'a> {
+    in_path: String,
+    out_path: String,
+    oligo: &'a OligoComputer,
+    cov: &'a CovComputer,
+    format: OutputFormat,
+    norm: bool,
+    filter: Option<RecordFilter>,
+    threads: usize,
+}
+
+impl<'a> Vectoriser<'a> {
+    pub fn new(
+        in_path: String,
+        out_path: String,
+        oligo: &'a OligoComputer,
+        cov: &'a CovComputer,
+    ) -> Self {
+        Self {
+            in_path,
+            out_path,
+            oligo,
+            cov,
+            format: OutputFormat::default(),
+            norm: true,
+            filter: None,
+            threads: ktio::threads::default_threads(),
+        }
+    }
+
+    pub fn set_format(&mut self, format: OutputFormat) {
+        self.format = format;
+    }
+
+    // whether both parts are normalised, for the precision of the output
+    pub fn set_norm(&mut self, norm: bool) {
+        self.norm = norm;
+    }
+
+    pub fn set_filter(&mut self, filter: Option<RecordFilter>) {
+        self.filter = filter;
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads;
+    }
+
+    // oligo names, then bin ranges prefixed with cov_
+    pub fn feature_names(&self) -> Vec<String> {
+        let mut names = self.oligo.feature_names();
+        names.extend(
+            self.cov
+                .feature_names()
+                .into_iter()
+                .map(|name| format!("cov_{}", name)),
+        );
+        names
+    }
+
+    // returns the number of records written
+    pub fn vectorise(&self) -> Result<usize, String> {
+        let counts = self.cov.open_counts()?;
+        let edges = self.cov.bin_edges();
+        let names = self.feature_names();
+        let format = SeqFormat::from_path(&self.in_path)?;
+        let mut records = Sequences::new(format, get_reader(&self.in_path)?)?;
+        records.set_filter(self.filter.clone());
+        let mut matrix = match self.format.matrix {
+            Some(matrix) => Some(MatrixWriter::new(
+                &format!("{}.{}", self.out_path, matrix.extension()),
+                matrix,
+                &names,
+                self.format.ids == IdPolicy::First,
+            )?),
+            None => None,
+        };
+        let mut out_buffer = match matrix {
+            Some(_) => None,
+            None => Some(self.format.writer(&self.out_path)?),
+        };
+        if let (Some(out_buffer), Some(header)) =
+            (out_buffer.as_mut(), self.format.header_row(&names))
+        {
+            out_buffer
+                .write_all(header.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+        }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .map_err(|e| format!("Unable to start {} threads: {}", self.threads, e))?;
+        let mut written = 0;
+
+        loop {
+            let batch: Vec<Sequence> = records.by_ref().take(BATCH_RECORDS).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let rows: Vec<Vec<f64>> = pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|seq| {
+                        let mut row = self.oligo.record_vector(&seq.seq);
+                        row.extend(self.cov.record_vector(seq, &counts, &edges));
+                        row
+                    })
+                    .collect()
+            });
+            written += batch.len();
+            if let Some(matrix) = matrix.as_mut() {
+                for (seq, row) in batch.iter().zip(rows.iter()) {
+                    matrix.write_row(&seq.id, row)?;
+                }
+            } else if let Some(out_buffer) = out_buffer.as_mut() {
+                let result: String = batch
+                    .iter()
+                    .zip(rows.iter())
+                    .map(|(seq, row)| {
+                        let fields: Vec<String> = row
+                            .iter()
+                            .map(|val| {
+                                if self.norm {
+                                    self.format.number(*val, Some(NUMBER_SIZE - 2))
+                                } else {
+                                    format!("{}", val)
+                                }
+                            })
+                            .collect();
+                        self.format.row(&seq.id, &fields)
+                    })
+                    .collect();
+                out_buffer
+                    .write_all(result.as_bytes())
+                    .map_err(|_| format!("Unable to write to file: {}", self.out_path))?;
+            }
+        }
+        if let Some(matrix) = matrix {
+            matrix.finish()?;
+        }
+
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use composition::oligo::{Normalisation, OligoOptions, OligoOutput};
+    use ktio::fops::create_directory;
+    use std::{collections::HashMap, fs};
+
+    const PATH_FQ: &str = "../test_data/reads.fq";
+
+    // fields of each row of a vectors file with IDs, by ID
+    fn rows_by_id(path: &str) -> Vec<(String, Vec<String>)> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let mut fields = line.split('\t').map(str::to_string);
+                (fields.next().unwrap(), fields.collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn vectorise_test() {
+        let out_dir = "../test_data/computed_vectorise";
+        create_directory(out_dir).expect("Directory must be creatable");
+        let mut format = OutputFormat::new("\t");
+        format.ids = IdPolicy::First;
+        let mut oligo =
+            OligoComputer::new(PATH_FQ.to_owned(), format!("{}/oligo.vectors", out_dir), 3);
+        oligo
+            .set_options(OligoOptions {
+                normalisation: Normalisation::Counts,
+                output: OligoOutput {
+                    format: format.clone(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .unwrap();
+        oligo.vectorise().unwrap();
+        let mut cov = CovComputer::new(PATH_FQ.to_owned(), out_dir.to_owned(), 4, 2, 3);
+        cov.set_norm(false);
+        cov.set_format(format.clone());
+        cov.build_table().unwrap();
+        cov.compute_coverages().unwrap();
+        // rows of each part alone, to be joined by record ID
+        let mut expected: HashMap<String, Vec<String>> =
+            rows_by_id(&format!("{}/oligo.vectors", out_dir))
+                .into_iter()
+                .collect();
+        for (id, fields) in rows_by_id(&format!("{}/kmers.vectors", out_dir)) {
+            expected.get_mut(&id).unwrap().extend(fields);
+        }
+
+        let vec_path = format!("{}/features.vectors", out_dir);
+        let mut vectoriser = Vectoriser::new(PATH_FQ.to_owned(), vec_path.clone(), &oligo, &cov);
+        vectoriser.set_format(format);
+        vectoriser.set_norm(false);
+        assert_eq!(vectoriser.vectorise().unwrap(), 2);
+        let rows = rows_by_id(&vec_path);
+        let ids: Vec<&str> = rows.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["Read_1", "Read_2"]);
+        // oligo columns, then coverage columns, for the same record
+        let names = vectoriser.feature_names();
+        assert_eq!(names.len(), 32 + 3);
+        assert_eq!(names[32..], ["cov_0-1", "cov_2-3", "cov_4+"]);
+        for (id, fields) in rows.iter() {
+            assert_eq!(fields.len(), names.len());
+            assert_eq!(fields, &expected[id]);
+        }
+
+        // rows of the records kept stay with their IDs
+        let ids_path = format!("{}/exclude.ids", out_dir);
+        fs::write(&ids_path, "Read_1\n").unwrap();
+        vectoriser.set_filter(Some(RecordFilter::exclude_from(&ids_path).unwrap()));
+        assert_eq!(vectoriser.vectorise().unwrap(), 1);
+        let rows = rows_by_id(&vec_path);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].0, "Read_2");
+        assert_eq!(rows[0].1, expected["Read_2"]);
+    }
+}
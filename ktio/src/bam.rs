@@ -0,0 +1,246 @@
+use flate2::bufread::MultiGzDecoder;
+use std::io::{BufRead, ErrorKind, Read};
+
+// flags of alignments that repeat a read already given by its primary alignment
+const SECONDARY: u16 = 0x100;
+const SUPPLEMENTARY: u16 = 0x800;
+const REVERSE: u16 = 0x10;
+
+// 4-bit base codes of BAM sequences
+const BASES: &[u8; 16] = b"=ACMGRSVTWYHKDBN";
+
+pub struct BamRecord {
+    pub name: String,
+    pub flag: u16,
+    // bases as sequenced, reverse strand alignments are complemented back
+    pub seq: Vec<u8>,
+}
+
+// records of a BAM file (BGZF blocks are gzip members), aligned or unaligned
+pub struct BamRecords<R: BufRead> {
+    reader: MultiGzDecoder<R>,
+    header_read: bool,
+    primary_only: bool,
+}
+
+fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' => b'A',
+        b'M' => b'K',
+        b'K' => b'M',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'V' => b'B',
+        b'B' => b'V',
+        b'H' => b'D',
+        b'D' => b'H',
+        base => base,
+    }
+}
+
+impl<R: BufRead> BamRecords<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: MultiGzDecoder::new(reader),
+            header_read: false,
+            primary_only: true,
+        }
+    }
+
+    // secondary and supplementary alignments are skipped unless disabled
+    pub fn set_primary_only(&mut self, primary_only: bool) {
+        self.primary_only = primary_only;
+    }
+
+    fn read_i32(&mut self) -> Result<i32, String> {
+        let mut bytes = [0; 4];
+        self.reader
+            .read_exact(&mut bytes)
+            .map_err(|_| "Truncated BAM header".to_string())?;
+        Ok(i32::from_le_bytes(bytes))
+    }
+
+    fn skip(&mut self, len: i32) -> Result<(), String> {
+        let len = u64::try_from(len).map_err(|_| "Corrupted BAM header".to_string())?;
+        let skipped = std::io::copy(&mut (&mut self.reader).take(len), &mut std::io::sink())
+            .map_err(|_| "Truncated BAM header".to_string())?;
+        if skipped != len {
+            return Err("Truncated BAM header".to_string());
+        }
+        Ok(())
+    }
+
+    // magic, SAM header text and reference names are not needed for reads
+    fn read_header(&mut self) -> Result<(), String> {
+        let mut magic = [0; 4];
+        self.reader
+            .read_exact(&mut magic)
+            .map_err(|_| "Not a BAM file".to_string())?;
+        if &magic != b"BAM\x01" {
+            return Err("Not a BAM file".to_string());
+        }
+        let text_len = self.read_i32()?;
+        self.skip(text_len)?;
+        for _ in 0..self.read_i32()? {
+            let name_len = self.read_i32()?;
+            self.skip(name_len + 4)?;
+        }
+        Ok(())
+    }
+
+    fn read_record(&mut self) -> Result<Option<BamRecord>, String> {
+        let mut bytes = [0; 4];
+        match self.reader.read_exact(&mut bytes) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(_) => return Err("Unable to read BAM record".to_string()),
+        }
+        let block_size = u32::from_le_bytes(bytes) as usize;
+        let mut block = vec![0; block_size];
+        self.reader
+            .read_exact(&mut block)
+            .map_err(|_| "Truncated BAM record".to_string())?;
+        if block.len() < 32 {
+            return Err("Corrupted BAM record".to_string());
+        }
+        let name_len = block[8] as usize;
+        let cigar_ops = u16::from_le_bytes([block[12], block[13]]) as usize;
+        let flag = u16::from_le_bytes([block[14], block[15]]);
+        let seq_len = u32::from_le_bytes(block[16..20].try_into().unwrap()) as usize;
+        let seq_start = 32 + name_len + 4 * cigar_ops;
+        if block.len() < seq_start + seq_len.div_ceil(2) + seq_len {
+            return Err("Corrupted BAM record".to_string());
+        }
+        // names are NUL terminated
+        let name = String::from_utf8_lossy(&block[32..32 + name_len])
+            .trim_end_matches('\0')
+            .to_string();
+        let packed = &block[seq_start..seq_start + seq_len.div_ceil(2)];
+        let mut seq: Vec<u8> = (0..seq_len)
+            .map(|pos| {
+                let code = if pos % 2 == 0 {
+                    packed[pos / 2] >> 4
+                } else {
+                    packed[pos / 2] & 0xf
+                };
+                BASES[code as usize]
+            })
+            .collect();
+        if flag & REVERSE != 0 {
+            seq.reverse();
+            seq.iter_mut().for_each(|base| *base = complement(*base));
+        }
+        Ok(Some(BamRecord { name, flag, seq }))
+    }
+}
+
+impl<R: BufRead> Iterator for BamRecords<R> {
+    type Item = Result<BamRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.header_read {
+            self.header_read = true;
+            if let Err(e) = self.read_header() {
+                return Some(Err(e));
+            }
+        }
+        loop {
+            match self.read_record() {
+                Ok(Some(record))
+                    if self.primary_only && record.flag & (SECONDARY | SUPPLEMENTARY) != 0 =>
+                {
+                    continue
+                }
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use flate2::{Compression, GzBuilder};
+    use std::{fs, io::Write};
+
+    // BAM of unaligned (name, flag, seq) records, as one BGZF block
+    pub(crate) fn write_bam(path: &str, records: &[(&str, u16, &str)]) {
+        let mut data = b"BAM\x01".to_vec();
+        let text = b"@HD\tVN:1.6\tSO:unknown\n";
+        data.extend_from_slice(&(text.len() as i32).to_le_bytes());
+        data.extend_from_slice(text);
+        data.extend_from_slice(&1_i32.to_le_bytes());
+        data.extend_from_slice(&5_i32.to_le_bytes());
+        data.extend_from_slice(b"chr1\0");
+        data.extend_from_slice(&1000_i32.to_le_bytes());
+        for (name, flag, seq) in records {
+            let mut block = Vec::new();
+            block.extend_from_slice(&(-1_i32).to_le_bytes());
+            block.extend_from_slice(&(-1_i32).to_le_bytes());
+            block.push(name.len() as u8 + 1);
+            block.push(255);
+            block.extend_from_slice(&4680_u16.to_le_bytes());
+            block.extend_from_slice(&0_u16.to_le_bytes());
+            block.extend_from_slice(&flag.to_le_bytes());
+            block.extend_from_slice(&(seq.len() as u32).to_le_bytes());
+            block.extend_from_slice(&(-1_i32).to_le_bytes());
+            block.extend_from_slice(&(-1_i32).to_le_bytes());
+            block.extend_from_slice(&0_i32.to_le_bytes());
+            block.extend_from_slice(name.as_bytes());
+            block.push(0);
+            let codes: Vec<u8> = seq
+                .bytes()
+                .map(|base| BASES.iter().position(|&code| code == base).unwrap() as u8)
+                .collect();
+            for pair in codes.chunks(2) {
+                block.push((pair[0] << 4) | pair.get(1).copied().unwrap_or(0));
+            }
+            block.extend(std::iter::repeat_n(30, seq.len()));
+            data.extend_from_slice(&(block.len() as u32).to_le_bytes());
+            data.extend_from_slice(&block);
+        }
+        // BC subfield with the block size less one, filled in once compressed
+        let mut encoder = GzBuilder::new()
+            .extra(b"BC\x02\x00\x00\x00".to_vec())
+            .write(Vec::new(), Compression::fast());
+        encoder.write_all(&data).unwrap();
+        let mut block = encoder.finish().unwrap();
+        let bsize = (block.len() - 1) as u16;
+        block[16..18].copy_from_slice(&bsize.to_le_bytes());
+        fs::write(path, block).unwrap();
+    }
+
+    #[test]
+    fn bam_records_test() {
+        let path = "../test_data/computed_reads.bam";
+        write_bam(
+            path,
+            &[
+                ("Read_1", 4, "ACGTNACGT"),
+                ("Read_2", 0x10, "AACCG"),
+                ("Read_3", 0x100, "ACGT"),
+                ("Read_4", 0x800, "ACGT"),
+            ],
+        );
+        let reader = crate::seq::get_reader(path).unwrap();
+        let records: Vec<BamRecord> = BamRecords::new(reader).map(Result::unwrap).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].name, "Read_1");
+        assert_eq!(records[0].seq, b"ACGTNACGT");
+        // reverse strand alignments are given back as sequenced
+        assert_eq!(records[1].seq, b"CGGTT");
+
+        let mut records = BamRecords::new(crate::seq::get_reader(path).unwrap());
+        records.set_primary_only(false);
+        assert_eq!(records.count(), 4);
+
+        fs::write(path, b"not a bam").unwrap();
+        let mut records = BamRecords::new(crate::seq::get_reader(path).unwrap());
+        assert!(records.next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    filter::RecordFilter,
+    matrix::npy_header,
+    seq::{get_reader, SeqFormat, Sequences},
+};
+use flate2::Crc;
+use std::{
+    fmt::Display,
+    fs,
+    io::{BufWriter, Read, Write},
+};
+
+// matrix metadata for loading vectors straight into numpy/scikit-learn
+pub struct Bundle {
+    feature_names: Vec<String>,
+    ids: Vec<String>,
+    settings: Vec<(String, String)>,
+}
+
+// IDs of the records that make up the rows of an output matrix
+pub fn record_ids(in_path: &str, filter: Option<RecordFilter>) -> Result<Vec<String>, String> {
+    let format = SeqFormat::from_path(in_path)?;
+    let mut records = Sequences::new(format, get_reader(in_path)?)?;
+    records.set_filter(filter);
+    Ok(records.map(|record| record.id).collect())
+}
+
+pub fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_strings(values: &[String]) -> String {
+    let values: Vec<String> = values.iter().map(|value| json_string(value)).collect();
+    format!("[{}]", values.join(", "))
+}
+
+// version 1.0 .npy file of the given dtype and shape
+fn npy(descr: &str, shape: &[usize], data: &[u8]) -> Vec<u8> {
+    let mut bytes = npy_header(descr, shape, 0);
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+// uncompressed zip archive of the given members, as read by numpy.load
+fn write_npz(path: &str, members: &[(&str, Vec<u8>)]) -> Result<(), String> {
+    let file = fs::File::create(path).map_err(|_| format!("Unable to write to file: {}", path))?;
+    let mut buff = BufWriter::new(file);
+    let mut central = Vec::new();
+    let mut offset = 0_u64;
+
+    for (name, data) in members {
+        if data.len() as u64 >= u32::MAX as u64 || offset >= u32::MAX as u64 {
+            return Err(String::from(
+                "NPZ archives larger than 4 GB are not supported",
+            ));
+        }
+        let mut crc = Crc::new();
+        crc.update(data);
+        // version 2.0, no flags, stored, 1980-01-01 00:00
+        let mut fields = Vec::new();
+        fields.extend_from_slice(&20_u16.to_le_bytes());
+        fields.extend_from_slice(&0_u16.to_le_bytes());
+        fields.extend_from_slice(&0_u16.to_le_bytes());
+        fields.extend_from_slice(&0_u16.to_le_bytes());
+        fields.extend_from_slice(&0x21_u16.to_le_bytes());
+        fields.extend_from_slice(&crc.sum().to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&0_u16.to_le_bytes());
+
+        let mut local = Vec::new();
+        local.extend_from_slice(&0x04034b50_u32.to_le_bytes());
+        local.extend_from_slice(&fields);
+        local.extend_from_slice(name.as_bytes());
+        buff.write_all(&local)
+            .and_then(|_| buff.write_all(data))
+            .map_err(|_| format!("Unable to write to file: {}", path))?;
+
+        central.extend_from_slice(&0x02014b50_u32.to_le_bytes());
+        central.extend_from_slice(&20_u16.to_le_bytes());
+        central.extend_from_slice(&fields);
+        // comment length, disk number, internal and external attributes
+        central.extend_from_slice(&[0; 10]);
+        central.extend_from_slice(&(offset as u32).to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+        offset += (local.len() + data.len()) as u64;
+    }
+    if offset >= u32::MAX as u64 {
+        return Err(String::from(
+            "NPZ archives larger than 4 GB are not supported",
+        ));
+    }
+
+    let mut end = Vec::new();
+    end.extend_from_slice(&0x06054b50_u32.to_le_bytes());
+    end.extend_from_slice(&[0; 4]);
+    end.extend_from_slice(&(members.len() as u16).to_le_bytes());
+    end.extend_from_slice(&(members.len() as u16).to_le_bytes());
+    end.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    end.extend_from_slice(&(offset as u32).to_le_bytes());
+    end.extend_from_slice(&0_u16.to_le_bytes());
+    buff.write_all(&central)
+        .and_then(|_| buff.write_all(&end))
+        .and_then(|_| buff.flush())
+        .map_err(|_| format!("Unable to write to file: {}", path))
+}
+
+impl Bundle {
+    pub fn new(feature_names: Vec<String>, ids: Vec<String>) -> Self {
+        Self {
+            feature_names,
+            ids,
+            settings: Vec::new(),
+        }
+    }
+
+    // numbers and booleans, written as JSON literals
+    pub fn set_setting(&mut self, key: &str, value: impl Display) {
+        self.settings.push((key.to_string(), value.to_string()));
+    }
+
+    pub fn set_setting_str(&mut self, key: &str, value: &str) {
+        self.settings.push((key.to_string(), json_string(value)));
+    }
+
+    // writes <matrix>.json and optionally <matrix>.npz with X and ids
+    pub fn write(
+        &self,
+        matrix_path: &str,
+        delim: &str,
+        header: bool,
+        npz: bool,
+    ) -> Result<(), String> {
+        // compressed matrices are read as written
+        let mut text = String::new();
+        get_reader(matrix_path)?
+            .read_to_string(&mut text)
+            .map_err(|_| format!("Unable to read file: {}", matrix_path))?;
+        let mut values: Vec<f64> = Vec::new();
+        let mut rows = 0;
+        for line in text.lines().skip(if header { 1 } else { 0 }) {
+            let row: Vec<f64> = line
+                .split(delim)
+                .map(|val| val.trim().parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("Invalid value in row {} of {}", rows + 1, matrix_path))?;
+            if row.len() != self.feature_names.len() {
+                return Err(format!(
+                    "Row {} of {} has {} values, expected {}",
+                    rows + 1,
+                    matrix_path,
+                    row.len(),
+                    self.feature_names.len()
+                ));
+            }
+            values.extend(row);
+            rows += 1;
+        }
+        if rows != self.ids.len() {
+            return Err(format!(
+                "{} has {} rows but {} record IDs",
+                matrix_path,
+                rows,
+                self.ids.len()
+            ));
+        }
+
+        let settings: Vec<String> = self
+            .settings
+            .iter()
+            .map(|(key, value)| format!("{}: {}", json_string(key), value))
+            .collect();
+        let json = format!(
+            "{{\"matrix\": {}, \"delimiter\": {}, \"header\": {}, \"dtype\": \"float64\", \"shape\": [{}, {}], \"feature_names\": {}, \"ids\": {}, \"settings\": {{{}}}}}\n",
+            json_string(matrix_path),
+            json_string(delim),
+            header,
+            rows,
+            self.feature_names.len(),
+            json_strings(&self.feature_names),
+            json_strings(&self.ids),
+            settings.join(", ")
+        );
+        let json_path = format!("{}.json", matrix_path);
+        fs::write(&json_path, json)
+            .map_err(|_| format!("Unable to write to file: {}", json_path))?;
+
+        if npz {
+            let x: Vec<u8> = values.iter().flat_map(|val| val.to_le_bytes()).collect();
+            // fixed width unicode, so that numpy.load needs no pickles
+            let width = self
+                .ids
+                .iter()
+                .map(|id| id.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(1);
+            let mut ids = Vec::with_capacity(self.ids.len() * width * 4);
+            for id in self.ids.iter() {
+                let chars = id.chars().count();
+                ids.extend(id.chars().flat_map(|c| (c as u32).to_le_bytes()));
+                ids.extend(std::iter::repeat_n(0_u8, (width - chars) * 4));
+            }
+            write_npz(
+                &format!("{}.npz", matrix_path),
+                &[
+                    ("X.npy", npy("<f8", &[rows, self.feature_names.len()], &x)),
+                    ("ids.npy", npy(&format!("<U{}", width), &[rows], &ids)),
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_test() {
+        let matrix = "../test_data/computed_bundle.tsv";
+        fs::write(matrix, "AA\tAC\n0.25\t0.75\n1\t0\n").unwrap();
+        let mut bundle = Bundle::new(
+            vec!["AA".to_string(), "AC".to_string()],
+            vec!["read_1".to_string(), "r\"2".to_string()],
+        );
+        bundle.set_setting("ksize", 2);
+        bundle.set_setting("normalised", true);
+        bundle.set_setting_str("command", "comp oligo");
+        bundle.write(matrix, "\t", true, true).unwrap();
+        let json = fs::read_to_string(format!("{}.json", matrix)).unwrap();
+        assert!(json.contains("\"shape\": [2, 2]"));
+        assert!(json.contains("\"ids\": [\"read_1\", \"r\\\"2\"]"));
+        assert!(json.contains(
+            "\"settings\": {\"ksize\": 2, \"normalised\": true, \"command\": \"comp oligo\"}"
+        ));
+
+        let npz = fs::read(format!("{}.npz", matrix)).unwrap();
+        assert_eq!(&npz[..4], b"PK\x03\x04");
+        // X is 64 byte aligned within its member and holds the rows in order
+        let x = npy("<f8", &[2, 2], &[]);
+        assert_eq!(x.len() % 64, 0);
+        let start = 30 + "X.npy".len() + x.len();
+        let values: Vec<f64> = npz[start..start + 32]
+            .chunks(8)
+            .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(values, vec![0.25, 0.75, 1.0, 0.0]);
+        assert_eq!(&npz[npz.len() - 22..npz.len() - 18], b"PK\x05\x06");
+
+        // rows must match the record IDs
+        let bundle = Bundle::new(vec!["AA".to_string(), "AC".to_string()], vec![]);
+        assert!(bundle.write(matrix, "\t", true, false).is_err());
+        assert_eq!(
+            record_ids("../test_data/reads.fa", None).unwrap().len(),
+            fs::read_to_string("../test_data/reads.fa")
+                .unwrap()
+                .matches('>')
+                .count()
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use std::{collections::HashSet, fs, sync::Arc};
+
+// Keeps or drops records by their ID, shared by every reader
+#[derive(Debug, Clone)]
+pub enum RecordFilter {
+    Include(Arc<HashSet<String>>),
+    Exclude(Arc<HashSet<String>>),
+}
+
+impl RecordFilter {
+    pub fn include_from(path: &str) -> Result<Self, String> {
+        Ok(RecordFilter::Include(Arc::new(load_ids(path)?)))
+    }
+
+    pub fn exclude_from(path: &str) -> Result<Self, String> {
+        Ok(RecordFilter::Exclude(Arc::new(load_ids(path)?)))
+    }
+
+    pub fn keep(&self, id: &str) -> bool {
+        match self {
+            RecordFilter::Include(ids) => ids.contains(id),
+            RecordFilter::Exclude(ids) => !ids.contains(id),
+        }
+    }
+}
+
+// one ID per line, FASTA/FASTQ markers and trailing descriptions are ignored
+fn load_ids(path: &str) -> Result<HashSet<String>, String> {
+    let text = fs::read_to_string(path).map_err(|_| format!("Unable to open: {}", path))?;
+    Ok(text
+        .lines()
+        .filter_map(|line| {
+            line.trim()
+                .trim_start_matches(['>', '@'])
+                .split_whitespace()
+                .next()
+        })
+        .map(|id| id.to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_filter_test() {
+        let include = RecordFilter::include_from("../test_data/reads.fa").unwrap();
+        assert!(include.keep("Record_1"));
+        assert!(include.keep("Record_2"));
+        assert!(!include.keep("Record_3"));
+        let exclude = RecordFilter::exclude_from("../test_data/reads.fa").unwrap();
+        assert!(!exclude.keep("Record_1"));
+        assert!(exclude.keep("Record_3"));
+        assert!(RecordFilter::include_from("../test_data/doesnotexist.txt").is_err());
+    }
+}
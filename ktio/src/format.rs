@@ -0,0 +1,220 @@
+use crate::matrix::MatrixFormat;
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+// whether rows start with the ID of their record
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdPolicy {
+    Omit,
+    First,
+}
+
+// codec of text vector outputs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputCompression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl OutputCompression {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputCompression::None => "",
+            OutputCompression::Gzip => ".gz",
+            OutputCompression::Zstd => ".zst",
+        }
+    }
+}
+
+// rows of only the non-zero columns of vectors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SparseFormat {
+    // <column>:<value> pairs with 0-based columns, IDs and delimiter as in dense rows
+    Pairs,
+    // libsvm/svmlight rows of a 0 label and 1-based <column>:<value> pairs, IDs as comments
+    Libsvm,
+}
+
+// layout of the rows written by vector writers
+#[derive(Debug, Clone)]
+pub struct OutputFormat {
+    pub delim: String,
+    // decimal places of fractional values, None keeps the writer's default
+    pub precision: Option<usize>,
+    pub header: bool,
+    pub ids: IdPolicy,
+    // binary matrix written in place of delimited rows
+    pub matrix: Option<MatrixFormat>,
+    pub compression: OutputCompression,
+    // non-zero columns written in place of every column
+    pub sparse: Option<SparseFormat>,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self {
+            delim: " ".to_owned(),
+            precision: None,
+            header: false,
+            ids: IdPolicy::Omit,
+            matrix: None,
+            compression: OutputCompression::None,
+            sparse: None,
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn new(delim: &str) -> Self {
+        Self {
+            delim: delim.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    // path of a text output, with the extension of its codec unless already given
+    pub fn path(&self, path: &str) -> String {
+        let extension = self.compression.extension();
+        if path.ends_with(extension) {
+            path.to_string()
+        } else {
+            format!("{}{}", path, extension)
+        }
+    }
+
+    // buffered writer of text rows at self.path(path), encoders finish when dropped
+    pub fn writer(&self, path: &str) -> Result<Box<dyn Write + Send>, String> {
+        let path = self.path(path);
+        let file = File::create(&path).map_err(|_| format!("Unable to write to file: {}", path))?;
+        Ok(match self.compression {
+            OutputCompression::None => Box::new(BufWriter::new(file)),
+            OutputCompression::Gzip => Box::new(BufWriter::new(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            ))),
+            OutputCompression::Zstd => Box::new(BufWriter::new(
+                zstd::Encoder::new(file, 3)
+                    .map_err(|e| format!("Unable to compress {}: {}", path, e))?
+                    .auto_finish(),
+            )),
+        })
+    }
+
+    // fractional value, default precision of None writes the shortest exact value
+    pub fn number(&self, value: f64, default_precision: Option<usize>) -> String {
+        match self.precision.or(default_precision) {
+            Some(precision) => format!("{:.*}", precision, value),
+            None => format!("{}", value),
+        }
+    }
+
+    // point coordinates, kept as (x,y) in space separated rows and as columns otherwise
+    pub fn point(&self, coords: &[String]) -> String {
+        if self.delim == " " {
+            format!("({})", coords.join(","))
+        } else {
+            coords.join(&self.delim)
+        }
+    }
+
+    pub fn row(&self, id: &str, fields: &[String]) -> String {
+        match self.ids {
+            IdPolicy::Omit => format!("{}\n", fields.join(&self.delim)),
+            IdPolicy::First => format!("{}{}{}\n", id, self.delim, fields.join(&self.delim)),
+        }
+    }
+
+    // row of the non-zero (column, value) pairs of a vector, columns are 0-based
+    pub fn sparse_row(&self, id: &str, pairs: &[(usize, String)]) -> String {
+        match self.sparse.unwrap_or(SparseFormat::Pairs) {
+            SparseFormat::Pairs => {
+                let fields: Vec<String> = pairs
+                    .iter()
+                    .map(|(col, val)| format!("{}:{}", col, val))
+                    .collect();
+                self.row(id, &fields)
+            }
+            SparseFormat::Libsvm => {
+                let mut row = String::from("0");
+                for (col, val) in pairs {
+                    write!(row, " {}:{}", col + 1, val).unwrap();
+                }
+                if self.ids == IdPolicy::First {
+                    write!(row, " # {}", id).unwrap();
+                }
+                row.push('\n');
+                row
+            }
+        }
+    }
+
+    pub fn header_row(&self, names: &[String]) -> Option<String> {
+        if !self.header {
+            return None;
+        }
+        Some(self.row("id", names))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn output_format_test() {
+        let fields = vec!["1".to_string(), "2".to_string()];
+        let mut format = OutputFormat::default();
+        assert_eq!(format.row("r1", &fields), "1 2\n");
+        assert_eq!(format.header_row(&fields), None);
+        assert_eq!(format.number(0.25, None), "0.25");
+        assert_eq!(format.number(0.25, Some(3)), "0.250");
+        assert_eq!(format.point(&fields), "(1,2)");
+
+        format = OutputFormat::new("\t");
+        format.precision = Some(1);
+        format.header = true;
+        format.ids = IdPolicy::First;
+        assert_eq!(format.row("r1", &fields), "r1\t1\t2\n");
+        assert_eq!(format.header_row(&fields).unwrap(), "id\t1\t2\n");
+        assert_eq!(format.number(0.25, Some(3)), "0.2");
+        assert_eq!(format.point(&fields), "1\t2");
+    }
+
+    #[test]
+    fn sparse_row_test() {
+        let pairs = vec![(0, "0.5".to_string()), (3, "0.25".to_string())];
+        let mut format = OutputFormat::new("\t");
+        assert_eq!(format.sparse_row("r1", &pairs), "0:0.5\t3:0.25\n");
+        format.ids = IdPolicy::First;
+        assert_eq!(format.sparse_row("r1", &pairs), "r1\t0:0.5\t3:0.25\n");
+        format.sparse = Some(SparseFormat::Libsvm);
+        assert_eq!(format.sparse_row("r1", &pairs), "0 1:0.5 4:0.25 # r1\n");
+        format.ids = IdPolicy::Omit;
+        assert_eq!(format.sparse_row("r1", &[]), "0\n");
+    }
+
+    #[test]
+    fn output_compression_test() {
+        let mut format = OutputFormat::default();
+        assert_eq!(format.path("out.tsv"), "out.tsv");
+        for compression in [OutputCompression::Gzip, OutputCompression::Zstd] {
+            format.compression = compression;
+            let path = format.path("../test_data/computed_compressed.tsv");
+            assert_eq!(format.path(&path), path);
+            let mut writer = format.writer(&path).unwrap();
+            writer.write_all(b"1 2\n3 4\n").unwrap();
+            drop(writer);
+            let mut text = String::new();
+            crate::seq::get_reader(&path)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            assert_eq!(text, "1 2\n3 4\n");
+        }
+    }
+}
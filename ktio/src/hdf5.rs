@@ -0,0 +1,362 @@
+#[cfg(feature = "hdf5")]
+use hdf5_sys::{
+    h5::{hsize_t, H5open},
+    h5a::{H5Aclose, H5Acreate2, H5Awrite},
+    h5d::{H5Dclose, H5Dcreate2, H5Dget_space, H5Dset_extent, H5Dwrite},
+    h5f::{H5Fclose, H5Fcreate, H5F_ACC_TRUNC, H5F_LIBVER_LATEST},
+    h5i::hid_t,
+    h5p::{
+        H5Pclose, H5Pcreate, H5Pset_chunk, H5Pset_libver_bounds, H5P_CLS_DATASET_CREATE,
+        H5P_CLS_FILE_ACCESS, H5P_DEFAULT,
+    },
+    h5s::{H5Sclose, H5Screate_simple, H5Sselect_hyperslab, H5S_SELECT_SET, H5S_UNLIMITED},
+    h5t::{
+        H5Tclose, H5Tcopy, H5Tset_cset, H5Tset_size, H5T_CSET_UTF8, H5T_C_S1, H5T_NATIVE_DOUBLE,
+        H5T_VARIABLE,
+    },
+};
+#[cfg(feature = "hdf5")]
+use std::{
+    ffi::{c_char, CString},
+    ptr,
+};
+
+// rows written at once, also the number of rows of a chunk
+#[cfg(feature = "hdf5")]
+const CHUNK_ROWS: usize = 1024;
+
+// float64 "vectors" dataset of rows by features, chunked and grown as rows are written,
+// with the feature names in its "features" attribute and the record IDs in an "ids"
+// dataset of the same length
+pub struct Hdf5Writer {
+    #[cfg(feature = "hdf5")]
+    path: String,
+    #[cfg(feature = "hdf5")]
+    file: hid_t,
+    #[cfg(feature = "hdf5")]
+    vectors: hid_t,
+    #[cfg(feature = "hdf5")]
+    ids: Option<hid_t>,
+    #[cfg(feature = "hdf5")]
+    cols: usize,
+    #[cfg(feature = "hdf5")]
+    rows: usize,
+    #[cfg(feature = "hdf5")]
+    pending: Vec<f64>,
+    #[cfg(feature = "hdf5")]
+    pending_ids: Vec<CString>,
+}
+
+#[cfg(feature = "hdf5")]
+fn c_string(path: &str, val: &str) -> Result<CString, String> {
+    CString::new(val).map_err(|_| format!("Unable to write to file: {}: {:?}", path, val))
+}
+
+// variable length UTF-8 strings, closed by the caller
+#[cfg(feature = "hdf5")]
+unsafe fn string_type() -> hid_t {
+    let dtype = H5Tcopy(*H5T_C_S1);
+    H5Tset_size(dtype, H5T_VARIABLE);
+    H5Tset_cset(dtype, H5T_CSET_UTF8);
+    dtype
+}
+
+// chunked dataset of rank 1 or 2 with no rows and no limit on the rows
+#[cfg(feature = "hdf5")]
+unsafe fn create_dataset(file: hid_t, name: &str, dtype: hid_t, cols: Option<usize>) -> hid_t {
+    let name = CString::new(name).unwrap();
+    let (dims, max_dims, chunk) = match cols {
+        Some(cols) => (
+            vec![0, cols as hsize_t],
+            vec![H5S_UNLIMITED, cols as hsize_t],
+            vec![CHUNK_ROWS as hsize_t, usize::max(1, cols) as hsize_t],
+        ),
+        None => (vec![0], vec![H5S_UNLIMITED], vec![CHUNK_ROWS as hsize_t]),
+    };
+    let space = H5Screate_simple(dims.len() as i32, dims.as_ptr(), max_dims.as_ptr());
+    let dcpl = H5Pcreate(*H5P_CLS_DATASET_CREATE);
+    H5Pset_chunk(dcpl, chunk.len() as i32, chunk.as_ptr());
+    let dataset = H5Dcreate2(
+        file,
+        name.as_ptr(),
+        dtype,
+        space,
+        H5P_DEFAULT,
+        dcpl,
+        H5P_DEFAULT,
+    );
+    H5Pclose(dcpl);
+    H5Sclose(space);
+    dataset
+}
+
+// grows a dataset by the rows of buf and writes them after its first rows
+#[cfg(feature = "hdf5")]
+unsafe fn append(
+    dataset: hid_t,
+    dtype: hid_t,
+    first: usize,
+    rows: usize,
+    cols: Option<usize>,
+    buf: *const std::ffi::c_void,
+) -> bool {
+    let (extent, start, count) = match cols {
+        Some(cols) => (
+            vec![(first + rows) as hsize_t, cols as hsize_t],
+            vec![first as hsize_t, 0],
+            vec![rows as hsize_t, cols as hsize_t],
+        ),
+        None => (
+            vec![(first + rows) as hsize_t],
+            vec![first as hsize_t],
+            vec![rows as hsize_t],
+        ),
+    };
+    if H5Dset_extent(dataset, extent.as_ptr()) < 0 {
+        return false;
+    }
+    let file_space = H5Dget_space(dataset);
+    let mem_space = H5Screate_simple(count.len() as i32, count.as_ptr(), ptr::null());
+    let status = H5Sselect_hyperslab(
+        file_space,
+        H5S_SELECT_SET,
+        start.as_ptr(),
+        ptr::null(),
+        count.as_ptr(),
+        ptr::null(),
+    ) >= 0
+        && H5Dwrite(dataset, dtype, mem_space, file_space, H5P_DEFAULT, buf) >= 0;
+    H5Sclose(mem_space);
+    H5Sclose(file_space);
+    status
+}
+
+impl Hdf5Writer {
+    #[cfg(not(feature = "hdf5"))]
+    pub fn new(_path: &str, _names: &[String], _with_ids: bool) -> Result<Self, String> {
+        Err("kmertools was built without the hdf5 feature".to_string())
+    }
+
+    // feature names are stored in the "features" attribute, IDs when with_ids is set
+    #[cfg(feature = "hdf5")]
+    pub fn new(path: &str, names: &[String], with_ids: bool) -> Result<Self, String> {
+        let error = || format!("Unable to write to file: {}", path);
+        let c_path = c_string(path, path)?;
+        let c_names = names
+            .iter()
+            .map(|name| c_string(path, name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let name_ptrs: Vec<*const c_char> = c_names.iter().map(|name| name.as_ptr()).collect();
+        unsafe {
+            if H5open() < 0 {
+                return Err("Unable to initialise the HDF5 library".to_string());
+            }
+            // newer object headers keep attributes larger than 64KB out of the header
+            let fapl = H5Pcreate(*H5P_CLS_FILE_ACCESS);
+            H5Pset_libver_bounds(fapl, H5F_LIBVER_LATEST, H5F_LIBVER_LATEST);
+            let file = H5Fcreate(c_path.as_ptr(), H5F_ACC_TRUNC, H5P_DEFAULT, fapl);
+            H5Pclose(fapl);
+            if file < 0 {
+                return Err(error());
+            }
+            let vectors = create_dataset(file, "vectors", *H5T_NATIVE_DOUBLE, Some(names.len()));
+            let dtype = string_type();
+            let dims = [names.len() as hsize_t];
+            let space = H5Screate_simple(1, dims.as_ptr(), ptr::null());
+            let attr_name = CString::new("features").unwrap();
+            let attr = H5Acreate2(
+                vectors,
+                attr_name.as_ptr(),
+                dtype,
+                space,
+                H5P_DEFAULT,
+                H5P_DEFAULT,
+            );
+            let written = attr >= 0 && H5Awrite(attr, dtype, name_ptrs.as_ptr().cast()) >= 0;
+            H5Aclose(attr);
+            H5Sclose(space);
+            let ids = with_ids.then(|| create_dataset(file, "ids", dtype, None));
+            H5Tclose(dtype);
+            if vectors < 0 || !written || ids.is_some_and(|ids| ids < 0) {
+                H5Fclose(file);
+                return Err(error());
+            }
+            Ok(Self {
+                path: path.to_string(),
+                file,
+                vectors,
+                ids,
+                cols: names.len(),
+                rows: 0,
+                pending: Vec::with_capacity(CHUNK_ROWS * names.len()),
+                pending_ids: Vec::new(),
+            })
+        }
+    }
+
+    #[cfg(not(feature = "hdf5"))]
+    pub fn write_row(&mut self, _id: &str, _values: &[f64]) -> Result<(), String> {
+        unreachable!("Hdf5Writer cannot be created without the hdf5 feature")
+    }
+
+    #[cfg(feature = "hdf5")]
+    pub fn write_row(&mut self, id: &str, values: &[f64]) -> Result<(), String> {
+        self.pending.extend_from_slice(values);
+        if self.ids.is_some() {
+            self.pending_ids.push(c_string(&self.path, id)?);
+        }
+        if self.pending.len() >= CHUNK_ROWS * usize::max(1, self.cols) {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "hdf5")]
+    fn flush(&mut self) -> Result<(), String> {
+        let rows = match self.cols {
+            0 => self.pending_ids.len(),
+            cols => self.pending.len() / cols,
+        };
+        if rows == 0 {
+            return Ok(());
+        }
+        let id_ptrs: Vec<*const c_char> = self.pending_ids.iter().map(|id| id.as_ptr()).collect();
+        let written = unsafe {
+            append(
+                self.vectors,
+                *H5T_NATIVE_DOUBLE,
+                self.rows,
+                rows,
+                Some(self.cols),
+                self.pending.as_ptr().cast(),
+            ) && self.ids.is_none_or(|ids| {
+                let dtype = string_type();
+                let written = append(ids, dtype, self.rows, rows, None, id_ptrs.as_ptr().cast());
+                H5Tclose(dtype);
+                written
+            })
+        };
+        if !written {
+            return Err(format!("Unable to write to file: {}", self.path));
+        }
+        self.rows += rows;
+        self.pending.clear();
+        self.pending_ids.clear();
+        Ok(())
+    }
+
+    #[cfg(not(feature = "hdf5"))]
+    pub fn finish(self) -> Result<(), String> {
+        unreachable!("Hdf5Writer cannot be created without the hdf5 feature")
+    }
+
+    #[cfg(feature = "hdf5")]
+    pub fn finish(mut self) -> Result<(), String> {
+        self.flush()?;
+        let closed = unsafe {
+            let mut closed = H5Dclose(self.vectors) >= 0;
+            if let Some(ids) = self.ids {
+                closed &= H5Dclose(ids) >= 0;
+            }
+            closed && H5Fclose(self.file) >= 0
+        };
+        if !closed {
+            return Err(format!("Unable to write to file: {}", self.path));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "hdf5"))]
+mod tests {
+    use super::*;
+    use hdf5_sys::{
+        h5::{herr_t, H5free_memory},
+        h5a::{H5Aopen, H5Aread},
+        h5d::{H5Dopen2, H5Dread},
+        h5f::{H5Fopen, H5F_ACC_RDONLY},
+        h5s::{H5Sget_simple_extent_dims, H5S_ALL},
+    };
+    use std::ffi::{c_void, CStr};
+
+    // extent of a dataset of the given rank
+    unsafe fn dims(dataset: hid_t, rank: usize) -> Vec<hsize_t> {
+        let mut dims = vec![0; rank];
+        let space = H5Dget_space(dataset);
+        H5Sget_simple_extent_dims(space, dims.as_mut_ptr(), ptr::null_mut());
+        H5Sclose(space);
+        dims
+    }
+
+    // variable length strings read by read into buffers the library allocated
+    unsafe fn strings(len: usize, read: impl FnOnce(hid_t, *mut c_void) -> herr_t) -> Vec<String> {
+        let mut ptrs: Vec<*mut c_char> = vec![ptr::null_mut(); len];
+        let dtype = string_type();
+        assert!(read(dtype, ptrs.as_mut_ptr().cast()) >= 0);
+        H5Tclose(dtype);
+        ptrs.into_iter()
+            .map(|ptr| {
+                let val = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+                H5free_memory(ptr.cast());
+                val
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hdf5_round_trip_test() {
+        let path = "../test_data/computed_hdf5.h5";
+        let names = vec!["AA".to_string(), "AC".to_string(), "AG".to_string()];
+        let mut writer = Hdf5Writer::new(path, &names, true).unwrap();
+        // more rows than a chunk, so the datasets are grown more than once
+        let rows = CHUNK_ROWS + 5;
+        for row in 0..rows {
+            let val = row as f64;
+            writer
+                .write_row(&format!("r{}", row), &[val, 0.5, -val])
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        unsafe {
+            let c_path = CString::new(path).unwrap();
+            let file = H5Fopen(c_path.as_ptr(), H5F_ACC_RDONLY, H5P_DEFAULT);
+            assert!(file >= 0);
+            let name = CString::new("vectors").unwrap();
+            let vectors = H5Dopen2(file, name.as_ptr(), H5P_DEFAULT);
+            assert_eq!(dims(vectors, 2), vec![rows as hsize_t, 3]);
+            let mut values = vec![0_f64; rows * 3];
+            let status = H5Dread(
+                vectors,
+                *H5T_NATIVE_DOUBLE,
+                H5S_ALL,
+                H5S_ALL,
+                H5P_DEFAULT,
+                values.as_mut_ptr().cast(),
+            );
+            assert!(status >= 0);
+            for (row, values) in values.chunks(3).enumerate() {
+                let val = row as f64;
+                assert_eq!(values, [val, 0.5, -val]);
+            }
+
+            let name = CString::new("features").unwrap();
+            let attr = H5Aopen(vectors, name.as_ptr(), H5P_DEFAULT);
+            let features = strings(3, |dtype, buf| H5Aread(attr, dtype, buf));
+            assert_eq!(features, names);
+            H5Aclose(attr);
+            H5Dclose(vectors);
+
+            let name = CString::new("ids").unwrap();
+            let ids = H5Dopen2(file, name.as_ptr(), H5P_DEFAULT);
+            assert_eq!(dims(ids, 1), vec![rows as hsize_t]);
+            let ids_read = strings(rows, |dtype, buf| {
+                H5Dread(ids, dtype, H5S_ALL, H5S_ALL, H5P_DEFAULT, buf)
+            });
+            let expected: Vec<String> = (0..rows).map(|row| format!("r{}", row)).collect();
+            assert_eq!(ids_read, expected);
+            H5Dclose(ids);
+            H5Fclose(file);
+        }
+    }
+}
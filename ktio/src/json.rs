@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+// value of a field of a flat JSON object
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+    Null,
+    List(Vec<Value>),
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        while self.pos < self.text.len() && self.text[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        self.skip_space();
+        if self.text.get(self.pos) != Some(&c) {
+            return Err(format!("expected '{}'", c as char));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_space();
+        self.text.get(self.pos).copied()
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            let c = *self.text.get(self.pos).ok_or("unterminated string")?;
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = *self.text.get(self.pos).ok_or("unterminated string")?;
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => bytes.push(b'\n'),
+                        b't' => bytes.push(b'\t'),
+                        b'r' => bytes.push(b'\r'),
+                        b'"' | b'\\' | b'/' => bytes.push(escaped),
+                        _ => return Err("unsupported escape".to_string()),
+                    }
+                }
+                c => bytes.push(c),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| "string is not UTF-8".to_string())
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some(b'"') => Ok(Value::Str(self.string()?)),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Value::List(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Value::List(values));
+                        }
+                        _ => return Err("expected ',' or ']'".to_string()),
+                    }
+                }
+            }
+            Some(_) => {
+                let start = self.pos;
+                while self.pos < self.text.len() && !b",]} \t\r\n".contains(&self.text[self.pos]) {
+                    self.pos += 1;
+                }
+                let word = std::str::from_utf8(&self.text[start..self.pos]).unwrap_or("");
+                match word {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "null" => Ok(Value::Null),
+                    _ => word
+                        .parse()
+                        .map(Value::Num)
+                        .map_err(|_| format!("unexpected value {}", word)),
+                }
+            }
+            None => Err("unexpected end".to_string()),
+        }
+    }
+}
+
+// fields of a flat JSON object such as {"op": "query", "kmers": ["ACGT"]}, nested
+// objects are not supported
+pub fn parse_object(text: &str) -> Result<HashMap<String, Value>, String> {
+    let mut parser = Parser {
+        text: text.as_bytes(),
+        pos: 0,
+    };
+    let mut fields = HashMap::new();
+    parser.expect(b'{')?;
+    if parser.peek() != Some(b'}') {
+        loop {
+            let key = parser.string()?;
+            parser.expect(b':')?;
+            fields.insert(key, parser.value()?);
+            match parser.peek() {
+                Some(b',') => parser.pos += 1,
+                _ => break,
+            }
+        }
+    }
+    parser.expect(b'}')?;
+    if parser.peek().is_some() {
+        return Err("trailing characters".to_string());
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_object_test() {
+        let fields =
+            parse_object("{\"op\": \"query\", \"k\": 3,\n \"all\": true, \"vals\": [0.5, -1]}")
+                .unwrap();
+        assert_eq!(fields["op"], Value::Str("query".to_string()));
+        assert_eq!(fields["k"], Value::Num(3.0));
+        assert_eq!(fields["all"], Value::Bool(true));
+        assert_eq!(
+            fields["vals"],
+            Value::List(vec![Value::Num(0.5), Value::Num(-1.0)])
+        );
+        assert!(parse_object("{}").unwrap().is_empty());
+        assert!(parse_object("{\"op\": \"query\"} x").is_err());
+        assert!(parse_object("{\"op\" \"query\"}").is_err());
+    }
+}
@@ -1,3 +1,12 @@
+pub mod bam;
+pub mod bundle;
+pub mod filter;
 pub mod fops;
+pub mod format;
+pub mod hdf5;
+pub mod json;
+pub mod matrix;
 pub mod mmap;
+pub mod profile;
 pub mod seq;
+pub mod threads;
@@ -0,0 +1,331 @@
+use crate::hdf5::Hdf5Writer;
+use parquet::{
+    basic::{ConvertedType, Repetition, Type as PhysicalType},
+    data_type::{ByteArray, ByteArrayType, DoubleType},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::types::Type,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    sync::Arc,
+};
+
+// values buffered before a parquet row group is written
+const ROW_GROUP_VALUES: usize = 1 << 22;
+
+// binary matrices written in place of delimited text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatrixFormat {
+    // float64 .npy of rows by features, record IDs are not stored
+    Npy,
+    // a float64 column per feature, after a string id column when IDs are written
+    Parquet,
+    // chunked float64 "vectors" dataset, names in its "features" attribute and IDs in an
+    // "ids" dataset when IDs are written
+    Hdf5,
+}
+
+impl MatrixFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            MatrixFormat::Npy => "npy",
+            MatrixFormat::Parquet => "parquet",
+            MatrixFormat::Hdf5 => "h5",
+        }
+    }
+
+    // an error for formats kmertools was built without, checked before any work is done
+    pub fn available(self) -> Result<(), String> {
+        if self == MatrixFormat::Hdf5 && cfg!(not(feature = "hdf5")) {
+            return Err("kmertools was built without the hdf5 feature".to_string());
+        }
+        Ok(())
+    }
+}
+
+// version 1.0 .npy header of the given dtype and shape, padded to at least min_len bytes
+// so that it can be rewritten in place, data following it is 64 byte aligned
+pub(crate) fn npy_header(descr: &str, shape: &[usize], min_len: usize) -> Vec<u8> {
+    let shape: Vec<String> = shape.iter().map(|dim| dim.to_string()).collect();
+    let shape = if shape.len() == 1 {
+        format!("({},)", shape[0])
+    } else {
+        format!("({})", shape.join(", "))
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+    // magic, version and header length take 10 bytes
+    let mut len = 10 + header.len() + 1;
+    len = usize::max(len, min_len).next_multiple_of(64);
+    header.push_str(&" ".repeat(len - 10 - header.len() - 1));
+    header.push('\n');
+    let mut bytes = Vec::with_capacity(len);
+    bytes.extend_from_slice(b"\x93NUMPY\x01\x00");
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes
+}
+
+enum Writer {
+    Npy {
+        buff: BufWriter<File>,
+        header_len: usize,
+    },
+    Parquet {
+        writer: Box<SerializedFileWriter<File>>,
+        ids: Option<Vec<ByteArray>>,
+        columns: Vec<Vec<f64>>,
+    },
+    Hdf5(Hdf5Writer),
+}
+
+// rows of a vector matrix streamed to a binary file
+pub struct MatrixWriter {
+    path: String,
+    writer: Writer,
+    cols: usize,
+    rows: usize,
+}
+
+impl MatrixWriter {
+    // column names are used by parquet and HDF5, IDs are kept when with_ids is set
+    pub fn new(
+        path: &str,
+        format: MatrixFormat,
+        names: &[String],
+        with_ids: bool,
+    ) -> Result<Self, String> {
+        let create =
+            || File::create(path).map_err(|_| format!("Unable to write to file: {}", path));
+        let writer = match format {
+            MatrixFormat::Npy => {
+                // room for the largest row count, rewritten once rows are known
+                let header = npy_header("<f8", &[usize::MAX, names.len()], 0);
+                let mut buff = BufWriter::new(create()?);
+                buff.write_all(&npy_header("<f8", &[0, names.len()], header.len()))
+                    .map_err(|_| format!("Unable to write to file: {}", path))?;
+                Writer::Npy {
+                    buff,
+                    header_len: header.len(),
+                }
+            }
+            MatrixFormat::Parquet => {
+                let mut fields = Vec::with_capacity(names.len() + 1);
+                if with_ids {
+                    fields.push(
+                        Type::primitive_type_builder("id", PhysicalType::BYTE_ARRAY)
+                            .with_repetition(Repetition::REQUIRED)
+                            .with_converted_type(ConvertedType::UTF8)
+                            .build(),
+                    );
+                }
+                for name in names {
+                    fields.push(
+                        Type::primitive_type_builder(name, PhysicalType::DOUBLE)
+                            .with_repetition(Repetition::REQUIRED)
+                            .build(),
+                    );
+                }
+                let fields = fields
+                    .into_iter()
+                    .map(|field| field.map(Arc::new))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("Invalid parquet schema: {}", e))?;
+                let schema = Type::group_type_builder("vectors")
+                    .with_fields(fields)
+                    .build()
+                    .map_err(|e| format!("Invalid parquet schema: {}", e))?;
+                let writer = SerializedFileWriter::new(
+                    create()?,
+                    Arc::new(schema),
+                    Arc::new(WriterProperties::builder().build()),
+                )
+                .map_err(|e| format!("Unable to write to file: {}: {}", path, e))?;
+                Writer::Parquet {
+                    writer: Box::new(writer),
+                    ids: with_ids.then(Vec::new),
+                    columns: vec![Vec::new(); names.len()],
+                }
+            }
+            MatrixFormat::Hdf5 => Writer::Hdf5(Hdf5Writer::new(path, names, with_ids)?),
+        };
+
+        Ok(Self {
+            path: path.to_string(),
+            writer,
+            cols: names.len(),
+            rows: 0,
+        })
+    }
+
+    pub fn write_row(&mut self, id: &str, values: &[f64]) -> Result<(), String> {
+        if values.len() != self.cols {
+            return Err(format!(
+                "Expected {} values in a row, got {}",
+                self.cols,
+                values.len()
+            ));
+        }
+        self.rows += 1;
+        match &mut self.writer {
+            Writer::Npy { buff, .. } => {
+                for val in values {
+                    buff.write_all(&val.to_le_bytes())
+                        .map_err(|_| format!("Unable to write to file: {}", self.path))?;
+                }
+            }
+            Writer::Parquet { ids, columns, .. } => {
+                if let Some(ids) = ids {
+                    ids.push(ByteArray::from(id));
+                }
+                columns
+                    .iter_mut()
+                    .zip(values)
+                    .for_each(|(column, val)| column.push(*val));
+                if columns.first().map_or(0, Vec::len) * self.cols >= ROW_GROUP_VALUES {
+                    self.write_row_group()?;
+                }
+            }
+            Writer::Hdf5(writer) => writer.write_row(id, values)?,
+        }
+        Ok(())
+    }
+
+    fn write_row_group(&mut self) -> Result<(), String> {
+        let Writer::Parquet {
+            writer,
+            ids,
+            columns,
+        } = &mut self.writer
+        else {
+            return Ok(());
+        };
+        let pending = match ids {
+            Some(ids) => ids.len(),
+            None => columns.first().map_or(0, Vec::len),
+        };
+        if pending == 0 {
+            return Ok(());
+        }
+        let error = |e: parquet::errors::ParquetError| {
+            format!("Unable to write to file: {}: {}", self.path, e)
+        };
+        let mut group = writer.next_row_group().map_err(error)?;
+        if let Some(ids) = ids {
+            let mut column = group.next_column().map_err(error)?.unwrap();
+            column
+                .typed::<ByteArrayType>()
+                .write_batch(ids, None, None)
+                .map_err(error)?;
+            column.close().map_err(error)?;
+            ids.clear();
+        }
+        for values in columns.iter_mut() {
+            let mut column = group.next_column().map_err(error)?.unwrap();
+            column
+                .typed::<DoubleType>()
+                .write_batch(values, None, None)
+                .map_err(error)?;
+            column.close().map_err(error)?;
+            values.clear();
+        }
+        group.close().map_err(error)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        self.write_row_group()?;
+        let error = |_| format!("Unable to write to file: {}", self.path);
+        match self.writer {
+            Writer::Npy {
+                mut buff,
+                header_len,
+            } => {
+                buff.seek(SeekFrom::Start(0)).map_err(error)?;
+                buff.write_all(&npy_header("<f8", &[self.rows, self.cols], header_len))
+                    .and_then(|_| buff.flush())
+                    .map_err(error)?;
+            }
+            Writer::Parquet { writer, .. } => {
+                writer
+                    .close()
+                    .map_err(|e| format!("Unable to write to file: {}: {}", self.path, e))?;
+            }
+            Writer::Hdf5(writer) => writer.finish()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::Field,
+    };
+    use std::fs;
+
+    #[test]
+    fn npy_matrix_test() {
+        let path = "../test_data/computed_matrix.npy";
+        let names = vec!["AA".to_string(), "AC".to_string()];
+        let mut writer = MatrixWriter::new(path, MatrixFormat::Npy, &names, false).unwrap();
+        writer.write_row("r1", &[0.25, 0.75]).unwrap();
+        writer.write_row("r2", &[1.0, 0.0]).unwrap();
+        assert!(writer.write_row("r3", &[1.0]).is_err());
+        writer.finish().unwrap();
+
+        let npy = fs::read(path).unwrap();
+        let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize + 10;
+        assert_eq!(header_len % 64, 0);
+        let header = String::from_utf8_lossy(&npy[10..header_len]);
+        assert!(header.contains("'shape': (2, 2)"));
+        let values: Vec<f64> = npy[header_len..]
+            .chunks(8)
+            .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(values, vec![0.25, 0.75, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn parquet_matrix_test() {
+        let path = "../test_data/computed_matrix.parquet";
+        let names = vec!["0-1".to_string(), "2+".to_string()];
+        let mut writer = MatrixWriter::new(path, MatrixFormat::Parquet, &names, true).unwrap();
+        writer.write_row("r1", &[0.25, 0.75]).unwrap();
+        writer.write_row("r2", &[1.0, 0.0]).unwrap();
+        writer.finish().unwrap();
+
+        let reader = SerializedFileReader::new(File::open(path).unwrap()).unwrap();
+        let rows: Vec<Vec<(String, Field)>> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().into_columns())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][0], ("id".to_string(), Field::Str("r1".to_string())));
+        assert_eq!(rows[0][2], ("2+".to_string(), Field::Double(0.75)));
+        assert_eq!(rows[1][1], ("0-1".to_string(), Field::Double(1.0)));
+    }
+
+    #[test]
+    fn hdf5_matrix_test() {
+        let path = "../test_data/computed_matrix.h5";
+        let names = vec!["AA".to_string(), "AC".to_string()];
+        let writer = MatrixWriter::new(path, MatrixFormat::Hdf5, &names, true);
+        if cfg!(feature = "hdf5") {
+            let mut writer = writer.unwrap();
+            writer.write_row("r1", &[0.25, 0.75]).unwrap();
+            assert!(writer.write_row("r2", &[1.0]).is_err());
+            writer.finish().unwrap();
+            assert!(fs::read(path).unwrap().starts_with(b"\x89HDF\r\n\x1a\n"));
+        } else {
+            assert!(writer.is_err());
+            assert!(MatrixFormat::Hdf5.available().is_err());
+        }
+    }
+}
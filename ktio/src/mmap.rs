@@ -1,4 +1,4 @@
-use memmap2::{MmapMut, MmapOptions};
+use memmap2::{Mmap, MmapMut, MmapOptions};
 use std::{cell::UnsafeCell, fs::OpenOptions, ptr};
 
 // https://stackoverflow.com/questions/65178245/how-do-i-write-to-a-mutable-slice-from-multiple-threads-at-arbitrary-indexes-wit/65182786#65182786
@@ -45,3 +45,11 @@ pub fn mmap_file_for_writing(path: &str, size: usize) -> Result<MmapMut, String>
             .map_err(|_| format!("Could not mmap: {}", path))
     }
 }
+
+pub fn mmap_file_for_reading(path: &str) -> Result<Mmap, String> {
+    let file = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .map_err(|_| format!("Could not mmap: {}", path))?;
+    unsafe { Mmap::map(&file).map_err(|_| format!("Could not mmap: {}", path)) }
+}
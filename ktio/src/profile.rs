@@ -0,0 +1,133 @@
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+// wall time and peak resident memory at the end of a stage
+#[derive(Debug, Clone)]
+pub struct Stage {
+    pub name: String,
+    pub wall: Duration,
+    pub max_rss: Option<u64>,
+}
+
+pub struct Profiler {
+    command: String,
+    start: Instant,
+    stages: Vec<Stage>,
+}
+
+// peak resident set size of this process in bytes, linux only
+pub fn max_rss_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+fn format_rss(max_rss: Option<u64>) -> String {
+    match max_rss {
+        Some(bytes) => format!("{:.2} MB", bytes as f64 / (1 << 20) as f64),
+        None => "NA".to_string(),
+    }
+}
+
+impl Profiler {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            start: Instant::now(),
+            stages: Vec::new(),
+        }
+    }
+
+    pub fn stage<T>(&mut self, name: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.stages.push(Stage {
+            name: name.to_string(),
+            wall: start.elapsed(),
+            max_rss: max_rss_bytes(),
+        });
+        result
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = format!("{:<12}{:>14}{:>16}\n", "stage", "wall (s)", "max RSS");
+        for stage in self.stages.iter() {
+            report += &format!(
+                "{:<12}{:>14.3}{:>16}\n",
+                stage.name,
+                stage.wall.as_secs_f64(),
+                format_rss(stage.max_rss)
+            );
+        }
+        report += &format!(
+            "{:<12}{:>14.3}{:>16}\n",
+            "total",
+            self.start.elapsed().as_secs_f64(),
+            format_rss(max_rss_bytes())
+        );
+        report
+    }
+
+    pub fn to_json(&self) -> String {
+        let rss = |max_rss: Option<u64>| max_rss.map_or("null".to_string(), |b| b.to_string());
+        let stages: Vec<String> = self
+            .stages
+            .iter()
+            .map(|stage| {
+                format!(
+                    "{{\"name\": \"{}\", \"wall_seconds\": {:.6}, \"max_rss_bytes\": {}}}",
+                    stage.name,
+                    stage.wall.as_secs_f64(),
+                    rss(stage.max_rss)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"command\": \"{}\", \"wall_seconds\": {:.6}, \"max_rss_bytes\": {}, \"stages\": [{}]}}\n",
+            self.command,
+            self.start.elapsed().as_secs_f64(),
+            rss(max_rss_bytes()),
+            stages.join(", ")
+        )
+    }
+
+    pub fn write_json(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_json()).map_err(|_| format!("Unable to write to file: {}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiler_test() {
+        let mut profiler = Profiler::new("ctr");
+        let value = profiler.stage("count", || {
+            std::thread::sleep(Duration::from_millis(5));
+            42
+        });
+        profiler.stage("merge", || ());
+        assert_eq!(value, 42);
+        assert_eq!(profiler.stages().len(), 2);
+        assert!(profiler.stages()[0].wall >= Duration::from_millis(5));
+        assert!(profiler
+            .report()
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("count"));
+        let json = profiler.to_json();
+        assert!(json.starts_with("{\"command\": \"ctr\""));
+        assert!(json.contains("{\"name\": \"merge\", \"wall_seconds\": "));
+        #[cfg(target_os = "linux")]
+        assert!(max_rss_bytes().unwrap() > 0);
+    }
+}
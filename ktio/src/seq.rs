@@ -1,18 +1,165 @@
 use bio::io::fasta::{Reader as FastaReader, Records as FastaRecords};
 use bio::io::fastq::{Reader as FastqReader, Records as FastqRecords};
-use std::fs::File;
+
+use crate::{bam::BamRecords, filter::RecordFilter};
+use flate2::bufread::MultiGzDecoder;
+use std::fs::{self, File};
 use std::io::{self, BufRead, BufReader, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, process};
+
+// inputs spooled by this process, for unique temporary file names
+static SPOOLED: AtomicUsize = AtomicUsize::new(0);
+
+// decompressed bytes looked at to tell gzip compressed FASTA from FASTQ
+const SNIFF_BYTES: u64 = 1 << 12;
+
+fn cram_error(path: &str) -> String {
+    format!(
+        "CRAM is not supported, convert {} to BAM or FASTQ (samtools fastq) first",
+        path
+    )
+}
+
+fn is_gzip(buffer: &[u8]) -> bool {
+    buffer.starts_with(&[0x1f, 0x8b])
+}
+
+// BAM is BGZF, gzip members with a BC extra subfield, whose data starts with BAM\1
+fn is_bam(buffer: &[u8]) -> bool {
+    // deflate with the FEXTRA flag, the extra field follows the 10 byte header
+    if buffer.len() < 12 || !is_gzip(buffer) || buffer[2] != 8 || buffer[3] & 4 == 0 {
+        return false;
+    }
+    let xlen = u16::from_le_bytes([buffer[10], buffer[11]]) as usize;
+    let Some(mut extra) = buffer.get(12..12 + xlen) else {
+        return false;
+    };
+    let mut bgzf = false;
+    while extra.len() >= 4 {
+        let slen = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        bgzf |= &extra[..2] == b"BC";
+        extra = extra.get(4 + slen..).unwrap_or_default();
+    }
+    let mut magic = [0; 4];
+    bgzf && MultiGzDecoder::new(buffer).read_exact(&mut magic).is_ok() && &magic == b"BAM\x01"
+}
 
 // Record set entries of type R, which implement BufRead trait (stdin/file)
 pub enum RecordSet<R: BufRead> {
-    Fasta(FastaRecords<BufReader<R>>),
-    Fastq(FastqRecords<BufReader<R>>),
+    Fasta(FastaRecords<BufReader<CleanLines<R>>>),
+    Fastq(FastqRecords<BufReader<CleanLines<R>>>),
+    Bam(BamRecords<R>),
 }
 
 pub struct Sequence {
     pub n: usize,
     pub id: String,
+    // header text after the ID
+    pub desc: Option<String>,
     pub seq: Vec<u8>,
+    // FASTQ qualities, only kept when asked for
+    pub qual: Option<Vec<u8>>,
+}
+
+impl Sequence {
+    // FASTQ record when qualities are kept, FASTA otherwise
+    pub fn to_record(&self) -> String {
+        let header = match &self.desc {
+            Some(desc) => format!("{} {}", self.id, desc),
+            None => self.id.clone(),
+        };
+        match &self.qual {
+            Some(qual) => format!(
+                "@{}\n{}\n+\n{}\n",
+                header,
+                String::from_utf8_lossy(&self.seq),
+                String::from_utf8_lossy(qual)
+            ),
+            None => format!(">{}\n{}\n", header, String::from_utf8_lossy(&self.seq)),
+        }
+    }
+}
+
+// lines of the input as the parsers expect them: no byte order mark, \r\n endings
+// or surrounding whitespace, and no blank lines; FASTA also loses ; comment lines,
+// space between > and the ID and spaces within sequence lines
+pub struct CleanLines<R: BufRead> {
+    inner: R,
+    format: SeqFormat,
+    raw: Vec<u8>,
+    line: Vec<u8>,
+    pos: usize,
+    first: bool,
+}
+
+impl<R: BufRead> CleanLines<R> {
+    pub fn new(format: SeqFormat, inner: R) -> Self {
+        Self {
+            inner,
+            format,
+            raw: Vec::new(),
+            line: Vec::new(),
+            pos: 0,
+            first: true,
+        }
+    }
+
+    // next non-empty cleaned line into self.line, false at the end of the input
+    fn next_line(&mut self) -> io::Result<bool> {
+        loop {
+            self.raw.clear();
+            if self.inner.read_until(b'\n', &mut self.raw)? == 0 {
+                return Ok(false);
+            }
+            let mut line = self.raw.as_slice();
+            if self.first {
+                line = line.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(line);
+                self.first = false;
+            }
+            let line = line.trim_ascii();
+            self.line.clear();
+            self.pos = 0;
+            match (self.format, line.first()) {
+                (_, None) | (SeqFormat::Fasta, Some(b';')) => continue,
+                (SeqFormat::Fasta, Some(b'>')) => {
+                    self.line.push(b'>');
+                    self.line.extend_from_slice(line[1..].trim_ascii_start());
+                }
+                (SeqFormat::Fasta, _) => self
+                    .line
+                    .extend(line.iter().filter(|c| !c.is_ascii_whitespace())),
+                _ => self.line.extend_from_slice(line),
+            }
+            self.line.push(b'\n');
+            return Ok(true);
+        }
+    }
+}
+
+impl<R: BufRead> Read for CleanLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.line.len() && !self.next_line()? {
+            return Ok(0);
+        }
+        let len = usize::min(buf.len(), self.line.len() - self.pos);
+        buf[..len].copy_from_slice(&self.line[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+// IDs end at the first whitespace, FASTQ parsing only splits headers at spaces,
+// so tab separated text is moved from the ID to the description
+fn split_header(id: &str, desc: Option<&str>) -> (String, Option<String>) {
+    let (id, rest) = id.split_once(char::is_whitespace).unwrap_or((id, ""));
+    let desc = [rest.trim(), desc.unwrap_or("").trim()]
+        .iter()
+        .filter(|text| !text.is_empty())
+        .copied()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    (id.to_string(), (!desc.is_empty()).then_some(desc))
 }
 
 pub struct SeqStats {
@@ -20,10 +167,12 @@ pub struct SeqStats {
     pub total_length: usize,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SeqFormat {
     Fasta,
     Fastq,
+    // unaligned or aligned reads, CRAM is refused
+    Bam,
 }
 
 impl SeqFormat {
@@ -36,53 +185,144 @@ impl SeqFormat {
             return Some(SeqFormat::Fastq);
         } else if path.ends_with(".fasta") || path.ends_with(".fa") || path.ends_with(".fna") {
             return Some(SeqFormat::Fasta);
+        } else if path.ends_with(".bam") {
+            return Some(SeqFormat::Bam);
         }
         None
     }
+
+    // format of a file from its name, CRAM is refused with a hint to convert it
+    pub fn from_path(path: &str) -> Result<SeqFormat, String> {
+        if path.ends_with(".cram") {
+            return Err(cram_error(path));
+        }
+        Self::get(path).ok_or(format!("Unsupported file format: {}", path))
+    }
+
+    // format of a stream from its first bytes, as for stdin, gzip compressed FASTA/FASTQ is
+    // told apart from BAM by inflating the start of the first member
+    pub fn sniff(buffer: &[u8]) -> SeqFormat {
+        if is_bam(buffer) {
+            return SeqFormat::Bam;
+        }
+        if is_gzip(buffer) {
+            // a truncated member still gives the bytes inflated before the end of the buffer
+            let mut text = Vec::new();
+            let _ = MultiGzDecoder::new(buffer)
+                .take(SNIFF_BYTES)
+                .read_to_end(&mut text);
+            return Self::sniff_text(&text);
+        }
+        Self::sniff_text(buffer)
+    }
+
+    fn sniff_text(buffer: &[u8]) -> SeqFormat {
+        let buffer = buffer.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(buffer);
+        match buffer.iter().find(|c| !c.is_ascii_whitespace()) {
+            Some(b'>') | Some(b';') => SeqFormat::Fasta,
+            _ => SeqFormat::Fastq,
+        }
+    }
 }
 
 pub struct Sequences<R: BufRead> {
     pub current_record: usize,
     pub records: RecordSet<R>,
+    pub filter: Option<RecordFilter>,
+    qualities: bool,
 }
 
 impl<R: BufRead> Sequences<R> {
     pub fn new(format: SeqFormat, reader: R) -> Result<Self, String> {
         match format {
             SeqFormat::Fastq => {
-                let fastq_reader = FastqReader::new(reader);
+                let fastq_reader = FastqReader::new(CleanLines::new(format, reader));
                 Ok(Sequences {
                     current_record: 0,
                     records: RecordSet::Fastq(fastq_reader.records()),
+                    filter: None,
+                    qualities: false,
                 })
             }
             SeqFormat::Fasta => {
-                let fasta_reader = FastaReader::new(reader);
+                let fasta_reader = FastaReader::new(CleanLines::new(format, reader));
                 Ok(Sequences {
                     current_record: 0,
                     records: RecordSet::Fasta(fasta_reader.records()),
+                    filter: None,
+                    qualities: false,
                 })
             }
+            SeqFormat::Bam => Ok(Sequences {
+                current_record: 0,
+                records: RecordSet::Bam(BamRecords::new(reader)),
+                filter: None,
+                qualities: false,
+            }),
         }
     }
 
+    // BAM secondary and supplementary alignments are skipped unless disabled
+    pub fn set_primary_only(&mut self, primary_only: bool) {
+        if let RecordSet::Bam(records) = &mut self.records {
+            records.set_primary_only(primary_only);
+        }
+    }
+
+    pub fn set_filter(&mut self, filter: Option<RecordFilter>) {
+        self.filter = filter;
+    }
+
+    // keep FASTQ qualities with the records, for writing them back
+    pub fn set_qualities(&mut self, qualities: bool) {
+        self.qualities = qualities;
+    }
+
+    fn keep(&self, id: &str) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.keep(id))
+    }
+
     pub fn seq_stats(format: SeqFormat, reader: R) -> SeqStats {
+        Sequences::seq_stats_filtered(format, reader, None)
+    }
+
+    pub fn seq_stats_filtered(
+        format: SeqFormat,
+        reader: R,
+        filter: Option<&RecordFilter>,
+    ) -> SeqStats {
         let mut total_length = 0_usize;
         let mut seq_count = 0_usize;
+        let keep = |id: &str| filter.is_none_or(|filter| filter.keep(id));
 
         match format {
             SeqFormat::Fastq => {
-                let fastq_reader = FastqReader::new(reader);
+                let fastq_reader = FastqReader::new(CleanLines::new(format, reader));
                 for record in fastq_reader.records() {
-                    total_length += record.unwrap().seq().len();
-                    seq_count += 1;
+                    let record = record.unwrap();
+                    if keep(&split_header(record.id(), None).0) {
+                        total_length += record.seq().len();
+                        seq_count += 1;
+                    }
                 }
             }
             SeqFormat::Fasta => {
-                let fasta_reader = FastaReader::new(reader);
+                let fasta_reader = FastaReader::new(CleanLines::new(format, reader));
                 for record in fasta_reader.records() {
-                    total_length += record.unwrap().seq().len();
-                    seq_count += 1;
+                    let record = record.unwrap();
+                    if keep(record.id()) {
+                        total_length += record.seq().len();
+                        seq_count += 1;
+                    }
+                }
+            }
+            SeqFormat::Bam => {
+                for record in BamRecords::new(reader) {
+                    let record = record.unwrap();
+                    if keep(&record.name) {
+                        total_length += record.seq.len();
+                        seq_count += 1;
+                    }
                 }
             }
         }
@@ -100,33 +340,40 @@ impl<R: BufRead> Iterator for Sequences<R> {
     fn next(&mut self) -> Option<Self::Item> {
         // records do not have a common trait to get id and seq, we can create one
         // but this looks simpler for the time being
-        match self.records {
-            RecordSet::Fastq(ref mut records) => {
-                let next_record = records.next();
-                if let Some(record) = next_record {
-                    let record = record.unwrap();
-                    self.current_record += 1;
-                    return Some(Sequence {
-                        n: self.current_record - 1,
-                        id: record.id().to_string(),
-                        seq: record.seq().to_vec(),
-                    });
+        loop {
+            let ((id, desc), seq, qual) = match self.records {
+                RecordSet::Fastq(ref mut records) => {
+                    let record = records.next()?.unwrap();
+                    (
+                        split_header(record.id(), record.desc()),
+                        record.seq().to_vec(),
+                        self.qualities.then(|| record.qual().to_vec()),
+                    )
                 }
-                None
-            }
-            RecordSet::Fasta(ref mut records) => {
-                let next_record = records.next();
-                if let Some(record) = next_record {
-                    let record = record.unwrap();
-                    self.current_record += 1;
-                    return Some(Sequence {
-                        n: self.current_record - 1,
-                        id: record.id().to_string(),
-                        seq: record.seq().to_vec(),
-                    });
+                RecordSet::Fasta(ref mut records) => {
+                    let record = records.next()?.unwrap();
+                    (
+                        split_header(record.id(), record.desc()),
+                        record.seq().to_vec(),
+                        None,
+                    )
+                }
+                RecordSet::Bam(ref mut records) => {
+                    let record = records.next()?.unwrap();
+                    ((record.name, None), record.seq, None)
                 }
-                None
+            };
+            if !self.keep(&id) {
+                continue;
             }
+            self.current_record += 1;
+            return Some(Sequence {
+                n: self.current_record - 1,
+                id,
+                desc,
+                seq,
+                qual,
+            });
         }
     }
 
@@ -138,28 +385,180 @@ impl<R: BufRead> Iterator for Sequences<R> {
     }
 }
 
+// read name shared by both mates, without the /1 or /2 suffix
+pub fn mate_name(id: &str) -> &str {
+    id.strip_suffix("/1")
+        .or_else(|| id.strip_suffix("/2"))
+        .unwrap_or(id)
+}
+
+// mates of paired files read in step, the filter applies to the names of the pairs
+pub struct PairedSequences<R: BufRead> {
+    mates_1: Sequences<R>,
+    mates_2: Sequences<R>,
+    filter: Option<RecordFilter>,
+    current_pair: usize,
+}
+
+impl<R: BufRead> PairedSequences<R> {
+    pub fn new(mates_1: Sequences<R>, mates_2: Sequences<R>) -> Self {
+        Self {
+            mates_1,
+            mates_2,
+            filter: None,
+            current_pair: 0,
+        }
+    }
+
+    pub fn set_filter(&mut self, filter: Option<RecordFilter>) {
+        self.filter = filter;
+    }
+}
+
+impl<R: BufRead> Iterator for PairedSequences<R> {
+    type Item = Result<(Sequence, Sequence), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (mut mate_1, mut mate_2) = match (self.mates_1.next(), self.mates_2.next()) {
+                (Some(mate_1), Some(mate_2)) => (mate_1, mate_2),
+                (None, None) => return None,
+                (Some(mate), None) | (None, Some(mate)) => {
+                    return Some(Err(format!("Missing mate of: {}", mate.id)))
+                }
+            };
+            if mate_name(&mate_1.id) != mate_name(&mate_2.id) {
+                return Some(Err(format!(
+                    "Mates are out of sync: {} {}",
+                    mate_1.id, mate_2.id
+                )));
+            }
+            if !self
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.keep(mate_name(&mate_1.id)))
+            {
+                continue;
+            }
+            self.current_pair += 1;
+            mate_1.n = self.current_pair - 1;
+            mate_2.n = self.current_pair - 1;
+            return Some(Ok((mate_1, mate_2)));
+        }
+    }
+}
+
 pub fn get_reader(path: &str) -> Result<BufReader<Box<dyn Read + Sync + Send>>, String> {
     if path == "-" {
         let stdin = io::stdin();
         Ok(BufReader::new(Box::new(stdin)))
     } else {
-        let is_zip = path.ends_with(".gz");
         let file = File::open(path).map_err(|_| format!("Unable to open: {}", path))?;
-        if is_zip {
+        if path.ends_with(".gz") {
             let decoder = flate2::read::GzDecoder::new(file);
             Ok(BufReader::new(Box::new(decoder)))
+        } else if path.ends_with(".zst") {
+            let decoder = zstd::Decoder::new(file)
+                .map_err(|e| format!("Unable to decompress {}: {}", path, e))?;
+            Ok(BufReader::new(Box::new(decoder)))
         } else {
             Ok(BufReader::new(Box::new(file)))
         }
     }
 }
 
+// sequences read in one or more passes, stdin can only be read once so it is copied to a
+// temporary file when more passes are needed, which is removed with the input
+pub struct SeqInput {
+    path: String,
+    spooled: bool,
+}
+
+impl SeqInput {
+    pub fn open(path: &str, passes: usize) -> Result<Self, String> {
+        if path != "-" || passes < 2 {
+            return Ok(Self {
+                path: path.to_string(),
+                spooled: false,
+            });
+        }
+        Self::spool(&mut io::stdin().lock())
+    }
+
+    // copy of a stream in the temporary directory
+    pub fn spool(reader: &mut impl Read) -> Result<Self, String> {
+        let path = env::temp_dir()
+            .join(format!(
+                "kmertools-{}-{}.seq",
+                process::id(),
+                SPOOLED.fetch_add(1, Ordering::Relaxed)
+            ))
+            .to_string_lossy()
+            .into_owned();
+        let mut file =
+            File::create(&path).map_err(|_| format!("Unable to write to file: {}", path))?;
+        let input = Self {
+            path,
+            spooled: true,
+        };
+        io::copy(reader, &mut file)
+            .map_err(|_| format!("Unable to write to file: {}", input.path))?;
+        Ok(input)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // records of a pass over the input, in the format of its first bytes, gzip compressed
+    // FASTA/FASTQ is inflated as it is read
+    pub fn records(&self) -> Result<Sequences<BufReader<Box<dyn Read + Sync + Send>>>, String> {
+        let mut reader = get_reader(&self.path)?;
+        let buffer = reader
+            .fill_buf()
+            .map_err(|_| String::from("Invalid stream"))?;
+        if buffer.starts_with(b"CRAM") {
+            return Err(cram_error(&self.path));
+        }
+        let format = SeqFormat::sniff(buffer);
+        if format != SeqFormat::Bam && is_gzip(buffer) {
+            let decoder: Box<dyn Read + Sync + Send> = Box::new(MultiGzDecoder::new(reader));
+            return Sequences::new(format, BufReader::new(decoder));
+        }
+        Sequences::new(format, reader)
+    }
+}
+
+impl Drop for SeqInput {
+    fn drop(&mut self) {
+        if self.spooled {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+// both mate files one after the other, for counting the k-mers of all reads
+pub fn get_mates_reader(
+    path_1: &str,
+    path_2: &str,
+) -> Result<BufReader<Box<dyn Read + Sync + Send>>, String> {
+    let mates_1 = get_reader(path_1)?;
+    let mates_2 = get_reader(path_2)?;
+    // the first file may not end with a line break
+    Ok(BufReader::new(Box::new(
+        mates_1.chain(&b"\n"[..]).chain(mates_2),
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::{collections::HashSet, sync::Arc};
     const PATH_FQ: &str = "../test_data/reads.fq";
     const PATH_FA: &str = "../test_data/reads.fa";
     const PATH_FQ_GZ: &str = "../test_data/reads.fq.gz";
+    const PATH_MESSY_FA: &str = "../test_data/computed_messy.fa";
+    const PATH_MESSY_FQ: &str = "../test_data/computed_messy.fq";
 
     #[test]
     fn seq_stats_test() {
@@ -231,4 +630,196 @@ mod tests {
         let finish = seqs.next();
         assert!(finish.is_none());
     }
+
+    #[test]
+    fn seq_input_test() {
+        let input = SeqInput::open(PATH_FA, 2).unwrap();
+        assert_eq!(input.path(), PATH_FA);
+        let ids: Vec<String> = input.records().unwrap().map(|record| record.id).collect();
+        assert_eq!(ids, vec!["Record_1", "Record_2"]);
+
+        let mut stream = ">Record_1\nACGT\n>Record_2\nGGCC\n".as_bytes();
+        let input = SeqInput::spool(&mut stream).unwrap();
+        let path = input.path().to_string();
+        for _ in 0..2 {
+            let ids: Vec<String> = input.records().unwrap().map(|record| record.id).collect();
+            assert_eq!(ids, vec!["Record_1", "Record_2"]);
+        }
+        drop(input);
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn load_fq_filtered_test() {
+        let mut ids = HashSet::new();
+        ids.insert("Read_2".to_string());
+        let filter = RecordFilter::Include(Arc::new(ids));
+        let reader = get_reader(PATH_FQ).unwrap();
+        let stats = Sequences::seq_stats_filtered(SeqFormat::Fastq, reader, Some(&filter));
+        assert_eq!(stats.seq_count, 1);
+        assert_eq!(stats.total_length, 72);
+        let reader = get_reader(PATH_FQ).unwrap();
+        let mut seqs = Sequences::new(SeqFormat::Fastq, reader).unwrap();
+        seqs.set_filter(Some(filter));
+        let record = seqs.next().unwrap();
+        assert_eq!("Read_2", record.id);
+        assert_eq!(0, record.n);
+        assert!(seqs.next().is_none());
+    }
+
+    #[test]
+    fn load_messy_fa_test() {
+        // byte order mark, \r\n endings, blank and comment lines, space after >
+        // and within sequence lines, tab separated description
+        std::fs::write(
+            PATH_MESSY_FA,
+            b"\xef\xbb\xbf\r\n;exported from a spreadsheet\r\n> Record_1 sample=A plasmid\r\nGGGTGATGGC CGCTGCCGAT\r\n\r\nggcgtcaaat  \r\n>Record_2\tcontig two\r\nACGT\r\n\r\n",
+        )
+        .unwrap();
+        let stats = Sequences::seq_stats(SeqFormat::Fasta, get_reader(PATH_MESSY_FA).unwrap());
+        assert_eq!(stats.seq_count, 2);
+        assert_eq!(stats.total_length, 34);
+        let mut seqs =
+            Sequences::new(SeqFormat::Fasta, get_reader(PATH_MESSY_FA).unwrap()).unwrap();
+        let record_1 = seqs.next().unwrap();
+        assert_eq!("Record_1", record_1.id);
+        assert_eq!(Some("sample=A plasmid".to_string()), record_1.desc);
+        assert_eq!(b"GGGTGATGGCCGCTGCCGATggcgtcaaat".to_vec(), record_1.seq);
+        let record_2 = seqs.next().unwrap();
+        assert_eq!("Record_2", record_2.id);
+        assert_eq!(Some("contig two".to_string()), record_2.desc);
+        assert_eq!(b"ACGT".to_vec(), record_2.seq);
+        assert!(seqs.next().is_none());
+    }
+
+    #[test]
+    fn load_messy_fq_test() {
+        std::fs::write(
+            PATH_MESSY_FQ,
+            b"\r\n@Read_1\tlane=1 extra\r\nACGTACGT\r\n+\r\nIIIIIIII\r\n\r\n@Read_2 2:N:0\r\nGGCC\r\n+Read_2\r\nIIII\r\n",
+        )
+        .unwrap();
+        let mut ids = HashSet::new();
+        ids.insert("Read_1".to_string());
+        let filter = RecordFilter::Include(Arc::new(ids));
+        let stats = Sequences::seq_stats_filtered(
+            SeqFormat::Fastq,
+            get_reader(PATH_MESSY_FQ).unwrap(),
+            Some(&filter),
+        );
+        assert_eq!(stats.seq_count, 1);
+        assert_eq!(stats.total_length, 8);
+        let mut seqs =
+            Sequences::new(SeqFormat::Fastq, get_reader(PATH_MESSY_FQ).unwrap()).unwrap();
+        let record_1 = seqs.next().unwrap();
+        assert_eq!("Read_1", record_1.id);
+        assert_eq!(Some("lane=1 extra".to_string()), record_1.desc);
+        assert_eq!(b"ACGTACGT".to_vec(), record_1.seq);
+        let record_2 = seqs.next().unwrap();
+        assert_eq!("Read_2", record_2.id);
+        assert_eq!(Some("2:N:0".to_string()), record_2.desc);
+        assert_eq!(b"GGCC".to_vec(), record_2.seq);
+        assert!(seqs.next().is_none());
+
+        let mut seqs =
+            Sequences::new(SeqFormat::Fastq, get_reader(PATH_MESSY_FQ).unwrap()).unwrap();
+        seqs.set_qualities(true);
+        assert_eq!(
+            seqs.next().unwrap().to_record(),
+            "@Read_1 lane=1 extra\nACGTACGT\n+\nIIIIIIII\n"
+        );
+    }
+
+    #[test]
+    fn paired_sequences_test() {
+        let (path_1, path_2) = (
+            "../test_data/computed_mates_1.fq",
+            "../test_data/computed_mates_2.fq",
+        );
+        let mates = |path| Sequences::new(SeqFormat::Fastq, get_reader(path).unwrap()).unwrap();
+        std::fs::write(path_1, "@P1/1\nACGT\n+\nIIII\n@P2/1\nAACC\n+\nIIII").unwrap();
+        std::fs::write(path_2, "@P1/2\nGGTT\n+\nIIII\n@P2/2\nCCA\n+\nIII\n").unwrap();
+        let pairs: Vec<(Sequence, Sequence)> = PairedSequences::new(mates(path_1), mates(path_2))
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(mate_name(&pairs[1].0.id), "P2");
+        assert_eq!((pairs[1].0.n, pairs[1].1.seq.as_slice()), (1, &b"CCA"[..]));
+
+        let mut ids = HashSet::new();
+        ids.insert("P2".to_string());
+        let mut pairs = PairedSequences::new(mates(path_1), mates(path_2));
+        pairs.set_filter(Some(RecordFilter::Include(Arc::new(ids))));
+        assert_eq!(pairs.next().unwrap().unwrap().1.id, "P2/2");
+        assert!(pairs.next().is_none());
+
+        // both files are counted as one
+        let stats =
+            Sequences::seq_stats(SeqFormat::Fastq, get_mates_reader(path_1, path_2).unwrap());
+        assert_eq!((stats.seq_count, stats.total_length), (4, 15));
+
+        std::fs::write(path_2, "@P1/2\nGGTT\n+\nIIII\n").unwrap();
+        let mut pairs = PairedSequences::new(mates(path_1), mates(path_2));
+        assert!(pairs.next().unwrap().is_ok());
+        assert_eq!(
+            pairs.next().unwrap().err(),
+            Some("Missing mate of: P2/1".to_string())
+        );
+        std::fs::write(path_2, "@P2/2\nGGTT\n+\nIIII\n").unwrap();
+        let mut pairs = PairedSequences::new(mates(path_1), mates(path_2));
+        assert!(pairs.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn sniff_format_test() {
+        assert!(matches!(SeqFormat::sniff(b">r\nACGT"), SeqFormat::Fasta));
+        assert!(matches!(
+            SeqFormat::sniff(b"\xEF\xBB\xBF\r\n;c\n>r"),
+            SeqFormat::Fasta
+        ));
+        assert!(matches!(
+            SeqFormat::sniff(b"@r\nACGT\n+\nIIII"),
+            SeqFormat::Fastq
+        ));
+    }
+
+    #[test]
+    fn sniff_compressed_test() {
+        // gzip compressed FASTQ is not BAM
+        let fq_gz = std::fs::read(PATH_FQ_GZ).unwrap();
+        assert_eq!(SeqFormat::sniff(&fq_gz), SeqFormat::Fastq);
+        assert_eq!(SeqFormat::sniff(&fq_gz[..64]), SeqFormat::Fastq);
+        let path = "../test_data/computed_sniff.bam";
+        crate::bam::tests::write_bam(path, &[("Read_1", 4, "ACGTACGT")]);
+        let bam = std::fs::read(path).unwrap();
+        assert_eq!(SeqFormat::sniff(&bam), SeqFormat::Bam);
+        // gzip members without the BC subfield or BAM magic are not BAM
+        assert_eq!(SeqFormat::sniff(&[0x1f, 0x8b, 8]), SeqFormat::Fastq);
+        assert_eq!(
+            SeqFormat::from_path("reads.cram").unwrap_err(),
+            cram_error("reads.cram")
+        );
+    }
+
+    #[test]
+    fn seq_input_stdin_compressed_test() {
+        // gzip compressed FASTQ on stdin
+        let fq_gz = std::fs::read(PATH_FQ_GZ).unwrap();
+        let input = SeqInput::spool(&mut fq_gz.as_slice()).unwrap();
+        let ids: Vec<String> = input.records().unwrap().map(|record| record.id).collect();
+        assert_eq!(ids, vec!["Read_1", "Read_2"]);
+        // BAM on stdin
+        let path = "../test_data/computed_stdin.bam";
+        crate::bam::tests::write_bam(path, &[("Read_1", 4, "ACGTACGT"), ("Read_2", 4, "GGCC")]);
+        let bam = std::fs::read(path).unwrap();
+        let input = SeqInput::spool(&mut bam.as_slice()).unwrap();
+        let records: Vec<Sequence> = input.records().unwrap().collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id, "Read_1");
+        assert_eq!(records[1].seq, b"GGCC");
+        // CRAM is refused
+        let input = SeqInput::spool(&mut &b"CRAM\x03\x01"[..]).unwrap();
+        let e = input.records().err().unwrap();
+        assert!(e.starts_with("CRAM is not supported"), "{}", e);
+    }
 }
@@ -0,0 +1,66 @@
+use std::{
+    cmp::{max, min},
+    env, fs, thread,
+};
+
+// threads used when none are given, KMERTOOLS_THREADS takes precedence over the
+// CPUs this process may use under SLURM allocations, cgroup quotas and the affinity mask
+pub fn default_threads() -> usize {
+    if let Some(threads) = parse_threads(env::var("KMERTOOLS_THREADS").ok()) {
+        return threads;
+    }
+    // honours the affinity mask (taskset, cpusets)
+    let mut threads = thread::available_parallelism().map_or(1, |n| n.get());
+    if let Some(cpus) = parse_threads(env::var("SLURM_CPUS_PER_TASK").ok()) {
+        threads = min(threads, cpus);
+    }
+    if let Some(cpus) = cgroup_cpus() {
+        threads = min(threads, cpus);
+    }
+    max(1, threads)
+}
+
+fn parse_threads(value: Option<String>) -> Option<usize> {
+    value?.trim().parse().ok().filter(|&threads| threads > 0)
+}
+
+// CPU quota of the cgroup (v2 cpu.max or v1 cfs quota), rounded up
+pub fn cgroup_cpus() -> Option<usize> {
+    if let Ok(text) = fs::read_to_string("/sys/fs/cgroup/cpu.max") {
+        return parse_cpu_max(&text);
+    }
+    let quota = fs::read_to_string("/sys/fs/cgroup/cpu/cpu.cfs_quota_us").ok()?;
+    let period = fs::read_to_string("/sys/fs/cgroup/cpu/cpu.cfs_period_us").ok()?;
+    parse_cpu_max(&format!("{} {}", quota.trim(), period.trim()))
+}
+
+// "<quota> <period>", quota is "max" or negative when unlimited
+fn parse_cpu_max(text: &str) -> Option<usize> {
+    let mut parts = text.split_whitespace();
+    let quota: i64 = parts.next()?.parse().ok()?;
+    let period: i64 = parts.next()?.parse().ok()?;
+    if quota <= 0 || period <= 0 {
+        return None;
+    }
+    Some(max(1, (quota + period - 1) / period) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threads_test() {
+        assert_eq!(parse_threads(Some("8".to_string())), Some(8));
+        assert_eq!(parse_threads(Some(" 2\n".to_string())), Some(2));
+        assert_eq!(parse_threads(Some("0".to_string())), None);
+        assert_eq!(parse_threads(Some("all".to_string())), None);
+        assert_eq!(parse_threads(None), None);
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cpu_max("200000 100000\n"), Some(2));
+        assert_eq!(parse_cpu_max("150000 100000"), Some(2));
+        assert_eq!(parse_cpu_max("50000 100000"), Some(1));
+        assert_eq!(parse_cpu_max("-1 100000"), None);
+        assert!(default_threads() >= 1);
+    }
+}
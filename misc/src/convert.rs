@@ -0,0 +1,189 @@
+use kmer::{kmer_to_numeric, numeric_to_kmer};
+use rayon::prelude::*;
+use std::{
+    fs,
+    io::{BufRead, BufWriter, Write},
+};
+
+const BATCH_LINES: usize = 100_000;
+
+#[derive(Debug, Clone, Copy)]
+pub enum KmerFormat {
+    Acgt,
+    Numeric,
+    // text of jellyfish dump -c, binary .jf databases are not written
+    Jellyfish,
+    // text of jellyfish dump
+    JellyfishFasta,
+    // text of kmc_dump, binary KMC databases are not written
+    Kmc,
+}
+
+// ACGT k-mer and count of a kmers.counts line (numeric, ACGT or with --acgt-column)
+fn count_entry(line: &str, ksize: usize) -> Result<(String, &str), String> {
+    let (kmer, count) = line
+        .split_once('\t')
+        .ok_or(format!("Missing count after: {}", line))?;
+    let count = count.rsplit('\t').next().unwrap();
+    if !kmer.bytes().all(|c| c.is_ascii_digit()) {
+        kmer_to_numeric(kmer).ok_or(format!("Not an ACGT k-mer: {}", kmer))?;
+        return Ok((kmer.to_string(), count));
+    }
+    if ksize == 0 {
+        return Err("k-mer size is required to export numeric k-mers".to_string());
+    }
+    let value: u64 = kmer
+        .parse()
+        .map_err(|_| format!("Not a numeric k-mer: {}", kmer))?;
+    Ok((numeric_to_kmer(value, ksize), count))
+}
+
+fn convert_line(line: &str, to: KmerFormat, ksize: usize) -> Result<String, String> {
+    // k-mer is always the first column (kmers.counts and m2s tables)
+    let (kmer, rest) = match line.split_once('\t') {
+        Some((kmer, rest)) => (kmer, Some(rest)),
+        None => (line, None),
+    };
+    let kmer = match to {
+        KmerFormat::Jellyfish => {
+            let (kmer, count) = count_entry(line, ksize)?;
+            return Ok(format!("{} {}\n", kmer, count));
+        }
+        KmerFormat::JellyfishFasta => {
+            let (kmer, count) = count_entry(line, ksize)?;
+            return Ok(format!(">{}\n{}\n", count, kmer));
+        }
+        KmerFormat::Kmc => {
+            let (kmer, count) = count_entry(line, ksize)?;
+            return Ok(format!("{}\t{}\n", kmer, count));
+        }
+        KmerFormat::Acgt => {
+            let value: u64 = kmer
+                .parse()
+                .map_err(|_| format!("Not a numeric k-mer: {}", kmer))?;
+            numeric_to_kmer(value, ksize)
+        }
+        KmerFormat::Numeric => kmer_to_numeric(kmer)
+            .ok_or(format!("Not an ACGT k-mer: {}", kmer))?
+            .to_string(),
+    };
+    Ok(match rest {
+        Some(rest) => format!("{}\t{}\n", kmer, rest),
+        None => format!("{}\n", kmer),
+    })
+}
+
+pub fn convert_kmers(
+    in_path: &str,
+    out_path: &str,
+    to: KmerFormat,
+    ksize: usize,
+    threads: usize,
+) -> Result<(), String> {
+    let mut threads = threads;
+    if threads == 0 {
+        threads = ktio::threads::default_threads();
+    }
+    if let KmerFormat::Acgt = to {
+        if ksize == 0 || ksize > 32 {
+            return Err("k-mer size is required to produce ACGT k-mers".to_string());
+        }
+    }
+    let reader = ktio::seq::get_reader(in_path)?;
+    let outf =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let mut lines = reader.lines().map_while(Result::ok);
+
+    loop {
+        let batch: Vec<String> = lines.by_ref().take(BATCH_LINES).collect();
+        if batch.is_empty() {
+            break;
+        }
+        // converted in parallel, written in input order
+        let converted = pool.install(|| {
+            batch
+                .par_iter()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| convert_line(line.trim_end(), to, ksize))
+                .collect::<Result<Vec<String>, String>>()
+        })?;
+        buff.write_all(converted.join("").as_bytes())
+            .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ktio::fops::load_lines_sorted;
+
+    #[test]
+    fn convert_line_test() {
+        assert_eq!(
+            convert_line("27\t5", KmerFormat::Acgt, 4).unwrap(),
+            "ACGT\t5\n"
+        );
+        assert_eq!(
+            convert_line("ACGT\t5", KmerFormat::Numeric, 4).unwrap(),
+            "27\t5\n"
+        );
+        assert!(convert_line("ACGT\t5", KmerFormat::Acgt, 4).is_err());
+        assert!(convert_line("ACNT\t5", KmerFormat::Numeric, 4).is_err());
+    }
+
+    #[test]
+    fn export_line_test() {
+        assert_eq!(
+            convert_line("27\t5", KmerFormat::Jellyfish, 4).unwrap(),
+            "ACGT 5\n"
+        );
+        assert_eq!(
+            convert_line("ACGT\t5", KmerFormat::JellyfishFasta, 0).unwrap(),
+            ">5\nACGT\n"
+        );
+        assert_eq!(
+            convert_line("27\tACGT\t5", KmerFormat::Kmc, 4).unwrap(),
+            "ACGT\t5\n"
+        );
+        assert!(convert_line("27\t5", KmerFormat::Kmc, 0).is_err());
+        assert!(convert_line("ACGT", KmerFormat::Kmc, 4).is_err());
+    }
+
+    #[test]
+    fn convert_kmers_test() {
+        convert_kmers(
+            "../test_data/expected_counts_acgt_test.counts",
+            "../test_data/computed_counts_converted.counts",
+            KmerFormat::Numeric,
+            0,
+            4,
+        )
+        .unwrap();
+        convert_kmers(
+            "../test_data/computed_counts_converted.counts",
+            "../test_data/computed_counts_converted_acgt.counts",
+            KmerFormat::Acgt,
+            15,
+            4,
+        )
+        .unwrap();
+        let exp = load_lines_sorted("../test_data/expected_counts_acgt_test.counts");
+        let res = load_lines_sorted("../test_data/computed_counts_converted_acgt.counts");
+        assert_eq!(exp, res);
+        assert!(convert_kmers(
+            "../test_data/computed_counts_converted.counts",
+            "../test_data/computed_counts_converted_acgt.counts",
+            KmerFormat::Acgt,
+            0,
+            4,
+        )
+        .is_err());
+    }
+}
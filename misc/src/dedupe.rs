@@ -0,0 +1,113 @@
+use kmer::sketch::Sketch;
+use ktio::seq::{get_reader, SeqFormat, Sequences};
+use rayon::prelude::*;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+};
+
+pub fn sketch_file(path: &str, ksize: usize, size: usize) -> Result<Sketch, String> {
+    let format = SeqFormat::from_path(path)?;
+    let reader = get_reader(path)?;
+    let records = Sequences::new(format, reader)?;
+    let mut sketch = Sketch::new(ksize, size);
+    for record in records {
+        sketch.add_seq(&record.seq);
+    }
+    Ok(sketch)
+}
+
+// pairwise distances between sample sketches, pairs within max_distance are flagged
+pub fn dedupe_samples(
+    in_paths: &[String],
+    out_path: &str,
+    ksize: usize,
+    size: usize,
+    max_distance: f64,
+    threads: usize,
+) -> Result<usize, String> {
+    let mut threads = threads;
+    if threads == 0 {
+        threads = ktio::threads::default_threads();
+    }
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let sketches = pool.install(|| {
+        in_paths
+            .par_iter()
+            .map(|path| sketch_file(path, ksize, size))
+            .collect::<Result<Vec<Sketch>, String>>()
+    })?;
+    let pairs: Vec<(usize, usize)> = (0..sketches.len())
+        .flat_map(|i| (i + 1..sketches.len()).map(move |j| (i, j)))
+        .collect();
+    let rows = pool.install(|| {
+        pairs
+            .par_iter()
+            .map(|&(i, j)| {
+                let jaccard = sketches[i].jaccard(&sketches[j]);
+                let distance = sketches[i].distance(&sketches[j]);
+                (i, j, jaccard, distance, distance <= max_distance)
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let file =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(file);
+    let mut flagged = 0;
+    buff.write_all(b"sample_a\tsample_b\tjaccard\tdistance\tstatus\n")
+        .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    for (i, j, jaccard, distance, duplicate) in rows {
+        if duplicate {
+            flagged += 1;
+        }
+        writeln!(
+            buff,
+            "{}\t{}\t{:.6}\t{:.6}\t{}",
+            in_paths[i],
+            in_paths[j],
+            jaccard,
+            distance,
+            if duplicate { "duplicate" } else { "distinct" }
+        )
+        .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    }
+
+    Ok(flagged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedupe_samples_test() {
+        let out_path = "../test_data/computed_dedupe_samples.tsv";
+        let other_path = "../test_data/computed_dedupe_other.fa";
+        fs::write(other_path, ">other\nTTGACCGATAGGCTTACAGGATCCATGACCTAGGAT\n").unwrap();
+        let flagged = dedupe_samples(
+            &[
+                "../test_data/reads.fq".to_string(),
+                "../test_data/reads.fq.gz".to_string(),
+                other_path.to_string(),
+            ],
+            out_path,
+            15,
+            100,
+            0.01,
+            2,
+        )
+        .unwrap();
+        let table = fs::read_to_string(out_path).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        // same reads compressed or not
+        assert!(lines[1].ends_with("\t1.000000\t0.000000\tduplicate"));
+        assert!(lines[2].ends_with("\tdistinct"));
+        assert!(lines[3].ends_with("\tdistinct"));
+        assert_eq!(flagged, 1);
+    }
+}
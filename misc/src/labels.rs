@@ -0,0 +1,89 @@
+use kmer::{kmer::KmerGenerator, Kmer};
+use std::{collections::HashMap, fs};
+
+// k-mer to label mapping, e.g. k-mers unique to reference genomes
+pub struct KmerLabels {
+    ksize: usize,
+    labels: Vec<String>,
+    map: HashMap<Kmer, usize>,
+}
+
+impl KmerLabels {
+    // expects lines of <kmer in ACGT>\t<label>, all k-mers of the same size
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|_| format!("Unable to open: {}", path))?;
+        let mut ksize = 0;
+        let mut labels = Vec::new();
+        let mut label_ids = HashMap::new();
+        let mut map = HashMap::new();
+
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let mut parts = line.trim().split('\t');
+            let kmer = parts.next().unwrap();
+            let label = parts
+                .next()
+                .ok_or(format!("Missing label for k-mer: {}", kmer))?;
+            if ksize == 0 {
+                ksize = kmer.len();
+            }
+            if kmer.len() != ksize || ksize > 32 {
+                return Err(format!("Invalid k-mer: {}", kmer));
+            }
+            let (min_mer, _) = KmerGenerator::new(kmer.as_bytes(), ksize)
+                .canonical()
+                .next()
+                .ok_or(format!("Invalid k-mer: {}", kmer))?;
+            let label_id = *label_ids.entry(label.to_string()).or_insert_with(|| {
+                labels.push(label.to_string());
+                labels.len() - 1
+            });
+            map.insert(min_mer, label_id);
+        }
+
+        Ok(Self { ksize, labels, map })
+    }
+
+    pub fn ksize(&self) -> usize {
+        self.ksize
+    }
+
+    pub fn label(&self, label_id: usize) -> &str {
+        &self.labels[label_id]
+    }
+
+    // adds a vote per labelled k-mer of seq
+    pub fn vote(&self, seq: &[u8], votes: &mut HashMap<usize, u64>) {
+        for (min_mer, _) in KmerGenerator::new(seq, self.ksize).canonical() {
+            if let Some(&label_id) = self.map.get(&min_mer) {
+                *votes.entry(label_id).or_insert(0) += 1;
+            }
+        }
+    }
+
+    // majority label with the fraction of votes it received
+    pub fn majority(&self, votes: &HashMap<usize, u64>) -> Option<(&str, f64)> {
+        let total: u64 = votes.values().sum();
+        votes
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map(|(&label_id, &count)| (self.label(label_id), count as f64 / total as f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kmer_labels_test() {
+        let path = "../test_data/computed_kmer_labels.tsv";
+        fs::write(path, "AAAA\tgenome_a\nCCCC\tgenome_b\nACGA\tgenome_a\n").unwrap();
+        let labels = KmerLabels::load(path).unwrap();
+        assert_eq!(labels.ksize(), 4);
+        let mut votes = HashMap::new();
+        // TTTT is the reverse complement of AAAA
+        labels.vote(b"AAAATTTTGGGGACGA", &mut votes);
+        assert_eq!(labels.majority(&votes), Some(("genome_a", 0.75)));
+        assert!(labels.majority(&HashMap::new()).is_none());
+    }
+}
@@ -1 +1,9 @@
+pub mod convert;
+pub mod dedupe;
+pub mod labels;
+pub mod locate;
 pub mod minimisers;
+pub mod pairs;
+pub mod recruit;
+pub mod regions;
+pub mod shuffle;
@@ -0,0 +1,169 @@
+use ktio::{
+    filter::RecordFilter,
+    seq::{get_reader, SeqFormat, Sequence, Sequences},
+};
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::{BufWriter, Write},
+};
+
+const BATCH_RECORDS: usize = 10_000;
+
+fn rev_comp(kmer: &[u8]) -> Vec<u8> {
+    kmer.iter()
+        .rev()
+        .map(|base| match base {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            _ => b'A',
+        })
+        .collect()
+}
+
+// query k-mers, one per line (first column)
+pub fn load_kmers(path: &str) -> Result<Vec<String>, String> {
+    let data = fs::read_to_string(path).map_err(|_| format!("Unable to read file: {}", path))?;
+    Ok(data
+        .lines()
+        .filter_map(|line| line.split('\t').next())
+        .map(str::trim)
+        .filter(|kmer| !kmer.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+// forward and reverse complement forms of the query k-mers
+struct Queries {
+    kmers: Vec<String>,
+    lengths: BTreeSet<usize>,
+    lookup: HashMap<Vec<u8>, Vec<(usize, char)>>,
+}
+
+impl Queries {
+    fn new(kmers: &[String]) -> Result<Self, String> {
+        let mut lengths = BTreeSet::new();
+        let mut lookup: HashMap<Vec<u8>, Vec<(usize, char)>> = HashMap::new();
+
+        for (idx, kmer) in kmers.iter().enumerate() {
+            let fwd = kmer.to_ascii_uppercase().into_bytes();
+            if fwd.is_empty() || fwd.iter().any(|base| !b"ACGT".contains(base)) {
+                return Err(format!("Invalid k-mer: {}", kmer));
+            }
+            let rev = rev_comp(&fwd);
+            lengths.insert(fwd.len());
+            // palindromes are reported once
+            if rev != fwd {
+                lookup.entry(rev).or_default().push((idx, '-'));
+            }
+            lookup.entry(fwd).or_default().push((idx, '+'));
+        }
+
+        Ok(Self {
+            kmers: kmers.to_vec(),
+            lengths,
+            lookup,
+        })
+    }
+
+    // (position, query, strand) of every hit in seq
+    fn find(&self, seq: &[u8]) -> Vec<(usize, usize, char)> {
+        let seq = seq.to_ascii_uppercase();
+        let mut hits = Vec::new();
+        for &len in self.lengths.iter() {
+            for (pos, window) in seq.windows(len).enumerate() {
+                if let Some(queries) = self.lookup.get(window) {
+                    hits.extend(queries.iter().map(|&(idx, strand)| (pos, idx, strand)));
+                }
+            }
+        }
+        hits.sort();
+        hits
+    }
+}
+
+// reads containing the query k-mers or their reverse complements, returns the number of such reads
+pub fn locate_kmers(
+    in_path: &str,
+    kmers: &[String],
+    out_path: &str,
+    threads: usize,
+    filter: Option<RecordFilter>,
+) -> Result<usize, String> {
+    let mut threads = threads;
+    if threads == 0 {
+        threads = ktio::threads::default_threads();
+    }
+    let queries = Queries::new(kmers)?;
+    let format = SeqFormat::from_path(in_path)?;
+    let mut records = Sequences::new(format, get_reader(in_path)?)?;
+    records.set_filter(filter);
+    let outf =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let mut found = 0;
+
+    loop {
+        let batch: Vec<Sequence> = records.by_ref().take(BATCH_RECORDS).collect();
+        if batch.is_empty() {
+            break;
+        }
+        let rows: Vec<String> = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|record| {
+                    queries
+                        .find(&record.seq)
+                        .into_iter()
+                        .map(|(pos, idx, strand)| {
+                            format!(
+                                "{}\t{}\t{}\t{}\n",
+                                record.id, queries.kmers[idx], pos, strand
+                            )
+                        })
+                        .collect::<String>()
+                })
+                .collect()
+        });
+        for row in rows.iter().filter(|row| !row.is_empty()) {
+            buff.write_all(row.as_bytes())
+                .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+            found += 1;
+        }
+    }
+
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_kmers_test() {
+        let reads = "../test_data/computed_locate_reads.fa";
+        let out = "../test_data/computed_locate.tsv";
+        fs::write(
+            reads,
+            ">read_1\nTTACGGATCC\n>read_2\nAAAAAAAAAA\n>read_3\nggatccgtaa\n",
+        )
+        .unwrap();
+        let kmers = vec!["ACGGA".to_string(), "GGATCC".to_string()];
+        assert_eq!(locate_kmers(reads, &kmers, out, 2, None), Ok(2));
+        let res = fs::read_to_string(out).unwrap();
+        assert_eq!(
+            res,
+            "read_1\tACGGA\t2\t+\nread_1\tGGATCC\t4\t+\nread_3\tGGATCC\t0\t+\nread_3\tACGGA\t3\t-\n"
+        );
+        assert_eq!(
+            locate_kmers(reads, &["ACNGT".to_string()], out, 2, None),
+            Err("Invalid k-mer: ACNGT".to_string())
+        );
+    }
+}
@@ -1,24 +1,153 @@
+use crate::labels::KmerLabels;
 use indicatif::ProgressBar;
-use kmer::{minimiser::MinimiserGenerator, numeric_to_kmer};
-use ktio::seq::*;
+use kmer::{
+    kmer::KmerGenerator,
+    minimiser::{MinimiserGenerator, MinimiserWeights},
+    numeric_to_kmer,
+    spaced::SpacedSeed,
+    syncmer::SyncmerGenerator,
+    Kmer,
+};
+use ktio::{filter::RecordFilter, seq::*};
+use rayon::prelude::*;
 use scc::HashMap as SccMap;
 use std::{
+    collections::{BTreeMap, HashMap},
     fs,
-    io::{BufReader, BufWriter, Read, Write},
+    io::{BufRead, BufReader, BufWriter, Read, Write},
     sync::{atomic::AtomicU64, Arc, Mutex},
 };
 
-pub fn bin_sequences(wsize: usize, msize: usize, in_path: &str, out_path: &str, threads: usize) {
+// m-mer abundances from kmers.counts of ctr run with k = msize, numeric or ACGT
+// m-mers in the first column and counts in the last
+pub fn load_weights(path: &str, msize: usize) -> Result<MinimiserWeights, String> {
+    let mut counts = HashMap::new();
+    for line in get_reader(path)?.lines().map_while(Result::ok) {
+        let fields: Vec<&str> = line.trim().split('\t').collect();
+        if fields.len() < 2 {
+            continue;
+        }
+        let mmer = match fields[0].parse::<Kmer>() {
+            Ok(mmer) => mmer,
+            Err(_) if fields[0].len() == msize => KmerGenerator::new(fields[0].as_bytes(), msize)
+                .canonical()
+                .next()
+                .map(|(mmer, _)| mmer)
+                .ok_or(format!("Not an ACGT m-mer: {}", fields[0]))?,
+            Err(_) => return Err(format!("Expected a {}-mer, got: {}", msize, fields[0])),
+        };
+        let count = fields[fields.len() - 1]
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid count: {}", line))?;
+        counts.insert(mmer, count);
+    }
+    Ok(MinimiserWeights::new(counts))
+}
+
+// how the m-mers representing a sequence are picked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scheme<'a> {
+    // smallest m-mer of each window, or the one of the smallest hash, m-mers are the
+    // k-mers of the care positions of a spaced seed when there is one
+    Minimiser {
+        hashed: bool,
+        seed: Option<&'a SpacedSeed>,
+    },
+    // m-mers whose smallest s-mer starts at the offset (open), or at either end (closed)
+    // without one, windows are not used
+    Syncmer {
+        ssize: usize,
+        offset: Option<usize>,
+    },
+}
+
+impl Default for Scheme<'_> {
+    fn default() -> Self {
+        Scheme::Minimiser {
+            hashed: false,
+            seed: None,
+        }
+    }
+}
+
+impl Scheme<'_> {
+    // bases of the m-mers written, the weight of the seed for spaced minimisers
+    pub fn mmer_size(&self, msize: usize) -> usize {
+        match self {
+            Scheme::Minimiser {
+                seed: Some(seed), ..
+            } => seed.weight(),
+            _ => msize,
+        }
+    }
+}
+
+// how m-mers are picked and from which records, the default is plain minimisers of every
+// record
+#[derive(Debug, Clone, Default)]
+pub struct MinimiserOptions<'a> {
+    pub scheme: Scheme<'a>,
+    // m-mer abundances, rarer m-mers are picked first
+    pub weights: Option<&'a MinimiserWeights>,
+    pub filter: Option<RecordFilter>,
+    // seq_to_min writes the strand each m-mer was picked from
+    pub strand: bool,
+}
+
+// minimisers of windows of wsize, or of the whole sequence when wsize is 0, or syncmers,
+// as (m-mer, start, end, forward)
+fn minimisers<'a>(
+    seq: &'a [u8],
+    wsize: usize,
+    msize: usize,
+    scheme: Scheme<'a>,
+    weights: Option<&'a MinimiserWeights>,
+) -> Box<dyn Iterator<Item = (Kmer, usize, usize, bool)> + 'a> {
+    let wsize = if wsize == 0 { seq.len() } else { wsize };
+    match (scheme, weights) {
+        (Scheme::Syncmer { ssize, offset }, _) => match offset {
+            Some(offset) => Box::new(SyncmerGenerator::open(seq, msize, ssize, offset)),
+            None => Box::new(SyncmerGenerator::closed(seq, msize, ssize)),
+        },
+        (Scheme::Minimiser { hashed, seed }, weights) => {
+            let mg = match weights {
+                Some(weights) => MinimiserGenerator::weighted(seq, wsize, msize, weights),
+                None => MinimiserGenerator::new(seq, wsize, msize),
+            }
+            .with_hash(hashed);
+            match seed {
+                Some(seed) => Box::new(mg.with_seed(seed)),
+                None => Box::new(mg),
+            }
+        }
+    }
+}
+
+pub fn bin_sequences(
+    wsize: usize,
+    msize: usize,
+    in_path: &str,
+    out_path: &str,
+    threads: usize,
+    labels: Option<&KmerLabels>,
+    options: &MinimiserOptions,
+) {
+    let (scheme, weights) = (options.scheme, options.weights);
+    // spaced minimisers are k-mers of the seed weight
+    let mmer_size = scheme.mmer_size(msize);
     let mut threads = threads;
     if threads == 0 {
-        threads = rayon::current_num_threads();
+        threads = ktio::threads::default_threads();
     }
     let format = SeqFormat::get(in_path).unwrap();
     let reader = ktio::seq::get_reader(in_path).unwrap();
-    let records: Sequences<BufReader<Box<dyn Read + Sync + Send>>> =
+    let mut records: Sequences<BufReader<Box<dyn Read + Sync + Send>>> =
         Sequences::new(format, reader).unwrap();
+    records.set_filter(options.filter.clone());
     let pbar = ProgressBar::new_spinner();
     let result: SccMap<String, Vec<(String, usize, usize)>> = SccMap::new();
+    // label votes of each bin, only when labels are given
+    let votes: SccMap<String, HashMap<usize, u64>> = SccMap::new();
     let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
         .num_threads(threads)
         .build()
@@ -33,6 +162,7 @@ pub fn bin_sequences(wsize: usize, msize: usize, in_path: &str, out_path: &str,
             let result_arc_clone = Arc::clone(&result_arc);
             let total_records_clone = Arc::clone(&total_records);
             let pbar_clone = pbar.clone();
+            let votes = &votes;
 
             scope.spawn(move |_| {
                 loop {
@@ -41,14 +171,19 @@ pub fn bin_sequences(wsize: usize, msize: usize, in_path: &str, out_path: &str,
                         records_arc_clone.lock().unwrap().next()
                     };
                     if let Some(record) = record {
-                        let mgen = if wsize == 0 {
-                            MinimiserGenerator::new(&record.seq, record.seq.len(), msize)
-                        } else {
-                            MinimiserGenerator::new(&record.seq, wsize, msize)
-                        };
-                        for (k, s, e) in mgen {
+                        let mgen = minimisers(&record.seq, wsize, msize, scheme, weights);
+                        for (k, s, e, _) in mgen {
+                            if let Some(labels) = labels {
+                                let mut window_votes = HashMap::new();
+                                labels.vote(&record.seq[s..e], &mut window_votes);
+                                let mut bin_votes =
+                                    votes.entry(numeric_to_kmer(k, mmer_size)).or_default();
+                                for (label_id, count) in window_votes {
+                                    *bin_votes.entry(label_id).or_insert(0) += count;
+                                }
+                            }
                             result_arc_clone
-                                .entry(numeric_to_kmer(k, msize))
+                                .entry(numeric_to_kmer(k, mmer_size))
                                 .and_modify(|v| v.push((record.id.clone(), s, e)))
                                 .or_insert(vec![(record.id.clone(), s, e)]);
                         }
@@ -81,17 +216,48 @@ pub fn bin_sequences(wsize: usize, msize: usize, in_path: &str, out_path: &str,
     result_arc.scan(|k, v| {
         buff.write_all(format!("{k}\t{v:?}\n").as_bytes()).unwrap();
     });
+
+    if let Some(labels) = labels {
+        // minimiser, sequences in bin, majority label and its share of labelled k-mers
+        let outf = fs::File::create(format!("{out_path}.labels")).unwrap();
+        let mut buff = BufWriter::new(outf);
+        result_arc.scan(|k, v| {
+            let (label, confidence, labelled) = match votes.read(k, |_, bin_votes| {
+                let labelled: u64 = bin_votes.values().sum();
+                labels
+                    .majority(bin_votes)
+                    .map(|(label, confidence)| (label.to_string(), confidence, labelled))
+            }) {
+                Some(Some(majority)) => majority,
+                _ => ("unlabelled".to_string(), 0_f64, 0),
+            };
+            buff.write_all(
+                format!("{k}\t{}\t{label}\t{confidence:.4}\t{labelled}\n", v.len()).as_bytes(),
+            )
+            .unwrap();
+        });
+    }
 }
 
-pub fn seq_to_min(wsize: usize, msize: usize, in_path: &str, out_path: &str, threads: usize) {
+pub fn seq_to_min(
+    wsize: usize,
+    msize: usize,
+    in_path: &str,
+    out_path: &str,
+    threads: usize,
+    options: &MinimiserOptions,
+) {
+    let (scheme, weights, strand) = (options.scheme, options.weights, options.strand);
+    let mmer_size = scheme.mmer_size(msize);
     let mut threads = threads;
     if threads == 0 {
-        threads = rayon::current_num_threads();
+        threads = ktio::threads::default_threads();
     }
     let format = SeqFormat::get(in_path).unwrap();
     let reader = ktio::seq::get_reader(in_path).unwrap();
-    let records: Sequences<BufReader<Box<dyn Read + Sync + Send>>> =
+    let mut records: Sequences<BufReader<Box<dyn Read + Sync + Send>>> =
         Sequences::new(format, reader).unwrap();
+    records.set_filter(options.filter.clone());
     let pbar = ProgressBar::new_spinner();
     let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
         .num_threads(threads)
@@ -116,16 +282,17 @@ pub fn seq_to_min(wsize: usize, msize: usize, in_path: &str, out_path: &str, thr
                         records_arc_clone.lock().unwrap().next()
                     };
                     if let Some(record) = record {
-                        let mgen = if wsize == 0 {
-                            MinimiserGenerator::new(&record.seq, record.seq.len(), msize)
-                        } else {
-                            MinimiserGenerator::new(&record.seq, wsize, msize)
-                        };
+                        let mgen = minimisers(&record.seq, wsize, msize, scheme, weights);
                         let mut mins = Vec::new();
                         mins.push(record.id);
 
-                        for (k, s, e) in mgen {
-                            mins.push(format!("{}:{}-{}", numeric_to_kmer(k, msize), s, e));
+                        for (k, s, e, forward) in mgen {
+                            let mut min = format!("{}:{}-{}", numeric_to_kmer(k, mmer_size), s, e);
+                            // strand the minimiser was picked from
+                            if strand {
+                                min.push_str(if forward { ":+" } else { ":-" });
+                            }
+                            mins.push(min);
                         }
                         mins.push("\n".to_string());
                         {
@@ -158,6 +325,93 @@ pub fn seq_to_min(wsize: usize, msize: usize, in_path: &str, out_path: &str, thr
     pbar.finish();
 }
 
+// minimiser covering most of the sequence, ties go to the smaller minimiser
+fn dominant_minimiser(seq: &[u8], wsize: usize, msize: usize) -> Option<Kmer> {
+    if seq.len() < msize {
+        return None;
+    }
+    let mgen = minimisers(
+        seq,
+        wsize,
+        msize,
+        Scheme::Minimiser {
+            hashed: false,
+            seed: None,
+        },
+        None,
+    );
+    let mut spans: HashMap<Kmer, usize> = HashMap::new();
+    for (k, s, e, _) in mgen {
+        *spans.entry(k).or_insert(0) += e - s;
+    }
+    spans
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map(|(k, _)| k)
+}
+
+// groups sequences that share their dominant minimiser under every (wsize, msize) setting,
+// splitting bins caused by a single unlucky minimiser choice, returns the number of bins
+pub fn consensus_bins(
+    settings: &[(usize, usize)],
+    in_path: &str,
+    out_path: &str,
+    threads: usize,
+    filter: Option<RecordFilter>,
+) -> Result<usize, String> {
+    let mut threads = threads;
+    if threads == 0 {
+        threads = ktio::threads::default_threads();
+    }
+    let format = SeqFormat::from_path(in_path)?;
+    let mut records = Sequences::new(format, get_reader(in_path)?)?;
+    records.set_filter(filter);
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let mut bins: BTreeMap<Vec<Kmer>, Vec<String>> = BTreeMap::new();
+
+    loop {
+        let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+        if batch.is_empty() {
+            break;
+        }
+        let keys: Vec<Option<Vec<Kmer>>> = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|record| {
+                    settings
+                        .iter()
+                        .map(|&(wsize, msize)| dominant_minimiser(&record.seq, wsize, msize))
+                        .collect()
+                })
+                .collect()
+        });
+        // sequences shorter than a minimiser are not binned
+        for (record, key) in batch.into_iter().zip(keys) {
+            if let Some(key) = key {
+                bins.entry(key).or_default().push(record.id);
+            }
+        }
+    }
+
+    let outf =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    for (key, ids) in bins.iter() {
+        let key: Vec<String> = key
+            .iter()
+            .zip(settings)
+            .map(|(k, &(_, msize))| numeric_to_kmer(*k, msize))
+            .collect();
+        buff.write_all(format!("{}\t{:?}\n", key.join(","), ids).as_bytes())
+            .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    }
+
+    Ok(bins.len())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -167,7 +421,15 @@ mod tests {
 
     #[test]
     fn bin_sequences_test() {
-        bin_sequences(0, 10, PATH_FQ, "../test_data/computed_minimisers", 32);
+        bin_sequences(
+            0,
+            10,
+            PATH_FQ,
+            "../test_data/computed_minimisers",
+            32,
+            None,
+            &MinimiserOptions::default(),
+        );
         let exp = load_lines_sorted("../test_data/expected_minimisers");
         let res = load_lines_sorted("../test_data/computed_minimisers");
         println!("Result  : {:?}", res);
@@ -177,11 +439,205 @@ mod tests {
 
     #[test]
     fn seq_to_min_test() {
-        seq_to_min(31, 7, PATH_FQ, "../test_data/computed_seq_minimisers", 32);
+        seq_to_min(
+            31,
+            7,
+            PATH_FQ,
+            "../test_data/computed_seq_minimisers",
+            32,
+            &MinimiserOptions::default(),
+        );
         let exp = load_lines_sorted("../test_data/expected_seq_minimisers");
         let res = load_lines_sorted("../test_data/computed_seq_minimisers");
         println!("Result  : {:?}", res);
         println!("Expected: {:?}", exp);
         assert_eq!(exp, res);
     }
+
+    #[test]
+    fn seq_to_min_strand_test() {
+        let out_path = "../test_data/computed_seq_minimisers_strand";
+        let scheme = Scheme::Minimiser {
+            hashed: false,
+            seed: None,
+        };
+        seq_to_min(
+            31,
+            7,
+            PATH_FQ,
+            out_path,
+            4,
+            &MinimiserOptions {
+                scheme,
+                strand: true,
+                ..Default::default()
+            },
+        );
+        // same minimisers, each followed by :+ or :-
+        let exp = load_lines_sorted("../test_data/expected_seq_minimisers");
+        let res: Vec<String> = load_lines_sorted(out_path)
+            .iter()
+            .map(|line| line.replace(":+", "").replace(":-", ""))
+            .collect();
+        assert_eq!(exp, res);
+        let res = load_lines_sorted(out_path);
+        assert!(res.iter().any(|line| line.contains(":+")));
+        assert!(res.iter().any(|line| line.contains(":-")));
+    }
+
+    #[test]
+    fn seq_to_min_spaced_test() {
+        let out_path = "../test_data/computed_seq_minimisers_spaced";
+        // a seed without 0 positions picks the contiguous minimisers
+        let seed = SpacedSeed::parse("1111111").unwrap();
+        let scheme = Scheme::Minimiser {
+            hashed: false,
+            seed: Some(&seed),
+        };
+        seq_to_min(
+            31,
+            10,
+            PATH_FQ,
+            out_path,
+            4,
+            &MinimiserOptions {
+                scheme,
+                ..Default::default()
+            },
+        );
+        let exp = load_lines_sorted("../test_data/expected_seq_minimisers");
+        assert_eq!(exp, load_lines_sorted(out_path));
+        // gapped seeds write k-mers of their weight
+        let seed = SpacedSeed::parse("110110111").unwrap();
+        let scheme = Scheme::Minimiser {
+            hashed: false,
+            seed: Some(&seed),
+        };
+        seq_to_min(
+            31,
+            10,
+            PATH_FQ,
+            out_path,
+            4,
+            &MinimiserOptions {
+                scheme,
+                ..Default::default()
+            },
+        );
+        let res = load_lines_sorted(out_path);
+        assert_eq!(res.len(), exp.len());
+        assert!(res.iter().all(|line| line
+            .split('\t')
+            .skip(1)
+            .filter(|min| !min.trim().is_empty())
+            .all(|min| min.split(':').next().unwrap().len() == 7)));
+    }
+
+    #[test]
+    fn consensus_bins_test() {
+        let out_path = "../test_data/computed_minimisers_consensus";
+        // a single setting matches m2s bins of whole sequence minimisers
+        let single = consensus_bins(&[(0, 10)], PATH_FQ, out_path, 4, None).unwrap();
+        assert_eq!(
+            single,
+            load_lines_sorted("../test_data/expected_minimisers").len()
+        );
+        let consensus =
+            consensus_bins(&[(0, 10), (0, 12), (31, 15)], PATH_FQ, out_path, 4, None).unwrap();
+        assert!(consensus >= single);
+        let res = load_lines_sorted(out_path);
+        assert_eq!(res.len(), consensus);
+        assert!(res
+            .iter()
+            .all(|line| line.split('\t').next().unwrap().split(',').count() == 3));
+    }
+
+    #[test]
+    fn bin_sequences_labelled_test() {
+        let label_path = "../test_data/computed_minimiser_labels.tsv";
+        fs::write(label_path, "GGGTGATGGCCGCTG\tgenome_a\n").unwrap();
+        let labels = KmerLabels::load(label_path).unwrap();
+        let out_path = "../test_data/computed_minimisers_labelled";
+        bin_sequences(
+            0,
+            10,
+            PATH_FQ,
+            out_path,
+            4,
+            Some(&labels),
+            &MinimiserOptions::default(),
+        );
+        let exp = load_lines_sorted("../test_data/expected_minimisers");
+        let res = load_lines_sorted(out_path);
+        assert_eq!(exp, res);
+        let res = load_lines_sorted(format!("{out_path}.labels"));
+        assert_eq!(res.len(), exp.len());
+        assert!(res
+            .iter()
+            .any(|line| line.ends_with("\t1\tgenome_a\t1.0000\t1")));
+        assert!(res.iter().any(|line| line.contains("\tunlabelled\t")));
+    }
+
+    #[test]
+    fn bin_sequences_weighted_test() {
+        let weights_path = "../test_data/computed_minimiser_weights.counts";
+        fs::write(weights_path, "AAAACCCTTA\t50\nAAAACGACGC\t50\n").unwrap();
+        let weights = load_weights(weights_path, 10).unwrap();
+        assert_eq!(weights.len(), 2);
+        let out_path = "../test_data/computed_minimisers_weighted";
+        bin_sequences(
+            0,
+            10,
+            PATH_FQ,
+            out_path,
+            4,
+            None,
+            &MinimiserOptions {
+                weights: Some(&weights),
+                ..Default::default()
+            },
+        );
+        let res = load_lines_sorted(out_path);
+        // abundant minimisers give way to rarer ones
+        assert_eq!(res.len(), 2);
+        assert!(res
+            .iter()
+            .all(|line| !line.starts_with("AAAACCCTTA") && !line.starts_with("AAAACGACGC")));
+    }
+
+    #[test]
+    fn seq_to_syncmers_test() {
+        let out_path = "../test_data/computed_seq_syncmers";
+        let scheme = Scheme::Syncmer {
+            ssize: 5,
+            offset: None,
+        };
+        seq_to_min(
+            0,
+            15,
+            PATH_FQ,
+            out_path,
+            4,
+            &MinimiserOptions {
+                scheme,
+                ..Default::default()
+            },
+        );
+        let res = load_lines_sorted(out_path);
+        assert_eq!(res.len(), 2);
+        for line in res {
+            let fields: Vec<&str> = line.split('\t').collect();
+            assert!(fields.len() > 1);
+            // <syncmer>:<start>-<end> of 15 bases
+            for field in &fields[1..] {
+                let (syncmer, range) = field.split_once(':').unwrap();
+                let (start, end) = range.split_once('-').unwrap();
+                assert_eq!(syncmer.len(), 15);
+                assert_eq!(
+                    end.parse::<usize>().unwrap() - start.parse::<usize>().unwrap(),
+                    15
+                );
+            }
+        }
+    }
 }
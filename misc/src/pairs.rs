@@ -0,0 +1,165 @@
+use kmer::{minimiser::MinimiserGenerator, numeric_to_kmer, Kmer};
+use ktio::{filter::RecordFilter, seq::*};
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{BufWriter, Write},
+};
+
+// mate pairs seen and pairs whose mates share no bin
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PairConcordance {
+    pub pairs: u64,
+    pub discordant: u64,
+}
+
+impl PairConcordance {
+    pub fn rate(&self) -> f64 {
+        if self.pairs == 0 {
+            0_f64
+        } else {
+            self.discordant as f64 / self.pairs as f64
+        }
+    }
+}
+
+fn bins(seq: &[u8], wsize: usize, msize: usize) -> HashSet<Kmer> {
+    let mgen = if wsize == 0 {
+        MinimiserGenerator::new(seq, seq.len(), msize)
+    } else {
+        MinimiserGenerator::new(seq, wsize, msize)
+    };
+    mgen.map(|(k, _, _, _)| k).collect()
+}
+
+// mates of interleaved pairs should land in the same m2s bins, writes
+// <minimiser>\t<pairs>\t<discordant pairs>\t<discordance rate> for every bin and
+// returns the global discordance, a pair is discordant in a bin holding only one mate
+pub fn pair_concordance(
+    wsize: usize,
+    msize: usize,
+    in_path: &str,
+    out_path: &str,
+    threads: usize,
+    filter: Option<RecordFilter>,
+) -> Result<PairConcordance, String> {
+    let mut threads = threads;
+    if threads == 0 {
+        threads = ktio::threads::default_threads();
+    }
+    let format = SeqFormat::from_path(in_path)?;
+    let mut records = Sequences::new(format, get_reader(in_path)?)?;
+    records.set_filter(filter);
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let mut global = PairConcordance::default();
+    let mut per_bin: BTreeMap<Kmer, PairConcordance> = BTreeMap::new();
+
+    loop {
+        let batch: Vec<Sequence> = records.by_ref().take(10_000).collect();
+        if batch.is_empty() {
+            break;
+        }
+        if batch.len() % 2 == 1 {
+            return Err(format!("Missing mate of: {}", batch.last().unwrap().id));
+        }
+        for pair in batch.chunks(2) {
+            if mate_name(&pair[0].id) != mate_name(&pair[1].id) {
+                return Err(format!(
+                    "Records are not interleaved mates: {} {}",
+                    pair[0].id, pair[1].id
+                ));
+            }
+        }
+        let mate_bins: Vec<(HashSet<Kmer>, HashSet<Kmer>)> = pool.install(|| {
+            batch
+                .par_chunks(2)
+                .map(|pair| {
+                    (
+                        bins(&pair[0].seq, wsize, msize),
+                        bins(&pair[1].seq, wsize, msize),
+                    )
+                })
+                .collect()
+        });
+        for (bins_1, bins_2) in mate_bins {
+            global.pairs += 1;
+            if bins_1.is_disjoint(&bins_2) {
+                global.discordant += 1;
+            }
+            for bin in bins_1.union(&bins_2) {
+                let stats = per_bin.entry(*bin).or_default();
+                stats.pairs += 1;
+                if !(bins_1.contains(bin) && bins_2.contains(bin)) {
+                    stats.discordant += 1;
+                }
+            }
+        }
+    }
+
+    let outf =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    for (bin, stats) in per_bin.iter() {
+        buff.write_all(
+            format!(
+                "{}\t{}\t{}\t{:.4}\n",
+                numeric_to_kmer(*bin, msize),
+                stats.pairs,
+                stats.discordant,
+                stats.rate()
+            )
+            .as_bytes(),
+        )
+        .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    }
+
+    Ok(global)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ktio::fops::load_lines_sorted;
+
+    const READ_1: &str = "GGGTGATGGCCGCTGCCGATGGCGTCAAATCCCACCAAGTTACCCTTAACAACTTAAGGGTTTTCAAATAGA";
+    const READ_2: &str = "GTTCAGGGATACGACGTTTGTATTTTAAGAATCTGAAGCAGAAGTCGATGATAATACGCGTCGTTTTATCAT";
+
+    #[test]
+    fn mate_name_test() {
+        assert_eq!(mate_name("Read_1/1"), "Read_1");
+        assert_eq!(mate_name("Read_1/2"), "Read_1");
+        assert_eq!(mate_name("Read_1"), "Read_1");
+    }
+
+    #[test]
+    fn pair_concordance_test() {
+        let in_path = "../test_data/computed_pairs.fa";
+        let out_path = "../test_data/computed_pairs.concordance";
+        fs::write(
+            in_path,
+            format!(">a/1\n{READ_1}\n>a/2\n{READ_1}\n>b/1\n{READ_1}\n>b/2\n{READ_2}\n"),
+        )
+        .unwrap();
+        let global = pair_concordance(0, 10, in_path, out_path, 2, None).unwrap();
+        assert_eq!(
+            global,
+            PairConcordance {
+                pairs: 2,
+                discordant: 1
+            }
+        );
+        assert_eq!(global.rate(), 0.5);
+        let res = load_lines_sorted(out_path);
+        assert_eq!(res.len(), 2);
+        // first mates share a bin, only the pair b mate of READ_2 lands elsewhere
+        assert!(res.iter().any(|line| line.ends_with("\t2\t1\t0.5000")));
+        assert!(res.iter().any(|line| line.ends_with("\t1\t1\t1.0000")));
+
+        fs::write(in_path, format!(">a/1\n{READ_1}\n>b/2\n{READ_1}\n")).unwrap();
+        assert!(pair_concordance(0, 10, in_path, out_path, 2, None).is_err());
+    }
+}
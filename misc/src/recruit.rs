@@ -0,0 +1,162 @@
+use kmer::{minimiser::MinimiserGenerator, Kmer};
+use ktio::{
+    filter::RecordFilter,
+    seq::{get_reader, SeqFormat, Sequence, Sequences},
+};
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{BufWriter, Write},
+};
+
+const BATCH_RECORDS: usize = 10_000;
+
+fn minimisers(seq: &[u8], wsize: usize, msize: usize) -> HashSet<Kmer> {
+    if seq.len() < msize {
+        return HashSet::new();
+    }
+    let mgen = if wsize == 0 {
+        MinimiserGenerator::new(seq, seq.len(), msize)
+    } else {
+        MinimiserGenerator::new(seq, wsize, msize)
+    };
+    mgen.map(|(kmer, _, _, _)| kmer).collect()
+}
+
+// minimisers of reference sequences (e.g. MAG contigs) and the references containing them
+pub struct MinimiserIndex {
+    wsize: usize,
+    msize: usize,
+    refs: Vec<String>,
+    index: HashMap<Kmer, Vec<u32>>,
+}
+
+impl MinimiserIndex {
+    pub fn build(ref_path: &str, wsize: usize, msize: usize) -> Result<Self, String> {
+        let format = SeqFormat::from_path(ref_path)?;
+        let records = Sequences::new(format, get_reader(ref_path)?)?;
+        let mut refs = Vec::new();
+        let mut index: HashMap<Kmer, Vec<u32>> = HashMap::new();
+
+        for record in records {
+            let ref_id = refs.len() as u32;
+            for kmer in minimisers(&record.seq, wsize, msize) {
+                index.entry(kmer).or_default().push(ref_id);
+            }
+            refs.push(record.id);
+        }
+
+        Ok(Self {
+            wsize,
+            msize,
+            refs,
+            index,
+        })
+    }
+
+    pub fn reference(&self, ref_id: usize) -> &str {
+        &self.refs[ref_id]
+    }
+
+    // reference sharing most minimisers with seq, with shared and total minimisers of seq
+    pub fn assign(&self, seq: &[u8]) -> (Option<usize>, usize, usize) {
+        let mins = minimisers(seq, self.wsize, self.msize);
+        let mut shared: HashMap<u32, usize> = HashMap::new();
+        for kmer in mins.iter() {
+            for &ref_id in self.index.get(kmer).into_iter().flatten() {
+                *shared.entry(ref_id).or_insert(0) += 1;
+            }
+        }
+        // ties go to the reference seen first
+        match shared
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        {
+            Some((ref_id, count)) => (Some(ref_id as usize), count, mins.len()),
+            None => (None, 0, mins.len()),
+        }
+    }
+}
+
+// read -> reference assignment table, unassigned reads are reported with *
+pub fn map_reads(
+    ref_path: &str,
+    in_path: &str,
+    out_path: &str,
+    wsize: usize,
+    msize: usize,
+    threads: usize,
+    filter: Option<RecordFilter>,
+) -> Result<(), String> {
+    let mut threads = threads;
+    if threads == 0 {
+        threads = ktio::threads::default_threads();
+    }
+    let index = MinimiserIndex::build(ref_path, wsize, msize)?;
+    let format = SeqFormat::from_path(in_path)?;
+    let mut records = Sequences::new(format, get_reader(in_path)?)?;
+    records.set_filter(filter);
+    let outf =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+
+    loop {
+        let batch: Vec<Sequence> = records.by_ref().take(BATCH_RECORDS).collect();
+        if batch.is_empty() {
+            break;
+        }
+        let rows: Vec<String> = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|record| {
+                    let (ref_id, shared, total) = index.assign(&record.seq);
+                    let reference = ref_id.map_or("*", |ref_id| index.reference(ref_id));
+                    format!("{}\t{}\t{}\t{}\n", record.id, reference, shared, total)
+                })
+                .collect()
+        });
+        buff.write_all(rows.join("").as_bytes())
+            .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_reads_test() {
+        let refs = "../test_data/computed_recruit_refs.fa";
+        let reads = "../test_data/computed_recruit_reads.fa";
+        let out = "../test_data/computed_recruit.tsv";
+        fs::write(
+            refs,
+            ">ref_a\nACGTTGCATGCATTAGCTAGCATCGATCGATTAGCGCG\n>ref_b\nTTTGACCGATAGGCTTACAGGATCCATGACCTAGGATC\n",
+        )
+        .unwrap();
+        fs::write(
+            reads,
+            ">read_1\nCATTAGCTAGCATCGATCG\n>read_2\nGATAGGCTTACAGGATCCA\n>read_3\nAAAAAAAAAAAAAAAAAAA\n",
+        )
+        .unwrap();
+        map_reads(refs, reads, out, 6, 5, 2, None).unwrap();
+        let rows: Vec<Vec<String>> = fs::read_to_string(out)
+            .unwrap()
+            .lines()
+            .map(|line| line.split('\t').map(String::from).collect())
+            .collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0][..2], ["read_1", "ref_a"]);
+        assert_eq!(rows[1][..2], ["read_2", "ref_b"]);
+        assert_eq!(rows[2][..3], ["read_3", "*", "0"]);
+        // every minimiser of a read within a reference is shared
+        assert_eq!(rows[0][2], rows[0][3]);
+    }
+}
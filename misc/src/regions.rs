@@ -0,0 +1,237 @@
+use kmer::{kmer::KmerGenerator, numeric_to_kmer, Kmer};
+use ktio::seq::{get_reader, SeqFormat, Sequences};
+use rayon::prelude::*;
+use scc::HashMap as SccMap;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufWriter, Write},
+};
+
+pub const BACKGROUND: &str = "background";
+
+// annotated half open interval [start, end) on a sequence
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub seq_id: String,
+    pub start: usize,
+    pub end: usize,
+    pub label: String,
+}
+
+// BED is 0-based half open and labelled by name, GFF/GTF is 1-based closed and labelled by type
+pub fn load_regions(path: &str) -> Result<Vec<Region>, String> {
+    let gff = [".gff", ".gff3", ".gtf"]
+        .iter()
+        .any(|ext| path.trim_end_matches(".gz").ends_with(ext));
+    let reader = get_reader(path)?;
+    let mut regions = Vec::new();
+
+    for line in reader.lines().map_while(Result::ok) {
+        if line.trim().is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+        let cols: Vec<&str> = line.trim_end().split('\t').collect();
+        let parse = |col: Option<&&str>| -> Result<usize, String> {
+            col.and_then(|col| col.parse().ok())
+                .ok_or(format!("Invalid annotation line: {}", line))
+        };
+        let region = if gff {
+            if cols.len() < 5 {
+                return Err(format!("Invalid annotation line: {}", line));
+            }
+            Region {
+                seq_id: cols[0].to_string(),
+                start: parse(cols.get(3))?.saturating_sub(1),
+                end: parse(cols.get(4))?,
+                label: cols[2].to_string(),
+            }
+        } else {
+            Region {
+                seq_id: cols[0].to_string(),
+                start: parse(cols.get(1))?,
+                end: parse(cols.get(2))?,
+                label: cols
+                    .get(3)
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or(&"feature")
+                    .to_string(),
+            }
+        };
+        if region.end <= region.start {
+            return Err(format!("Invalid annotation line: {}", line));
+        }
+        regions.push(region);
+    }
+
+    Ok(regions)
+}
+
+// union of sorted intervals so overlapping regions of a label are counted once
+fn merge_intervals(mut intervals: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    intervals.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+// intervals of each label, plus the background not covered by any region
+fn label_intervals(regions: &[&Region], seq_len: usize) -> HashMap<String, Vec<(usize, usize)>> {
+    let mut intervals: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+    for region in regions {
+        intervals
+            .entry(region.label.clone())
+            .or_default()
+            .push((region.start.min(seq_len), region.end.min(seq_len)));
+    }
+    let covered = merge_intervals(regions.iter().map(|r| (r.start, r.end)).collect());
+    let mut background = Vec::new();
+    let mut pos = 0;
+    for (start, end) in covered {
+        if start.min(seq_len) > pos {
+            background.push((pos, start.min(seq_len)));
+        }
+        pos = pos.max(end);
+    }
+    if pos < seq_len {
+        background.push((pos, seq_len));
+    }
+    let mut intervals: HashMap<String, Vec<(usize, usize)>> = intervals
+        .into_iter()
+        .map(|(label, intervals)| (label, merge_intervals(intervals)))
+        .collect();
+    intervals.insert(BACKGROUND.to_string(), background);
+    intervals
+}
+
+// canonical k-mer counts of annotated regions and background, as kmer\tlabel\tcount
+pub fn count_regions(
+    in_path: &str,
+    regions_path: &str,
+    out_path: &str,
+    ksize: usize,
+    acgt: bool,
+    threads: usize,
+) -> Result<(), String> {
+    let mut threads = threads;
+    if threads == 0 {
+        threads = ktio::threads::default_threads();
+    }
+    let regions = load_regions(regions_path)?;
+    let mut seq_regions: HashMap<&str, Vec<&Region>> = HashMap::new();
+    for region in regions.iter() {
+        seq_regions.entry(&region.seq_id).or_default().push(region);
+    }
+    let format = SeqFormat::from_path(in_path)?;
+    let records = Sequences::new(format, get_reader(in_path)?)?;
+    let counts: SccMap<(String, Kmer), u32> = SccMap::new();
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+
+    for record in records {
+        let intervals = label_intervals(
+            seq_regions.get(record.id.as_str()).map_or(&[], |r| r),
+            record.seq.len(),
+        );
+        let intervals: Vec<(&String, (usize, usize))> = intervals
+            .iter()
+            .flat_map(|(label, intervals)| intervals.iter().map(move |&iv| (label, iv)))
+            .collect();
+        pool.install(|| {
+            intervals.par_iter().for_each(|&(label, (start, end))| {
+                for (min_mer, _) in KmerGenerator::new(&record.seq[start..end], ksize).canonical() {
+                    *counts.entry((label.clone(), min_mer)).or_insert(0) += 1;
+                }
+            });
+        });
+    }
+
+    let outf =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    let mut result = Ok(());
+    counts.scan(|(label, kmer), count| {
+        let line = if acgt {
+            format!("{}\t{}\t{}\n", numeric_to_kmer(*kmer, ksize), label, count)
+        } else {
+            format!("{}\t{}\t{}\n", kmer, label, count)
+        };
+        if result.is_ok() && buff.write_all(line.as_bytes()).is_err() {
+            result = Err(format!("Unable to write to file: {}", out_path));
+        }
+    });
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ktio::fops::load_lines_sorted;
+
+    #[test]
+    fn load_regions_test() {
+        let bed = "../test_data/computed_regions.bed";
+        fs::write(bed, "track name=x\nseq1\t0\t4\tpromoter\nseq1\t6\t9\n").unwrap();
+        let regions = load_regions(bed).unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].label, "promoter");
+        assert_eq!(regions[1].label, "feature");
+
+        let gff = "../test_data/computed_regions.gff";
+        fs::write(
+            gff,
+            "##gff-version 3\nseq1\tsrc\tgene\t1\t4\t.\t+\t.\tID=g1\n",
+        )
+        .unwrap();
+        let regions = load_regions(gff).unwrap();
+        assert_eq!(
+            regions,
+            vec![Region {
+                seq_id: "seq1".to_string(),
+                start: 0,
+                end: 4,
+                label: "gene".to_string()
+            }]
+        );
+
+        fs::write(bed, "seq1\t5\t4\n").unwrap();
+        assert!(load_regions(bed).is_err());
+    }
+
+    #[test]
+    fn count_regions_test() {
+        let fa = "../test_data/computed_regions.fa";
+        let bed = "../test_data/computed_regions_count.bed";
+        let out = "../test_data/computed_regions.counts";
+        fs::write(fa, ">seq1\nAAAAACCCCCGGG\n>seq2\nAAAA\n").unwrap();
+        // overlapping gene regions are counted once
+        fs::write(
+            bed,
+            "seq1\t0\t4\tgene\nseq1\t1\t5\tgene\nseq1\t5\t10\tpromoter\n",
+        )
+        .unwrap();
+        count_regions(fa, bed, out, 3, true, 2).unwrap();
+        let res = load_lines_sorted(out);
+        assert_eq!(
+            res,
+            vec![
+                "AAA\tbackground\t2",
+                "AAA\tgene\t3",
+                "CCC\tbackground\t1",
+                "CCC\tpromoter\t3",
+            ]
+        );
+    }
+}
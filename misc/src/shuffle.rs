@@ -0,0 +1,182 @@
+use ktio::seq::{get_reader, SeqFormat, Sequence, Sequences};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{BufWriter, Write},
+};
+
+const BATCH_RECORDS: usize = 10_000;
+
+// mononucleotide preserving shuffle
+fn shuffle_bases(seq: &[u8], rng: &mut StdRng) -> Vec<u8> {
+    let mut shuffled = seq.to_vec();
+    shuffled.shuffle(rng);
+    shuffled
+}
+
+// dinucleotide preserving shuffle as a random Eulerian walk over the transition graph
+// Altschul & Erickson (1985), with Wilson's algorithm for the random last-edge tree
+fn shuffle_dinucleotides(seq: &[u8], rng: &mut StdRng) -> Vec<u8> {
+    if seq.len() < 3 {
+        return seq.to_vec();
+    }
+    let mut edges: HashMap<u8, Vec<u8>> = HashMap::new();
+    for pair in seq.windows(2) {
+        edges.entry(pair[0]).or_default().push(pair[1]);
+    }
+    let last = seq[seq.len() - 1];
+    // random arborescence of last edges rooted at the last base
+    let mut next: HashMap<u8, usize> = HashMap::new();
+    let mut in_tree: HashSet<u8> = HashSet::from([last]);
+    let mut vertices: Vec<u8> = edges.keys().cloned().collect();
+    vertices.sort();
+    for &start in vertices.iter() {
+        let mut vertex = start;
+        while !in_tree.contains(&vertex) {
+            let choice = rng.random_range(0..edges[&vertex].len());
+            next.insert(vertex, choice);
+            vertex = edges[&vertex][choice];
+        }
+        let mut vertex = start;
+        while !in_tree.contains(&vertex) {
+            in_tree.insert(vertex);
+            vertex = edges[&vertex][next[&vertex]];
+        }
+    }
+    // shuffle out edges keeping the tree edge last
+    for vertex in vertices {
+        let out = edges.get_mut(&vertex).unwrap();
+        if let Some(&choice) = next.get(&vertex) {
+            let tree_edge = out.swap_remove(choice);
+            out.shuffle(rng);
+            out.push(tree_edge);
+        } else {
+            out.shuffle(rng);
+        }
+        out.reverse();
+    }
+    let mut shuffled = Vec::with_capacity(seq.len());
+    let mut vertex = seq[0];
+    shuffled.push(vertex);
+    while let Some(base) = edges.get_mut(&vertex).and_then(|out| out.pop()) {
+        shuffled.push(base);
+        vertex = base;
+    }
+    shuffled
+}
+
+pub fn shuffle_seq(seq: &[u8], klet: usize, rng: &mut StdRng) -> Vec<u8> {
+    match klet {
+        1 => shuffle_bases(seq, rng),
+        _ => shuffle_dinucleotides(seq, rng),
+    }
+}
+
+fn shuffle_record(record: &Sequence, klet: usize, count: usize, seed: u64) -> String {
+    // seeded per record so output does not depend on threads
+    let mut rng = StdRng::seed_from_u64(seed.wrapping_add(record.n as u64));
+    (0..count)
+        .map(|i| {
+            let shuffled = shuffle_seq(&record.seq, klet, &mut rng);
+            format!(
+                ">{}_shuffle_{}\n{}\n",
+                record.id,
+                i,
+                String::from_utf8_lossy(&shuffled)
+            )
+        })
+        .collect()
+}
+
+pub fn shuffle_sequences(
+    in_path: &str,
+    out_path: &str,
+    klet: usize,
+    count: usize,
+    seed: u64,
+    threads: usize,
+) -> Result<(), String> {
+    let mut threads = threads;
+    if threads == 0 {
+        threads = ktio::threads::default_threads();
+    }
+    if klet == 0 || klet > 2 {
+        return Err(format!("Unsupported k-let size: {}", klet));
+    }
+    let format = SeqFormat::from_path(in_path)?;
+    let mut records = Sequences::new(format, get_reader(in_path)?)?;
+    let outf =
+        fs::File::create(out_path).map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    let mut buff = BufWriter::new(outf);
+    let pool: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+
+    loop {
+        let batch: Vec<Sequence> = records.by_ref().take(BATCH_RECORDS).collect();
+        if batch.is_empty() {
+            break;
+        }
+        let shuffled: Vec<String> = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|record| shuffle_record(record, klet, count, seed))
+                .collect()
+        });
+        buff.write_all(shuffled.join("").as_bytes())
+            .map_err(|_| format!("Unable to write to file: {}", out_path))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dinucleotides(seq: &[u8]) -> HashMap<(u8, u8), usize> {
+        let mut counts = HashMap::new();
+        for pair in seq.windows(2) {
+            *counts.entry((pair[0], pair[1])).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn shuffle_dinucleotides_test() {
+        let seq = b"ACGTTGCATGCATTAGCTAGCATCGATCGATTAGCGCGATCGATTTAGCGCAGTCGATGCATGCNNACG";
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut changed = false;
+        for _ in 0..20 {
+            let shuffled = shuffle_seq(seq, 2, &mut rng);
+            assert_eq!(shuffled.len(), seq.len());
+            assert_eq!(shuffled[0], seq[0]);
+            assert_eq!(shuffled[seq.len() - 1], seq[seq.len() - 1]);
+            assert_eq!(dinucleotides(&shuffled), dinucleotides(seq));
+            changed |= shuffled != seq;
+        }
+        assert!(changed);
+
+        let mut shuffled = shuffle_seq(seq, 1, &mut rng);
+        let mut sorted = seq.to_vec();
+        shuffled.sort();
+        sorted.sort();
+        assert_eq!(shuffled, sorted);
+    }
+
+    #[test]
+    fn shuffle_sequences_test() {
+        let out_a = "../test_data/computed_shuffled_a.fa";
+        let out_b = "../test_data/computed_shuffled_b.fa";
+        shuffle_sequences("../test_data/reads.fq", out_a, 2, 3, 7, 1).unwrap();
+        shuffle_sequences("../test_data/reads.fq", out_b, 2, 3, 7, 4).unwrap();
+        // reproducible for a seed regardless of threads
+        let a = fs::read_to_string(out_a).unwrap();
+        assert_eq!(a, fs::read_to_string(out_b).unwrap());
+        assert_eq!(a.lines().filter(|line| line.starts_with('>')).count(), 6);
+        assert!(shuffle_sequences("../test_data/reads.fq", out_a, 3, 1, 7, 1).is_err());
+    }
+}
@@ -9,10 +9,13 @@ Modules:
     KmerGenerator      - an iterator object to generate k-mers
                          as (forward, reverse) numeric kmer tuples
     MinimiserGenerator - an iterator object to iterate minimisers
-                         as (kmer, start, end) numeric minimiser tuples
+                         as (kmer, start, end, is_forward) minimiser tuples
+    hamming_neighbours - numeric k-mers within a Hamming distance of a k-mer
+    utils              - reverse complements of sequences
 """
 
-from typing import List, Tuple, Dict, Iterator
+from types import ModuleType
+from typing import List, Optional, Tuple, Dict, Iterator
 
 Point = Tuple[float, float]
 
@@ -96,28 +99,95 @@ class KmerGenerator:
         """
         ...
 
+    def canonical(self) -> "CanonicalKmerGenerator":
+        """
+        Iterate the remaining k-mers as canonical k-mers.
+
+        Returns:
+            CanonicalKmerGenerator: An iterator over (canonical, is_forward) tuples.
+        """
+        ...
+
+    def canonical_indices(self) -> "CanonicalIndexGenerator":
+        """
+        Iterate the remaining k-mers as columns of their canonical k-mers.
+
+        Returns:
+            CanonicalIndexGenerator: An iterator over columns in the order of the OligoComputer header.
+        """
+        ...
+
+class CanonicalKmerGenerator:
+    """
+    An iterator object to generate canonical k-mers as (kmer, is_forward) tuples, where kmer is
+    the smaller of the forward and reverse k-mers and is_forward tells which one it was.
+    """
+
+    def __iter__(self) -> Iterator[Tuple[int, bool]]:
+        """
+        Return an iterator that yields (canonical, is_forward) tuples.
+
+        Returns:
+            Iterator[Tuple[int, bool]]: An iterator over canonical k-mer tuples.
+        """
+        ...
+
+    def to_acgt(self, kmer: int) -> str:
+        """
+        Translate a numeric k-mer to ACGT.
+
+        Args:
+            kmer (int): value of the k-mer.
+
+        Returns:
+            str: ACGT alphabetic representation of the kmer.
+        """
+        ...
+
+class CanonicalIndexGenerator:
+    """
+    An iterator object to generate the column of the canonical k-mer of each k-mer, in the
+    order of the OligoComputer header.
+    """
+
+    def __iter__(self) -> Iterator[int]:
+        """
+        Return an iterator that yields canonical k-mer columns.
+
+        Returns:
+            Iterator[int]: An iterator over canonical k-mer columns.
+        """
+        ...
+
 class MinimiserGenerator:
     """
-    An iterator object to iterate minimisers as (kmer, start, end) numeric minimiser tuples.
+    An iterator object to iterate minimisers as (kmer, start, end, is_forward) minimiser tuples.
     """
 
-    def __init__(self, seq: str, wsize: int, msize: int) -> None:
+    def __init__(
+        self, seq: str, wsize: int, msize: int, hashed: bool = False, seed: Optional[str] = None
+    ) -> None:
         """
         Initialise the MinimiserGenerator.
 
         Args:
             seq (str): The DNA sequence to generate minimisers from.
             wsize (int): size of the window.
-            msize (int): size of the minimiser.
+            msize (int): size of the minimiser, the seed sets it when there is one.
+            hashed (bool): order m-mers by an invertible hash instead of their value.
+            seed (Optional[str]): spaced seed such as 1101011, minimisers are k-mers of its 1 positions.
+
+        Raises:
+            ValueError: If the seed is not 0s and 1s starting and ending with 1, or does not fit in the window.
         """
         ...
 
-    def __iter__(self) -> Iterator[Tuple[int, int, int]]:
+    def __iter__(self) -> Iterator[Tuple[int, int, int, bool]]:
         """
-        Return an iterator that yields (kmer, start, end) numeric minimiser tuples.
+        Return an iterator that yields (kmer, start, end, is_forward) minimiser tuples.
 
         Returns:
-            Iterator[Tuple[int, int, int]]: An iterator over minimiser tuples as the tuple (minimiser, start, end).
+            Iterator[Tuple[int, int, int, bool]]: An iterator over minimiser tuples, is_forward tells whether the minimiser came from the forward strand.
         """
         ...
 
@@ -179,4 +249,62 @@ class OligoComputer:
         """
         ...
 
-__all__ = ["CgrComputer", "KmerGenerator", "MinimiserGenerator", "OligoComputer"]
+def hamming_neighbours(kmer: int, ksize: int, distance: int, canonical: bool = False) -> List[int]:
+    """
+    Enumerate the k-mers within Hamming distance of a k-mer, by distance starting with the k-mer.
+
+    Args:
+        kmer (int): value of the k-mer.
+        ksize (int): size of the k-mer.
+        distance (int): largest number of substituted bases.
+        canonical (bool): yield the smaller of each neighbour and its reverse complement, without repeats.
+
+    Returns:
+        List[int]: numeric k-mers within the distance.
+    """
+    ...
+
+class _Utils(ModuleType):
+    """
+    Sequence utilities, importable as pykmertools.utils.
+    """
+
+    @staticmethod
+    def reverse_complement(seq: str) -> str:
+        """
+        Reverse complement of a sequence, keeping the case of bases. U pairs with A and other
+        symbols such as N are kept as they are.
+
+        Args:
+            seq (str): sequence as a string.
+
+        Returns:
+            str: reverse complement of the sequence.
+        """
+        ...
+
+    @staticmethod
+    def reverse_complement_batch(seqs: List[str]) -> List[str]:
+        """
+        Reverse complements of a batch of sequences, computed in parallel.
+
+        Args:
+            seqs (List[str]): list of sequences.
+
+        Returns:
+            List[str]: reverse complements in the order of the sequences.
+        """
+        ...
+
+utils: _Utils
+
+__all__ = [
+    "CanonicalIndexGenerator",
+    "CanonicalKmerGenerator",
+    "CgrComputer",
+    "KmerGenerator",
+    "MinimiserGenerator",
+    "OligoComputer",
+    "hamming_neighbours",
+    "utils",
+]
@@ -1,8 +1,29 @@
-use std::{mem::transmute, sync::Arc};
+use std::{
+    mem::transmute,
+    sync::{Arc, OnceLock},
+};
 
-use kmer::{kmer::KmerGenerator as RsKmerGenerator, numeric_to_kmer, Kmer};
+use kmer::{
+    hamming::HammingNeighbours,
+    kmer::{CanonicalIndices, CanonicalKmers, KmerGenerator as RsKmerGenerator, MAX_DENSE_KSIZE},
+    numeric_to_kmer, Kmer,
+};
 use pyo3::prelude::*;
 
+// columns of each k-mer size, built once on first use
+static COLUMNS: [OnceLock<Arc<[usize]>>; MAX_DENSE_KSIZE + 1] =
+    [const { OnceLock::new() }; MAX_DENSE_KSIZE + 1];
+
+// large k uses the closed-form rank instead of a 4^k lookup table
+fn canonical_columns(ksize: usize) -> Arc<[usize]> {
+    match COLUMNS.get(ksize) {
+        Some(columns) => {
+            Arc::clone(columns.get_or_init(|| Arc::from(RsKmerGenerator::canonical_columns(ksize))))
+        }
+        None => Arc::from([]),
+    }
+}
+
 /// Computer for generating k-mers
 #[pyclass]
 pub struct KmerGenerator {
@@ -34,6 +55,27 @@ impl KmerGenerator {
         numeric_to_kmer(kmer, self.ksize)
     }
 
+    /// Iterator of the remaining k-mers as (canonical, is_forward) tuples
+    pub fn canonical(&self) -> CanonicalKmerGenerator {
+        CanonicalKmerGenerator {
+            _data: Arc::clone(&self._data),
+            _kg: self._kg.clone().canonical(),
+            ksize: self.ksize,
+        }
+    }
+
+    /// Iterator of the remaining k-mers as columns of their canonical k-mers, in the order
+    /// of the OligoComputer header
+    pub fn canonical_indices(&self) -> CanonicalIndexGenerator {
+        let _map = canonical_columns(self.ksize);
+        let static_map: &'static [usize] = unsafe { transmute(Arc::as_ref(&_map)) };
+        CanonicalIndexGenerator {
+            _data: Arc::clone(&self._data),
+            _ki: self._kg.clone().canonical_indices(static_map),
+            _map,
+        }
+    }
+
     pub fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
         slf
     }
@@ -42,3 +84,63 @@ impl KmerGenerator {
         slf._kg.next()
     }
 }
+
+/// Iterator of canonical k-mers
+#[pyclass]
+pub struct CanonicalKmerGenerator {
+    _data: Arc<[u8]>,
+    _kg: CanonicalKmers<'static, Kmer>,
+    ksize: usize,
+}
+
+#[pymethods]
+impl CanonicalKmerGenerator {
+    /// Translate numeric k-mer to ACGT
+    /// Attributes:
+    ///     kmer (int): value of the k-mer
+    #[pyo3(signature = (kmer))]
+    pub fn to_acgt(&self, kmer: u64) -> String {
+        numeric_to_kmer(kmer, self.ksize)
+    }
+
+    pub fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    pub fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<(Kmer, bool)> {
+        slf._kg.next()
+    }
+}
+
+/// Iterator of canonical k-mer columns
+#[pyclass]
+pub struct CanonicalIndexGenerator {
+    _data: Arc<[u8]>,
+    _map: Arc<[usize]>,
+    _ki: CanonicalIndices<'static, 'static>,
+}
+
+#[pymethods]
+impl CanonicalIndexGenerator {
+    pub fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    pub fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<usize> {
+        slf._ki.next()
+    }
+}
+
+/// K-mers within Hamming distance of a numeric k-mer, by distance
+/// Attributes:
+///     kmer (int): value of the k-mer
+///     ksize (int): size of the k-mer
+///     distance (int): largest number of substituted bases
+///     canonical (bool): smaller of each neighbour and its reverse complement, without repeats
+#[pyfunction]
+#[pyo3(signature = (kmer, ksize, distance, canonical=false))]
+pub fn hamming_neighbours(kmer: Kmer, ksize: usize, distance: usize, canonical: bool) -> Vec<Kmer> {
+    HammingNeighbours::new(kmer, ksize, distance)
+        .with_canonical(canonical)
+        .collect()
+}
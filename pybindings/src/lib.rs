@@ -2,3 +2,4 @@ pub mod cgr;
 pub mod kmer;
 pub mod min;
 pub mod oligo;
+pub mod utils;
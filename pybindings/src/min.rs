@@ -1,12 +1,16 @@
 use std::{mem::transmute, sync::Arc};
 
-use kmer::{minimiser::MinimiserGenerator as RsMinimiserGenerator, numeric_to_kmer, Kmer};
-use pyo3::prelude::*;
+use kmer::{
+    minimiser::MinimiserGenerator as RsMinimiserGenerator, numeric_to_kmer, spaced::SpacedSeed,
+    Kmer,
+};
+use pyo3::{exceptions::PyValueError, prelude::*};
 
 /// Computer for generating k-mers
 #[pyclass]
 pub struct MinimiserGenerator {
     _data: Arc<[u8]>,
+    _seed: Option<Arc<SpacedSeed>>,
     _mg: RsMinimiserGenerator<'static>,
     msize: usize,
 }
@@ -18,13 +22,40 @@ impl MinimiserGenerator {
     ///     seq (str): string from which to extract k-mers
     ///     wsize (int): size of the window
     ///     msize (int): size of the minimiser
+    ///     hashed (bool): order m-mers by an invertible hash instead of their value
+    ///     seed (str): spaced seed such as 1101011, minimisers are k-mers of its 1 positions
     #[new]
-    #[pyo3(signature = (seq, wsize, msize))]
-    pub fn new(seq: String, wsize: usize, msize: usize) -> Self {
+    #[pyo3(signature = (seq, wsize, msize, hashed=false, seed=None))]
+    pub fn new(
+        seq: String,
+        wsize: usize,
+        msize: usize,
+        hashed: bool,
+        seed: Option<String>,
+    ) -> PyResult<Self> {
+        let _seed = match seed.as_deref().map(SpacedSeed::parse) {
+            Some(Ok(seed)) => Some(Arc::new(seed)),
+            Some(Err(e)) => return Err(PyValueError::new_err(e)),
+            None => None,
+        };
         let _data: Arc<[u8]> = Arc::from(seq.into_boxed_str().into_boxed_bytes());
         let static_str: &'static [u8] = unsafe { transmute(Arc::as_ref(&_data)) };
-        let _mg = RsMinimiserGenerator::new(static_str, wsize, msize);
-        Self { _mg, _data, msize }
+        let mut _mg = RsMinimiserGenerator::new(static_str, wsize, msize).with_hash(hashed);
+        let mut msize = msize;
+        if let Some(seed) = &_seed {
+            if seed.span() > wsize {
+                return Err(PyValueError::new_err("Seed must fit in the window"));
+            }
+            let static_seed: &'static SpacedSeed = unsafe { transmute(Arc::as_ref(seed)) };
+            _mg = _mg.with_seed(static_seed);
+            msize = seed.weight();
+        }
+        Ok(Self {
+            _mg,
+            _data,
+            _seed,
+            msize,
+        })
     }
 
     /// Translate numeric minimiser to ACGT
@@ -41,8 +72,8 @@ impl MinimiserGenerator {
 
     /// Translate numeric k-mer to ACGT
     /// Returns:
-    ///     Tuple[int, int, int]: minimiser, start pos, end pos
-    pub fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<(Kmer, usize, usize)> {
+    ///     Tuple[int, int, int, bool]: minimiser, start pos, end pos, is_forward
+    pub fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<(Kmer, usize, usize, bool)> {
         slf._mg.next()
     }
 }
@@ -1,4 +1,7 @@
-use kmer::{kmer::KmerGenerator, numeric_to_kmer};
+use kmer::{
+    kmer::{KmerGenerator, MAX_DENSE_KSIZE},
+    numeric_to_kmer,
+};
 use pyo3::prelude::*;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::collections::HashMap;
@@ -20,7 +23,21 @@ impl OligoComputer {
     #[new]
     #[pyo3(signature = (ksize))]
     fn new(ksize: usize) -> Self {
-        let (min_mer_pos_map, pos_min_mer_map, kcount) = KmerGenerator::kmer_pos_maps(ksize);
+        // large k uses the closed-form rank instead of a 4^k lookup table
+        let (min_mer_pos_map, pos_min_mer_map, kcount) = if ksize <= MAX_DENSE_KSIZE {
+            let (_, pos_min_mer_map, kcount) = KmerGenerator::kmer_pos_maps(ksize);
+            (
+                KmerGenerator::canonical_columns(ksize),
+                pos_min_mer_map,
+                kcount,
+            )
+        } else {
+            (
+                Vec::new(),
+                HashMap::new(),
+                KmerGenerator::canonical_count(ksize),
+            )
+        };
 
         Self {
             ksize,
@@ -39,12 +56,12 @@ impl OligoComputer {
         let mut vec = vec![0_f64; self.kcount];
         let mut total = 0_f64;
 
-        for (fmer, rmer) in KmerGenerator::new(seq.as_bytes(), self.ksize) {
-            let min_mer = u64::min(fmer, rmer);
+        for min_mer_pos in
+            KmerGenerator::new(seq.as_bytes(), self.ksize).canonical_indices(&self.pos_map)
+        {
             unsafe {
                 // we already know the size of the vector and
-                // min_mer is absolutely smaller than that
-                let &min_mer_pos = self.pos_map.get_unchecked(min_mer as usize);
+                // every position is smaller than that
                 *vec.get_unchecked_mut(min_mer_pos) += 1_f64;
                 total += 1_f64;
             }
@@ -68,6 +85,12 @@ impl OligoComputer {
 
     /// Generate the header for oligo nucletide vector
     fn get_header(&self) -> Vec<String> {
+        if self.pos_map.is_empty() {
+            return (0..4_u64.pow(self.ksize as u32))
+                .filter(|&kmer| kmer <= KmerGenerator::rev_comp(kmer, self.ksize))
+                .map(|kmer| numeric_to_kmer(kmer, self.ksize))
+                .collect();
+        }
         let mut kmers = vec![String::new(); self.kcount];
         for (&pos, &kmer) in self.pos_kmer.iter() {
             kmers[pos] = numeric_to_kmer(kmer, self.ksize);
@@ -0,0 +1,38 @@
+use kmer::revcomp::reverse_complement_in_place;
+use pyo3::prelude::*;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+/// Reverse complement of a sequence, keeping the case of bases, U pairs with A and other
+/// symbols such as N are kept as they are
+/// Attributes:
+///     seq (str): sequence as a string
+#[pyfunction]
+#[pyo3(signature = (seq))]
+pub fn reverse_complement(seq: String) -> String {
+    let mut rc = seq.into_bytes();
+    reverse_complement_in_place(&mut rc);
+    // reversed bytes of non ASCII symbols are no longer UTF-8 and are replaced
+    String::from_utf8(rc).unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into())
+}
+
+/// Reverse complements of a batch of sequences, computed in parallel
+/// Attributes:
+///     seqs (list[str]): list of sequences
+#[pyfunction]
+#[pyo3(signature = (seqs))]
+pub fn reverse_complement_batch(seqs: Vec<String>) -> Vec<String> {
+    seqs.into_par_iter().map(reverse_complement).collect()
+}
+
+/// Adds the utils submodule, importable as pykmertools.utils
+pub fn add_utils(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let utils = PyModule::new(m.py(), "utils")?;
+    utils.add_function(wrap_pyfunction!(reverse_complement, &utils)?)?;
+    utils.add_function(wrap_pyfunction!(reverse_complement_batch, &utils)?)?;
+    m.add_submodule(&utils)?;
+    // submodules of extension modules are not found by import without this
+    m.py()
+        .import("sys")?
+        .getattr("modules")?
+        .set_item("pykmertools.utils", &utils)
+}
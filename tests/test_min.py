@@ -11,14 +11,15 @@ def test_min():
         7,
     )
     mins = [
-        "ACGATAT",
-        "ACGCCTA",
-        "AGAGCTA",
-        "AAATCCC",
-        "AATCCCG",
-        "AATCGAT",
-        "AAAGCGC",
+        ("ACGATAT", False),
+        ("ACGCCTA", False),
+        ("AGAGCTA", True),
+        ("AAATCCC", True),
+        ("AATCCCG", True),
+        ("AATCGAT", False),
+        ("AAAGCGC", True),
     ]
 
-    for (kmer, _, _), min in zip(min_gen, mins):
+    for (kmer, _, _, forward), (min, strand) in zip(min_gen, mins):
         assert min_gen.to_acgt(kmer) == min
+        assert forward == strand